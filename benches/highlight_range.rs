@@ -0,0 +1,58 @@
+// benches/highlight_range.rs - Compare whole-file and viewport-only token extraction
+//
+// Run with `cargo bench --bench highlight_range`.
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use texty::syntax::{LanguageId, SyntaxHighlighter, get_language_config};
+
+const FILE_LINES: usize = 20_000;
+const VIEWPORT_HEIGHT: usize = 50;
+const ITERATIONS: u32 = 20;
+
+fn big_rust_file() -> String {
+    let mut code = String::new();
+    for i in 0..FILE_LINES / 5 {
+        code.push_str(&format!(
+            "fn function_{i}(value: u32) -> u32 {{\n    // add a constant\n    let offset = {i};\n    value + offset\n}}\n"
+        ));
+    }
+    code
+}
+
+/// Mean time to extract tokens for `lines`, starting each run from a
+/// highlighter whose cached window doesn't cover them
+fn time_extraction(highlighter: &mut SyntaxHighlighter, lines: Range<usize>) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        highlighter.highlight_range(FILE_LINES * 2..FILE_LINES * 2 + 1);
+        let start = Instant::now();
+        highlighter.highlight_range(lines.clone());
+        total += start.elapsed();
+    }
+    total / ITERATIONS
+}
+
+fn main() {
+    let code = big_rust_file();
+    let config = get_language_config(LanguageId::Rust);
+    let mut highlighter = SyntaxHighlighter::new(config).expect("rust highlighter");
+    highlighter.parse(&code).expect("parse");
+
+    let whole_file = time_extraction(&mut highlighter, 0..FILE_LINES);
+    let middle = FILE_LINES / 2;
+    let viewport = time_extraction(&mut highlighter, middle..middle + VIEWPORT_HEIGHT);
+
+    // Scrolling within the margin reuses the extracted window
+    highlighter.highlight_range(middle..middle + VIEWPORT_HEIGHT);
+    let start = Instant::now();
+    for offset in 1..=ITERATIONS as usize {
+        highlighter.highlight_range(middle + offset..middle + offset + VIEWPORT_HEIGHT);
+    }
+    let scroll = start.elapsed() / ITERATIONS;
+
+    println!("{FILE_LINES} line file, {VIEWPORT_HEIGHT} line viewport, mean of {ITERATIONS} runs");
+    println!("  whole file:         {whole_file:?}");
+    println!("  viewport:           {viewport:?}");
+    println!("  scroll by one line: {scroll:?}");
+}
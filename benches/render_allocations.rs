@@ -0,0 +1,97 @@
+// benches/render_allocations.rs - Count allocations made while drawing the editor pane
+//
+// Run with `cargo bench --bench render_allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::widgets::Widget;
+use texty::editor::Editor;
+use texty::ui::theme::Theme;
+use texty::ui::widgets::editor_pane::EditorPane;
+
+const FILE_LINES: usize = 10_000;
+const SCREEN: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: 120,
+    height: 50,
+};
+const FRAMES: usize = 200;
+
+/// Passes through to the system allocator, counting every allocation
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Mean allocations and time per call of `frame`
+fn measure(mut frame: impl FnMut()) -> (usize, Duration) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    (allocations / FRAMES, elapsed / FRAMES as u32)
+}
+
+fn main() {
+    let mut editor = Editor::new();
+    editor.buffer.file_path = None;
+    let text: String = (0..FILE_LINES)
+        .map(|i| format!("    let value_{i} = compute({i}, \"some text\");\n"))
+        .collect();
+    editor.buffer.insert_text(&text, 0, 0).expect("insert");
+    editor.viewport.offset_line = FILE_LINES / 2;
+    let theme = Theme::default();
+    let first = editor.viewport.offset_line;
+    let height = SCREEN.height as usize;
+
+    // Fetching the visible lines alone, before and after
+    let (owned, _) = measure(|| {
+        for line_idx in first..first + height {
+            std::hint::black_box(editor.buffer.line(line_idx));
+        }
+    });
+    let (borrowed, _) = measure(|| {
+        for line in editor.buffer.visible_lines(first, height) {
+            std::hint::black_box(line);
+        }
+    });
+
+    let mut screen = Buffer::empty(SCREEN);
+    let (render, render_time) = measure(|| {
+        EditorPane::new(&editor, &theme).render(SCREEN, &mut screen);
+    });
+
+    println!(
+        "{FILE_LINES} line file, {}x{} screen, mean of {FRAMES} frames",
+        SCREEN.width, SCREEN.height
+    );
+    println!("  Buffer::line per visible line: {owned} allocations");
+    println!("  Buffer::visible_lines:         {borrowed} allocations");
+    println!("  full editor pane render:       {render} allocations, {render_time:?}");
+}
@@ -0,0 +1,32 @@
+[
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (switch_case)
+  (switch_default)
+  (object)
+  (object_pattern)
+  (array)
+  (array_pattern)
+  (arguments)
+  (formal_parameters)
+  (named_imports)
+  (export_clause)
+  (parenthesized_expression)
+  (object_type)
+  (enum_body)
+  (type_arguments)
+  (type_parameters)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+  ">"
+] @outdent
+
+[
+  (template_string)
+  (comment)
+] @ignore
@@ -0,0 +1,29 @@
+[
+  (function_definition)
+  (class_definition)
+  (if_statement)
+  (for_statement)
+  (while_statement)
+  (with_statement)
+  (try_statement)
+  (argument_list)
+  (parameters)
+  (list)
+  (dictionary)
+  (set)
+  (tuple)
+  (parenthesized_expression)
+] @indent
+
+; Clauses line up with the statement they belong to
+[
+  (elif_clause)
+  (else_clause)
+  (except_clause)
+  (finally_clause)
+  ")"
+  "]"
+  "}"
+] @outdent
+
+(string) @ignore
@@ -0,0 +1,33 @@
+[
+  (block)
+  (declaration_list)
+  (field_declaration_list)
+  (ordered_field_declaration_list)
+  (enum_variant_list)
+  (field_initializer_list)
+  (match_block)
+  (match_arm)
+  (arguments)
+  (parameters)
+  (type_parameters)
+  (type_arguments)
+  (array_expression)
+  (tuple_expression)
+  (token_tree)
+  (use_list)
+  (where_clause)
+  (let_declaration)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+  ">"
+] @outdent
+
+[
+  (string_literal)
+  (raw_string_literal)
+  (block_comment)
+] @ignore
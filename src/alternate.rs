@@ -0,0 +1,123 @@
+// src/alternate.rs - Companion files for `:A`, such as a C source and its header
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Directory names that hold parallel source and header trees
+const TREES: &[&str] = &["src", "source", "include", "inc"];
+
+/// Companion extensions for each extension, tried in order
+pub fn default_pairs() -> HashMap<String, Vec<String>> {
+    let pairs: &[(&str, &[&str])] = &[
+        ("c", &["h"]),
+        ("h", &["c", "cpp", "cc", "m"]),
+        ("cpp", &["h", "hpp", "hh"]),
+        ("cc", &["h", "hh", "hpp"]),
+        ("cxx", &["h", "hxx", "hpp"]),
+        ("hpp", &["cpp", "cc", "cxx"]),
+        ("hh", &["cc", "cpp"]),
+        ("hxx", &["cxx", "cpp"]),
+        ("m", &["h"]),
+    ];
+    pairs
+        .iter()
+        .map(|(ext, companions)| {
+            let companions = companions.iter().map(|c| c.to_string()).collect();
+            (ext.to_string(), companions)
+        })
+        .collect()
+}
+
+/// Existing companions of `path` under `pairs`, nearest first. They are
+/// looked for next to the file, in the same place under a parallel tree
+/// (`src/net/` for `include/net/`), and in sibling `src`/`include` folders.
+pub fn alternate_files(path: &Path, pairs: &HashMap<String, Vec<String>>) -> Vec<PathBuf> {
+    let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) else {
+        return Vec::new();
+    };
+    let Some(companions) = pairs.get(&ext.to_string_lossy().to_lowercase()) else {
+        return Vec::new();
+    };
+    let stem = stem.to_string_lossy();
+
+    let mut found = Vec::new();
+    for dir in search_dirs(path.parent().unwrap_or(Path::new(""))) {
+        for companion in companions {
+            let candidate = dir.join(format!("{}.{}", stem, companion));
+            if candidate.is_file() && !found.contains(&candidate) {
+                found.push(candidate);
+            }
+        }
+    }
+    found
+}
+
+/// Directories to look in for companions of a file in `dir`, nearest first
+fn search_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![dir.to_path_buf()];
+
+    let components: Vec<_> = dir.components().collect();
+    for (i, component) in components.iter().enumerate() {
+        let name = component.as_os_str().to_string_lossy();
+        if !TREES.contains(&name.as_ref()) {
+            continue;
+        }
+        for other in TREES.iter().filter(|&&tree| tree != name) {
+            let mut swapped: PathBuf = components[..i].iter().collect();
+            swapped.push(other);
+            swapped.extend(&components[i + 1..]);
+            dirs.push(swapped);
+        }
+    }
+
+    if let Some(parent) = dir.parent() {
+        dirs.extend(TREES.iter().map(|tree| parent.join(tree)));
+    }
+    dirs.dedup();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alternate_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "src/net/socket.c",
+            "include/net/socket.h",
+            "lib/util.cpp",
+            "lib/util.h",
+            "lib/util.hpp",
+            "src/main.c",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let pairs = default_pairs();
+
+        // A parallel include tree
+        assert_eq!(
+            alternate_files(&root.join("src/net/socket.c"), &pairs),
+            [root.join("include/net/socket.h")]
+        );
+        assert_eq!(
+            alternate_files(&root.join("include/net/socket.h"), &pairs),
+            [root.join("src/net/socket.c")]
+        );
+        // Several candidates, in the order of the pair list
+        assert_eq!(
+            alternate_files(&root.join("lib/util.cpp"), &pairs),
+            [root.join("lib/util.h"), root.join("lib/util.hpp")]
+        );
+        assert!(alternate_files(&root.join("src/main.c"), &pairs).is_empty());
+        assert!(alternate_files(&root.join("README"), &pairs).is_empty());
+
+        // Configured pairs
+        let pairs = HashMap::from([("c".to_string(), vec!["cpp".to_string()])]);
+        assert!(alternate_files(&root.join("src/net/socket.c"), &pairs).is_empty());
+    }
+}
@@ -1,11 +1,87 @@
 use crate::syntax::{LanguageId, LanguageRegistry, SyntaxHighlighter, get_language_config};
+use crate::editorconfig::LineEnding;
 use crate::motion::Position;
-use lru::LruCache;
+use crate::options::EditorOptions;
 use ropey::Rope;
+use std::borrow::Cow;
 use std::fs;
-use std::path::Path;
-use std::num::NonZeroUsize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
+use tree_sitter::{InputEdit, Point};
+
+/// Write `content` to `path` without ever leaving a half-written file behind.
+///
+/// The data goes to a temporary file in the same directory, is fsynced, and is
+/// then renamed over the target, so readers see either the old or the new file.
+/// With `backup`, the previous contents are first copied to `<path>~`.
+pub fn write_atomic(path: &Path, content: &str, backup: bool) -> std::io::Result<()> {
+    // Write through symlinks instead of replacing them with a regular file
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let existing = fs::metadata(&target).ok();
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        if let Some(metadata) = &existing {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        drop(file);
+
+        if backup && existing.is_some() {
+            let mut backup_path = target.clone().into_os_string();
+            backup_path.push("~");
+            fs::copy(&target, backup_path)?;
+        }
+
+        fs::rename(&temp_path, &target)?;
+
+        // Persist the rename itself; not every platform can sync a directory
+        if let Ok(dir_handle) = fs::File::open(&dir) {
+            let _ = dir_handle.sync_all();
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Case transformation applied by `~`, `gu`, `gU` and `g~`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseChange {
+    Lower,
+    Upper,
+    Toggle,
+}
+
+impl CaseChange {
+    /// Transform one character. May yield several characters (e.g. `ß` -> `SS`).
+    pub fn apply(self, c: char) -> String {
+        let upper = match self {
+            CaseChange::Lower => false,
+            CaseChange::Upper => true,
+            CaseChange::Toggle => c.is_lowercase(),
+        };
+        if upper {
+            c.to_uppercase().collect()
+        } else {
+            c.to_lowercase().collect()
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum BufferError {
@@ -49,8 +125,11 @@ pub struct Buffer {
     pub modified: bool,
     pub version: usize,
     pub highlighter: Option<SyntaxHighlighter>,
-    // Performance optimization: LRU cache for line content to avoid repeated allocations
-    line_cache: LruCache<usize, String>,
+    /// The file ends with a newline; it is kept when saving even if edits removed it
+    pub final_newline: bool,
+    /// Line breaks are written as this ending when saving; the text being
+    /// edited keeps its own
+    pub line_ending: Option<LineEnding>,
     // Performance optimization: debounce highlighter updates to avoid blocking on every keystroke
     highlight_debounce: Duration,
     last_highlight_time: Instant,
@@ -65,8 +144,8 @@ impl Buffer {
             modified: false,
             version: 0,
             highlighter: None,
-            // Cache 256 lines (typical viewport + margin)
-            line_cache: LruCache::new(NonZeroUsize::new(256).unwrap()),
+            final_newline: false,
+            line_ending: None,
             // Debounce highlighter updates by 50ms to avoid blocking typing
             highlight_debounce: Duration::from_millis(50),
             last_highlight_time: Instant::now(),
@@ -84,41 +163,56 @@ impl Default for Buffer {
 impl Buffer {
     pub fn insert_char(&mut self, char: char, line: usize, col: usize) -> Result<(), BufferError> {
         let char_idx = self.rope.line_to_char(line) + col;
-        self.rope.insert_char(char_idx, char);
+        self.insert_chars(char_idx, char.encode_utf8(&mut [0; 4]));
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
         Ok(())
     }
 
-    /// Get line content with LRU caching to avoid repeated allocations
-    /// TODO: Integrate this into the rendering pipeline to reduce allocations
-    /// Currently unused, kept for future optimization
-    #[allow(dead_code)]
-    fn line_cached(&mut self, line_idx: usize) -> Option<String> {
-        // Check cache first
-        if let Some(cached) = self.line_cache.get(&line_idx) {
-            return Some(cached.clone());
-        }
+    /// Insert `text` at `char_idx` and report the edit to the highlighter
+    fn insert_chars(&mut self, char_idx: usize, text: &str) {
+        let start = self.edit_point(char_idx);
+        self.rope.insert(char_idx, text);
+        let new_end = self.edit_point(char_idx + text.chars().count());
+        self.report_edit(start, start, new_end);
+    }
 
-        // Cache miss - fetch and cache
-        if let Some(line) = self.line(line_idx) {
-            self.line_cache.put(line_idx, line.clone());
-            Some(line)
-        } else {
-            None
+    /// Remove the chars in `range` and report the edit to the highlighter
+    fn remove_chars(&mut self, range: std::ops::Range<usize>) {
+        let start = self.edit_point(range.start);
+        let old_end = self.edit_point(range.end);
+        self.rope.remove(range);
+        self.report_edit(start, old_end, start);
+    }
+
+    /// Byte offset and tree-sitter point (row, byte column) of `char_idx`
+    fn edit_point(&self, char_idx: usize) -> (usize, Point) {
+        let byte = self.rope.char_to_byte(char_idx);
+        let row = self.rope.char_to_line(char_idx);
+        (byte, Point::new(row, byte - self.rope.line_to_byte(row)))
+    }
+
+    /// Let the highlighter shift its cached tokens past the edit instead of
+    /// extracting them all again
+    fn report_edit(&mut self, start: (usize, Point), old_end: (usize, Point), new_end: (usize, Point)) {
+        if let Some(highlighter) = &mut self.highlighter {
+            highlighter.edit(&InputEdit {
+                start_byte: start.0,
+                old_end_byte: old_end.0,
+                new_end_byte: new_end.0,
+                start_position: start.1,
+                old_end_position: old_end.1,
+                new_end_position: new_end.1,
+            });
         }
     }
 
-    /// Invalidate cache for specific line (and nearby lines for safety)
-    fn invalidate_line_cache(&mut self, line_idx: usize) {
-        self.line_cache.pop(&line_idx);
-        // Also invalidate adjacent lines since edits can affect them
-        if line_idx > 0 {
-            self.line_cache.pop(&(line_idx - 1));
+    /// Invalidate all cached tokens after the whole text was replaced
+    fn clear_caches(&mut self) {
+        if let Some(highlighter) = &mut self.highlighter {
+            highlighter.invalidate_lines_from(0);
         }
-        self.line_cache.pop(&(line_idx + 1));
     }
 
     /// Schedule highlighter update with debouncing
@@ -132,41 +226,54 @@ impl Buffer {
         }
     }
 
-    /// Check if there's a pending highlight update that should be processed
-    pub fn check_pending_highlight(&mut self) {
+    /// Whether an edit is waiting on the debounce to be highlighted
+    pub fn has_pending_highlight(&self) -> bool {
+        self.highlight_pending
+    }
+
+    /// Check if there's a pending highlight update that should be processed.
+    ///
+    /// Returns true if the highlights were refreshed and need redrawing.
+    pub fn check_pending_highlight(&mut self) -> bool {
         if self.highlight_pending && self.last_highlight_time.elapsed() >= self.highlight_debounce {
             self.update_highlighter().ok();
             self.last_highlight_time = Instant::now();
             self.highlight_pending = false;
+            return true;
         }
+        false
     }
 
     pub fn delete_char(&mut self, line: usize, col: usize) -> Result<(), BufferError> {
         if col == 0 && line > 0 {
             // Delete newline
             let char_idx = self.rope.line_to_char(line);
-            self.rope.remove(char_idx - 1..char_idx);
+            self.remove_chars(char_idx - 1..char_idx);
         } else if col > 0 {
             let char_idx = self.rope.line_to_char(line) + col;
-            self.rope.remove(char_idx - 1..char_idx);
+            self.remove_chars(char_idx - 1..char_idx);
         } else if col == 0 && line == 0 {
             // At position (0, 0) with only one line - delete the only character
+            if self.rope.len_chars() == 0 {
+                return Ok(());
+            }
             let char_idx = self.rope.line_to_char(line);
-            self.rope.remove(char_idx..char_idx + 1);
+            self.remove_chars(char_idx..char_idx + 1);
         }
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
         Ok(())
     }
 
     pub fn insert_text(&mut self, text: &str, line: usize, col: usize) -> Result<(), BufferError> {
+        if text.is_empty() {
+            return Ok(());
+        }
         let char_idx = self.rope.line_to_char(line) + col;
-        self.rope.insert(char_idx, text);
+        self.insert_chars(char_idx, text);
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
         Ok(())
     }
@@ -175,6 +282,18 @@ impl Buffer {
         self.rope.len_lines()
     }
 
+    /// Index of the last line holding content. A trailing newline ends the
+    /// final line rather than starting an empty one.
+    pub fn last_content_line(&self) -> usize {
+        let lines = self.rope.len_lines();
+        let len = self.rope.len_chars();
+        if lines > 1 && self.rope.char(len - 1) == '\n' {
+            lines - 2
+        } else {
+            lines.saturating_sub(1)
+        }
+    }
+
     pub fn line(&self, line_idx: usize) -> Option<String> {
         if line_idx < self.rope.len_lines() {
             let line = self.rope.line(line_idx).to_string();
@@ -188,6 +307,30 @@ impl Buffer {
         }
     }
 
+    /// Up to `count` lines from `start`, without their newlines.
+    ///
+    /// Lines are borrowed from the rope when they sit in a single chunk, which
+    /// is nearly always, so drawing a screen doesn't allocate a String per line.
+    pub fn visible_lines(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> impl Iterator<Item = (usize, Cow<'_, str>)> + '_ {
+        let end = start.saturating_add(count).min(self.rope.len_lines());
+        (start.min(end)..end).map(move |line_idx| {
+            let mut line = self.rope.line(line_idx);
+            let len = line.len_chars();
+            if len > 0 && line.char(len - 1) == '\n' {
+                line = line.slice(..len - 1);
+            }
+            let text = match line.as_str() {
+                Some(text) => Cow::Borrowed(text),
+                None => Cow::Owned(line.to_string()),
+            };
+            (line_idx, text)
+        })
+    }
+
     pub fn line_len(&self, line_idx: usize) -> usize {
         if line_idx < self.rope.len_lines() {
             self.rope.line(line_idx).len_chars()
@@ -200,11 +343,12 @@ impl Buffer {
         let content = fs::read_to_string(path.as_ref())?;
         self.rope = Rope::from_str(&content);
         self.file_path = Some(path.as_ref().to_string_lossy().to_string());
+        self.final_newline = content.ends_with('\n');
         self.modified = false;
         self.version = 0;
 
         // Clear cache when loading new file
-        self.line_cache.clear();
+        self.clear_caches();
 
         // Detect language and set highlighter
         if let Some(extension) = path.as_ref().extension() {
@@ -240,12 +384,42 @@ impl Buffer {
     }
 
     pub fn save_to_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), BufferError> {
-        fs::write(path.as_ref(), self.rope.to_string())?;
+        self.save_to_file_with_backup(path, false)
+    }
+
+    /// Save atomically, keeping the previous file contents in `<path>~` if `backup` is set
+    pub fn save_to_file_with_backup<P: AsRef<Path>>(&mut self, path: P, backup: bool) -> Result<(), BufferError> {
+        write_atomic(path.as_ref(), &self.content_for_save(), backup)?;
         self.file_path = Some(path.as_ref().to_string_lossy().to_string());
         self.modified = false;
         Ok(())
     }
 
+    /// Replace the whole contents, e.g. to restore an undo snapshot
+    pub fn set_rope(&mut self, rope: Rope) {
+        if rope == self.rope {
+            return;
+        }
+        self.rope = rope;
+        self.modified = true;
+        self.version += 1;
+        self.clear_caches();
+        self.schedule_highlight();
+    }
+
+    /// Text written to disk: the buffer contents, with the final newline put
+    /// back if the file had one and line breaks converted to `line_ending`
+    fn content_for_save(&self) -> String {
+        let mut content = self.rope.to_string();
+        if self.final_newline && !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        match self.line_ending {
+            Some(ending) => ending.convert(&content),
+            None => content,
+        }
+    }
+
     /// Async version of load_from_file - runs file I/O on thread pool to avoid blocking UI
     pub async fn load_from_file_async<P: AsRef<Path>>(&mut self, path: P) -> Result<(), BufferError> {
         let path_buf = path.as_ref().to_path_buf();
@@ -258,11 +432,12 @@ impl Buffer {
 
         self.rope = Rope::from_str(&content);
         self.file_path = Some(path.as_ref().to_string_lossy().to_string());
+        self.final_newline = content.ends_with('\n');
         self.modified = false;
         self.version = 0;
 
         // Clear cache when loading new file
-        self.line_cache.clear();
+        self.clear_caches();
 
         // Detect language and set highlighter
         if let Some(extension) = path.as_ref().extension() {
@@ -297,13 +472,14 @@ impl Buffer {
         Ok(())
     }
 
-    /// Async version of save_to_file - runs file I/O on thread pool to avoid blocking UI
-    pub async fn save_to_file_async<P: AsRef<Path>>(&mut self, path: P) -> Result<(), BufferError> {
+    /// Async version of save_to_file_with_backup - runs file I/O on thread pool
+    /// to avoid blocking UI
+    pub async fn save_to_file_async<P: AsRef<Path>>(&mut self, path: P, backup: bool) -> Result<(), BufferError> {
         let path_buf = path.as_ref().to_path_buf();
-        let content = self.rope.to_string();
+        let content = self.content_for_save();
 
         tokio::task::spawn_blocking(move || {
-            std::fs::write(&path_buf, content)
+            write_atomic(&path_buf, &content, backup)
                 .map_err(BufferError::Io)
         })
         .await
@@ -322,23 +498,50 @@ impl Buffer {
     ) -> Result<(usize, usize), BufferError> {
         let original_text = self.rope.to_string();
         let formatted_text = formatter.format_text(&original_text)?;
+        // Already formatted: leave the buffer (and its modified flag) alone
+        if formatted_text == original_text {
+            return Ok((cursor_line, cursor_col));
+        }
 
-        // Simple cursor mapping: keep same line, clamp column
-        let new_line_count = formatted_text.lines().count();
-        let new_line = cursor_line.min(new_line_count.saturating_sub(1));
-        let new_col = if let Some(line) = formatted_text.lines().nth(new_line) {
-            cursor_col.min(line.len())
-        } else {
-            0
-        };
+        let (new_line, new_col) =
+            map_position_after_format(&original_text, &formatted_text, cursor_line, cursor_col);
 
         self.rope = Rope::from_str(&formatted_text);
         self.modified = true;
         self.version += 1;
+        self.clear_caches();
         // TODO: Update highlighter
         Ok((new_line, new_col))
     }
 
+    /// Apply a formatter's edits, given as spans of the current text, keeping
+    /// the cursor on the same text as `format_buffer` does
+    pub fn apply_format_edits(
+        &mut self,
+        mut edits: Vec<(Position, Position, String)>,
+        cursor_line: usize,
+        cursor_col: usize,
+    ) -> Result<(usize, usize), BufferError> {
+        let original_text = self.rope.to_string();
+        // Back to front so earlier spans stay put; stable, so inserts at
+        // the same place keep their order
+        edits.sort_by_key(|(start, _, _)| (start.line, start.col));
+        for (start, end, text) in edits.iter().rev() {
+            self.replace_range(*start, *end, text)?;
+        }
+
+        let formatted_text = self.rope.to_string();
+        if formatted_text == original_text {
+            return Ok((cursor_line, cursor_col));
+        }
+        Ok(map_position_after_format(
+            &original_text,
+            &formatted_text,
+            cursor_line,
+            cursor_col,
+        ))
+    }
+
     /// Set language using registry (for dynamic language detection)
     pub fn set_language_from_registry(
         &mut self,
@@ -380,7 +583,8 @@ impl Buffer {
     pub fn update_highlighter(&mut self) -> Result<(), BufferError> {
         if let Some(highlighter) = &mut self.highlighter {
             let text = self.rope.to_string();
-            highlighter.parse(&text).map_err(|_| {
+            // Edits were reported as they happened, so only their lines are re-extracted
+            highlighter.reparse(&text).map_err(|_| {
                 BufferError::Io(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
                     "Parse error",
@@ -408,20 +612,52 @@ impl Buffer {
 
         // Extract the text first (for yanking)
         let deleted = self.rope.slice(start_char..end_char).to_string();
+        if deleted.is_empty() {
+            return Ok(deleted);
+        }
 
         // Delete the range
-        self.rope.remove(start_char..end_char);
+        self.remove_chars(start_char..end_char);
         self.modified = true;
         self.version += 1;
-        // Invalidate cache for affected lines
-        for line in start.line..=end.line.min(self.line_count()) {
-            self.invalidate_line_cache(line);
-        }
         self.schedule_highlight();
 
         Ok(deleted)
     }
 
+    /// Replace the text between two positions with `new_text` as one edit,
+    /// returning the position just after the inserted text
+    pub fn replace_range(
+        &mut self,
+        start: Position,
+        end: Position,
+        new_text: &str,
+    ) -> Result<Position, BufferError> {
+        let (start, end) = if start.line < end.line
+            || (start.line == end.line && start.col <= end.col)
+        {
+            (start, end)
+        } else {
+            (end, start)
+        };
+
+        let start_char = self.rope.line_to_char(start.line) + start.col;
+        let end_char = self.rope.line_to_char(end.line) + end.col;
+        let after = start_char + new_text.chars().count();
+
+        if self.rope.slice(start_char..end_char) == new_text {
+            return Ok(self.char_to_position(after));
+        }
+
+        self.remove_chars(start_char..end_char);
+        self.insert_chars(start_char, new_text);
+        self.modified = true;
+        self.version += 1;
+        self.schedule_highlight();
+
+        Ok(self.char_to_position(after))
+    }
+
     /// Delete an entire line and return it
     pub fn delete_line(&mut self, line: usize) -> Result<String, BufferError> {
         if line >= self.line_count() {
@@ -432,10 +668,13 @@ impl Buffer {
         let line_end = self.rope.line_to_char(line + 1);
 
         let deleted = self.rope.slice(line_start..line_end).to_string();
-        self.rope.remove(line_start..line_end);
+        if deleted.is_empty() {
+            // The empty last line of a buffer
+            return Ok(deleted);
+        }
+        self.remove_chars(line_start..line_end);
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
 
         Ok(deleted)
@@ -443,19 +682,21 @@ impl Buffer {
 
     /// Delete multiple lines and return them
     pub fn delete_lines(&mut self, start_line: usize, count: usize) -> Result<String, BufferError> {
+        if start_line >= self.line_count() {
+            return Ok(String::new());
+        }
         let end_line = (start_line + count).min(self.line_count());
 
         let start_char = self.rope.line_to_char(start_line);
         let end_char = self.rope.line_to_char(end_line);
 
         let deleted = self.rope.slice(start_char..end_char).to_string();
-        self.rope.remove(start_char..end_char);
+        if deleted.is_empty() {
+            return Ok(deleted);
+        }
+        self.remove_chars(start_char..end_char);
         self.modified = true;
         self.version += 1;
-        // Invalidate cache for affected lines
-        for line in start_line..end_line {
-            self.invalidate_line_cache(line);
-        }
         self.schedule_highlight();
 
         Ok(deleted)
@@ -483,31 +724,54 @@ impl Buffer {
         self.line(line).unwrap_or_default()
     }
 
-    /// Join current line with next line
+    /// Get `count` lines starting at `start`, joined without the final newline
+    pub fn get_lines(&self, start: usize, count: usize) -> String {
+        let end = (start + count).min(self.line_count());
+        (start..end)
+            .map(|line| self.get_line_content(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Join `line` with the one below (Vim's `J`). The next line's indent is
+    /// dropped and a single space put between them, unless the line already
+    /// ends in a blank, either side is empty, or the next line starts with `)`.
     pub fn join_lines(&mut self, line: usize) -> Result<(), BufferError> {
-        if line + 1 >= self.line_count() {
-            return Ok(());
-        }
+        self.join_with(line, true)
+    }
 
-        // Find the end of current line (before newline)
-        let current_line_end = self.rope.line_to_char(line + 1) - 1;
-        let next_line_start = self.rope.line_to_char(line + 1);
+    /// Join `line` with the one below, leaving whitespace alone (Vim's `gJ`)
+    pub fn join_lines_no_space(&mut self, line: usize) -> Result<(), BufferError> {
+        self.join_with(line, false)
+    }
 
-        // Remove newline
-        self.rope.remove(current_line_end..next_line_start);
+    fn join_with(&mut self, line: usize, spaced: bool) -> Result<(), BufferError> {
+        if line >= self.last_content_line() {
+            return Ok(());
+        }
 
-        // Add a space if there isn't one
-        let space_pos = self.rope.line_to_char(line + 1) - 1;
-        if self.rope.len_chars() > space_pos {
-            let last_char = self.rope.char(space_pos);
-            if !last_char.is_whitespace() {
-                self.rope.insert_char(space_pos + 1, ' ');
+        let current = self.get_line_content(line);
+        let next = self.get_line_content(line + 1);
+        let join_at = self.rope.line_to_char(line) + current.chars().count();
+        let mut remove_end = self.rope.line_to_char(line + 1);
+        let mut separator = "";
+        if spaced {
+            let rest = next.trim_start_matches([' ', '\t']);
+            remove_end += next.chars().count() - rest.chars().count();
+            if !current.is_empty()
+                && !current.ends_with([' ', '\t'])
+                && !rest.is_empty()
+                && !rest.starts_with(')')
+            {
+                separator = " ";
             }
         }
 
+        self.remove_chars(join_at..remove_end);
+        self.insert_chars(join_at, separator);
+
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
 
         Ok(())
@@ -524,10 +788,9 @@ impl Buffer {
         let end_idx = (char_idx + count).min(self.rope.len_chars());
 
         let deleted = self.rope.slice(char_idx..end_idx).to_string();
-        self.rope.remove(char_idx..end_idx);
+        self.remove_chars(char_idx..end_idx);
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
 
         Ok(deleted)
@@ -541,71 +804,163 @@ impl Buffer {
         }
 
         let char_idx = self.rope.line_to_char(line) + col;
-        self.rope.remove(char_idx..char_idx + 1);
-        self.rope.insert_char(char_idx, new_char);
+        if self.rope.char(char_idx) == new_char {
+            return Ok(());
+        }
+        self.remove_chars(char_idx..char_idx + 1);
+        self.insert_chars(char_idx, new_char.encode_utf8(&mut [0; 4]));
 
         self.modified = true;
         self.version += 1;
-        self.invalidate_line_cache(line);
         self.schedule_highlight();
 
         Ok(())
     }
 
-    /// Indent a range of lines
-    pub fn indent_range(&mut self, start_line: usize, end_line: usize, amount: usize) -> Result<(), BufferError> {
-        let indent_str = " ".repeat(amount);
+    /// Swap the characters at `col` and `col + 1`, returning whether both exist
+    pub fn swap_chars(&mut self, line: usize, col: usize) -> Result<bool, BufferError> {
+        if col + 1 >= self.get_line_content(line).chars().count() {
+            return Ok(false);
+        }
+        let start = Position::new(line, col);
+        let end = Position::new(line, col + 2);
+        let swapped: String = self.get_range(start, end).chars().rev().collect();
+        self.replace_range(start, end, &swapped)?;
+        Ok(true)
+    }
 
-        for line in (start_line..=end_line.min(self.line_count().saturating_sub(1))).rev() {
-            let line_start = self.rope.line_to_char(line);
-            self.rope.insert(line_start, &indent_str);
+    /// Change the case of the text between `start` (inclusive) and `end` (exclusive)
+    pub fn change_case(&mut self, start: Position, end: Position, case: CaseChange) -> Result<(), BufferError> {
+        let original = self.get_range(start, end);
+        let changed: String = original.chars().map(|c| case.apply(c)).collect();
+        if changed == original {
+            return Ok(());
         }
 
+        let first = if start.line <= end.line { start } else { end };
+        let start_char = self.position_to_char(first);
+        self.remove_chars(start_char..start_char + original.chars().count());
+        self.insert_chars(start_char, &changed);
+
         self.modified = true;
         self.version += 1;
-        // Invalidate cache for affected lines
-        for line in start_line..=end_line.min(self.line_count().saturating_sub(1)) {
-            self.invalidate_line_cache(line);
-        }
         self.schedule_highlight();
 
         Ok(())
     }
 
-    /// Unindent a range of lines
-    pub fn unindent_range(&mut self, start_line: usize, end_line: usize, amount: usize) -> Result<(), BufferError> {
-        let indent_str = " ".repeat(amount);
-
-        for line in start_line..=end_line.min(self.line_count().saturating_sub(1)) {
-            if let Some(line_content) = self.line(line) {
-                if line_content.starts_with(&indent_str) {
-                    let line_start = self.rope.line_to_char(line);
-                    let line_end = line_start + indent_str.len();
-                    self.rope.remove(line_start..line_end);
-                } else {
-                    // Remove as many spaces as possible up to amount
-                    let line_start = self.rope.line_to_char(line);
-                    let remove_count = line_content
-                        .chars()
-                        .take(amount)
-                        .take_while(|c| *c == ' ')
-                        .count();
-                    if remove_count > 0 {
-                        let line_end = line_start + remove_count;
-                        self.rope.remove(line_start..line_end);
-                    }
-                }
+    /// Indent a range of lines by `levels` shift widths
+    pub fn indent_range(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        levels: usize,
+        options: &EditorOptions,
+    ) -> Result<(), BufferError> {
+        self.shift_range(start_line, end_line, levels as isize, options)
+    }
+
+    /// Unindent a range of lines by `levels` shift widths
+    pub fn unindent_range(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        levels: usize,
+        options: &EditorOptions,
+    ) -> Result<(), BufferError> {
+        self.shift_range(start_line, end_line, -(levels as isize), options)
+    }
+
+    /// Shift lines by `levels` shift widths, rebuilding each line's leading
+    /// whitespace so mixed tabs and spaces follow `expand_tab`. Empty lines are left alone.
+    fn shift_range(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        levels: isize,
+        options: &EditorOptions,
+    ) -> Result<(), BufferError> {
+        let tab_width = options.tab_width.max(1);
+        let last_line = end_line.min(self.line_count().saturating_sub(1));
+        let mut changed = false;
+
+        for line in start_line..=last_line {
+            let Some(content) = self.line(line) else {
+                continue;
+            };
+            if content.is_empty() {
+                continue;
+            }
+
+            let leading: Vec<char> = content
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let width = leading.iter().fold(0, |width, c| match c {
+                '\t' => (width / tab_width + 1) * tab_width,
+                _ => width + 1,
+            });
+            let new_width = (width as isize + levels * tab_width as isize).max(0) as usize;
+            let new_indent = if options.expand_tab {
+                " ".repeat(new_width)
+            } else {
+                "\t".repeat(new_width / tab_width) + &" ".repeat(new_width % tab_width)
+            };
+            if new_indent.chars().eq(leading.iter().copied()) {
+                continue;
+            }
+
+            let line_start = self.rope.line_to_char(line);
+            self.remove_chars(line_start..line_start + leading.len());
+            self.insert_chars(line_start, &new_indent);
+            changed = true;
+        }
+
+        if changed {
+            self.modified = true;
+            self.version += 1;
+            self.schedule_highlight();
+        }
+
+        Ok(())
+    }
+
+    /// Comment out lines `start_line..=end_line` with the line comment `token`,
+    /// or uncomment them if every non-blank line is already commented. The
+    /// token goes after each line's indentation; blank lines are left alone.
+    pub fn toggle_comment(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        token: &str,
+    ) -> Result<(), BufferError> {
+        let last_line = end_line.min(self.line_count().saturating_sub(1));
+        let lines: Vec<(usize, String)> = (start_line..=last_line)
+            .filter_map(|line| self.line(line).map(|content| (line, content)))
+            .filter(|(_, content)| !content.trim().is_empty())
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let uncomment = lines
+            .iter()
+            .all(|(_, content)| content.trim_start().starts_with(token));
+
+        for (line, content) in lines {
+            let indent = content.chars().take_while(|c| c.is_whitespace()).count();
+            let at = self.rope.line_to_char(line) + indent;
+            if uncomment {
+                let rest = &content.trim_start()[token.len()..];
+                let len = token.chars().count() + usize::from(rest.starts_with(' '));
+                self.remove_chars(at..at + len);
+            } else {
+                self.insert_chars(at, &format!("{} ", token));
             }
         }
 
         self.modified = true;
         self.version += 1;
-        // Invalidate cache for affected lines
-        for line in start_line..=end_line.min(self.line_count().saturating_sub(1)) {
-            self.invalidate_line_cache(line);
-        }
         self.schedule_highlight();
-
         Ok(())
     }
 
@@ -623,6 +978,62 @@ impl Buffer {
     }
 }
 
+/// Find where `(line, col)` of `old` ended up in `new` after reformatting.
+///
+/// Formatters mostly move whitespace around, so the position is found by
+/// counting non-whitespace characters. Counting from the start and from the
+/// end agree unless the formatter also added or removed characters (such as a
+/// trailing comma), in which case the count that lands on the same character
+/// wins. A cursor on whitespace lands just after the preceding character.
+fn map_position_after_format(old: &str, new: &str, line: usize, col: usize) -> (usize, usize) {
+    let mut before = 0;
+    let mut after = 0;
+    let mut on_char = None;
+    let mut prev_char = None;
+    for (ch, pos) in non_whitespace_positions(old) {
+        match pos.cmp(&(line, col)) {
+            std::cmp::Ordering::Less => {
+                before += 1;
+                prev_char = Some(ch);
+            }
+            std::cmp::Ordering::Equal => on_char = Some(ch),
+            std::cmp::Ordering::Greater => after += 1,
+        }
+    }
+
+    let chars: Vec<_> = non_whitespace_positions(new).collect();
+    let from_end = chars.len().checked_sub(after + 1);
+    let (wanted, from_start, shift) = match (on_char, prev_char) {
+        (Some(ch), _) => (ch, before, 0),
+        (None, Some(ch)) => (ch, before - 1, 1),
+        (None, None) => return (0, 0),
+    };
+
+    let idx = [Some(from_start), from_end]
+        .into_iter()
+        .flatten()
+        .find(|&idx| chars.get(idx).is_some_and(|&(ch, _)| ch == wanted))
+        .unwrap_or(from_start);
+    match chars.get(idx).or(chars.last()) {
+        Some(&(_, (line, col))) => (line, col + shift),
+        None => (0, 0),
+    }
+}
+
+/// Non-whitespace characters of `text` with their (line, column)
+fn non_whitespace_positions(text: &str) -> impl Iterator<Item = (char, (usize, usize))> + '_ {
+    let mut pos = (0, 0);
+    text.chars().filter_map(move |ch| {
+        let current = pos;
+        if ch == '\n' {
+            pos = (pos.0 + 1, 0);
+        } else {
+            pos.1 += 1;
+        }
+        (!ch.is_whitespace()).then_some((ch, current))
+    })
+}
+
 #[test]
 fn test_insert_char() {
     let mut buffer = Buffer::new();
@@ -665,6 +1076,116 @@ fn test_load_and_save() {
     assert_eq!(content, "hello\nworld");
 }
 
+#[test]
+fn test_final_newline_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    for content in ["with\nnewline\n", "without\nnewline"] {
+        let path = dir.path().join("file.txt");
+        fs::write(&path, content).unwrap();
+
+        let mut buffer = Buffer::new();
+        buffer.load_from_file(&path).unwrap();
+        assert_eq!(buffer.final_newline, content.ends_with('\n'));
+        buffer.save_to_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+}
+
+#[test]
+fn test_final_newline_survives_deleting_last_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, "a\nb\n").unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.load_from_file(&path).unwrap();
+    buffer.delete_range(Position::new(0, 1), Position::new(1, 1)).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a\n");
+    buffer.delete_range(Position::new(0, 1), Position::new(1, 0)).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a");
+    buffer.save_to_file(&path).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\n");
+}
+
+#[test]
+fn test_atomic_save_replaces_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, "old contents").unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.insert_text("new", 0, 0).unwrap();
+    buffer.save_to_file(&path).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    assert!(!buffer.modified);
+    // Only the target remains; the temp file was renamed away
+    let entries: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn test_atomic_save_keeps_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, "old contents").unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.insert_text("new", 0, 0).unwrap();
+    buffer.save_to_file_with_backup(&path, true).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file.txt~")).unwrap(),
+        "old contents"
+    );
+}
+
+#[tokio::test]
+async fn test_async_save_keeps_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    fs::write(&path, "old contents").unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.insert_text("new", 0, 0).unwrap();
+    buffer.save_to_file_async(&path, true).await.unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file.txt~")).unwrap(),
+        "old contents"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_atomic_save_preserves_permissions_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let real = dir.path().join("real.sh");
+    fs::write(&real, "echo old").unwrap();
+    fs::set_permissions(&real, fs::Permissions::from_mode(0o755)).unwrap();
+    let link = dir.path().join("link.sh");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    write_atomic(&link, "echo new", false).unwrap();
+
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_to_string(&real).unwrap(), "echo new");
+    let mode = fs::metadata(&real).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o755);
+}
+
+#[test]
+fn test_atomic_save_failure_leaves_no_temp_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing_dir = dir.path().join("missing").join("file.txt");
+
+    assert!(write_atomic(&missing_dir, "data", false).is_err());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
 #[test]
 fn test_empty_buffer_line_count() {
     let buffer = Buffer::new();
@@ -780,6 +1301,269 @@ fn test_line_to_byte_consistency() {
     assert!(byte1 > byte0);
 }
 
+#[test]
+fn test_last_content_line() {
+    let mut buffer = Buffer::new();
+    assert_eq!(buffer.last_content_line(), 0);
+    buffer.insert_text("a\nb", 0, 0).unwrap();
+    assert_eq!(buffer.last_content_line(), 1);
+    buffer.insert_text("\n", 1, 1).unwrap();
+    assert_eq!(buffer.line_count(), 3);
+    assert_eq!(buffer.last_content_line(), 1);
+}
+
+#[test]
+fn test_indent_and_unindent_range() {
+    let mut buffer = Buffer::new();
+    buffer.insert_text("a\n\n  b\nc", 0, 0).unwrap();
+    let options = EditorOptions::default();
+
+    buffer.indent_range(0, 2, 1, &options).unwrap();
+    assert_eq!(buffer.rope.to_string(), "    a\n\n      b\nc");
+
+    buffer.unindent_range(0, 2, 1, &options).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a\n\n  b\nc");
+}
+
+#[test]
+fn test_shift_mixed_indentation() {
+    let mut buffer = Buffer::new();
+    buffer.insert_text("\t  x\n  \ty", 0, 0).unwrap();
+    let tabs = EditorOptions {
+        tab_width: 4,
+        expand_tab: false,
+        ..EditorOptions::default()
+    };
+
+    buffer.indent_range(0, 1, 1, &tabs).unwrap();
+    assert_eq!(buffer.rope.to_string(), "\t\t  x\n\t\ty");
+
+    let spaces = EditorOptions::default();
+    buffer.unindent_range(0, 1, 1, &spaces).unwrap();
+    assert_eq!(buffer.rope.to_string(), "      x\n    y");
+}
+
+#[test]
+fn test_change_case_range() {
+    let mut buffer = Buffer::new();
+    buffer.insert_text("Hello, Wörld 42\nÉtÉ", 0, 0).unwrap();
+
+    buffer.change_case(Position::new(0, 0), Position::new(0, 12), CaseChange::Upper).unwrap();
+    assert_eq!(buffer.line(0).unwrap(), "HELLO, WÖRLD 42");
+
+    buffer.change_case(Position::new(0, 7), Position::new(1, 2), CaseChange::Toggle).unwrap();
+    assert_eq!(buffer.rope.to_string(), "HELLO, wörld 42\néTÉ");
+
+    buffer.change_case(Position::new(1, 0), Position::new(1, 3), CaseChange::Lower).unwrap();
+    assert_eq!(buffer.line(1).unwrap(), "été");
+}
+
+#[test]
+fn test_case_change_expands_characters() {
+    assert_eq!(CaseChange::Upper.apply('ß'), "SS");
+    assert_eq!(CaseChange::Toggle.apply('1'), "1");
+    assert_eq!(CaseChange::Toggle.apply('Σ'), "σ");
+}
+
+#[test]
+fn test_edit_keeps_highlight_tokens_above() {
+    let mut buffer = Buffer::new();
+    buffer.highlighter = Some(SyntaxHighlighter::new(get_language_config(LanguageId::Rust)).unwrap());
+    buffer.insert_text(&"fn f() {}\n".repeat(80), 0, 0).unwrap();
+    buffer.update_highlighter().unwrap();
+
+    // Keep the edit from re-highlighting straight away
+    buffer.highlight_debounce = Duration::from_secs(60);
+    buffer.last_highlight_time = Instant::now();
+    buffer.insert_text("let x = 1; ", 40, 0).unwrap();
+    let highlighter = buffer.highlighter.as_ref().unwrap();
+    assert!(highlighter.is_line_cached(10));
+    assert!(!highlighter.is_line_cached(40));
+
+    buffer.update_highlighter().unwrap();
+    assert!(buffer.highlighter.as_ref().unwrap().get_line_highlights(40).is_some());
+}
+
+#[test]
+fn test_edit_moves_highlight_tokens_below() {
+    let mut buffer = Buffer::new();
+    buffer.highlighter = Some(SyntaxHighlighter::new(get_language_config(LanguageId::Rust)).unwrap());
+    buffer.insert_text(&"fn f() {}\n".repeat(80), 0, 0).unwrap();
+    buffer.update_highlighter().unwrap();
+    let before = buffer.highlighter.as_ref().unwrap().get_line_highlights(49).unwrap()[0].start;
+
+    buffer.highlight_debounce = Duration::from_secs(60);
+    buffer.last_highlight_time = Instant::now();
+    buffer.insert_text("fn g() {}\n", 40, 0).unwrap();
+    let highlighter = buffer.highlighter.as_ref().unwrap();
+    assert!(!highlighter.is_line_cached(40));
+    // Line 50 is the old line 49, ten bytes further on
+    assert_eq!(highlighter.get_line_highlights(50).unwrap()[0].start, before + 10);
+}
+
+#[test]
+fn test_pending_highlight_flushes_after_debounce() {
+    let mut buffer = Buffer::new();
+    buffer.highlighter = Some(SyntaxHighlighter::new(get_language_config(LanguageId::Rust)).unwrap());
+    buffer.highlight_debounce = Duration::from_millis(20);
+    buffer.last_highlight_time = Instant::now();
+    buffer.insert_text("fn f() {}\n", 0, 0).unwrap();
+    assert!(buffer.has_pending_highlight());
+    assert!(!buffer.check_pending_highlight());
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(buffer.check_pending_highlight());
+    assert!(!buffer.has_pending_highlight());
+    assert!(buffer.highlighter.as_ref().unwrap().get_line_highlights(0).is_some());
+}
+
+#[test]
+fn test_format_mapping_follows_wrapped_lines() {
+    let old = "fn f() { call(alpha, beta, gamma); }\n";
+    let new = "fn f() {\n    call(\n        alpha,\n        beta,\n        gamma,\n    );\n}\n";
+    // On the `b` of `beta`
+    assert_eq!(map_position_after_format(old, new, 0, 21), (3, 8));
+    // On the space before `gamma`
+    assert_eq!(map_position_after_format(old, new, 0, 26), (3, 13));
+    // The closing brace, after the formatter added a trailing comma
+    assert_eq!(map_position_after_format(old, new, 0, 35), (6, 0));
+    assert_eq!(map_position_after_format(old, new, 0, 33), (5, 5));
+}
+
+#[test]
+fn test_join_lines() {
+    let mut buffer = Buffer::new();
+    buffer.insert_text("a\n    b\nc \n  d\ne\n)\n\nf\n", 0, 0).unwrap();
+    buffer.join_lines(0).unwrap();
+    assert_eq!(buffer.line(0).unwrap(), "a b");
+    // No extra space after a trailing blank or before `)`
+    buffer.join_lines(1).unwrap();
+    assert_eq!(buffer.line(1).unwrap(), "c d");
+    buffer.join_lines(2).unwrap();
+    assert_eq!(buffer.line(2).unwrap(), "e)");
+    // Nor when joining an empty line
+    buffer.join_lines(2).unwrap();
+    assert_eq!(buffer.line(2).unwrap(), "e)");
+    assert_eq!(buffer.line(3).unwrap(), "f");
+
+    buffer.insert_text("\n  g", 3, 1).unwrap();
+    buffer.join_lines_no_space(3).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a b\nc d\ne)\nf  g\n");
+    // The last line has nothing below it to join
+    buffer.join_lines(3).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a b\nc d\ne)\nf  g\n");
+}
+
+#[test]
+fn test_format_mapping_follows_joined_lines() {
+    let old = "let x = [\n    1,\n    2\n];\nlet y = 3;\n";
+    let new = "let x = [1, 2];\nlet y = 3;\n";
+    assert_eq!(map_position_after_format(old, new, 2, 4), (0, 12));
+    assert_eq!(map_position_after_format(old, new, 4, 4), (1, 4));
+    // Leading whitespace and positions past the end of the text
+    assert_eq!(map_position_after_format(old, new, 1, 0), (0, 9));
+    assert_eq!(map_position_after_format(old, "", 4, 4), (0, 0));
+}
+
+#[test]
+fn test_apply_format_edits() {
+    let mut buffer = Buffer::new();
+    buffer.rope = Rope::from_str("fn main(){\nlet  x=1;}\n");
+    let edits = vec![
+        (Position::new(1, 9), Position::new(1, 9), "\n".to_string()),
+        (Position::new(0, 9), Position::new(0, 9), " ".to_string()),
+        (Position::new(1, 0), Position::new(1, 0), "    ".to_string()),
+        (Position::new(1, 3), Position::new(1, 5), " ".to_string()),
+        (Position::new(1, 6), Position::new(1, 7), " = ".to_string()),
+    ];
+    // The cursor on `1` follows it
+    let cursor = buffer.apply_format_edits(edits, 1, 7).unwrap();
+    assert_eq!(buffer.rope.to_string(), "fn main() {\n    let x = 1;\n}\n");
+    assert_eq!(cursor, (1, 12));
+
+    let version = buffer.version;
+    assert_eq!(buffer.apply_format_edits(Vec::new(), 0, 3).unwrap(), (0, 3));
+    assert_eq!(buffer.version, version);
+}
+
+#[test]
+fn test_noop_edits_keep_buffer_unmodified() {
+    let options = EditorOptions::default();
+    let mut buffer = Buffer::new();
+    buffer.delete_char(0, 0).unwrap();
+    buffer.delete_line(0).unwrap();
+    assert!(!buffer.modified);
+
+    buffer.rope = Rope::from_str("abc\n");
+    buffer.replace_char(0, 1, 'b').unwrap();
+    buffer.insert_text("", 0, 0).unwrap();
+    buffer
+        .delete_range(Position::new(0, 2), Position::new(0, 2))
+        .unwrap();
+    buffer.delete_lines(1, 3).unwrap();
+    buffer
+        .replace_range(Position::new(0, 1), Position::new(0, 2), "b")
+        .unwrap();
+    buffer.unindent_range(0, 0, 1, &options).unwrap();
+    buffer.set_rope(Rope::from_str("abc\n"));
+    assert!(!buffer.modified);
+    assert_eq!(buffer.version, 0);
+
+    buffer.replace_char(0, 1, 'x').unwrap();
+    assert!(buffer.modified);
+    assert_eq!(buffer.version, 1);
+}
+
+#[test]
+fn test_replace_range() {
+    let mut buffer = Buffer::new();
+    buffer.rope = Rope::from_str("one two\nthree\n");
+
+    let end = buffer
+        .replace_range(Position::new(0, 4), Position::new(1, 3), "2\n3")
+        .unwrap();
+    assert_eq!(buffer.rope.to_string(), "one 2\n3ee\n");
+    assert_eq!(end, Position::new(1, 1));
+    assert_eq!(buffer.version, 1);
+    assert!(buffer.modified);
+
+    // Reversed positions, and an empty span is a plain insert
+    let end = buffer
+        .replace_range(Position::new(0, 5), Position::new(0, 0), "")
+        .unwrap();
+    assert_eq!(buffer.rope.to_string(), "\n3ee\n");
+    assert_eq!(end, Position::new(0, 0));
+    let end = buffer
+        .replace_range(Position::new(1, 0), Position::new(1, 0), ">> ")
+        .unwrap();
+    assert_eq!(buffer.rope.to_string(), "\n>> 3ee\n");
+    assert_eq!(end, Position::new(1, 3));
+    assert_eq!(buffer.version, 3);
+}
+
+#[test]
+fn test_visible_lines_match_line() {
+    let mut buffer = Buffer::new();
+    // Long enough to span several rope chunks
+    let text: String = (0..2000).map(|i| format!("line {i}\r\n")).collect();
+    buffer.insert_text(&text, 0, 0).unwrap();
+
+    let lines: Vec<_> = buffer.visible_lines(1995, 10).collect();
+    assert_eq!(lines.len(), 6);
+    for (idx, line) in &lines {
+        assert_eq!(Some(line.to_string()), buffer.line(*idx));
+    }
+    assert_eq!(lines[5], (2000, Cow::Borrowed("")));
+    assert_eq!(buffer.visible_lines(3000, 10).count(), 0);
+    assert!(
+        buffer
+            .visible_lines(0, 2000)
+            .filter(|(_, line)| matches!(line, Cow::Borrowed(_)))
+            .count()
+            > 1900
+    );
+}
+
 // proptest! {
 //     #[test]
 //     fn buffer_operations_preserve_invariants(ops in prop::collection::vec((any::<char>(), 0..10usize, 0..100usize), 1..50)) {
@@ -791,3 +1575,41 @@ fn test_line_to_byte_consistency() {
 //         prop_assert!(buffer.line_count() >= 1);
 //     }
 // }
+
+#[test]
+fn test_toggle_comment() {
+    let mut buffer = Buffer::new();
+    buffer.rope = Rope::from_str("fn main() {\n    let x = 1;\n\n    call(x);\n}\n");
+
+    buffer.toggle_comment(1, 3, "//").unwrap();
+    assert_eq!(
+        buffer.rope,
+        "fn main() {\n    // let x = 1;\n\n    // call(x);\n}\n"
+    );
+    assert!(buffer.modified);
+
+    buffer.toggle_comment(1, 3, "//").unwrap();
+    assert_eq!(buffer.rope, "fn main() {\n    let x = 1;\n\n    call(x);\n}\n");
+
+    // A token without a following space is removed too
+    buffer.rope = Rope::from_str("#a\n  # b\n");
+    buffer.toggle_comment(0, 1, "#").unwrap();
+    assert_eq!(buffer.rope, "a\n  b\n");
+}
+
+#[test]
+fn test_toggle_comment_mixed_lines_comments_all() {
+    let mut buffer = Buffer::new();
+    buffer.rope = Rope::from_str("# done\nx = 1\n");
+
+    buffer.toggle_comment(0, 1, "#").unwrap();
+    assert_eq!(buffer.rope, "# # done\n# x = 1\n");
+    buffer.toggle_comment(0, 1, "#").unwrap();
+    assert_eq!(buffer.rope, "# done\nx = 1\n");
+
+    // Only blank lines: nothing to do
+    buffer.rope = Rope::from_str("\n  \n");
+    buffer.modified = false;
+    buffer.toggle_comment(0, 1, "#").unwrap();
+    assert!(!buffer.modified);
+}
@@ -1,6 +1,8 @@
 use clap::Parser;
 use std::path::PathBuf;
 
+use crate::syntax::LanguageId;
+
 #[derive(Debug, Default, Parser)]
 #[command(name = "texty")]
 #[command(version = "0.1.0")]
@@ -20,6 +22,18 @@ pub struct CliArgs {
     /// List all available built-in themes and exit
     #[arg(long = "list-themes", short = 'L', action = clap::ArgAction::SetTrue)]
     pub list_themes: bool,
+
+    /// Treat the file as this type (rust, python, javascript, typescript)
+    /// instead of going by its extension
+    #[arg(long = "ft", visible_alias = "filetype", value_parser = parse_filetype)]
+    pub filetype: Option<LanguageId>,
+}
+
+fn parse_filetype(name: &str) -> Result<LanguageId, String> {
+    LanguageId::from_name(name).ok_or_else(|| {
+        let known: Vec<_> = LanguageId::ALL.iter().map(|id| id.name()).collect();
+        format!("unknown file type (expected one of {})", known.join(", "))
+    })
 }
 
 impl CliArgs {
@@ -84,6 +98,15 @@ mod tests {
         assert_eq!(args.theme, "monokai");
     }
 
+    #[test]
+    fn test_parse_filetype() {
+        let args = CliArgs::parse_from(["texty", "--ft", "python", "script"]);
+        assert_eq!(args.filetype, Some(LanguageId::Python));
+        let args = CliArgs::parse_from(["texty", "--filetype=rs"]);
+        assert_eq!(args.filetype, Some(LanguageId::Rust));
+        assert!(CliArgs::try_parse_from(["texty", "--ft", "cobol"]).is_err());
+    }
+
     #[test]
     fn test_directory_detection() {
         let temp_dir = TempDir::new().unwrap();
@@ -97,6 +120,7 @@ mod tests {
             terminal_palette: false,
             theme: "monokai".to_string(),
             list_themes: false,
+            filetype: None,
         };
 
         let dir_args = CliArgs {
@@ -104,6 +128,7 @@ mod tests {
             terminal_palette: false,
             theme: "monokai".to_string(),
             list_themes: false,
+            filetype: None,
         };
 
         let nonexistent_args = CliArgs {
@@ -111,6 +136,7 @@ mod tests {
             terminal_palette: false,
             theme: "monokai".to_string(),
             list_themes: false,
+            filetype: None,
         };
 
         assert!(file_args.exists());
@@ -0,0 +1,154 @@
+// src/code_point.rs - Typing a character by its code point after Ctrl-V in insert mode
+
+/// How the digits after Ctrl-V are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Radix {
+    /// `Ctrl-V 065`: up to three decimal digits
+    Decimal,
+    /// `Ctrl-V u00e9`: up to four hex digits
+    Hex4,
+    /// `Ctrl-V U0001f600`: up to eight hex digits
+    Hex8,
+}
+
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Radix::Decimal => 10,
+            Radix::Hex4 | Radix::Hex8 => 16,
+        }
+    }
+
+    fn max_digits(self) -> usize {
+        match self {
+            Radix::Decimal => 3,
+            Radix::Hex4 => 4,
+            Radix::Hex8 => 8,
+        }
+    }
+}
+
+/// What to do after a key was fed to a [`CodePointEntry`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodePointStep {
+    /// More digits may follow
+    Pending,
+    /// The entry is complete; insert this character
+    Insert(char),
+    /// The digits are not a character that can be inserted; insert nothing
+    Invalid(String),
+}
+
+/// Keys typed after Ctrl-V, collected until they name a character. As in
+/// Vim, a key other than `u`, `U` or a digit straight after Ctrl-V is
+/// inserted as it is.
+#[derive(Debug, Clone, Default)]
+pub struct CodePointEntry {
+    radix: Option<Radix>,
+    digits: String,
+}
+
+impl CodePointEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `c` continues the entry; any other key ends it
+    pub fn accepts(&self, c: char) -> bool {
+        match self.radix {
+            None => true,
+            Some(radix) => c.is_digit(radix.base()),
+        }
+    }
+
+    /// Feed a key that [`accepts`](Self::accepts) took
+    pub fn push(&mut self, c: char) -> CodePointStep {
+        let Some(radix) = self.radix else {
+            self.radix = match c {
+                'u' => Some(Radix::Hex4),
+                'U' => Some(Radix::Hex8),
+                '0'..='9' => Some(Radix::Decimal),
+                _ => return CodePointStep::Insert(c),
+            };
+            // Decimal entry starts with its first digit; `u`/`U` only pick the radix
+            if c.is_ascii_digit() {
+                self.digits.push(c);
+            }
+            return CodePointStep::Pending;
+        };
+        self.digits.push(c);
+        if self.digits.len() == radix.max_digits() {
+            self.finish()
+        } else {
+            CodePointStep::Pending
+        }
+    }
+
+    /// End the entry with the digits typed so far
+    pub fn finish(&mut self) -> CodePointStep {
+        let radix = self.radix.unwrap_or(Radix::Decimal);
+        let Ok(value) = u32::from_str_radix(&self.digits, radix.base()) else {
+            return CodePointStep::Invalid("No code point given".to_string());
+        };
+        match char::from_u32(value) {
+            Some(c) if !c.is_control() || c == '\t' || c == '\n' => CodePointStep::Insert(c),
+            _ => CodePointStep::Invalid(format!("Invalid code point: U+{:04X}", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(keys: &str) -> CodePointStep {
+        let mut entry = CodePointEntry::new();
+        let mut step = CodePointStep::Pending;
+        for c in keys.chars() {
+            if !entry.accepts(c) {
+                return entry.finish();
+            }
+            step = entry.push(c);
+        }
+        step
+    }
+
+    #[test]
+    fn test_hex_and_decimal() {
+        assert_eq!(feed("u00e9"), CodePointStep::Insert('é'));
+        assert_eq!(feed("U0001F600"), CodePointStep::Insert('😀'));
+        assert_eq!(feed("065"), CodePointStep::Insert('A'));
+        assert_eq!(feed("u00"), CodePointStep::Pending);
+    }
+
+    #[test]
+    fn test_short_code_ended_by_other_key() {
+        assert_eq!(feed("u3bb "), CodePointStep::Insert('λ'));
+        assert_eq!(feed("9x"), CodePointStep::Insert('\t'));
+    }
+
+    #[test]
+    fn test_other_key_is_literal() {
+        assert_eq!(feed("x"), CodePointStep::Insert('x'));
+    }
+
+    #[test]
+    fn test_invalid_code_points() {
+        assert_eq!(
+            feed("ud800"),
+            CodePointStep::Invalid("Invalid code point: U+D800".to_string())
+        );
+        assert_eq!(
+            feed("U00110000"),
+            CodePointStep::Invalid("Invalid code point: U+110000".to_string())
+        );
+        assert_eq!(
+            feed("000"),
+            CodePointStep::Invalid("Invalid code point: U+0000".to_string())
+        );
+        assert_eq!(
+            feed("u "),
+            CodePointStep::Invalid("No code point given".to_string())
+        );
+    }
+}
@@ -1,3 +1,23 @@
+use crate::buffer::CaseChange;
+use crate::motion::{CharSearch, TextObject};
+
+/// Lines a linewise operator such as `gc` covers, counted from the cursor line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineMotion {
+    /// The cursor line and this many lines below (`gcc`, `gcj`)
+    Down(usize),
+    /// The cursor line and this many lines above (`gck`)
+    Up(usize),
+    /// Up to the first line (`gcgg`)
+    FileStart,
+    /// Down to the last line (`gcG`)
+    FileEnd,
+    /// Down to the end of the paragraph (`gc}`)
+    ParagraphForward(usize),
+    /// Up to the start of the paragraph (`gc{`)
+    ParagraphBackward(usize),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     // Basic movement
@@ -10,33 +30,85 @@ pub enum Command {
     MoveWordForward(usize),
     MoveWordBackward(usize),
     MoveWordEnd(usize),
+    MoveWordEndBackward(usize),
+    MoveBigWordEndBackward(usize),
 
     // Line motion
     MoveLineStart,
     MoveLineEnd(usize),
     MoveFirstNonBlank,
 
+    // Character search within the line: `f`/`t`/`F`/`T`, then `;` and `,`
+    FindChar(CharSearch, usize),
+    RepeatFindChar { reverse: bool, count: usize },
+
     // File motion
     MoveFileStart,
     MoveFileEnd,
+    /// Go to the first non-blank of a 0-based line, clamped to the buffer (`:{number}`)
+    GotoLine(usize),
+    /// Go to the line this percentage of the way through the file (`{count}%`)
+    GotoPercent(usize),
+    /// Jump to the bracket matching the one at or after the cursor (`%`)
+    MoveMatchingPair,
     MoveScreenTop,
     MoveScreenMiddle,
     MoveScreenBottom,
 
+    // Inspection
+    /// Show the code point and UTF-8 bytes of the character under the cursor (`ga`)
+    ShowCharInfo,
+    /// Show line, word, char and byte counts for the buffer or selection (`g Ctrl-G`)
+    ShowCounts,
+    /// Show the highlight captures of the token under the cursor (`:syntax info`)
+    ShowCurrentSyntaxCapture,
+
+    // Git hunk motion
+    NextHunk(usize),
+    PrevHunk(usize),
+
+    /// Previous / next line indented less than the cursor line (`[i` / `]i`)
+    MoveMatchingIndentUp(usize),
+    MoveMatchingIndentDown(usize),
+
     // Character insertion/deletion
     InsertChar(char),
     DeleteChar,
     DeleteCharForward(usize),
+    /// Delete the word before the cursor in insert mode (`Ctrl-W`)
+    DeleteWordBackInsert,
+    /// Delete back to the indent, then the line start, in insert mode (`Ctrl-U`)
+    DeleteToLineStartInsert,
+    /// Insert the text of the last insert again, in insert mode (`Ctrl-A`)
+    ReplayLastInsert,
+    /// Open the quickfix window over the last list of locations (`:copen`)
+    OpenQuickfix,
+    /// Jump to the next / previous quickfix location (`]q` / `[q`)
+    QuickfixNext(usize),
+    QuickfixPrev(usize),
+    // Keys of the open quickfix window
+    QuickfixSelectNext,
+    QuickfixSelectPrev,
+    QuickfixAccept,
+    /// Show what the language server wrote to stderr (`:lsp log`)
+    ShowLspLog,
+    /// Insert the character whose code point is typed next (`Ctrl-V u00e9`)
+    InsertCodePoint,
     ReplaceChar(char),
+    /// Swap the character under the cursor with the next one, or the last two
+    /// at the end of the line (`Ctrl-T`)
+    TransposeChars,
 
     // Line operations
-    DeleteLine,
+    DeleteLine(usize),
     DeleteLineIntoRegister(char),
 
     // Word operations
     DeleteWord(usize),
     DeleteToEndWord(usize),
     DeleteToStartWord(usize),
+    DeleteWordEndBackward(usize),
+    DeleteBigWordEndBackward(usize),
     DeleteInnerWord(usize),
     DeleteAWord(usize),
 
@@ -47,22 +119,28 @@ pub enum Command {
     DeleteToStartOfFile,
 
     // Yank operations
-    YankLine,
+    YankLine(usize),
     YankWord(usize),
     YankToEnd,
     YankToStart,
     YankInnerWord(usize),
     YankAWord(usize),
+    YankWordEndBackward(usize),
+    YankBigWordEndBackward(usize),
 
     // Change operations
     ChangeLine,
     ChangeWord(usize),
+    ChangeWordEndBackward(usize),
+    ChangeBigWordEndBackward(usize),
     ChangeToEnd,
     ChangeToStart,
     ChangeInnerWord(usize),
     ChangeAWord(usize),
     SubstituteChar,
     SubstituteLine,
+    /// Run the last `:s` again on the current line, without its flags (`&`)
+    RepeatSubstitute,
 
     // Paste operations
     PasteAfter,
@@ -70,23 +148,92 @@ pub enum Command {
 
     // Join operations
     JoinLines(usize),
+    /// Join lines without adding or removing spaces (`gJ`)
+    JoinLinesNoSpace(usize),
+    /// Join the selected lines (visual `J`)
+    JoinSelection,
+    /// Join the selected lines without adding or removing spaces (visual `gJ`)
+    JoinSelectionNoSpace,
+
+    // Case operations
+    ToggleCaseChar(usize),
+    CaseWord(CaseChange, usize),
+    /// Change case through the end of the word (`gUe`)
+    CaseToWordEnd(CaseChange, usize),
+    CaseToEnd(CaseChange),
+    CaseToStart(CaseChange),
+    CaseLine(CaseChange, usize),
+    CaseInnerWord(CaseChange, usize),
+    CaseSelection(CaseChange),
+    /// Yank the selection (visual `y`)
+    YankSelection,
+    /// Delete the selection into a register (visual `d`/`x`)
+    DeleteSelection,
+    /// Format the file when the selection covers all of it, or else reindent
+    /// the selected lines (visual `=`)
+    FormatSelection,
+    /// Replace every character of the selection with this one (visual `r`)
+    ReplaceSelection(char),
+
+    // Bracket and quote text objects: `di(`, `ca"`, `yi{`
+    DeleteTextObject(TextObject),
+    ChangeTextObject(TextObject),
+    YankTextObject(TextObject),
+    /// Select a text object in visual mode (`vi(`, `vai`)
+    SelectTextObject(TextObject),
+
+    // Comment toggling (`gc`)
+    ToggleComment(LineMotion),
+    ToggleCommentSelection,
 
     // Indent operations
     IndentLine(usize),
     UnindentLine(usize),
+    IndentSelection(usize),
+    UnindentSelection(usize),
+    /// Reindent lines from the syntax tree (`=G`, `=j`)
+    Reindent(LineMotion),
+    /// Reindent every line from the syntax tree (`:reindent`)
+    ReindentBuffer,
+
+    // Repeat
+    RepeatLastChange,
 
     // Undo/Redo
-    Undo,
-    Redo,
+    Undo(usize),
+    Redo(usize),
 
-    // Mode switching
-    InsertMode,
+    // Multiple cursors
+    AddCursor,
+
+    // Mode switching; the count repeats the inserted text on Escape
+    InsertMode(usize),
+    AppendAfter(usize),
+    AppendLineEnd(usize),
+    InsertLineStart(usize),
+    OpenLineBelow(usize),
+    OpenLineAbove(usize),
     NormalMode,
+    /// Run one normal-mode command, then go back to inserting (insert-mode `Ctrl-O`)
+    InsertNormalCommand,
+    /// Close the topmost overlay, or else the selection or pending keys
+    Escape,
+    /// Close every overlay at once: completion, code actions, hover, listings
+    /// and the quickfix window (`Ctrl-L`)
+    CloseAllOverlays,
     VisualChar,
     VisualLine,
+    /// Select every line of the buffer linewise (`Ctrl-A`, like `ggVG`)
+    SelectAll,
+    /// Move the cursor to the other end of the visual selection (`o`)
+    VisualSwapEnds,
 
     // Command mode
     EnterCommandMode,
+    /// Run the last command line again (`@:`)
+    RepeatExCommand(usize),
+    /// Run what the last `@` ran again (`@@`)
+    RepeatLastExecuted(usize),
 
     // File operations
     SaveFile,
@@ -106,18 +253,58 @@ pub enum Command {
     Hover,
     WorkspaceSymbols,
     CodeAction,
+    OrganizeImports,
 
     // Fuzzy search
     OpenFuzzySearch,
+    OpenRecentFiles,
+    OpenAlternateFile,
     FuzzySearchUp,
     FuzzySearchDown,
     FuzzySearchSelect,
     FuzzySearchCancel,
     FuzzySearchToggleRecursive,
     FuzzySearchToggleGitignore,
+    FuzzySearchToggleHidden,
     FuzzySearchLoadMore,
 }
 
+impl Command {
+    /// Whether `.` should replay this command
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            Command::IndentLine(_)
+                | Command::UnindentLine(_)
+                | Command::ToggleCaseChar(_)
+                | Command::CaseWord(..)
+                | Command::CaseToWordEnd(..)
+                | Command::CaseToEnd(_)
+                | Command::CaseToStart(_)
+                | Command::CaseLine(..)
+                | Command::CaseInnerWord(..)
+                | Command::ToggleComment(_)
+                | Command::Reindent(_)
+        )
+    }
+
+    /// Whether this command, issued in insert mode, starts a new undo step
+    /// for the text typed after it, like Enter and the arrow keys do
+    pub fn breaks_insert_undo(&self) -> bool {
+        matches!(
+            self,
+            Command::InsertChar('\n')
+                | Command::MoveLeft
+                | Command::MoveRight
+                | Command::MoveUp
+                | Command::MoveDown
+                | Command::DeleteWordBackInsert
+                | Command::DeleteToLineStartInsert
+                | Command::ReplayLastInsert
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,75 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Default, Deserialize)]
 pub struct TextyConfig {
     pub theme: Option<String>,
+    /// Keep a `<file>~` backup of the previous contents when saving
+    pub backup: Option<bool>,
+    /// Always end saved files with a newline, even if they didn't have one
+    pub ensure_final_newline: Option<bool>,
+    /// Status line layout, e.g. `"%M| %f %m | %l:%c"`
+    pub status_line: Option<String>,
+    /// Run the file type's formatter before every save
+    pub format_on_save: Option<bool>,
+    /// Apply the language server's organize imports action before saving
+    pub organize_imports_on_save: Option<bool>,
+    /// Apply the language server's fix-all action before saving
+    pub fix_all_on_save: Option<bool>,
+    /// Milliseconds the cursor must rest before hover information or the
+    /// diagnostics under it pop up; 0 or unset turns this off
+    pub hover_delay: Option<u64>,
+    /// Companion extensions for `:A`, e.g. `{ c = ["h"], h = ["c", "cpp"] }`;
+    /// each entry replaces the built-in list for that extension
+    pub alternates: Option<HashMap<String, Vec<String>>>,
+    /// Copy yanks to the system clipboard as well, like Vim's
+    /// `clipboard=unnamedplus`
+    pub yank_to_clipboard: Option<bool>,
+    /// Change the cursor shape with the mode; `false` leaves the terminal's own
+    pub cursor_shape: Option<bool>,
+    /// Display columns to draw a ruler at, e.g. `[80, 100]`
+    pub colorcolumn: Option<Vec<usize>>,
+    /// Show the language server's type and parameter hints in the text;
+    /// on by default
+    pub inlay_hints: Option<bool>,
+    /// Close brackets and quotes as they are typed in insert mode; `false`
+    /// turns this off, and a string such as `"()[]{}\"\""` picks the pairs
+    pub auto_pairs: Option<AutoPairsConfig>,
+    /// Ignore case in searches and the fuzzy finder unless the query has an
+    /// uppercase letter; on by default
+    pub smart_case: Option<bool>,
+    /// Pressing Enter in a comment continues it on the new line; on by
+    /// default
+    pub continue_comments: Option<bool>,
+    /// Most matches `:grep` collects before it stops scanning; 5000 by default
+    pub grep_max_results: Option<usize>,
+    /// Characters besides letters and digits that are part of a word, by
+    /// language, e.g. `{ javascript = "_$", rust = "_" }`. Word motions, text
+    /// objects and word lookups follow them.
+    pub word_chars: Option<HashMap<String, String>>,
+    /// Build command `:make` runs, e.g. `"cargo build --message-format=short"`;
+    /// `make` by default
+    pub make_command: Option<String>,
+    /// Regex picking errors out of `:make`'s output, with the named groups
+    /// `file` and `line` and optionally `col` and `message`. By default it
+    /// matches `file:line:col: message`.
+    pub error_format: Option<String>,
+    /// User commands, e.g. `{ W = "w", Jq = "%!jq ." }`: `:W` runs `:w`.
+    /// Names start with an uppercase letter.
+    pub commands: Option<HashMap<String, String>>,
+    /// Cursor shape per mode, e.g. `{ insert = "underline" }`; modes are
+    /// `normal`, `insert`, `visual` and `command`, shapes `block`, `bar` and
+    /// `underline`
+    pub cursor_shapes: Option<HashMap<String, crate::options::CursorShape>>,
+}
+
+/// `auto_pairs` is either on/off or the pairs to use
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AutoPairsConfig {
+    Enabled(bool),
+    Pairs(String),
 }
 
 impl TextyConfig {
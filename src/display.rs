@@ -0,0 +1,290 @@
+// src/display.rs - Map buffer columns (char indices) to screen columns
+
+use unicode_width::UnicodeWidthChar;
+
+/// Number of screen cells `c` occupies when it starts at `display_col`.
+///
+/// Tabs extend to the next multiple of `tab_width`; wide characters such as CJK
+/// and most emoji take two cells, combining marks none.
+pub fn char_width(c: char, display_col: usize, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = tab_width.max(1);
+        tab_width - display_col % tab_width
+    } else {
+        // Control characters are drawn as a single replacement cell
+        c.width().unwrap_or(1)
+    }
+}
+
+/// Screen column at which the character at `char_col` starts.
+///
+/// Columns past the end of the line continue one cell per character.
+pub fn display_col(line: &str, char_col: usize, tab_width: usize) -> usize {
+    let mut col = 0;
+    let mut chars = 0;
+    for c in line.chars().take(char_col) {
+        if c == '\n' {
+            break;
+        }
+        col += char_width(c, col, tab_width);
+        chars += 1;
+    }
+    col + (char_col - chars)
+}
+
+/// Index of the character covering screen column `target` of `line`.
+///
+/// Returns the line length (excluding the newline) if `target` is past the end.
+pub fn char_col_at(line: &str, target: usize, tab_width: usize) -> usize {
+    let mut col = 0;
+    for (idx, c) in line.chars().enumerate() {
+        if c == '\n' {
+            return idx;
+        }
+        let width = char_width(c, col, tab_width);
+        if target < col + width.max(1) {
+            return idx;
+        }
+        col += width;
+    }
+    line.chars().count()
+}
+
+/// Text drawn between a line's characters that isn't part of the buffer, such
+/// as an inlay hint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualText {
+    /// Index of the character it is drawn before; the line length puts it at
+    /// the end
+    pub col: usize,
+    pub text: String,
+}
+
+/// `line` as drawn with `virtual_text` (sorted by column) inserted, and for
+/// each of its characters whether it is the buffer's
+pub fn with_virtual_text(line: &str, virtual_text: &[VirtualText]) -> (String, Vec<bool>) {
+    let mut drawn = String::new();
+    let mut is_buffer = Vec::new();
+    let mut pending = virtual_text.iter().peekable();
+    let mut insert_until = |col: Option<usize>, drawn: &mut String, is_buffer: &mut Vec<bool>| {
+        while let Some(virtual_text) = pending.next_if(|v| col.is_none_or(|col| v.col <= col)) {
+            drawn.push_str(&virtual_text.text);
+            is_buffer.extend(virtual_text.text.chars().map(|_| false));
+        }
+    };
+    for (idx, c) in line.chars().enumerate() {
+        // Text past the end goes before the line break
+        let end = c == '\n' || c == '\r';
+        insert_until((!end).then_some(idx), &mut drawn, &mut is_buffer);
+        drawn.push(c);
+        is_buffer.push(true);
+    }
+    insert_until(None, &mut drawn, &mut is_buffer);
+    (drawn, is_buffer)
+}
+
+/// Index in the line drawn with `virtual_text` of the buffer character at
+/// `char_col`
+pub fn virtual_col(virtual_text: &[VirtualText], char_col: usize) -> usize {
+    let before: usize = virtual_text
+        .iter()
+        .filter(|v| v.col <= char_col)
+        .map(|v| v.text.chars().count())
+        .sum();
+    char_col + before
+}
+
+/// A character laid out on screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    /// Index of the character in the line
+    pub char_idx: usize,
+    /// Screen column relative to the left edge of the view
+    pub col: usize,
+    /// Text to draw: tabs are expanded to spaces and wide characters cut by the
+    /// left edge are replaced by spaces
+    pub text: String,
+}
+
+/// What to draw for one character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Char(char),
+    /// Spaces: an expanded tab, or a wide character cut by an edge of the view
+    Blank(usize),
+}
+
+impl Glyph {
+    pub fn push_to(self, out: &mut String) {
+        match self {
+            Glyph::Char(c) => out.push(c),
+            Glyph::Blank(n) => out.extend(std::iter::repeat_n(' ', n)),
+        }
+    }
+}
+
+/// Lay out the part of `line` visible between screen columns `offset` and
+/// `offset + width`, calling `f` with each character's index, its screen
+/// column relative to the left edge of the view, and what to draw.
+///
+/// Nothing is allocated, so this suits drawing every line of every frame.
+pub fn layout_visible(
+    line: &str,
+    offset: usize,
+    width: usize,
+    tab_width: usize,
+    mut f: impl FnMut(usize, usize, Glyph),
+) {
+    let end = offset + width;
+    let mut col = 0;
+
+    for (char_idx, c) in line.chars().enumerate() {
+        if col >= end || c == '\n' || c == '\r' {
+            break;
+        }
+        let char_cols = char_width(c, col, tab_width);
+        let start = col;
+        col += char_cols;
+        if start < offset && col <= offset {
+            continue;
+        }
+
+        let glyph = if c == '\t' || start < offset {
+            // Only the part inside the view is drawn
+            Glyph::Blank(col.min(end) - start.max(offset))
+        } else if col > end {
+            // A wide character that doesn't fit at the right edge
+            Glyph::Blank(end - start)
+        } else if c.is_control() {
+            Glyph::Char('\u{fffd}')
+        } else {
+            Glyph::Char(c)
+        };
+        f(char_idx, start.max(offset) - offset, glyph);
+    }
+}
+
+/// Lay out the part of `line` visible between screen columns `offset` and
+/// `offset + width`
+pub fn visible_cells(line: &str, offset: usize, width: usize, tab_width: usize) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    layout_visible(line, offset, width, tab_width, |char_idx, col, glyph| {
+        let mut text = String::new();
+        glyph.push_to(&mut text);
+        cells.push(Cell {
+            char_idx,
+            col,
+            text,
+        });
+    });
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_col_with_tabs_and_wide_chars() {
+        assert_eq!(display_col("\tx", 1, 4), 4);
+        assert_eq!(display_col("ab\tx", 3, 4), 4);
+        assert_eq!(display_col("日本x", 2, 4), 4);
+        assert_eq!(display_col("e\u{301}x", 2, 4), 1);
+        // Past the end of the line
+        assert_eq!(display_col("\t", 3, 4), 6);
+    }
+
+    #[test]
+    fn test_virtual_text_is_inserted_before_its_column() {
+        let hint = |col, text: &str| VirtualText {
+            col,
+            text: text.to_string(),
+        };
+        let hints = [hint(0, "a: "), hint(5, ": i32")];
+        let (drawn, is_buffer) = with_virtual_text("let x\n", &hints);
+        assert_eq!(drawn, "a: let x: i32\n");
+        assert_eq!(is_buffer.iter().filter(|b| **b).count(), 6);
+        assert!(!is_buffer[0] && is_buffer[3]);
+        // A hint goes before the character at its column
+        assert_eq!(virtual_col(&hints, 0), 3);
+        assert_eq!(virtual_col(&hints, 4), 7);
+        assert_eq!(virtual_col(&hints, 5), 13);
+        // Hints past the end of a line without a break still show
+        assert_eq!(with_virtual_text("x", &[hint(3, "!")]).0, "x!");
+    }
+
+    #[test]
+    fn test_char_col_at() {
+        assert_eq!(char_col_at("\tx", 0, 4), 0);
+        assert_eq!(char_col_at("\tx", 3, 4), 0);
+        assert_eq!(char_col_at("\tx", 4, 4), 1);
+        assert_eq!(char_col_at("日本x", 3, 4), 1);
+        assert_eq!(char_col_at("ab", 10, 4), 2);
+        assert_eq!(char_col_at("ab\n", 10, 4), 2);
+    }
+
+    #[test]
+    fn test_visible_cells_expand_tabs() {
+        let cells = visible_cells("\tab", 0, 10, 4);
+        assert_eq!(cells[0].text, "    ");
+        assert_eq!(
+            cells[1],
+            Cell {
+                char_idx: 1,
+                col: 4,
+                text: "a".into()
+            }
+        );
+        assert_eq!(cells.len(), 3);
+    }
+
+    #[test]
+    fn test_visible_cells_clip_at_edges() {
+        // Scrolled into the middle of a tab and a wide character
+        let cells = visible_cells("\tx日本", 2, 4, 4);
+        assert_eq!(
+            cells[0],
+            Cell {
+                char_idx: 0,
+                col: 0,
+                text: "  ".into()
+            }
+        );
+        assert_eq!(
+            cells[1],
+            Cell {
+                char_idx: 1,
+                col: 2,
+                text: "x".into()
+            }
+        );
+        // Only one of the two cells of 日 fits
+        assert_eq!(
+            cells[2],
+            Cell {
+                char_idx: 2,
+                col: 3,
+                text: " ".into()
+            }
+        );
+        assert_eq!(cells.len(), 3);
+
+        let cells = visible_cells("日本", 1, 4, 4);
+        assert_eq!(
+            cells[0],
+            Cell {
+                char_idx: 0,
+                col: 0,
+                text: " ".into()
+            }
+        );
+        assert_eq!(
+            cells[1],
+            Cell {
+                char_idx: 1,
+                col: 1,
+                text: "本".into()
+            }
+        );
+    }
+}
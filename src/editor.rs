@@ -1,34 +1,63 @@
 // src/editor.rs - Core editor coordinator
 
-use crate::buffer::Buffer;
-use crate::command::Command;
+use crate::buffer::{Buffer, BufferError, CaseChange};
+use crate::code_point::{CodePointEntry, CodePointStep};
+use crate::command::{Command, LineMotion};
 use crate::cursor::Cursor;
+use crate::editorconfig::EditorConfig;
+use crate::ex::{self, Global, LineCommand, LineRange, RetabOptions, SortOptions, Substitute};
+use crate::file_watcher::FileWatcher;
 use crate::formatter::external::{Formatter, get_formatter_config};
-use crate::fuzzy_search::FuzzySearchState;
+use crate::formatter::indent::IndentationEngine;
+use crate::fuzzy_search::{FuzzySearchState, SymbolItem};
+use crate::git::GitSigns;
+use crate::lsp::client::LspError;
 use crate::lsp::completion::CompletionManager;
 use crate::lsp::diagnostics::DiagnosticManager;
-use crate::lsp::manager::LspManager;
+use crate::lsp::manager::{HealthEvent, LspManager};
 use crate::lsp::progress::ProgressManager;
+use crate::lsp::workspace_edit::lsp_position;
+use crate::messages::{MessageHistory, Severity};
 use crate::mode::Mode;
-use crate::motion::Position;
+use crate::motion::{CharSearch, Position, WordChars};
+use crate::undo::{Snapshot, UndoHistory};
+use crate::options::EditorOptions;
+use crate::inlay_hints::InlayHints;
+use crate::quickfix::{QuickfixEntry, QuickfixList};
+use crate::recent_files::RecentFiles;
 use crate::registers::Registers;
 use crate::syntax::{LanguageId, LanguageRegistry, load_languages_config};
 use crate::ui::widgets::completion::CompletionPopup;
+use crate::ui::widgets::gutter::Gutter;
 use crate::vim_parser::VimParser;
 use crate::viewport::Viewport;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use lsp_types::{Diagnostic, Url};
+use regex::Regex;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+/// Lines scrolled per notch of the mouse wheel
+const SCROLL_LINES: usize = 3;
+
+/// How long the server gets to answer a request for inlay hints
+const INLAY_HINT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 pub struct Editor {
     pub buffer: Buffer,
     pub cursor: Cursor,
+    /// Extra cursors added with Ctrl-N; typing edits at all of them
+    pub cursors: Vec<Cursor>,
     pub mode: Mode,
     pub viewport: Viewport,
     pub formatter: Option<Formatter>,
     pub lsp_manager: LspManager,
+    /// Restarting crashed servers in the background, so the UI keeps running
+    lsp_health_check: Option<tokio::task::JoinHandle<Vec<HealthEvent>>>,
+    /// The build `:make` started, while it runs
+    make_job: Option<MakeJob>,
     pub completion_manager: CompletionManager,
     pub diagnostic_manager: DiagnosticManager,
     pub diagnostics: Arc<Mutex<HashMap<Url, Vec<Diagnostic>>>>, // Synchronous access for UI
@@ -41,8 +70,20 @@ pub struct Editor {
     pub fuzzy_search: Option<FuzzySearchState>,
     // UI overlays
     pub hover_content: Option<Vec<String>>, // Content for hover window
+    /// The hover window was opened by the idle timer; the next input closes it
+    idle_hover: bool,
+    /// Hover information the idle timer asked for, while the server answers
+    idle_hover_request: Option<IdleHoverRequest>,
+    /// Read-only listing from `:reg`, `:messages` or `:lsp log`, shown until the next key press
+    pub listing: Option<Listing>,
+    /// Status line messages so far, for `:messages`
+    pub messages: MessageHistory,
     pub code_actions: Option<Vec<lsp_types::CodeAction>>, // Available code actions
     pub code_action_selected: usize,        // Selected code action index
+    /// Locations from find-references, `:grep` or `:diagnostics`
+    pub quickfix: QuickfixList,
+    /// The quickfix window is open and takes the keys
+    pub quickfix_open: bool,
     // Command line
     pub command_line: String,           // Current command line input
     pub command_history: Vec<String>,   // Command history
@@ -52,6 +93,135 @@ pub struct Editor {
     pub vim_parser: VimParser,
     pub registers: Registers,
     pub visual_start: Option<Position>,
+    pub visual_line: bool,
+    /// Last repeatable change, replayed by `.`
+    pub last_change: Option<Command>,
+    pub options: EditorOptions,
+    /// Options from the user's config; each opened file starts from these
+    /// before its `.editorconfig` settings are applied
+    user_options: EditorOptions,
+    /// Watches the open file for changes made by other programs
+    pub file_watcher: Option<FileWatcher>,
+    /// The file changed on disk while the buffer had unsaved edits; waiting for reload/keep
+    pub reload_prompt: bool,
+    /// Anchor and cursor of the last visual selection (the `'<` / `'>` marks)
+    pub last_visual: Option<(Position, Position)>,
+    /// Text typed since entering insert mode, repeated for a count and kept
+    /// for `Ctrl-A` once insert mode ends
+    insert_session: Option<InsertSession>,
+    /// Keys typed after Ctrl-V in insert mode, until they name a character
+    code_point_entry: Option<CodePointEntry>,
+    /// Insert-mode `Ctrl-O` is running a normal-mode command: the cursor it
+    /// left in normal mode and the insert column to go back to if the command
+    /// doesn't move it
+    insert_return: Option<(Position, usize)>,
+    /// `:lsp` subcommand waiting for the event loop to run it
+    pub pending_lsp_action: Option<LspAction>,
+    /// Set once a queued `:wq` has written the buffer; the event loop exits
+    pub quit_requested: bool,
+    /// Changes that `u` and Ctrl-R step through
+    pub undo_history: UndoHistory,
+    /// The current insert has been recorded as a single undo step
+    insert_undo_recorded: bool,
+    /// Undo checkpoints taken and not yet recorded. Commands run from inside
+    /// another, like `:fmt` or `.`, are recorded only by the outermost one.
+    undo_depth: usize,
+    /// Files opened in this and earlier sessions, for the recent files picker
+    pub recent_files: RecentFiles,
+    /// Added/modified/removed markers against the file's git index version
+    pub git_signs: GitSigns,
+    /// The language server's type and parameter hints for the visible lines
+    pub inlay_hints: InlayHints,
+    /// Inlay hints asked for, while the server answers
+    inlay_hint_request: Option<InlayHintRequest>,
+    /// The last `f`/`t`/`F`/`T` search, repeated by `;` and `,`
+    pub last_find: Option<CharSearch>,
+    /// Pattern of the last `:s` or `:g`, reused by an empty `:s//repl/`
+    pub last_search: Option<Regex>,
+    /// The last `:s`, repeated by `&` and `:&`
+    pub last_substitute: Option<Substitute>,
+    /// User commands from `:command` and the config, by name
+    pub command_aliases: HashMap<String, String>,
+    /// What the last `@` ran, for `@@`; `:` is the last command line
+    last_executed: Option<char>,
+}
+
+/// State captured before running a command, to record it as an undo step if
+/// the command changes the buffer
+struct UndoCheckpoint {
+    snapshot: Snapshot,
+    version: usize,
+    generation: usize,
+    was_insert: bool,
+}
+
+/// An `:lsp` subcommand. These talk to servers asynchronously, so the command
+/// line only queues them and the event loop runs them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspAction {
+    Stop,
+    Restart,
+    Status,
+    /// Fetch code actions for a range and show them in a menu
+    CodeActions(lsp_types::Range),
+    /// Apply the code action chosen from the menu
+    ApplyCodeAction(Box<lsp_types::CodeAction>),
+    /// Fetch hover information for a position and show it
+    Hover(lsp_types::Position),
+    /// Find references to the symbol at a position and list them in the quickfix window
+    References(lsp_types::Position),
+    /// Search the workspace's symbols for the symbol picker
+    WorkspaceSymbols(String),
+    /// Apply the server's organize imports action to the buffer
+    OrganizeImports,
+    /// Format the buffer with the server's `textDocument/formatting`
+    Format,
+    /// Apply the on-save code actions, then write the buffer to `path`;
+    /// `quit` exits once it is written
+    Save { path: String, quit: bool },
+}
+
+/// Lines shown in a window over the text, such as the `:reg` listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub title: String,
+    pub lines: Vec<String>,
+    /// Show the last lines when they don't all fit, as for a log
+    pub follow_end: bool,
+    /// Which lines are drawn in the error color, by index
+    pub errors: Vec<bool>,
+}
+
+/// Insert started by a counted command such as `3o`, replayed on Escape
+#[derive(Debug, Clone)]
+struct InsertSession {
+    count: usize,
+    text: String,
+    /// Entered with `o`/`O`: every repeat goes on a fresh line
+    open_line: bool,
+}
+
+/// Hover information asked for by the idle timer. The reply is dropped if
+/// the cursor or text has moved on by the time it arrives.
+struct IdleHoverRequest {
+    position: lsp_types::Position,
+    version: usize,
+    handle: tokio::task::JoinHandle<Result<Option<lsp_types::Hover>, LspError>>,
+}
+
+/// Inlay hints for `lines` of the text at `version`, asked for in the
+/// background. `None` is answered if the request failed or timed out.
+struct InlayHintRequest {
+    version: usize,
+    lines: std::ops::Range<usize>,
+    handle: tokio::task::JoinHandle<Option<Vec<lsp_types::InlayHint>>>,
+}
+
+/// A `:make` running in the background; its errors are listed when it ends
+struct MakeJob {
+    command: String,
+    format: Regex,
+    handle: std::thread::JoinHandle<std::io::Result<(String, bool)>>,
 }
 
 impl Default for Editor {
@@ -77,10 +247,13 @@ impl Editor {
         Self {
             buffer,
             cursor: Cursor::new(),
+            cursors: Vec::new(),
             mode: Mode::Normal,
             viewport: Viewport::new(20, 80),
             formatter,
             lsp_manager: LspManager::new(),
+            lsp_health_check: None,
+            make_job: None,
             completion_manager: CompletionManager::new(),
             diagnostic_manager: DiagnosticManager::new(),
             diagnostics: Arc::new(Mutex::new(HashMap::new())),
@@ -91,8 +264,14 @@ impl Editor {
             language_registry,
             fuzzy_search: None,
             hover_content: None,
+            idle_hover: false,
+            idle_hover_request: None,
+            listing: None,
+            messages: MessageHistory::default(),
             code_actions: None,
             code_action_selected: 0,
+            quickfix: QuickfixList::default(),
+            quickfix_open: false,
             command_line: String::new(),
             command_history: Vec::new(),
             command_history_index: 0,
@@ -100,13 +279,147 @@ impl Editor {
             vim_parser: VimParser::new(),
             registers: Registers::new(),
             visual_start: None,
+            visual_line: false,
+            last_change: None,
+            options: EditorOptions::default(),
+            user_options: EditorOptions::default(),
+            file_watcher: None,
+            reload_prompt: false,
+            last_visual: None,
+            insert_session: None,
+            code_point_entry: None,
+            insert_return: None,
+            pending_lsp_action: None,
+            quit_requested: false,
+            undo_history: UndoHistory::new(),
+            recent_files: RecentFiles::new(),
+            git_signs: GitSigns::default(),
+            inlay_hints: InlayHints::default(),
+            inlay_hint_request: None,
+            last_find: None,
+            last_search: None,
+            last_substitute: None,
+            command_aliases: HashMap::new(),
+            last_executed: None,
+            insert_undo_recorded: false,
+            undo_depth: 0,
         }
     }
 
+    /// Run a command, recording it as an undo step if it changes the buffer.
+    /// Returns true if the editor should quit.
     pub fn execute_command(&mut self, cmd: Command) -> bool {
+        if self.code_point_entry.is_some() {
+            return self.feed_code_point(cmd);
+        }
+        if self.mode == Mode::Insert && cmd.breaks_insert_undo() {
+            self.insert_undo_recorded = false;
+        }
+        // Deleting back or replaying text in insert mode is an undo step of its own
+        let own_step = matches!(
+            cmd,
+            Command::DeleteWordBackInsert
+                | Command::DeleteToLineStartInsert
+                | Command::ReplayLastInsert
+        );
+        // After insert-mode `Ctrl-O`, Escape stays in normal mode and any
+        // other command goes back to inserting
+        let after_ctrl_o =
+            self.insert_return.is_some() && !matches!(cmd, Command::InsertNormalCommand);
+        if after_ctrl_o && matches!(cmd, Command::Escape | Command::NormalMode) {
+            self.insert_return = None;
+        }
+        let checkpoint = self.undo_checkpoint();
+        let quit = self.run_command(cmd);
+        self.record_undo(checkpoint);
+        if after_ctrl_o {
+            self.resume_insert();
+        }
+        self.record_status();
+        if own_step {
+            self.insert_undo_recorded = false;
+        }
+        quit
+    }
+
+    /// Whether insert-mode `Ctrl-O` waits for a normal-mode command
+    pub fn in_insert_normal_command(&self) -> bool {
+        self.insert_return.is_some()
+    }
+
+    /// Go back to inserting once the command run with insert-mode `Ctrl-O`
+    /// is done. One opened the command line when it closes; one that left
+    /// normal mode, like `v` or `o`, just ends the wait.
+    pub fn resume_insert(&mut self) {
+        let Some((normal_cursor, insert_col)) = self.insert_return else {
+            return;
+        };
+        match self.mode {
+            Mode::Command => return,
+            Mode::Normal => {
+                if self.cursor.line == normal_cursor.line && self.cursor.col == normal_cursor.col {
+                    self.cursor.col = insert_col.min(self.line_char_count(self.cursor.line));
+                }
+                self.mode = Mode::Insert;
+            }
+            _ => {}
+        }
+        self.insert_return = None;
+    }
+
+    /// Feed a key to the pending Ctrl-V entry. A key that can't continue it
+    /// ends the entry with the digits so far and then runs as usual.
+    fn feed_code_point(&mut self, cmd: Command) -> bool {
+        let Some(mut entry) = self.code_point_entry.take() else {
+            return self.execute_command(cmd);
+        };
+        let (step, rest) = match cmd {
+            Command::InsertChar(c) if entry.accepts(c) => (entry.push(c), None),
+            cmd => (entry.finish(), Some(cmd)),
+        };
+        match &step {
+            CodePointStep::Pending => self.code_point_entry = Some(entry),
+            CodePointStep::Insert(c) => {
+                self.execute_command(Command::InsertChar(*c));
+            }
+            CodePointStep::Invalid(_) => {}
+        }
+        let quit = rest.is_some_and(|cmd| self.execute_command(cmd));
+        if let CodePointStep::Invalid(message) = step {
+            self.show_error(message);
+        }
+        quit
+    }
+
+    fn run_command(&mut self, cmd: Command) -> bool {
         // Clear status message on new commands (except for commands that just show status)
         if !matches!(cmd, Command::FormatBuffer) {
             self.status_message = None;
+            self.record_status();
+        }
+
+        if cmd.is_repeatable() {
+            self.last_change = Some(cmd.clone());
+        }
+
+        // Moving around mid-insert starts a new insert, so nothing is repeated
+        // and only the text typed from here on is kept
+        if self.mode == Mode::Insert
+            && !matches!(
+                cmd,
+                Command::InsertChar(_)
+                    | Command::InsertCodePoint
+                    | Command::DeleteChar
+                    | Command::ReplayLastInsert
+                    | Command::NormalMode
+                    | Command::InsertNormalCommand
+            )
+        {
+            self.insert_session = Some(InsertSession {
+                count: 1,
+                text: String::new(),
+                open_line: false,
+            });
         }
 
         // Returns true if should quit
@@ -134,11 +447,21 @@ impl Editor {
                 }
             }
             Command::InsertChar(c) => {
-                if self.mode == Mode::Insert {
+                if self.mode == Mode::Insert && !self.cursors.is_empty() {
+                    self.insert_char_at_cursors(c);
+                } else if self.mode == Mode::Insert && self.insert_auto_pair(c) {
+                    // Typed as part of a bracket or quote pair
+                } else if self.mode == Mode::Insert && c == '\n' && self.continue_comment() {
+                    // Broke a comment line and carried on the comment
+                } else if self.mode == Mode::Insert {
                     let _ = self
                         .buffer
                         .insert_char(c, self.cursor.line, self.cursor.col);
 
+                    if let Some(session) = &mut self.insert_session {
+                        session.text.push(c);
+                    }
+
                     // Handle cursor positioning based on character type
                     if c == '\n' {
                         // Move to beginning of next line after newline
@@ -162,12 +485,26 @@ impl Editor {
                 }
             }
             Command::DeleteChar => {
-                if self.mode == Mode::Insert {
+                if self.mode == Mode::Insert && !self.cursors.is_empty() {
+                    self.delete_char_at_cursors();
+                } else if self.mode == Mode::Insert {
+                    if let Some(session) = &mut self.insert_session
+                        && session.text.pop().is_none()
+                    {
+                        // Backspacing over older text can't be replayed
+                        self.insert_session = None;
+                    }
                     if self.cursor.col > 0 {
+                        if self.at_empty_auto_pair() {
+                            // Deleting the opener of an empty pair takes its closer too
+                            let _ = self
+                                .buffer
+                                .delete_char_forward(self.cursor.line, self.cursor.col, 1);
+                        }
                         // Normal backspace: delete previous character in current line
                         let _ = self
                             .buffer
-                            .delete_char(self.cursor.line, self.cursor.col - 1);
+                            .delete_char(self.cursor.line, self.cursor.col);
                         self.cursor.col -= 1;
                     } else if self.cursor.col == 0 && self.cursor.line > 0 {
                         // Backspace at line start: delete newline and join with previous line
@@ -190,7 +527,7 @@ impl Editor {
                         if self.cursor.col > 0 {
                             let _ = self
                                 .buffer
-                                .delete_char(self.cursor.line, self.cursor.col - 1);
+                                .delete_char(self.cursor.line, self.cursor.col);
                             self.cursor.col -= 1;
                         } else if self.cursor.col == 0 && self.cursor.line > 0 {
                             // Backspace at line start in normal mode
@@ -208,9 +545,18 @@ impl Editor {
                     }
                 }
             }
+            Command::DeleteWordBackInsert => self.delete_back_insert(true),
+            Command::DeleteToLineStartInsert => self.delete_back_insert(false),
+            Command::ReplayLastInsert => self.replay_last_insert(),
             Command::OpenFuzzySearch => {
                 self.open_fuzzy_search();
             }
+            Command::OpenRecentFiles => {
+                self.open_recent_files();
+            }
+            Command::OpenAlternateFile => {
+                self.open_alternate_file();
+            }
             Command::FuzzySearchUp => {
                 if let Some(fuzzy) = &mut self.fuzzy_search
                     && let Some(item) = fuzzy.select_prev()
@@ -231,6 +577,24 @@ impl Editor {
                     }
                 }
             }
+            Command::FuzzySearchSelect
+                if self.fuzzy_search.as_ref().is_some_and(|f| f.symbols.is_some()) =>
+            {
+                let symbol = self
+                    .fuzzy_search
+                    .as_ref()
+                    .and_then(|f| f.get_selected_symbol())
+                    .cloned();
+                if let Some(symbol) = symbol {
+                    self.fuzzy_search = None;
+                    self.mode = Mode::Normal;
+                    if self.open_location(&symbol.path, symbol.line) {
+                        let text = self.buffer.get_line_content(self.cursor.line);
+                        let col = crate::quickfix::utf16_to_char_col(&text, symbol.character);
+                        self.cursor.col = self.clamp_position(Position::new(self.cursor.line, col)).col;
+                    }
+                }
+            }
             Command::FuzzySearchSelect => {
                 // Extract selected item info first to avoid borrow conflicts
                 let selected_item = self
@@ -246,10 +610,10 @@ impl Editor {
                             fuzzy.navigate_to_directory(item.path);
                         }
                     } else {
-                        // Open file full-screen and close fuzzy search (Enter key behavior)
+                        // Close fuzzy search and open file full-screen (Enter key behavior)
+                        self.fuzzy_search = None;
+                        self.mode = Mode::Normal;
                         self.open_file(&item.path.to_string_lossy()).ok();
-                        self.fuzzy_search = None; // Close fuzzy search
-                        self.mode = Mode::Normal; // Return to normal mode
                     }
                 }
             }
@@ -279,36 +643,89 @@ impl Editor {
                     self.status_message = Some(format!("Gitignore filtering {}", mode_text));
                 }
             }
+            Command::FuzzySearchToggleHidden => {
+                if let Some(fuzzy) = &mut self.fuzzy_search {
+                    fuzzy.toggle_hidden();
+                    let mode_text = if fuzzy.show_hidden { "shown" } else { "hidden" };
+                    self.status_message = Some(format!("Hidden files {}", mode_text));
+                }
+            }
             Command::FuzzySearchLoadMore => {
                 if let Some(fuzzy) = &mut self.fuzzy_search {
                     fuzzy.load_more_results();
                 }
             }
-            Command::InsertMode => self.mode = Mode::Insert,
-            Command::NormalMode => self.mode = Mode::Normal,
-
-            Command::FormatBuffer => {
-                if let Some(formatter) = &self.formatter {
-                    let cursor_line = self.cursor.line;
-                    let cursor_col = self.cursor.col;
-                    match self
-                        .buffer
-                        .format_buffer(formatter, cursor_line, cursor_col)
+            Command::AddCursor => self.add_cursor(),
+            Command::InsertMode(count) => self.start_insert(count, false),
+            Command::AppendAfter(count) => {
+                let line_len = self.buffer.line(self.cursor.line).unwrap_or_default().chars().count();
+                self.cursor.col = (self.cursor.col + 1).min(line_len);
+                self.start_insert(count, false);
+            }
+            Command::AppendLineEnd(count) => {
+                self.cursor.col = self.buffer.line(self.cursor.line).unwrap_or_default().chars().count();
+                self.start_insert(count, false);
+            }
+            Command::InsertLineStart(count) => {
+                let pos = Position::new(self.cursor.line, self.cursor.col);
+                self.cursor.col = crate::motion::first_non_blank(&self.buffer, pos).col;
+                self.start_insert(count, false);
+            }
+            Command::OpenLineBelow(count) => {
+                self.open_line_below();
+                self.start_insert(count, true);
+            }
+            Command::OpenLineAbove(count) => {
+                if self.buffer.insert_text("\n", self.cursor.line, 0).is_ok() {
+                    self.cursor.col = 0;
+                    self.notify_text_change();
+                }
+                self.start_insert(count, true);
+            }
+            Command::NormalMode => {
+                if self.mode == Mode::Visual {
+                    self.exit_visual();
+                }
+                if self.mode == Mode::Insert {
+                    self.finish_insert();
+                }
+                self.completion_popup.hide();
+                self.cursors.clear();
+                self.mode = Mode::Normal;
+            }
+            Command::Escape => self.escape(),
+            Command::CloseAllOverlays => self.close_overlays(),
+            Command::InsertNormalCommand => {
+                if self.mode == Mode::Insert {
+                    // The text typed so far is the last insert, as on Escape
+                    if let Some(session) = self.insert_session.take()
+                        && !session.text.is_empty()
                     {
-                        Ok((new_line, new_col)) => {
-                            self.cursor.line = new_line;
-                            self.cursor.col = new_col;
-                            self.status_message = Some("Formatted".to_string());
-                        }
-                        Err(e) => {
-                            self.status_message = Some(format!("Format failed: {}", e));
-                        }
+                        self.registers.store_inserted(session.text);
                     }
-                } else {
+                    self.completion_popup.hide();
+                    self.cursors.clear();
+                    let insert_col = self.cursor.col;
+                    let line_len = self.line_char_count(self.cursor.line);
+                    self.cursor.col = insert_col.min(line_len.saturating_sub(1));
+                    self.insert_return =
+                        Some((Position::new(self.cursor.line, self.cursor.col), insert_col));
+                    self.mode = Mode::Normal;
+                }
+            }
+
+            Command::FormatBuffer => match self.format_current_buffer() {
+                Ok(true) => self.status_message = Some("Formatted".to_string()),
+                // Without a formatter program the language server may format
+                Ok(false) if self.current_language.is_some() => {
+                    self.pending_lsp_action = Some(LspAction::Format);
+                }
+                Ok(false) => {
                     self.status_message =
                         Some("No formatter available for this file type".to_string());
                 }
-            }
+                Err(e) => self.show_error(format!("Format failed: {}", e)),
+            },
             Command::Completion => {
                 // TODO: Implement async completion with proper UI integration
                 // For now, completion is a placeholder
@@ -318,51 +735,36 @@ impl Editor {
                 eprintln!("LSP goto definition not implemented yet");
             }
             Command::FindReferences => {
-                // TODO: Implement LSP find references
-                eprintln!("LSP find references not implemented yet");
+                let position = self.cursor_lsp_position();
+                self.pending_lsp_action = Some(LspAction::References(position));
             }
             Command::Hover => {
                 // Toggle hover information
                 if self.hover_content.is_some() {
                     self.hide_hover();
                 } else {
-                    // TODO: Request hover information from LSP server
-                    // For now, show a placeholder
-                    self.show_hover(vec![
-                        "This is hover information".to_string(),
-                        "It would show type information,".to_string(),
-                        "documentation, etc.".to_string(),
-                    ]);
+                    let position = self.cursor_lsp_position();
+                    self.pending_lsp_action = Some(LspAction::Hover(position));
                 }
             }
-            Command::WorkspaceSymbols => {
-                // TODO: Implement LSP workspace symbols
-                eprintln!("LSP workspace symbols not implemented yet");
-            }
+            Command::WorkspaceSymbols => self.open_workspace_symbols(),
             Command::CodeAction => {
-                // Toggle code actions menu
                 if self.code_actions.is_some() {
                     self.hide_code_actions();
                 } else {
-                    // TODO: Request code actions from LSP server
-                    // For now, show placeholder actions
-                    self.show_code_actions(vec![
-                        lsp_types::CodeAction {
-                            title: "Add missing import".to_string(),
-                            kind: Some(lsp_types::CodeActionKind::QUICKFIX),
-                            ..Default::default()
-                        },
-                        lsp_types::CodeAction {
-                            title: "Extract function".to_string(),
-                            kind: Some(lsp_types::CodeActionKind::REFACTOR_EXTRACT),
-                            ..Default::default()
-                        },
-                        lsp_types::CodeAction {
-                            title: "Sort imports".to_string(),
-                            kind: Some(lsp_types::CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
-                            ..Default::default()
-                        },
-                    ]);
+                    // Actions for the selection, or else the cursor position
+                    let cursor = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+                    let (start, end) = self.visual_selection().unwrap_or((cursor, cursor));
+                    if self.mode == Mode::Visual {
+                        self.exit_visual();
+                        self.mode = Mode::Normal;
+                    }
+                    let rope = &self.buffer.rope;
+                    let range = lsp_types::Range::new(
+                        lsp_position(rope, self.buffer.position_to_char(start)),
+                        lsp_position(rope, self.buffer.position_to_char(end)),
+                    );
+                    self.pending_lsp_action = Some(LspAction::CodeActions(range));
                 }
             }
             Command::CodeActionNext => {
@@ -372,27 +774,34 @@ impl Editor {
                 self.select_prev_code_action();
             }
             Command::CodeActionAccept => {
-                if let Some(action) = self.get_selected_code_action() {
-                    // TODO: Execute the selected code action
-                    eprintln!("Executing code action: {}", action.title);
+                if let Some(action) = self.get_selected_code_action().cloned() {
                     self.hide_code_actions();
+                    self.pending_lsp_action = Some(LspAction::ApplyCodeAction(Box::new(action)));
                 }
             }
+            Command::OrganizeImports => {
+                self.pending_lsp_action = Some(LspAction::OrganizeImports);
+            }
             Command::EnterCommandMode => {
+                // From visual mode the command line starts with the selection's range
+                let range = if self.mode == Mode::Visual {
+                    self.exit_visual();
+                    "'<,'>"
+                } else {
+                    ""
+                };
                 self.enter_command_mode();
+                self.command_line = range.to_string();
             }
+            Command::RepeatExCommand(count) => return self.repeat_ex_command(count),
+            Command::RepeatLastExecuted(count) => match self.last_executed {
+                Some(':') => return self.repeat_ex_command(count),
+                _ => self.show_error("No previous register executed"),
+            },
             Command::SaveFile => {
                 let path = self.buffer.file_path.as_ref().cloned();
                 if let Some(path) = path {
-                    // Spawn async save in background to avoid blocking UI
-                    let path_clone = path.clone();
-                    let content = self.buffer.rope.to_string();
-                    tokio::spawn(async move {
-                        if let Err(e) = tokio::fs::write(&path_clone, &content).await {
-                            eprintln!("Error saving file '{}': {}", path_clone, e);
-                        }
-                    });
-                    self.buffer.modified = false;
+                    self.save_buffer(&path, false);
 
                     // TODO: Notify LSP server about file save
                     // Async LSP operations need proper integration with sync UI
@@ -405,7 +814,11 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut new_pos = pos;
                 for _ in 0..count {
-                    new_pos = motion::word_forward(&self.buffer, new_pos);
+                    new_pos = motion::word_forward_with(
+                        &self.buffer,
+                        new_pos,
+                        &self.options.word_chars,
+                    );
                     // Clamp to buffer bounds
                     new_pos.line = new_pos.line.min(self.buffer.line_count().saturating_sub(1));
                     new_pos.col = new_pos.col.min(self.buffer.line_len(new_pos.line));
@@ -418,7 +831,8 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut new_pos = pos;
                 for _ in 0..count {
-                    new_pos = motion::word_backward(&self.buffer, new_pos);
+                    new_pos =
+                        motion::word_backward_with(&self.buffer, new_pos, &self.options.word_chars);
                 }
                 self.cursor.line = new_pos.line;
                 self.cursor.col = new_pos.col;
@@ -428,13 +842,33 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut new_pos = pos;
                 for _ in 0..count {
-                    new_pos = motion::word_end(&self.buffer, new_pos);
+                    new_pos = motion::word_end_with(
+                        &self.buffer,
+                        new_pos,
+                        &self.options.word_chars,
+                    );
                     new_pos.line = new_pos.line.min(self.buffer.line_count().saturating_sub(1));
                     new_pos.col = new_pos.col.min(self.buffer.line_len(new_pos.line).saturating_sub(1));
                 }
                 self.cursor.line = new_pos.line;
                 self.cursor.col = new_pos.col;
             }
+            Command::FindChar(search, count) => {
+                self.last_find = Some(search);
+                self.find_char(search, count, false);
+            }
+            Command::RepeatFindChar { reverse, count } => {
+                if let Some(search) = self.last_find {
+                    let search = if reverse { search.reversed() } else { search };
+                    self.find_char(search, count, true);
+                }
+            }
+            Command::MoveWordEndBackward(count) | Command::MoveBigWordEndBackward(count) => {
+                let big = matches!(cmd, Command::MoveBigWordEndBackward(_));
+                let (start, _) = self.word_end_backward_span(count, big);
+                self.cursor.line = start.line;
+                self.cursor.col = start.col;
+            }
             Command::MoveLineStart => {
                 self.cursor.col = 0;
             }
@@ -457,6 +891,46 @@ impl Editor {
                 self.cursor.line = self.buffer.line_count().saturating_sub(1);
                 self.cursor.col = 0;
             }
+            Command::GotoLine(line) => {
+                self.move_to_line_start(line.min(self.buffer.last_content_line()));
+            }
+            Command::GotoPercent(percent) => {
+                // Rounded up like Vim, so `1%` of a short file is its first line
+                let lines = self.buffer.last_content_line() + 1;
+                let line = (percent.min(100) * lines).div_ceil(100).clamp(1, lines);
+                self.move_to_line_start(line - 1);
+            }
+            Command::MoveMatchingPair => {
+                let line = self.buffer.get_line_content(self.cursor.line);
+                let bracket = line
+                    .chars()
+                    .skip(self.cursor.col)
+                    .position(|c| "()[]{}".contains(c))
+                    .map(|offset| Position::new(self.cursor.line, self.cursor.col + offset));
+                if let Some(found) =
+                    bracket.and_then(|pos| crate::motion::find_matching_pair(&self.buffer, pos))
+                {
+                    self.cursor.line = found.line;
+                    self.cursor.col = found.col;
+                }
+            }
+            Command::ShowCharInfo => self.show_char_info(),
+            Command::ShowCounts => self.show_counts(),
+            Command::ShowCurrentSyntaxCapture => self.show_syntax_captures(),
+            Command::NextHunk(count) => self.jump_to_hunk(count, true),
+            Command::MoveMatchingIndentUp(count) | Command::MoveMatchingIndentDown(count) => {
+                let forward = matches!(cmd, Command::MoveMatchingIndentDown(_));
+                if let Some(line) = crate::motion::indent_boundary(
+                    &self.buffer,
+                    self.cursor.line,
+                    forward,
+                    count,
+                    self.options.tab_width,
+                ) {
+                    self.move_to_line_start(line);
+                }
+            }
+            Command::PrevHunk(count) => self.jump_to_hunk(count, false),
             Command::MoveScreenTop => {
                 self.cursor.line = self.viewport.offset_line;
             }
@@ -478,19 +952,160 @@ impl Editor {
                     self.notify_text_change();
                 }
             }
-            Command::DeleteLine => {
-                if let Ok(_deleted) = self.buffer.delete_line(self.cursor.line) {
-                    // Yank to registers
-                    // TODO: self.registers.yank(deleted, '"');
-                    self.notify_text_change();
+            Command::TransposeChars => self.transpose_chars(),
+            Command::ToggleCaseChar(count) => {
+                let line_len = self.line_char_count(self.cursor.line);
+                if line_len > 0 {
+                    let start = Position::new(self.cursor.line, self.cursor.col.min(line_len - 1));
+                    let end = Position::new(self.cursor.line, (start.col + count).min(line_len));
+                    if self.buffer.change_case(start, end, CaseChange::Toggle).is_ok() {
+                        let line_len = self.line_char_count(self.cursor.line);
+                        self.cursor.col = end.col.min(line_len.saturating_sub(1));
+                        self.notify_text_change();
+                    }
+                }
+            }
+            Command::CaseWord(case, count) => {
+                let start = Position::new(self.cursor.line, self.cursor.col);
+                let mut end = start;
+                for _ in 0..count {
+                    end = crate::motion::word_forward_with(
+                        &self.buffer,
+                        end,
+                        &self.options.word_chars,
+                    );
+                }
+                // Like `cw`, stop at the end of the line instead of wrapping
+                if end.line != start.line {
+                    end = Position::new(start.line, self.line_char_count(start.line));
+                }
+                self.change_case(start, end, case);
+            }
+            Command::CaseToWordEnd(case, count) => {
+                let start = Position::new(self.cursor.line, self.cursor.col);
+                let mut end = start;
+                for _ in 0..count {
+                    end = crate::motion::word_end_with(&self.buffer, end, &self.options.word_chars);
+                }
+                // `e` is inclusive, so take in the character it lands on
+                let end_col = (end.col + 1).min(self.line_char_count(end.line));
+                let end = Position::new(end.line, end_col);
+                self.change_case(start, end, case);
+            }
+            Command::CaseToEnd(case) => {
+                let start = Position::new(self.cursor.line, self.cursor.col);
+                let end = Position::new(self.cursor.line, self.line_char_count(self.cursor.line));
+                self.change_case(start, end, case);
+            }
+            Command::CaseToStart(case) => {
+                let start = Position::new(self.cursor.line, 0);
+                let end = Position::new(self.cursor.line, self.cursor.col);
+                self.change_case(start, end, case);
+            }
+            Command::CaseLine(case, count) => {
+                let last = (self.cursor.line + count.max(1) - 1).min(self.buffer.last_content_line());
+                let start = Position::new(self.cursor.line, 0);
+                let end = Position::new(last, self.line_char_count(last));
+                let col = self.cursor.col;
+                self.change_case(start, end, case);
+                self.cursor.col = col;
+            }
+            Command::CaseInnerWord(case, count) => {
+                let cursor = Position::new(self.cursor.line, self.cursor.col);
+                let (start, mut end) =
+                    crate::motion::inner_word_with(&self.buffer, cursor, &self.options.word_chars);
+                for _ in 1..count {
+                    if end.col >= self.line_char_count(end.line) {
+                        break;
+                    }
+                    end = crate::motion::inner_word_with(
+                        &self.buffer,
+                        end,
+                        &self.options.word_chars,
+                    ).1;
                 }
+                self.change_case(start, end, case);
+            }
+            Command::CaseSelection(case) => {
+                if let Some((start, end)) = self.visual_selection() {
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    self.change_case(start, end, case);
+                }
+            }
+            Command::YankSelection => {
+                let reg = self.target_register('0');
+                if let Some((start, end)) = self.visual_selection() {
+                    let visual_line = self.visual_line;
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    let text = self.buffer.get_range(start, end);
+                    self.status_message = Some(if visual_line {
+                        let lines = end.line - start.line + 1;
+                        if lines > 1 {
+                            format!("Yanked {} lines", lines)
+                        } else {
+                            format!("Yanked line ({} chars)", text.len())
+                        }
+                    } else {
+                        format!("Yanked {} chars", text.chars().count())
+                    });
+                    self.yank_to_register(text, reg, visual_line);
+                    self.cursor.line = start.line;
+                    self.cursor.col = start.col;
+                }
+            }
+            Command::DeleteSelection => {
+                let reg = self.target_register('"');
+                if let Some((start, end)) = self.visual_selection() {
+                    let visual_line = self.visual_line;
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    if visual_line {
+                        self.delete_lines_to_register(LineRange::new(start.line, end.line), reg);
+                    } else if let Ok(deleted) = self.buffer.delete_range(start, end) {
+                        self.registers.yank(deleted, reg);
+                        let pos = self.clamp_position(start);
+                        self.cursor.line = pos.line;
+                        self.cursor.col = pos.col;
+                        self.notify_text_change();
+                    }
+                }
+            }
+            Command::FormatSelection => {
+                if let Some((start, end)) = self.visual_selection() {
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    if start.line == 0 && end.line >= self.buffer.last_content_line() {
+                        self.execute_command(Command::FormatBuffer);
+                    } else {
+                        self.reindent_lines(start.line, end.line);
+                    }
+                }
+            }
+            Command::ReplaceSelection(ch) => {
+                if let Some((start, end)) = self.visual_selection() {
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    self.replace_selection(start, end, ch);
+                }
+            }
+            Command::DeleteLine(count) => {
+                let reg = self.target_register('"');
+                let last_line = self.buffer.last_content_line();
+                let end = (self.cursor.line + count.max(1) - 1).min(last_line);
+                self.delete_lines_to_register(LineRange::new(self.cursor.line, end), reg);
             }
             Command::DeleteWord(count) => {
                 use crate::motion::{self, Position};
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.notify_text_change();
@@ -513,47 +1128,49 @@ impl Editor {
                     self.notify_text_change();
                 }
             }
-            Command::JoinLines(count) => {
-                for _ in 0..count {
-                    if self.buffer.join_lines(self.cursor.line).is_ok() {
-                        self.notify_text_change();
-                    }
+            Command::JoinLines(count) => self.join_lines(count, true),
+            Command::JoinLinesNoSpace(count) => self.join_lines(count, false),
+            Command::JoinSelection | Command::JoinSelectionNoSpace => {
+                if let Some(range) = self.visual_line_range() {
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                    self.cursor.line = range.start;
+                    let spaced = matches!(cmd, Command::JoinSelection);
+                    self.join_lines(range.end - range.start + 1, spaced);
                 }
             }
 
             // ===== Yank commands =====
-            Command::YankLine => {
-                let text = self.buffer.get_line_content(self.cursor.line);
-                // TODO: self.registers.yank(text, '"');
-                self.status_message = Some(format!("Yanked line ({} chars)", text.len()));
+            Command::YankLine(count) => {
+                let reg = self.target_register('0');
+                let text = self.buffer.get_lines(self.cursor.line, count);
+                let lines = count.min(self.buffer.line_count().saturating_sub(self.cursor.line));
+                self.status_message = Some(if lines > 1 {
+                    format!("Yanked {} lines", lines)
+                } else {
+                    format!("Yanked line ({} chars)", text.len())
+                });
+                self.yank_to_register(text, reg, true);
             }
             Command::YankToEnd => {
                 use crate::motion::{self, Position};
+                let reg = self.target_register('0');
                 let start = Position::new(self.cursor.line, self.cursor.col);
                 let end = motion::line_end(&self.buffer, start);
                 let text = self.buffer.get_range(start, end);
-                // TODO: self.registers.yank(text, '"');
                 self.status_message = Some(format!("Yanked to end ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
             }
 
             // ===== Paste commands =====
+            Command::PasteAfter | Command::PasteBefore if self.mode == Mode::Visual => {
+                self.paste_over_selection(matches!(cmd, Command::PasteAfter));
+            }
             Command::PasteAfter => {
-                // TODO: if let Some(text) = self.registers.get('"') {
-                //     let text = text.to_string();
-                //     if self.buffer.insert_text(&text, self.cursor.line, self.cursor.col + 1).is_ok() {
-                //         self.cursor.col += text.len();
-                //         self.notify_text_change();
-                //     }
-                // }
+                self.paste(true);
             }
             Command::PasteBefore => {
-                // TODO: if let Some(text) = self.registers.get('"') {
-                //     let text = text.to_string();
-                //     if self.buffer.insert_text(&text, self.cursor.line, self.cursor.col).is_ok() {
-                //         self.cursor.col += text.len();
-                //         self.notify_text_change();
-                //     }
-                // }
+                self.paste(false);
             }
 
             // ===== Change commands =====
@@ -562,6 +1179,41 @@ impl Editor {
                     self.mode = Mode::Insert;
                 }
             }
+            Command::ShowLspLog => self.show_lsp_log(),
+            Command::OpenQuickfix => {
+                if self.quickfix.is_empty() {
+                    self.status_message = Some("No quickfix list".to_string());
+                } else {
+                    self.quickfix.selected = self.quickfix.current;
+                    self.quickfix_open = true;
+                }
+            }
+            Command::QuickfixNext(count) | Command::QuickfixPrev(count) => {
+                let forward = matches!(cmd, Command::QuickfixNext(_));
+                match self.quickfix.step(count, forward).cloned() {
+                    Some(entry) => self.jump_to_quickfix_entry(&entry),
+                    None => self.status_message = Some("No quickfix list".to_string()),
+                }
+            }
+            Command::QuickfixSelectNext => self.quickfix.select_next(),
+            Command::QuickfixSelectPrev => self.quickfix.select_prev(),
+            Command::QuickfixAccept => {
+                self.quickfix_open = false;
+                if let Some(entry) = self.quickfix.accept_selected().cloned() {
+                    self.jump_to_quickfix_entry(&entry);
+                }
+            }
+            Command::InsertCodePoint => {
+                if self.mode == Mode::Insert {
+                    self.code_point_entry = Some(CodePointEntry::new());
+                }
+            }
+            Command::RepeatSubstitute => {
+                let range = LineRange::single(self.cursor.line);
+                if let Err(e) = self.repeat_substitute(range, false) {
+                    self.show_error(e);
+                }
+            }
             Command::SubstituteLine => {
                 self.cursor.col = 0;
                 if self.buffer.delete_range(
@@ -584,13 +1236,27 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.mode = Mode::Insert;
                     self.notify_text_change();
                 }
             }
+            Command::ChangeWordEndBackward(count) | Command::ChangeBigWordEndBackward(count) => {
+                let big = matches!(cmd, Command::ChangeBigWordEndBackward(_));
+                let (start, end) = self.word_end_backward_span(count, big);
+                if let Ok(_deleted) = self.buffer.delete_range(start, end) {
+                    self.cursor.line = start.line;
+                    self.cursor.col = start.col;
+                    self.mode = Mode::Insert;
+                    self.notify_text_change();
+                }
+            }
             Command::ChangeToEnd => {
                 use crate::motion::{self, Position};
                 let start = Position::new(self.cursor.line, self.cursor.col);
@@ -604,29 +1270,66 @@ impl Editor {
             // ===== Visual mode =====
             Command::VisualChar => {
                 self.mode = Mode::Visual;
+                self.visual_start = Some(Position::new(self.cursor.line, self.cursor.col));
+                self.visual_line = false;
                 self.status_message = Some("-- VISUAL --".to_string());
             }
             Command::VisualLine => {
                 self.mode = Mode::Visual;
+                self.visual_start = Some(Position::new(self.cursor.line, self.cursor.col));
+                self.visual_line = true;
                 self.status_message = Some("-- VISUAL LINE --".to_string());
             }
-
-            // ===== Undo/Redo =====
-            Command::Undo => {
-                // TODO: implement undo
-                self.status_message = Some("Undo not yet implemented".to_string());
+            Command::SelectAll => {
+                self.mode = Mode::Visual;
+                self.visual_start = Some(Position::new(0, 0));
+                self.visual_line = true;
+                self.cursor.line = self.buffer.last_content_line();
+                self.cursor.col = 0;
+                self.status_message = Some("-- VISUAL LINE --".to_string());
+            }
+            Command::SelectTextObject(object) => {
+                let pos = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+                if let Some((start, end)) = crate::motion::text_object_range(
+                    &self.buffer,
+                    pos,
+                    object,
+                    self.options.tab_width,
+                ) && start != end
+                {
+                    // The selection's last character, or the last line of a
+                    // linewise object
+                    let last = self
+                        .buffer
+                        .char_to_position(self.buffer.position_to_char(end) - 1);
+                    self.visual_line = object.is_linewise();
+                    self.visual_start = Some(start);
+                    self.cursor.line = last.line;
+                    self.cursor.col = if self.visual_line { 0 } else { last.col };
+                }
             }
-            Command::Redo => {
-                // TODO: implement redo
-                self.status_message = Some("Redo not yet implemented".to_string());
+            Command::VisualSwapEnds => {
+                if let Some(anchor) = self.visual_start {
+                    self.visual_start = Some(Position::new(self.cursor.line, self.cursor.col));
+                    self.cursor.line = anchor.line;
+                    self.cursor.col = anchor.col;
+                }
             }
 
+            // ===== Undo/Redo =====
+            Command::Undo(count) => self.undo(count),
+            Command::Redo(count) => self.redo(count),
+
             Command::DeleteToStartWord(count) => {
                 use crate::motion::{self, Position};
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut start_pos = pos;
                 for _ in 0..count {
-                    start_pos = motion::word_backward(&self.buffer, start_pos);
+                    start_pos = motion::word_backward_with(
+                        &self.buffer,
+                        start_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(start_pos, pos) {
                     self.cursor.line = start_pos.line;
@@ -634,12 +1337,25 @@ impl Editor {
                     self.notify_text_change();
                 }
             }
+            Command::DeleteWordEndBackward(count) | Command::DeleteBigWordEndBackward(count) => {
+                let big = matches!(cmd, Command::DeleteBigWordEndBackward(_));
+                let (start, end) = self.word_end_backward_span(count, big);
+                if let Ok(_deleted) = self.buffer.delete_range(start, end) {
+                    self.cursor.line = start.line;
+                    self.cursor.col = start.col;
+                    self.notify_text_change();
+                }
+            }
             Command::DeleteToEndWord(count) => {
                 use crate::motion::{self, Position};
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_end(&self.buffer, end_pos);
+                    end_pos = motion::word_end_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.notify_text_change();
@@ -651,7 +1367,11 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 // Remove trailing whitespace
                 while end_pos.col > 0 && self.cursor.line == end_pos.line {
@@ -673,12 +1393,53 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.notify_text_change();
                 }
             }
+            Command::DeleteTextObject(object) | Command::ChangeTextObject(object) => {
+                let change = matches!(cmd, Command::ChangeTextObject(_));
+                let reg = self.target_register('"');
+                let pos = Position::new(self.cursor.line, self.cursor.col);
+                let Some((start, end)) = crate::motion::text_object_range(
+                    &self.buffer,
+                    pos,
+                    object,
+                    self.options.tab_width,
+                ) else {
+                    return false;
+                };
+                let Ok(deleted) = self.buffer.delete_range(start, end) else {
+                    return false;
+                };
+                if object.is_linewise() {
+                    self.registers.yank_lines(deleted, reg);
+                } else if !deleted.is_empty() {
+                    self.registers.yank(deleted, reg);
+                }
+                // Changing the lines of a block leaves an empty line to type on
+                let block = start.line < end.line && start.col == 0 && end.col == 0;
+                if change && block {
+                    let _ = self.buffer.insert_text("\n", start.line, 0);
+                }
+                self.cursor.line = start.line;
+                self.cursor.col = start.col;
+                if change {
+                    self.mode = Mode::Insert;
+                } else if object.is_linewise() {
+                    self.move_to_line_start(start.line);
+                } else {
+                    let line_len = self.line_char_count(start.line);
+                    self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+                }
+                self.notify_text_change();
+            }
             Command::DeleteToEndOfFile => {
                 use crate::motion::Position;
                 let start = Position::new(self.cursor.line, self.cursor.col);
@@ -697,37 +1458,80 @@ impl Editor {
                     self.notify_text_change();
                 }
             }
-            Command::DeleteLineIntoRegister(_reg) => {
-                if let Ok(_deleted) = self.buffer.delete_line(self.cursor.line) {
-                    // TODO: self.registers.yank(deleted, reg);
+            Command::DeleteLineIntoRegister(reg) => {
+                if let Ok(deleted) = self.buffer.delete_line(self.cursor.line) {
+                    self.registers.yank_lines(deleted, reg);
                     self.notify_text_change();
                 }
             }
             Command::YankWord(count) => {
                 use crate::motion::{self, Position};
+                let reg = self.target_register('0');
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 let text = self.buffer.get_range(pos, end_pos);
-                // TODO: self.registers.yank(text, '"');
                 self.status_message = Some(format!("Yanked word ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
+            }
+            Command::YankWordEndBackward(count) | Command::YankBigWordEndBackward(count) => {
+                let big = matches!(cmd, Command::YankBigWordEndBackward(_));
+                let reg = self.target_register('0');
+                let (start, end) = self.word_end_backward_span(count, big);
+                let text = self.buffer.get_range(start, end);
+                self.status_message = Some(format!("Yanked word ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
+                self.cursor.line = start.line;
+                self.cursor.col = start.col;
+            }
+            Command::YankTextObject(object) => {
+                let reg = self.target_register('0');
+                let pos = Position::new(self.cursor.line, self.cursor.col);
+                if let Some((start, end)) = crate::motion::text_object_range(
+                    &self.buffer,
+                    pos,
+                    object,
+                    self.options.tab_width,
+                ) {
+                    let text = self.buffer.get_range(start, end);
+                    if object.is_linewise() {
+                        let lines = text.lines().count();
+                        self.status_message = Some(format!("Yanked {} lines", lines));
+                        self.yank_to_register(text, reg, true);
+                    } else {
+                        self.status_message = Some(format!("Yanked {} chars", text.chars().count()));
+                        self.yank_to_register(text, reg, false);
+                    }
+                    self.cursor.line = start.line;
+                    self.cursor.col = start.col;
+                }
             }
             Command::YankToStart => {
                 use crate::motion::Position;
+                let reg = self.target_register('0');
                 let start = Position::new(self.cursor.line, 0);
                 let end = Position::new(self.cursor.line, self.cursor.col);
                 let text = self.buffer.get_range(start, end);
-                // TODO: self.registers.yank(text, '"');
                 self.status_message = Some(format!("Yanked to start ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
             }
             Command::YankInnerWord(count) => {
                 use crate::motion::{self, Position};
+                let reg = self.target_register('0');
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 // Remove trailing whitespace
                 while end_pos.col > 0 && self.cursor.line == end_pos.line {
@@ -740,20 +1544,25 @@ impl Editor {
                     }
                 }
                 let text = self.buffer.get_range(pos, end_pos);
-                // TODO: self.registers.yank(text, '"');
                 self.status_message = Some(format!("Yanked inner word ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
             }
             Command::YankAWord(count) => {
                 // Same as YankWord for now
                 use crate::motion::{self, Position};
+                let reg = self.target_register('0');
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 let text = self.buffer.get_range(pos, end_pos);
-                // TODO: self.registers.yank(text, '"');
                 self.status_message = Some(format!("Yanked word ({} chars)", text.len()));
+                self.yank_to_register(text, reg, false);
             }
             Command::ChangeToStart => {
                 use crate::motion::Position;
@@ -770,7 +1579,11 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.mode = Mode::Insert;
@@ -783,7 +1596,11 @@ impl Editor {
                 let pos = Position::new(self.cursor.line, self.cursor.col);
                 let mut end_pos = pos;
                 for _ in 0..count {
-                    end_pos = motion::word_forward(&self.buffer, end_pos);
+                    end_pos = motion::word_forward_with(
+                        &self.buffer,
+                        end_pos,
+                        &self.options.word_chars,
+                    );
                 }
                 if let Ok(_deleted) = self.buffer.delete_range(pos, end_pos) {
                     self.mode = Mode::Insert;
@@ -791,88 +1608,357 @@ impl Editor {
                 }
             }
             Command::IndentLine(count) => {
-                if self.buffer.indent_range(self.cursor.line, self.cursor.line + count - 1, 4).is_ok() {
+                let end = self.cursor.line + count.max(1) - 1;
+                if self.buffer.indent_range(self.cursor.line, end, 1, &self.options).is_ok() {
+                    self.move_to_line_start(self.cursor.line);
                     self.notify_text_change();
                 }
             }
             Command::UnindentLine(count) => {
-                if self.buffer.unindent_range(self.cursor.line, self.cursor.line + count - 1, 4).is_ok() {
+                let end = self.cursor.line + count.max(1) - 1;
+                if self.buffer.unindent_range(self.cursor.line, end, 1, &self.options).is_ok() {
+                    self.move_to_line_start(self.cursor.line);
+                    self.notify_text_change();
+                }
+            }
+            Command::IndentSelection(levels) | Command::UnindentSelection(levels) => {
+                let Some(range) = self.visual_line_range() else {
+                    return false;
+                };
+                let indent = matches!(cmd, Command::IndentSelection(_));
+                let result = if indent {
+                    self.buffer.indent_range(range.start, range.end, levels, &self.options)
+                } else {
+                    self.buffer.unindent_range(range.start, range.end, levels, &self.options)
+                };
+                if result.is_ok() {
+                    // `.` shifts the same number of lines from the cursor
+                    let lines = range.end - range.start + 1;
+                    self.last_change = Some(if indent {
+                        Command::IndentLine(lines)
+                    } else {
+                        Command::UnindentLine(lines)
+                    });
+                    let line_len = self.buffer.get_line_content(self.cursor.line).chars().count();
+                    self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
                     self.notify_text_change();
                 }
             }
+            Command::Reindent(motion) => {
+                let (start, end) = self.line_motion_range(motion);
+                self.reindent_lines(start, end);
+            }
+            Command::ReindentBuffer => {
+                self.reindent_lines(0, self.buffer.last_content_line());
+            }
+            Command::ToggleComment(motion) => {
+                let (start, end) = self.line_motion_range(motion);
+                self.toggle_comment(start, end);
+            }
+            Command::ToggleCommentSelection => {
+                let Some(range) = self.visual_line_range() else {
+                    return false;
+                };
+                if self.toggle_comment(range.start, range.end) {
+                    // `.` toggles the same number of lines from the cursor
+                    let lines = range.end - range.start;
+                    self.last_change = Some(Command::ToggleComment(LineMotion::Down(lines)));
+                    self.exit_visual();
+                    self.mode = Mode::Normal;
+                }
+            }
+            Command::RepeatLastChange => {
+                if let Some(change) = self.last_change.clone() {
+                    return self.execute_command(change);
+                }
+            }
 
             _ => {
                 // Unknown command
-                self.status_message = Some(format!("Unknown command: {:?}", cmd));
+                self.show_error(format!("Unknown command: {:?}", cmd));
             }
         }
         // Update desired_col
         self.cursor.desired_col = self.cursor.col;
         // Scroll to keep cursor visible
         self.viewport
-            .scroll_to_cursor(self.cursor.line, self.cursor.col);
+            .scroll_to_cursor(self.cursor.line, self.cursor_display_col());
         false // Don't quit by default
     }
 
-    pub fn handle_resize(&mut self, rows: u16, cols: u16) {
-        self.viewport.rows = rows as usize - 1; // Leave room for status bar (1 line)
-        self.viewport.cols = cols as usize;
+    /// Screen column of the cursor within its line, counting tab stops, wide
+    /// characters and inlay hints
+    pub fn cursor_display_col(&self) -> usize {
+        self.display_col_at(self.cursor.line, self.cursor.col)
     }
 
-    pub fn get_buffer_uri(&self) -> Option<Url> {
-        self.buffer
-            .file_path
-            .as_ref()
-            .and_then(|path| Url::from_file_path(path).ok())
+    /// Screen column of the character at `col` of `line`, counting tab stops,
+    /// wide characters and the inlay hints drawn before it
+    pub fn display_col_at(&self, line: usize, col: usize) -> usize {
+        let text = self.buffer.line(line).unwrap_or_default();
+        let hints = self.inlay_hints.line(line);
+        if hints.is_empty() {
+            return crate::display::display_col(&text, col, self.options.tab_width);
+        }
+        let (drawn, _) = crate::display::with_virtual_text(&text, hints);
+        let drawn_col = crate::display::virtual_col(hints, col);
+        crate::display::display_col(&drawn, drawn_col, self.options.tab_width)
     }
 
-    pub fn open_file(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.buffer.load_from_file(path)?;
-        self.buffer.file_path = Some(path.to_string());
-
-        // Reset viewport and cursor to ensure clean rendering state
-        self.viewport.offset_line = 0;
-        self.viewport.offset_col = 0;
-        self.cursor.line = 0;
-        self.cursor.col = 0;
+    /// Register selected with a `"x` prefix for the current command, or `default`
+    fn target_register(&mut self, default: char) -> char {
+        self.vim_parser.take_register().unwrap_or(default)
+    }
 
-        // Update language based on file extension
-        let language_config = crate::syntax::language::get_language_config_by_extension(
-            std::path::Path::new(path)
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or(""),
-        );
+    /// Store yanked text in `reg`. With `yank_to_clipboard` set, yanks into
+    /// the default register are copied to `+` and the system clipboard too.
+    fn yank_to_register(&mut self, text: String, reg: char, linewise: bool) {
+        let store = |registers: &mut Registers, text: String, reg: char| {
+            if linewise {
+                registers.yank_lines(text, reg);
+            } else {
+                registers.yank(text, reg);
+            }
+        };
+        if reg == '0' && self.options.yank_to_clipboard {
+            store(&mut self.registers, text.clone(), '+');
+            #[cfg(feature = "clipboard")]
+            if let Err(e) =
+                crate::clipboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(&text))
+            {
+                log::warn!("Failed to copy to the system clipboard: {}", e);
+            }
+        }
+        store(&mut self.registers, text, reg);
+    }
 
-        if let Some(config) = language_config {
-            self.current_language = Some(config.id);
+    /// Put register contents after (`p`) or before (`P`) the cursor
+    fn paste(&mut self, after: bool) {
+        let reg = self.target_register('"');
+        let text = match self.registers.get(reg) {
+            Some(text) if !text.is_empty() => text.to_string(),
+            _ => return,
+        };
 
-            // Initialize syntax highlighter for this language
-            match crate::syntax::highlighter::SyntaxHighlighter::new(config) {
-                Ok(highlighter) => {
-                    self.buffer.highlighter = Some(highlighter);
-                    // Parse the loaded content
-                    let _ = self.buffer.update_highlighter();
-                }
-                Err(_) => {
-                    // Syntax highlighting failed to initialize, continue without it
-                    self.buffer.highlighter = None;
-                }
+        if self.registers.is_linewise(reg) {
+            let target_line = if after {
+                self.cursor.line + 1
+            } else {
+                self.cursor.line
+            };
+            let inserted = if target_line >= self.buffer.line_count() {
+                // Below the last line: lead with the newline instead of trailing it
+                let last_line = self.buffer.line_count().saturating_sub(1);
+                let end_col = self.buffer.get_line_content(last_line).chars().count();
+                let block = format!("\n{}", text.trim_end_matches('\n'));
+                self.buffer.insert_text(&block, last_line, end_col)
+            } else {
+                self.buffer.insert_text(&text, target_line, 0)
+            };
+            if inserted.is_ok() {
+                self.cursor.line = target_line.min(self.buffer.line_count().saturating_sub(1));
+                let pos = crate::motion::first_non_blank(
+                    &self.buffer,
+                    Position::new(self.cursor.line, 0),
+                );
+                self.cursor.col = pos.col;
+                self.notify_text_change();
             }
         } else {
-            self.current_language = None;
-            self.buffer.highlighter = None;
+            let line_len = self.buffer.get_line_content(self.cursor.line).chars().count();
+            let col = if after && line_len > 0 {
+                (self.cursor.col + 1).min(line_len)
+            } else {
+                self.cursor.col.min(line_len)
+            };
+            if self.buffer.insert_text(&text, self.cursor.line, col).is_ok() {
+                if !text.contains('\n') {
+                    self.cursor.col = col + text.chars().count() - 1;
+                } else {
+                    self.cursor.col = col;
+                }
+                self.notify_text_change();
+            }
         }
+    }
 
-        // TODO: Notify LSP server about file open
-        // Async LSP operations need proper integration with sync UI
+    /// Replace the visual selection with register contents. With `p` the
+    /// replaced text goes to the unnamed register; `P` leaves it alone so the
+    /// same text can be pasted over several selections.
+    fn paste_over_selection(&mut self, keep_replaced: bool) {
+        let reg = self.target_register('"');
+        let Some((start, end)) = self.visual_selection() else {
+            return;
+        };
+        let visual_line = self.visual_line;
+        self.exit_visual();
+        self.mode = Mode::Normal;
+        let text = match self.registers.get(reg) {
+            Some(text) if !text.is_empty() => text.to_string(),
+            _ => return,
+        };
+        let linewise = self.registers.is_linewise(reg);
 
-        Ok(())
+        // Whole lines keep their line break; lines pasted into the middle of
+        // a line split it so they stay lines of their own
+        let replacement = if visual_line {
+            text.strip_suffix('\n').unwrap_or(&text).to_string()
+        } else if linewise {
+            format!("\n{}", text)
+        } else {
+            text.clone()
+        };
+        let replaced = self.buffer.get_range(start, end);
+        let Ok(after) = self.buffer.replace_range(start, end, &replacement) else {
+            return;
+        };
+
+        if keep_replaced {
+            if visual_line {
+                self.registers.yank_lines(replaced, '"');
+            } else {
+                self.registers.yank(replaced, '"');
+            }
+        }
+        if visual_line || linewise {
+            self.move_to_line_start(start.line + usize::from(!visual_line));
+        } else if text.contains('\n') {
+            self.cursor.line = start.line;
+            self.cursor.col = start.col;
+        } else {
+            self.cursor.line = after.line;
+            self.cursor.col = after.col.saturating_sub(1);
+        }
+        self.notify_text_change();
     }
 
-    /// Async version of open_file - uses async file loading to avoid blocking UI
-    pub async fn open_file_async(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.buffer.load_from_file_async(path).await?;
+    pub fn handle_resize(&mut self, rows: u16, cols: u16) {
+        // Leave room for status bar (1 line)
+        self.viewport.rows = (rows as usize).saturating_sub(1);
+        self.viewport.cols = cols as usize;
+    }
+
+    /// Click to place the cursor, drag to select, and scroll with the wheel
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if self.fuzzy_search.is_some()
+            || !matches!(self.mode, Mode::Normal | Mode::Insert | Mode::Visual)
+        {
+            return;
+        }
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(pos) = self.screen_to_buffer(event.row, event.column) {
+                    self.click_at(pos);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(pos) = self.screen_to_buffer(event.row, event.column) {
+                    self.drag_to(pos);
+                }
+            }
+            MouseEventKind::ScrollUp => self.scroll_view(-(SCROLL_LINES as isize)),
+            MouseEventKind::ScrollDown => self.scroll_view(SCROLL_LINES as isize),
+            _ => {}
+        }
+    }
+
+    /// Buffer position shown at a screen cell of the editor pane, or `None`
+    /// for the status bar. Clicks in the gutter or past the end of a line land
+    /// on its nearest character; rows below the last line on the last line.
+    pub fn screen_to_buffer(&self, row: u16, col: u16) -> Option<Position> {
+        if row as usize >= self.viewport.rows {
+            return None;
+        }
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        let line = (self.viewport.offset_line + row as usize).min(last_line);
+        let text = self.buffer.get_line_content(line);
+        let display_col = col.saturating_sub(Gutter::WIDTH) as usize + self.viewport.offset_col;
+        let (drawn, is_buffer) = crate::display::with_virtual_text(&text, self.inlay_hints.line(line));
+        let drawn_col = crate::display::char_col_at(&drawn, display_col, self.options.tab_width);
+        // A click on a hint lands on the character after it
+        let mut char_col = is_buffer.iter().take(drawn_col).filter(|b| **b).count();
+        // Only insert mode puts the cursor past the last character
+        if self.mode != Mode::Insert {
+            char_col = char_col.min(text.chars().count().saturating_sub(1));
+        }
+        Some(Position::new(line, char_col))
+    }
+
+    fn click_at(&mut self, pos: Position) {
+        match self.mode {
+            Mode::Visual => {
+                self.exit_visual();
+                self.mode = Mode::Normal;
+            }
+            Mode::Insert => {
+                // Typing after the click is a separate change, not repeated by a count
+                self.insert_undo_recorded = false;
+                self.insert_session = None;
+            }
+            _ => {}
+        }
+        self.vim_parser.reset();
+        self.cursors.clear();
+        self.cursor.line = pos.line;
+        self.cursor.col = pos.col;
+    }
+
+    /// Extend the visual selection to `pos`, starting one at the cursor
+    fn drag_to(&mut self, pos: Position) {
+        if self.mode != Mode::Visual {
+            self.execute_command(Command::NormalMode);
+            self.execute_command(Command::VisualChar);
+        }
+        self.cursor.line = pos.line;
+        self.cursor.col = pos.col.min(self.line_char_count(pos.line).saturating_sub(1));
+    }
+
+    /// Scroll the view by `delta` lines, dragging the cursor along if it
+    /// would leave the screen
+    fn scroll_view(&mut self, delta: isize) {
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        let offset = self.viewport.offset_line.saturating_add_signed(delta);
+        self.viewport.offset_line = offset.min(last_line);
+
+        let first = self.viewport.offset_line;
+        let last = first + self.viewport.rows.max(1) - 1;
+        let line = self.cursor.line.clamp(first, last.min(last_line));
+        if line != self.cursor.line {
+            self.cursor.line = line;
+            let max_col = self.line_char_count(line);
+            let max_col = if self.mode == Mode::Insert {
+                max_col
+            } else {
+                max_col.saturating_sub(1)
+            };
+            self.cursor.col = self.cursor.col.min(max_col);
+        }
+    }
+
+    pub fn get_buffer_uri(&self) -> Option<Url> {
+        self.buffer
+            .file_path
+            .as_ref()
+            .and_then(|path| std::path::absolute(path).ok())
+            .and_then(|path| Url::from_file_path(path).ok())
+    }
+
+    /// Clean up before exiting: close the open document and shut down language servers
+    pub async fn shutdown(&mut self) {
+        if let (Some(language), Some(uri)) = (self.current_language, self.get_buffer_uri()) {
+            let _ = self.lsp_manager.close_document(language, &uri).await;
+        }
+        let _ = self.lsp_manager.shutdown_all().await;
+    }
+
+    pub fn open_file(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.buffer.load_from_file(path)?;
+        self.undo_history.clear();
+        self.clear_inlay_hints();
+        // Files opened while browsing the fuzzy search are only previews
+        if self.mode != Mode::FuzzySearch {
+            self.recent_files.add(std::path::Path::new(path));
+        }
         self.buffer.file_path = Some(path.to_string());
 
         // Reset viewport and cursor to ensure clean rendering state
@@ -881,432 +1967,5785 @@ impl Editor {
         self.cursor.line = 0;
         self.cursor.col = 0;
 
-        // Update language based on file extension
-        let language_config = crate::syntax::language::get_language_config_by_extension(
-            std::path::Path::new(path)
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or(""),
-        );
+        self.detect_language(path);
+        self.load_project_options(path);
+        self.update_git_signs();
 
-        if let Some(config) = language_config {
-            self.current_language = Some(config.id);
+        self.watch_file(path);
 
-            // Initialize syntax highlighter for this language
-            match crate::syntax::highlighter::SyntaxHighlighter::new(config) {
-                Ok(highlighter) => {
-                    self.buffer.highlighter = Some(highlighter);
-                    // Parse the loaded content
-                    let _ = self.buffer.update_highlighter();
-                }
-                Err(_) => {
-                    // Syntax highlighting failed to initialize, continue without it
-                    self.buffer.highlighter = None;
-                }
+        // TODO: Notify LSP server about file open
+        // Async LSP operations need proper integration with sync UI
+
+        Ok(())
+    }
+
+    /// Apply the user's config, which every opened file starts from
+    pub fn apply_config(&mut self, config: &crate::config::TextyConfig) {
+        self.user_options.apply_config(config);
+        self.options = self.user_options.clone();
+        for (name, definition) in config.commands.iter().flatten() {
+            if ex::is_user_command_name(name) {
+                self.command_aliases.insert(name.clone(), definition.clone());
+            } else {
+                self.show_error(format!("Invalid user command name in config: {}", name));
             }
+        }
+    }
+
+    /// Reset the options to the user's and apply the file's `.editorconfig`,
+    /// so project settings win over the user's config
+    fn load_project_options(&mut self, path: &str) {
+        self.options = self.user_options.clone();
+        self.options
+            .apply_editorconfig(&EditorConfig::for_file(std::path::Path::new(path)));
+        self.options.use_language_word_chars(self.current_language);
+    }
+
+    /// Add a cursor at the next occurrence of the word under the newest cursor,
+    /// or on the next line in the same column when not on a word
+    fn add_cursor(&mut self) {
+        let newest = self.cursors.last().unwrap_or(&self.cursor);
+        let pos = self.clamp_position(Position::new(newest.line, newest.col));
+        let on_word = self
+            .buffer
+            .line(pos.line)
+            .and_then(|line| line.chars().nth(pos.col))
+            .is_some_and(|c| self.options.word_chars.is_word_char(c));
+
+        let target = if on_word {
+            self.next_word_occurrence(pos)
+        } else if pos.line + 1 < self.buffer.line_count() {
+            Some(self.clamp_position(Position::new(pos.line + 1, pos.col)))
         } else {
-            self.current_language = None;
-            self.buffer.highlighter = None;
+            None
+        };
+
+        let taken = |c: &Cursor| Some(Position::new(c.line, c.col)) == target;
+        match target {
+            Some(target) if !taken(&self.cursor) && !self.cursors.iter().any(taken) => {
+                self.cursors.push(Cursor {
+                    line: target.line,
+                    col: target.col,
+                    desired_col: target.col,
+                });
+                self.status_message = Some(format!("{} cursors", self.cursors.len() + 1));
+            }
+            _ => self.status_message = Some("No more matches".to_string()),
         }
+    }
 
-        // TODO: Notify LSP server about file open
-        // This is currently blocked by the async/sync boundary
-        // Async LSP operations need proper integration with sync UI
+    /// Position of the next whole-word match of the word at `pos`, wrapping
+    /// around the end of the buffer, at the same offset into the word
+    fn next_word_occurrence(&self, pos: Position) -> Option<Position> {
+        let (start, end) = crate::motion::inner_word_with(
+            &self.buffer,
+            pos,
+            &self.options.word_chars,
+        );
+        let line = self.buffer.line(pos.line).unwrap_or_default();
+        let word: String = line.chars().skip(start.col).take(end.col - start.col).collect();
 
-        Ok(())
+        let text = self.buffer.rope.to_string();
+        let is_word = |c: char| self.options.word_chars.is_word_char(c);
+        let matches: Vec<usize> = text
+            .match_indices(&word)
+            .map(|(byte, _)| byte)
+            .filter(|&byte| {
+                !text[..byte].chars().next_back().is_some_and(is_word)
+                    && !text[byte + word.len()..].chars().next().is_some_and(is_word)
+            })
+            .collect();
+
+        let from = self.buffer.rope.char_to_byte(self.buffer.position_to_char(end));
+        let byte = matches
+            .iter()
+            .find(|&&byte| byte >= from)
+            .or(matches.first())
+            .copied()?;
+        let found = self.buffer.char_to_position(self.buffer.rope.byte_to_char(byte));
+        (found != start).then(|| Position::new(found.line, found.col + pos.col - start.col))
     }
 
-    pub fn notify_text_change(&mut self) {
-        // Adjust viewport if it's out of bounds after buffer changes
-        let max_line = self.buffer.line_count().saturating_sub(1);
-        if self.viewport.offset_line > max_line {
-            self.viewport.offset_line = max_line;
+    /// Keep `pos` inside the buffer
+    fn clamp_position(&self, pos: Position) -> Position {
+        let line = pos.line.min(self.buffer.line_count().saturating_sub(1));
+        let len = self.buffer.line(line).unwrap_or_default().chars().count();
+        Position::new(line, pos.col.min(len))
+    }
+
+    /// Char offsets of all cursors in buffer order, flagged if primary
+    fn cursor_offsets(&self) -> Vec<(usize, bool)> {
+        let mut offsets: Vec<(usize, bool)> = std::iter::once((&self.cursor, true))
+            .chain(self.cursors.iter().map(|c| (c, false)))
+            .map(|(c, primary)| {
+                let pos = self.clamp_position(Position::new(c.line, c.col));
+                (self.buffer.position_to_char(pos), primary)
+            })
+            .collect();
+        offsets.sort();
+        offsets
+    }
+
+    /// Move the cursors to new char offsets, merging any that now coincide
+    fn set_cursor_offsets(&mut self, offsets: Vec<(usize, bool)>) {
+        let mut merged: Vec<(usize, bool)> = Vec::new();
+        for (offset, primary) in offsets {
+            match merged.last_mut() {
+                Some(last) if last.0 == offset => last.1 |= primary,
+                _ => merged.push((offset, primary)),
+            }
         }
 
-        // Adjust cursor if it's out of bounds
-        if self.cursor.line > max_line {
-            self.cursor.line = max_line;
+        self.cursors.clear();
+        for (offset, primary) in merged {
+            let pos = self.buffer.char_to_position(offset);
+            let cursor = Cursor {
+                line: pos.line,
+                col: pos.col,
+                desired_col: pos.col,
+            };
+            if primary {
+                self.cursor = cursor;
+            } else {
+                self.cursors.push(cursor);
+            }
         }
+    }
 
-        // Adjust cursor column if it's beyond the end of the current line
-        if let Some(line) = self.buffer.line(self.cursor.line) {
-            let line_len = line.len();
-            if self.cursor.col > line_len {
-                self.cursor.col = line_len;
+    /// Type `c` at every cursor. Edits go back to front so the offsets of the
+    /// cursors still to be handled stay valid.
+    fn insert_char_at_cursors(&mut self, c: char) {
+        // Counted inserts are only replayed at a single cursor
+        self.insert_session = None;
+        let offsets = self.cursor_offsets();
+        for &(offset, _) in offsets.iter().rev() {
+            let pos = self.buffer.char_to_position(offset);
+            let _ = self.buffer.insert_char(c, pos.line, pos.col);
+        }
+        // Each cursor moves past its own character and those inserted before it
+        let moved = offsets
+            .into_iter()
+            .enumerate()
+            .map(|(i, (offset, primary))| (offset + i + 1, primary))
+            .collect();
+        self.set_cursor_offsets(moved);
+        self.notify_text_change();
+    }
+
+    /// Type `c` at the cursor as half of a bracket or quote pair: step over
+    /// the closer when it is already next, or insert an opener together with
+    /// its closer. False if `c` should be inserted as it is.
+    fn insert_auto_pair(&mut self, c: char) -> bool {
+        let pairs = &self.options.auto_pairs;
+        let line: Vec<char> = self
+            .buffer
+            .line(self.cursor.line)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        let next = line.get(self.cursor.col).copied();
+        let prev = self.cursor.col.checked_sub(1).and_then(|col| line.get(col)).copied();
+        let words = &self.options.word_chars;
+        let is_word = |ch: Option<char>| ch.is_some_and(|ch| words.is_word_char(ch));
+
+        if next == Some(c) && pairs.iter().any(|&(_, close)| close == c) {
+            self.cursor.col += 1;
+        } else if let Some(&(open, close)) = pairs.iter().find(|&&(open, _)| open == c) {
+            // An opener before a word is about to wrap it, and a quote after
+            // one is an apostrophe or ends a string
+            if is_word(next) || (open == close && is_word(prev)) {
+                return false;
             }
+            let _ = self.buffer.insert_text(
+                &format!("{}{}", open, close),
+                self.cursor.line,
+                self.cursor.col,
+            );
+            self.cursor.col += 1;
+        } else {
+            return false;
         }
+        // Only what was typed is replayed; a closer stepped over was typed too
+        if let Some(session) = &mut self.insert_session {
+            session.text.push(c);
+        }
+        self.notify_text_change();
+        true
+    }
 
-        // Clear overlays when text changes
-        self.hide_hover();
-        self.hide_code_actions();
+    /// Enter in a comment: start the new line with the comment's indentation
+    /// and marker. On a line with nothing after the marker, such as one just
+    /// continued, the marker is removed instead to end the comment. Returns
+    /// false outside a comment.
+    fn continue_comment(&mut self) -> bool {
+        if !self.options.continue_comments {
+            return false;
+        }
+        let Some(language) = self.current_language else {
+            return false;
+        };
+        let line = self.buffer.get_line_content(self.cursor.line);
+        let before: String = line.chars().take(self.cursor.col).collect();
+        let config = crate::syntax::get_language_config(language);
+        // Only a line starting with `*` needs to know it is in a block comment
+        let in_block_comment = before.trim_start().starts_with('*')
+            && (0..self.cursor.line)
+                .rev()
+                .find_map(|line| {
+                    config.block_comment_state(&self.buffer.get_line_content(line))
+                })
+                .unwrap_or(false);
+        let Some(prefix) = config.comment_continuation(&before, in_block_comment) else {
+            return false;
+        };
 
-        // TODO: Implement async text change notifications
-        // For now, LSP operations are handled asynchronously in command handlers
+        if before.trim_end() == prefix.trim_end() && line.chars().count() <= self.cursor.col {
+            let indent: String = before.chars().take_while(|c| c.is_whitespace()).collect();
+            let _ = self.buffer.replace_range(
+                Position::new(self.cursor.line, 0),
+                Position::new(self.cursor.line, self.cursor.col),
+                &indent,
+            );
+            self.cursor.col = indent.chars().count();
+            // Removing text can't be replayed
+            self.insert_session = None;
+        } else {
+            let text = format!("\n{}", prefix);
+            let _ = self.buffer.insert_text(&text, self.cursor.line, self.cursor.col);
+            self.cursor.line += 1;
+            self.cursor.col = prefix.chars().count();
+            if let Some(session) = &mut self.insert_session {
+                session.text.push_str(&text);
+            }
+        }
+        self.notify_text_change();
+        true
     }
 
-    /// Show hover information at cursor position
-    pub fn show_hover(&mut self, content: Vec<String>) {
-        self.hover_content = Some(content);
+    /// Whether the cursor sits between an opener and its closer, as right
+    /// after typing the opener
+    fn at_empty_auto_pair(&self) -> bool {
+        let Some(line) = self.buffer.line(self.cursor.line) else {
+            return false;
+        };
+        let mut chars = line.chars().skip(self.cursor.col.saturating_sub(1));
+        match (chars.next(), chars.next()) {
+            (Some(open), Some(close)) if self.cursor.col > 0 => {
+                self.options.auto_pairs.contains(&(open, close))
+            }
+            _ => false,
+        }
     }
 
-    /// Hide hover information
-    pub fn hide_hover(&mut self) {
-        self.hover_content = None;
+    /// Backspace at every cursor
+    fn delete_char_at_cursors(&mut self) {
+        self.insert_session = None;
+        let offsets = self.cursor_offsets();
+        for &(offset, _) in offsets.iter().rev() {
+            if offset > 0 {
+                let pos = self.buffer.char_to_position(offset);
+                let _ = self.buffer.delete_char(pos.line, pos.col);
+            }
+        }
+        let mut deleted = 0;
+        let moved = offsets
+            .into_iter()
+            .map(|(offset, primary)| {
+                if offset > 0 {
+                    deleted += 1;
+                }
+                (offset - deleted, primary)
+            })
+            .collect();
+        self.set_cursor_offsets(moved);
+        self.notify_text_change();
     }
 
-    /// Show code actions menu
-    pub fn show_code_actions(&mut self, actions: Vec<lsp_types::CodeAction>) {
-        self.code_actions = Some(actions);
-        self.code_action_selected = 0;
+    /// Open the `:reg` listing: one line per non-empty register with its type
+    /// (`l`inewise or `c`harwise) and the start of its contents
+    fn show_registers(&mut self) {
+        const PREVIEW_CHARS: usize = 40;
+        let entries = self.registers.non_empty();
+        if entries.is_empty() {
+            self.status_message = Some("All registers are empty".to_string());
+            return;
+        }
+
+        let mut lines = vec!["Type Name Content".to_string()];
+        for (reg, text) in entries {
+            let kind = if self.registers.is_linewise(reg) { 'l' } else { 'c' };
+            let preview: String = text
+                .chars()
+                .flat_map(|c| match c {
+                    '\n' => vec!['^', 'J'],
+                    '\t' => vec!['^', 'I'],
+                    c => vec![c],
+                })
+                .take(PREVIEW_CHARS)
+                .collect();
+            lines.push(format!("  {}  \"{}   {}", kind, reg, preview));
+        }
+        self.listing = Some(Listing {
+            title: " Registers ".to_string(),
+            lines,
+            follow_end: false,
+            errors: Vec::new(),
+        });
     }
 
-    /// Hide code actions menu
-    pub fn hide_code_actions(&mut self) {
-        self.code_actions = None;
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rope: self.buffer.rope.clone(),
+            cursor: Position::new(self.cursor.line, self.cursor.col),
+        }
     }
 
-    /// Navigate code actions menu
-    pub fn select_next_code_action(&mut self) {
-        if let Some(actions) = &self.code_actions
-            && !actions.is_empty()
-        {
-            self.code_action_selected = (self.code_action_selected + 1) % actions.len();
+    /// Take a checkpoint to pass to `record_undo` once a change is done
+    fn undo_checkpoint(&mut self) -> UndoCheckpoint {
+        self.undo_depth += 1;
+        UndoCheckpoint {
+            snapshot: self.snapshot(),
+            version: self.buffer.version,
+            generation: self.undo_history.generation(),
+            was_insert: self.mode == Mode::Insert,
         }
     }
 
-    pub fn select_prev_code_action(&mut self) {
-        if let Some(actions) = &self.code_actions
-            && !actions.is_empty()
-        {
-            self.code_action_selected = if self.code_action_selected == 0 {
-                actions.len() - 1
-            } else {
-                self.code_action_selected - 1
+    /// Record the state from `checkpoint` if the buffer changed since. Everything
+    /// typed in one insert, including the change that started it, is one step,
+    /// unless Enter or a cursor movement breaks it up. A checkpoint taken inside
+    /// another is left to the outer one, so the change is recorded once.
+    fn record_undo(&mut self, checkpoint: UndoCheckpoint) {
+        self.undo_depth -= 1;
+        if self.undo_depth > 0 {
+            return;
+        }
+        let changed = self.buffer.version != checkpoint.version
+            && self.undo_history.generation() == checkpoint.generation;
+        if changed && !(checkpoint.was_insert && self.insert_undo_recorded) {
+            self.undo_history.record(checkpoint.snapshot);
+        }
+        if changed {
+            self.insert_undo_recorded = self.mode == Mode::Insert;
+        }
+        if self.mode != Mode::Insert {
+            self.insert_undo_recorded = false;
+        }
+    }
+
+    /// Undo up to `count` changes, stopping at the oldest
+    fn undo(&mut self, count: usize) {
+        let mut undone = 0;
+        while undone < count {
+            let Some(previous) = self.undo_history.undo(self.snapshot()) else {
+                break;
             };
+            self.restore_snapshot(previous);
+            undone += 1;
         }
+        self.status_message = Some(match undone {
+            0 => "Already at oldest change".to_string(),
+            1 => "1 change undone".to_string(),
+            n => format!("{} changes undone", n),
+        });
     }
 
-    /// Get selected code action
-    pub fn get_selected_code_action(&self) -> Option<&lsp_types::CodeAction> {
-        self.code_actions.as_ref()?.get(self.code_action_selected)
+    /// Redo up to `count` undone changes, stopping at the newest
+    fn redo(&mut self, count: usize) {
+        let mut redone = 0;
+        while redone < count {
+            let Some(next) = self.undo_history.redo(self.snapshot()) else {
+                break;
+            };
+            self.restore_snapshot(next);
+            redone += 1;
+        }
+        self.status_message = Some(match redone {
+            0 => "Already at newest change".to_string(),
+            1 => "1 change redone".to_string(),
+            n => format!("{} changes redone", n),
+        });
     }
 
-    /// Enter command mode
-    pub fn enter_command_mode(&mut self) {
-        self.mode = Mode::Command;
-        self.command_line.clear();
-        self.command_history_index = self.command_history.len();
+    fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.buffer.set_rope(snapshot.rope);
+        let pos = self.clamp_position(snapshot.cursor);
+        self.cursor.line = pos.line;
+        self.cursor.col = pos.col;
+        self.notify_text_change();
     }
 
-    /// Handle command line input
-    pub fn handle_command_input(&mut self, c: char) -> Result<bool, Box<dyn std::error::Error>> {
-        // Returns true if should quit
-        match c {
-            '\n' | '\r' => {
-                // Execute command
-                let should_quit = self.execute_command_line()?;
-                self.mode = Mode::Normal;
-                self.command_line.clear();
-                return Ok(should_quit);
+    /// Enter insert mode, remembering the typed text to repeat it and for `Ctrl-A`
+    fn start_insert(&mut self, count: usize, open_line: bool) {
+        self.mode = Mode::Insert;
+        self.insert_session = Some(InsertSession {
+            count,
+            text: String::new(),
+            open_line,
+        });
+    }
+
+    /// Keep the text typed in this insert for `Ctrl-A`, and insert it another
+    /// `count - 1` times, each on a new line for `o`/`O`
+    fn finish_insert(&mut self) {
+        let Some(session) = self.insert_session.take() else {
+            return;
+        };
+        if session.text.is_empty() {
+            return;
+        }
+        self.registers.store_inserted(session.text.clone());
+        for _ in 1..session.count {
+            if session.open_line {
+                self.open_line_below();
             }
-            '\x08' | '\x7f' => {
-                // Backspace
-                self.command_line.pop();
+            if self
+                .buffer
+                .insert_text(&session.text, self.cursor.line, self.cursor.col)
+                .is_err()
+            {
+                break;
             }
-            '\x1b' => {
-                // Escape
-                self.mode = Mode::Normal;
-                self.command_line.clear();
+            self.move_past_inserted(&session.text);
+        }
+        self.notify_text_change();
+    }
+
+    /// Move the cursor to just after `text`, which was inserted at it
+    fn move_past_inserted(&mut self, text: &str) {
+        match text.rsplit_once('\n') {
+            Some((before, last)) => {
+                self.cursor.line += before.matches('\n').count() + 1;
+                self.cursor.col = last.chars().count();
             }
-            c if c.is_ascii_graphic() || c == ' ' => {
-                self.command_line.push(c);
+            None => self.cursor.col += text.chars().count(),
+        }
+    }
+
+    /// Insert the text of the last insert again at the cursor (insert-mode `Ctrl-A`)
+    fn replay_last_insert(&mut self) {
+        if self.mode != Mode::Insert {
+            return;
+        }
+        let text = self.registers.get_inserted().to_string();
+        if text.is_empty() {
+            self.status_message = Some("No previously inserted text".to_string());
+            return;
+        }
+        if self
+            .buffer
+            .insert_text(&text, self.cursor.line, self.cursor.col)
+            .is_ok()
+        {
+            // The replayed text becomes part of this insert, as if typed
+            if let Some(session) = &mut self.insert_session {
+                session.text.push_str(&text);
             }
-            _ => {}
+            self.move_past_inserted(&text);
+            self.notify_text_change();
         }
-        Ok(false) // Don't quit for other inputs
     }
 
-    /// Execute command line
-    fn execute_command_line(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
-        // Returns true if should quit
-        // Add to history
-        self.command_history.push(self.command_line.clone());
-        self.command_history_index = self.command_history.len();
+    /// Add an empty line after the cursor's line and move onto it
+    fn open_line_below(&mut self) {
+        let line_len = self.buffer.line(self.cursor.line).unwrap_or_default().chars().count();
+        if self.buffer.insert_text("\n", self.cursor.line, line_len).is_ok() {
+            self.cursor.line += 1;
+            self.cursor.col = 0;
+            self.notify_text_change();
+        }
+    }
 
-        let trimmed = self.command_line.trim();
-        if trimmed.is_empty() {
-            return Ok(false);
+    /// Span from the cursor back over `count` `ge` (or `gE` when `big`) motions.
+    /// The motion is inclusive, so the end is just past the cursor's character.
+    fn word_end_backward_span(&self, count: usize, big: bool) -> (Position, Position) {
+        let pos = Position::new(self.cursor.line, self.cursor.col);
+        let mut start = pos;
+        for _ in 0..count {
+            start = if big {
+                crate::motion::big_word_end_backward(&self.buffer, start)
+            } else {
+                crate::motion::word_end_backward_with(&self.buffer, start, &self.options.word_chars)
+            };
         }
+        let line_len = self.buffer.line(pos.line).unwrap_or_default().chars().count();
+        (start, Position::new(pos.line, (pos.col + 1).min(line_len)))
+    }
 
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        if parts.is_empty() {
-            return Ok(false);
+    /// Save the buffer to `path`, first applying the on-save code actions if
+    /// any are enabled. Those need the language server, so the save is then
+    /// queued for the event loop. Returns true if `quit` was asked for and the
+    /// buffer has already been written.
+    fn save_buffer(&mut self, path: &str, quit: bool) -> bool {
+        if self.current_language.is_some() && !self.options.save_code_action_kinds().is_empty() {
+            self.pending_lsp_action = Some(LspAction::Save {
+                path: path.to_string(),
+                quit,
+            });
+            return false;
         }
+        self.write_buffer(path) && quit
+    }
 
-        match parts[0] {
-            "q" | "quit" => {
-                // Quit
-                Ok(true)
-            }
-            "x" | "wq" => {
-                // Save and quit
-                if let Some(path) = self.buffer.file_path.clone() {
-                    let path_clone = path.clone();
-                    let content = self.buffer.rope.to_string();
-                    tokio::spawn(async move {
-                        if let Err(e) = tokio::fs::write(&path_clone, &content).await {
-                            eprintln!("Error saving file '{}': {}", path_clone, e);
-                        }
-                    });
-                    self.buffer.modified = false;
-                }
-                Ok(true)
+    /// Save the buffer to `path` atomically, reporting the result in the status line.
+    /// Formats it first when format-on-save is on; a formatter failure is
+    /// reported but doesn't stop the write. Returns true on success.
+    fn write_buffer(&mut self, path: &str) -> bool {
+        let checkpoint = self.undo_checkpoint();
+        let format_error = if self.options.format_on_save {
+            self.format_current_buffer().err()
+        } else {
+            None
+        };
+        self.tidy_for_save();
+        self.record_undo(checkpoint);
+        if self.options.ensure_final_newline {
+            self.buffer.final_newline = true;
+        }
+        self.buffer.line_ending = self.options.line_ending;
+        let written = match self
+            .buffer
+            .save_to_file_with_backup(path, self.options.write_backup)
+        {
+            Ok(()) => {
+                self.update_git_signs();
+                self.status_message = Some(format!(
+                    "\"{}\" {}L, {}B written",
+                    path,
+                    self.buffer.last_content_line() + 1,
+                    self.buffer.rope.len_bytes()
+                ));
+                true
             }
-            "w" | "write" => {
-                // Save file
-                if let Some(path) = self.buffer.file_path.clone() {
-                    let path_clone = path.clone();
-                    let content = self.buffer.rope.to_string();
-                    tokio::spawn(async move {
-                        if let Err(e) = tokio::fs::write(&path_clone, &content).await {
-                            eprintln!("Error saving file '{}': {}", path_clone, e);
-                        }
-                    });
-                    self.buffer.modified = false;
-                } else if parts.len() > 1 {
-                    // Save as new file
-                    let filename = parts[1].to_string();
-                    self.buffer.file_path = Some(filename.clone());
-                    let content = self.buffer.rope.to_string();
-                    tokio::spawn(async move {
-                        if let Err(e) = tokio::fs::write(&filename, &content).await {
-                            eprintln!("Error saving file '{}': {}", filename, e);
-                        }
-                    });
-                    self.buffer.modified = false;
-                }
-                Ok(false)
+            Err(e) => {
+                self.show_error(format!("Error writing \"{}\": {}", path, e));
+                false
             }
-            "e" | "edit" if parts.len() > 1 => {
-                // Open/edit file
-                let filename = parts[1].to_string();
-                self.open_file(&filename)?;
-                Ok(false)
+        };
+        if let Some(e) = format_error {
+            self.append_status(&format!("format failed: {}", e));
+        }
+        written
+    }
+
+    /// Trim trailing whitespace if the options ask to. Line breaks are
+    /// converted only in the text written, so the buffer keeps its own.
+    fn tidy_for_save(&mut self) {
+        if !self.options.trim_trailing_whitespace {
+            return;
+        }
+        let text: String = self
+            .buffer
+            .rope
+            .to_string()
+            .split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\n', '\r']);
+                let line_break = &line[content.len()..];
+                format!("{}{}", content.trim_end(), line_break)
+            })
+            .collect();
+        let version = self.buffer.version;
+        self.buffer.set_rope(ropey::Rope::from_str(&text));
+        if self.buffer.version != version {
+            self.notify_text_change();
+        }
+    }
+
+    /// Show `message` in the status line as an error, kept in the
+    /// `:messages` history as one
+    pub fn show_error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        self.messages.push(message.clone(), Severity::Error);
+        self.status_message = Some(message);
+    }
+
+    /// Keep a new status line message in the `:messages` history. Called
+    /// after every command and every pass of the main loop.
+    pub fn record_status(&mut self) {
+        self.messages.note_status(self.status_message.as_deref());
+    }
+
+    /// Open the `:messages` listing, newest at the bottom with errors
+    /// marked
+    fn show_messages(&mut self) {
+        if self.messages.is_empty() {
+            self.status_message = Some("No messages".to_string());
+            self.messages.skip_status("No messages");
+            return;
+        }
+        let (lines, errors): (Vec<String>, Vec<bool>) = self
+            .messages
+            .iter()
+            .map(|message| (message.display(), message.severity == Severity::Error))
+            .unzip();
+        self.listing = Some(Listing {
+            title: " Messages ".to_string(),
+            lines,
+            follow_end: true,
+            errors,
+        });
+    }
+
+    /// List the user commands and what they run
+    fn show_user_commands(&mut self) {
+        if self.command_aliases.is_empty() {
+            self.status_message = Some("No user commands".to_string());
+            return;
+        }
+        let mut commands: Vec<_> = self.command_aliases.iter().collect();
+        commands.sort();
+        let lines: Vec<String> = commands
+            .into_iter()
+            .map(|(name, definition)| format!("{:<12} {}", name, definition))
+            .collect();
+        self.listing = Some(Listing {
+            title: " User commands ".to_string(),
+            errors: vec![false; lines.len()],
+            lines,
+            follow_end: false,
+        });
+    }
+
+    /// Add `note` to the end of the status message
+    fn append_status(&mut self, note: &str) {
+        self.status_message = Some(match self.status_message.take() {
+            Some(message) => format!("{}; {}", message, note),
+            None => note.to_string(),
+        });
+    }
+
+    /// Run the buffer through its file type's formatter, keeping the cursor
+    /// on the same text. Returns false if there is no formatter.
+    fn format_current_buffer(&mut self) -> Result<bool, BufferError> {
+        let Some(formatter) = &self.formatter else {
+            return Ok(false);
+        };
+        let (line, col) = self
+            .buffer
+            .format_buffer(formatter, self.cursor.line, self.cursor.col)?;
+        self.cursor.line = line;
+        self.cursor.col = col;
+        Ok(true)
+    }
+
+    /// Write every modified buffer that has a file name, reporting how many were
+    /// written. Returns false if a modified buffer could not be written.
+    fn write_all(&mut self) -> bool {
+        // Only one buffer is open at a time for now
+        if !self.buffer.modified {
+            self.status_message = Some("No modified buffers".to_string());
+            return true;
+        }
+        let Some(path) = self.buffer.file_path.clone() else {
+            self.status_message = Some("No file name for modified buffer".to_string());
+            return false;
+        };
+        if !self.write_buffer(&path) {
+            return false;
+        }
+        self.status_message = Some("1 buffer written".to_string());
+        true
+    }
+
+    /// Start watching `path` for outside changes, replacing any previous watcher
+    fn watch_file(&mut self, path: &str) {
+        self.reload_prompt = false;
+        self.file_watcher = FileWatcher::new(std::path::Path::new(path)).ok();
+    }
+
+    /// Check whether the open file changed on disk. An unmodified buffer is
+    /// reloaded in place; otherwise the user is asked whether to reload or keep.
+    /// Returns true if the screen needs redrawing.
+    pub fn check_file_changes(&mut self) -> bool {
+        let changed = self
+            .file_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.has_changed());
+        if !changed || self.reload_prompt {
+            return false;
+        }
+
+        let Some(path) = self.buffer.file_path.clone() else {
+            return false;
+        };
+        // Our own saves and no-op touches leave the content identical
+        match std::fs::read_to_string(&path) {
+            Ok(content) if self.buffer.rope == content.as_str() => return false,
+            Ok(_) => {}
+            Err(_) => return false,
+        }
+
+        if self.buffer.modified {
+            self.reload_prompt = true;
+            self.status_message = Some(format!(
+                "\"{}\" changed on disk: [r]eload (discard changes) or [k]eep buffer",
+                path
+            ));
+        } else {
+            match self.reload_from_disk() {
+                Ok(()) => self.status_message = Some(format!("\"{}\" reloaded", path)),
+                Err(e) => self.show_error(format!("Reload failed: {}", e)),
             }
-            "syntax" | "syn" => {
-                // Toggle syntax highlighting
-                if parts.len() > 1 {
-                    match parts[1] {
-                        "on" => {
-                            // Enable syntax highlighting
-                            if let Some(language_id) = self.current_language {
-                                let config =
-                                    crate::syntax::language::get_language_config(language_id);
-                                if let Ok(highlighter) =
-                                    crate::syntax::highlighter::SyntaxHighlighter::new(config)
-                                {
-                                    self.buffer.highlighter = Some(highlighter);
-                                }
-                            }
-                        }
-                        "off" => {
-                            // Disable syntax highlighting
-                            self.buffer.highlighter = None;
-                        }
-                        _ => {}
-                    }
+        }
+        true
+    }
+
+    /// Answer the "changed on disk" prompt. Returns true if the key was consumed.
+    pub fn handle_reload_prompt(&mut self, c: char) -> bool {
+        if !self.reload_prompt {
+            return false;
+        }
+        match c {
+            'r' | 'R' => {
+                self.reload_prompt = false;
+                self.status_message = match self.reload_from_disk() {
+                    Ok(()) => Some("Reloaded from disk".to_string()),
+                    Err(e) => Some(format!("Reload failed: {}", e)),
+                };
+                true
+            }
+            'k' | 'K' | '\x1b' => {
+                self.reload_prompt = false;
+                self.status_message = Some("Kept buffer; file on disk differs".to_string());
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Re-read the open file, keeping the cursor and scroll position where possible
+    pub fn reload_from_disk(&mut self) -> Result<(), BufferError> {
+        let Some(path) = self.buffer.file_path.clone() else {
+            return Ok(());
+        };
+        self.buffer.load_from_file(&path)?;
+        let _ = self.buffer.update_highlighter();
+        self.update_git_signs();
+
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        self.cursor.line = self.cursor.line.min(last_line);
+        let line_len = self.line_char_count(self.cursor.line);
+        self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+        self.notify_text_change();
+        Ok(())
+    }
+
+    /// Pick the language from `path`'s extension and set up highlighting for it
+    fn detect_language(&mut self, path: &str) {
+        let language_config = crate::syntax::language::get_language_config_by_extension(
+            std::path::Path::new(path)
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or(""),
+        );
+        self.set_language(language_config.map(|config| config.id));
+    }
+
+    /// Treat the buffer as `language` whatever the file is called. The
+    /// highlighter, formatter and language server all follow it.
+    pub fn set_language(&mut self, language: Option<LanguageId>) {
+        self.current_language = language;
+        self.options.use_language_word_chars(language);
+        self.formatter = language
+            .and_then(get_formatter_config)
+            .and_then(|config| Formatter::new(config).ok());
+
+        let Some(language) = language else {
+            self.buffer.highlighter = None;
+            return;
+        };
+        let config = crate::syntax::language::get_language_config(language);
+        match crate::syntax::highlighter::SyntaxHighlighter::new(config) {
+            Ok(highlighter) => {
+                self.buffer.highlighter = Some(highlighter);
+                // Parse the loaded content
+                let _ = self.buffer.update_highlighter();
+            }
+            Err(_) => {
+                // Syntax highlighting failed to initialize, continue without it
+                self.buffer.highlighter = None;
+            }
+        }
+    }
+
+    /// Run `:set`. `:set ft=python` forces a file type and `:set ft=` clears
+    /// it; `:set cc=80,100` draws rulers and `:set cc=` removes them. `:set
+    /// isk=_-` picks the characters besides letters and digits that make up
+    /// words. `:set ft?`, `:set cc?` and `:set isk?` show the current value.
+    fn execute_set(&mut self, args: &[&str]) {
+        let Some(&arg) = args.first() else {
+            self.status_message = Some("Usage: :set <option>=<value>".to_string());
+            return;
+        };
+        let (option, value) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (arg.trim_end_matches('?'), None),
+        };
+        match option {
+            "filetype" | "ft" => self.set_filetype(value),
+            "colorcolumn" | "cc" => self.set_color_columns(value),
+            "iskeyword" | "isk" => self.set_word_chars(value),
+            "continuecomments" | "nocontinuecomments" => {
+                if !arg.ends_with('?') {
+                    self.options.continue_comments = !option.starts_with("no");
                 }
-                Ok(false)
+                let prefix = if self.options.continue_comments { "" } else { "no" };
+                self.status_message = Some(format!("{}continuecomments", prefix));
             }
-            "lsp" => {
-                // LSP commands
-                if parts.len() > 1 {
-                    match parts[1] {
-                        "restart" => {
-                            // TODO: Restart LSP servers
-                        }
-                        "stop" => {
-                            // TODO: Stop LSP servers
-                        }
-                        _ => {}
+            "smartcase" | "scs" | "nosmartcase" | "noscs" => {
+                if !arg.ends_with('?') {
+                    self.options.smart_case = !option.starts_with("no");
+                }
+                let prefix = if self.options.smart_case { "" } else { "no" };
+                self.status_message = Some(format!("{}smartcase", prefix));
+            }
+            _ => self.show_error(format!("Unknown option: {}", option)),
+        }
+    }
+
+    fn set_filetype(&mut self, value: Option<&str>) {
+        match value {
+            None => {}
+            Some("") => self.set_language(None),
+            Some(name) => match LanguageId::from_name(name) {
+                Some(language) => self.set_language(Some(language)),
+                None => {
+                    self.show_error(format!("Unknown filetype: {}", name));
+                    return;
+                }
+            },
+        }
+        self.status_message = Some(format!(
+            "filetype={}",
+            self.current_language.map(LanguageId::name).unwrap_or_default()
+        ));
+    }
+
+    /// Until another file is opened or the file type changes
+    fn set_word_chars(&mut self, value: Option<&str>) {
+        if let Some(value) = value {
+            match WordChars::new(value) {
+                Some(chars) => self.options.word_chars = chars,
+                None => {
+                    self.show_error(format!("Invalid iskeyword: {}", value));
+                    return;
+                }
+            }
+        }
+        self.status_message = Some(format!("iskeyword={}", self.options.word_chars.extra()));
+    }
+
+    /// Like Vim's window option, the rulers stay when another file is opened
+    fn set_color_columns(&mut self, value: Option<&str>) {
+        if let Some(value) = value {
+            match crate::options::parse_color_columns(value) {
+                Some(columns) => {
+                    self.user_options.color_columns = columns.clone();
+                    self.options.color_columns = columns;
+                }
+                None => {
+                    self.show_error(format!("Invalid colorcolumn: {}", value));
+                    return;
+                }
+            }
+        }
+        let columns: Vec<String> =
+            self.options.color_columns.iter().map(ToString::to_string).collect();
+        self.status_message = Some(format!("colorcolumn={}", columns.join(",")));
+    }
+
+    /// Recompute the git signs now. The index version is read again, as a
+    /// save may have named the buffer or the file may have been staged.
+    fn update_git_signs(&mut self) {
+        self.git_signs = match &self.buffer.file_path {
+            Some(path) => GitSigns::load(
+                std::path::Path::new(path),
+                &self.buffer.rope.to_string(),
+                self.buffer.version,
+            ),
+            None => GitSigns::default(),
+        };
+    }
+
+    /// Refresh the git signs once edits have paused. Returns true if they
+    /// changed and need redrawing.
+    pub fn poll_git_signs(&mut self) -> bool {
+        let rope = &self.buffer.rope;
+        self.git_signs.poll(self.buffer.version, || rope.to_string())
+    }
+
+    /// Run the `:lsp` subcommand queued by the command line. Returns true if
+    /// there was one.
+    pub async fn run_pending_lsp_action(&mut self) -> bool {
+        let Some(action) = self.pending_lsp_action.take() else {
+            return false;
+        };
+        if action == LspAction::Status {
+            self.show_lsp_status().await;
+            return true;
+        }
+
+        let Some(language) = self.current_language else {
+            self.status_message = Some("No language server for this file type".to_string());
+            return true;
+        };
+        match action {
+            LspAction::Stop => self.stop_language_server(language).await,
+            LspAction::Restart => self.restart_language_server(language).await,
+            LspAction::CodeActions(range) => self.request_code_actions(language, range).await,
+            LspAction::ApplyCodeAction(action) => self.apply_code_action(language, *action).await,
+            LspAction::Hover(position) => self.request_hover(language, position).await,
+            LspAction::References(position) => self.request_references(language, position).await,
+            LspAction::WorkspaceSymbols(query) => self.request_workspace_symbols(language, query).await,
+            LspAction::OrganizeImports => {
+                let kind = lsp_types::CodeActionKind::SOURCE_ORGANIZE_IMPORTS;
+                match self.apply_source_action(language, kind).await {
+                    Ok(Some(title)) => self.status_message = Some(format!("Applied: {}", title)),
+                    Ok(None) => self.status_message = Some("No imports to organize".to_string()),
+                    Err(e) => self.show_error(format!("Organize imports failed: {}", e)),
+                }
+            }
+            LspAction::Format => {
+                match self.format_with_language_server(language).await {
+                    Ok(true) => self.status_message = Some("Formatted".to_string()),
+                    Ok(false) => {
+                        self.status_message =
+                            Some("No formatter available for this file type".to_string());
                     }
+                    Err(e) => self.show_error(format!("Format failed: {}", e)),
                 }
-                Ok(false)
             }
+            LspAction::Save { path, quit } => self.save_with_code_actions(language, &path, quit).await,
+            LspAction::Status => {}
+        }
+        true
+    }
+
+    async fn show_lsp_status(&mut self) {
+        let statuses = self.lsp_manager.status().await;
+        self.status_message = Some(if statuses.is_empty() {
+            "No language servers running".to_string()
+        } else {
+            statuses
+                .iter()
+                .map(|(server, status)| format!("{}: {}", server, status.label()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+    }
+
+    /// Show what the buffer's language server wrote to stderr, newest at the bottom
+    fn show_lsp_log(&mut self) {
+        let Some(language) = self.current_language else {
+            self.status_message = Some("No language server for this file type".to_string());
+            return;
+        };
+        let server = self.lsp_manager.server_name(language);
+        let lines = self.lsp_manager.log_lines(language);
+        if lines.is_empty() {
+            self.status_message = Some(format!("Nothing logged by {}", server));
+            return;
+        }
+        self.listing = Some(Listing {
+            title: format!(" {} log ", server),
+            lines,
+            follow_end: true,
+            errors: Vec::new(),
+        });
+    }
+
+    /// Replace the quickfix list with `entries` and open its window
+    pub fn set_quickfix(&mut self, title: impl Into<String>, entries: Vec<QuickfixEntry>) {
+        let title = title.into();
+        if entries.is_empty() {
+            self.status_message = Some(format!("{}: nothing found", title));
+        }
+        self.quickfix_open = !entries.is_empty();
+        self.quickfix = QuickfixList::new(title, entries);
+    }
+
+    /// Fill the quickfix list with LSP locations, such as references, each
+    /// showing the line it points at
+    pub fn set_quickfix_locations(&mut self, title: &str, locations: &[lsp_types::Location]) {
+        let buffer_uri = self.get_buffer_uri();
+        let mut files: HashMap<Url, Vec<String>> = HashMap::new();
+        let mut entries = Vec::new();
+        for location in locations {
+            let Ok(path) = crate::lsp::workspace_edit::file_path(&location.uri) else {
+                continue;
+            };
+            let lines = files.entry(location.uri.clone()).or_insert_with(|| {
+                self.file_lines(&path, buffer_uri.as_ref() == Some(&location.uri))
+            });
+            let start = location.range.start;
+            let text = lines.get(start.line as usize).cloned().unwrap_or_default();
+            let col = crate::quickfix::utf16_to_char_col(&text, start.character as usize);
+            entries.push(QuickfixEntry::new(path, start.line as usize, col, text));
+        }
+        self.set_quickfix(title, entries);
+    }
+
+    /// Lines of the file at `path`: the buffer's when it is the file being
+    /// edited, or else what is on disk
+    fn file_lines(&self, path: &std::path::Path, is_buffer: bool) -> Vec<String> {
+        if is_buffer {
+            (0..self.buffer.line_count())
+                .map(|line| self.buffer.get_line_content(line))
+                .collect()
+        } else {
+            std::fs::read_to_string(path)
+                .map(|content| content.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        }
+    }
+
+    /// `:grep {pattern}`: every matching line in the files under the working directory
+    fn grep_to_quickfix(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            self.status_message = Some("Usage: :grep {pattern}".to_string());
+            return;
+        }
+        let regex = match ex::compile_pattern(pattern, self.options.smart_case) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.show_error(e);
+                return;
+            }
+        };
+        let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let max_results = self.options.grep_max_results;
+        let (entries, truncated) = crate::quickfix::grep(&root, &regex, max_results);
+        self.set_quickfix(format!("grep {}", pattern), entries);
+        if truncated {
+            self.quickfix.truncated = true;
+            self.status_message = Some(format!(
+                "Results truncated: stopped at {} matches (grep_max_results)",
+                max_results
+            ));
+        }
+    }
+
+    /// `:make [args]`: start the build command with `args` added. It runs in
+    /// the background; `finish_make` lists the errors it prints.
+    fn make_to_quickfix(&mut self, args: &str) {
+        if let Some(job) = &self.make_job {
+            self.show_error(format!("{}: still running", job.command));
+            return;
+        }
+        let format = match Regex::new(&self.options.error_format) {
+            Ok(format) => format,
+            Err(e) => {
+                self.show_error(format!("Invalid error_format: {}", e));
+                return;
+            }
+        };
+        let mut command = self.options.make_command.clone();
+        if !args.is_empty() {
+            command = format!("{} {}", command, args);
+        }
+        let run = command.clone();
+        let handle =
+            std::thread::spawn(move || crate::quickfix::run_capturing(&run));
+        self.status_message = Some(format!("Running {}...", command));
+        self.make_job = Some(MakeJob {
+            command,
+            format,
+            handle,
+        });
+    }
+
+    /// Whether a `:make` build is running
+    pub fn is_making(&self) -> bool {
+        self.make_job.is_some()
+    }
+
+    /// List the errors of a `:make` build that has ended. Returns true if
+    /// one had.
+    pub fn finish_make(&mut self) -> bool {
+        if !self.make_job.as_ref().is_some_and(|job| job.handle.is_finished()) {
+            return false;
+        }
+        let Some(MakeJob {
+            command,
+            format,
+            handle,
+        }) = self.make_job.take()
+        else {
+            return false;
+        };
+        let result = handle
+            .join()
+            .unwrap_or_else(|_| Err(std::io::Error::other("the build thread panicked")));
+        let (output, success) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.show_error(format!("{}: {}", command, e));
+                return true;
+            }
+        };
+
+        let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let entries = crate::quickfix::parse_errors(&output, &format, &root);
+        let count = entries.len();
+        self.set_quickfix(command.clone(), entries);
+        match (count, success) {
+            (0, true) => self.status_message = Some(format!("{}: finished", command)),
+            (0, false) => self.show_error(format!("{}: failed, but no errors were found", command)),
             _ => {
-                // Unknown command - could show error message
-                Ok(false)
+                let plural = if count == 1 { "" } else { "s" };
+                self.status_message = Some(format!("{}: {} error{}", command, count, plural));
             }
         }
+        true
     }
 
-    /// Get command line display text
-    pub fn get_command_line_display(&self) -> String {
-        if self.mode == Mode::Command {
-            format!(":{}", self.command_line)
+    /// `:diagnostics`: what the language servers reported for every file
+    fn diagnostics_to_quickfix(&mut self) {
+        let diagnostics = match self.diagnostics.lock() {
+            Ok(diagnostics) => diagnostics.clone(),
+            Err(_) => HashMap::new(),
+        };
+        let buffer_uri = self.get_buffer_uri();
+        let mut entries = Vec::new();
+        for (uri, list) in diagnostics {
+            let Ok(path) = crate::lsp::workspace_edit::file_path(&uri) else {
+                continue;
+            };
+            // Servers count columns in UTF-16 code units
+            let lines = self.file_lines(&path, buffer_uri.as_ref() == Some(&uri));
+            for d in list {
+                let start = d.range.start;
+                let text = lines.get(start.line as usize).map_or("", String::as_str);
+                let message = d.message.lines().next().unwrap_or_default();
+                entries.push(QuickfixEntry::new(
+                    path.clone(),
+                    start.line as usize,
+                    crate::quickfix::utf16_to_char_col(text, start.character as usize),
+                    format!("{}: {}", severity_label(&d), message),
+                ));
+            }
+        }
+        entries.sort_by(|a, b| (&a.path, a.line, a.col).cmp(&(&b.path, b.line, b.col)));
+        self.set_quickfix("Diagnostics", entries);
+    }
+
+    /// Put the cursor on the entry and say which one it is
+    fn jump_to_quickfix_entry(&mut self, entry: &QuickfixEntry) {
+        if self.open_location(&entry.path, entry.line) {
+            let pos = self.clamp_position(Position::new(entry.line, entry.col));
+            self.cursor.col = pos.col;
+            self.status_message = Some(format!(
+                "{} {}",
+                self.quickfix.position_text(),
+                entry.text.trim()
+            ));
+        }
+    }
+
+    /// Open `path` unless it is the file being edited and move the cursor to
+    /// `line`. An unsaved buffer is never replaced; returns false then, or
+    /// if the file can't be opened.
+    fn open_location(&mut self, path: &std::path::Path, line: usize) -> bool {
+        let same_file = self.buffer.file_path.as_ref().is_some_and(|current| {
+            let canonical = |p: &std::path::Path| std::fs::canonicalize(p).ok();
+            canonical(std::path::Path::new(current)) == canonical(path)
+        });
+        if !same_file {
+            if self.buffer.modified {
+                self.status_message =
+                    Some("No write since last change (:w first)".to_string());
+                return false;
+            }
+            let path = path.to_string_lossy().to_string();
+            if let Err(e) = self.open_file(&path) {
+                self.show_error(format!("Failed to open {}: {}", path, e));
+                return false;
+            }
+        }
+        self.cursor.line = line.min(self.buffer.line_count().saturating_sub(1));
+        self.cursor.col = 0;
+        true
+    }
+
+    /// Drop the buffer's diagnostics; whatever the old server reported no longer applies
+    async fn clear_buffer_diagnostics(&mut self) {
+        if let Some(uri) = self.get_buffer_uri() {
+            self.diagnostic_manager.clear_diagnostics(&uri).await;
+            if let Ok(mut diagnostics) = self.diagnostics.lock() {
+                diagnostics.remove(&uri);
+            }
+        }
+    }
+
+    async fn stop_language_server(&mut self, language: LanguageId) {
+        let server = self.lsp_manager.server_name(language);
+        self.clear_buffer_diagnostics().await;
+        self.clear_inlay_hints();
+        self.status_message = Some(if self.lsp_manager.stop(language).await {
+            format!("Stopped {}", server)
         } else {
-            String::new()
+            format!("{} is not running", server)
+        });
+    }
+
+    async fn restart_language_server(&mut self, language: LanguageId) {
+        let server = self.lsp_manager.server_name(language);
+        self.clear_buffer_diagnostics().await;
+        self.clear_inlay_hints();
+        match self.lsp_manager.restart(language).await {
+            Ok(()) => {
+                if let Some(uri) = self.get_buffer_uri() {
+                    let text = self.buffer.rope.to_string();
+                    let version = self.buffer.version as i32;
+                    if let Err(e) = self.lsp_manager.open_document(language, &uri, version, &text).await {
+                        log::warn!("Failed to reopen {} with {}: {}", uri, server, e);
+                    }
+                }
+                self.status_message = Some(format!("Restarted {}", server));
+            }
+            Err(e) => self.show_error(format!("Failed to restart {}: {}", server, e)),
         }
     }
 
-    fn open_fuzzy_search(&mut self) {
-        let mut fuzzy_state = FuzzySearchState::new();
-        fuzzy_state.current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    /// Ask the server for hover information and show it in the hover window
+    async fn request_hover(&mut self, language: LanguageId, position: lsp_types::Position) {
+        let Some(uri) = self.get_buffer_uri() else {
+            return;
+        };
+        let lines = match self.lsp_manager.hover(language, &uri, position).await {
+            Ok(hover) => hover_lines(hover),
+            Err(e) => {
+                self.show_error(format!("Hover failed: {}", e));
+                return;
+            }
+        };
+        if lines.is_empty() {
+            self.status_message = Some("No hover information".to_string());
+            return;
+        }
+        self.show_hover(lines);
+    }
+
+    async fn request_references(&mut self, language: LanguageId, position: lsp_types::Position) {
+        let Some(uri) = self.get_buffer_uri() else {
+            return;
+        };
+        let mut locations = match self.lsp_manager.references(language, &uri, position).await {
+            Ok(locations) => locations,
+            Err(e) => {
+                self.show_error(format!("Find references failed: {}", e));
+                return;
+            }
+        };
+        if locations.is_empty() {
+            self.status_message = Some("No references found".to_string());
+            return;
+        }
+        locations.sort_by(|a, b| (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start)));
+        self.set_quickfix_locations("References", &locations);
+    }
+
+    async fn request_workspace_symbols(&mut self, language: LanguageId, query: String) {
+        let response = match self.lsp_manager.workspace_symbols(language, query).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.show_error(format!("Workspace symbols failed: {}", e));
+                return;
+            }
+        };
+        // The picker may have been closed while the server was answering
+        if let Some(fuzzy) = &mut self.fuzzy_search {
+            fuzzy.set_symbols(response.map(symbol_items).unwrap_or_default());
+        }
+    }
+
+    /// Ask for inlay hints in the background once the text and view have
+    /// stayed the same for a moment. Returns true if they were asked for.
+    pub fn poll_inlay_hints(&mut self) -> bool {
+        if !self.options.inlay_hints || self.inlay_hint_request.is_some() {
+            return false;
+        }
+        let (Some(language), Some(uri)) = (self.current_language, self.get_buffer_uri()) else {
+            return false;
+        };
+        let first = self.viewport.offset_line;
+        let lines = first..(first + self.viewport.rows).min(self.buffer.line_count());
+        let version = self.buffer.version;
+        // The hints must be for the text on screen, so it is sent first if
+        // it changed since the last hints
+        let text = (self.inlay_hints.fetched_version() != Some(version))
+            .then(|| self.buffer.rope.to_string());
+        if !self.inlay_hints.due(version, lines.clone()) {
+            return false;
+        }
+        let range = lsp_types::Range::new(
+            lsp_types::Position::new(lines.start as u32, 0),
+            lsp_types::Position::new(lines.end as u32, 0),
+        );
+        let manager = self.lsp_manager.clone();
+        let handle = tokio::spawn(async move {
+            let request = async {
+                if let Some(text) = text {
+                    let _ = manager.change_document(language, &uri, version as i32, &text).await;
+                }
+                manager.inlay_hints(language, &uri, range).await
+            };
+            // Drawn as the view changes, so failures stay quiet
+            match tokio::time::timeout(INLAY_HINT_TIMEOUT, request).await {
+                Ok(Ok(hints)) => Some(hints),
+                Ok(Err(e)) => {
+                    log::debug!("Inlay hints failed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    log::debug!("Inlay hints timed out");
+                    None
+                }
+            }
+        });
+        self.inlay_hint_request = Some(InlayHintRequest { version, lines, handle });
+        true
+    }
+
+    /// Whether inlay hints are on their way from the server
+    pub fn is_waiting_for_inlay_hints(&self) -> bool {
+        self.inlay_hint_request.is_some()
+    }
+
+    /// Show the inlay hints once the server has answered, unless the text has
+    /// changed since. A request that failed is made again later. Returns true
+    /// if hints arrived.
+    pub async fn finish_inlay_hints(&mut self) -> bool {
+        if !self.inlay_hint_request.as_ref().is_some_and(|request| request.handle.is_finished()) {
+            return false;
+        }
+        let Some(request) = self.inlay_hint_request.take() else {
+            return false;
+        };
+        let Ok(Some(hints)) = request.handle.await else {
+            self.inlay_hints.failed(request.version, request.lines);
+            return false;
+        };
+        let hints = hints
+            .iter()
+            .map(|hint| {
+                let line = hint.position.line as usize;
+                let text = self.buffer.get_line_content(line);
+                let col = crate::quickfix::utf16_to_char_col(&text, hint.position.character as usize);
+                let text = crate::inlay_hints::hint_text(hint);
+                (line, crate::display::VirtualText { col, text })
+            })
+            .collect();
+        self.inlay_hints.set(request.version, hints);
+        true
+    }
+
+    /// Forget the inlay hints, along with any request for them on its way
+    fn clear_inlay_hints(&mut self) {
+        if let Some(request) = self.inlay_hint_request.take() {
+            request.handle.abort();
+        }
+        self.inlay_hints.clear();
+    }
+
+    /// Queue the symbol picker's query once typing has paused. Returns true
+    /// if one was queued.
+    pub fn poll_symbol_query(&mut self) -> bool {
+        if self.pending_lsp_action.is_some() {
+            return false;
+        }
+        let Some(query) = self
+            .fuzzy_search
+            .as_mut()
+            .and_then(|fuzzy| fuzzy.take_due_symbol_query())
+        else {
+            return false;
+        };
+        self.pending_lsp_action = Some(LspAction::WorkspaceSymbols(query));
+        true
+    }
+
+    /// Whether the symbol picker has a query waiting for typing to pause
+    pub fn has_pending_symbol_query(&self) -> bool {
+        self.fuzzy_search
+            .as_ref()
+            .is_some_and(|fuzzy| fuzzy.has_pending_symbol_query())
+    }
+
+    /// LSP position of the cursor
+    fn cursor_lsp_position(&self) -> lsp_types::Position {
+        let pos = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+        lsp_position(&self.buffer.rope, self.buffer.position_to_char(pos))
+    }
+
+    /// Diagnostics covering the cursor, as lines for the hover window
+    fn diagnostic_lines_at_cursor(&self) -> Vec<String> {
+        let Some(uri) = self.get_buffer_uri() else {
+            return Vec::new();
+        };
+        let position = self.cursor_lsp_position();
+        let at = (position.line, position.character);
+        let Ok(diagnostics) = self.diagnostics.lock() else {
+            return Vec::new();
+        };
+        diagnostics
+            .get(&uri)
+            .into_iter()
+            .flatten()
+            .filter(|d| {
+                let start = (d.range.start.line, d.range.start.character);
+                let end = (d.range.end.line, d.range.end.character);
+                start <= at && (at < end || start == end)
+            })
+            .flat_map(|d| {
+                let mut lines: Vec<String> = d.message.lines().map(str::to_string).collect();
+                if let Some(first) = lines.first_mut() {
+                    *first = format!("{}: {}", severity_label(d), first);
+                }
+                lines
+            })
+            .collect()
+    }
+
+    /// The cursor has rested for the hover delay: float the diagnostics under
+    /// it, or else ask the server for hover information. Only in normal mode
+    /// with no other window open. Returns true if something was shown.
+    pub fn show_idle_info(&mut self) -> bool {
+        if self.mode != Mode::Normal || self.hover_content.is_some() || self.code_actions.is_some() {
+            return false;
+        }
+        let diagnostics = self.diagnostic_lines_at_cursor();
+        if !diagnostics.is_empty() {
+            self.show_hover(diagnostics);
+            self.idle_hover = true;
+            return true;
+        }
+        if let Some(language) = self.current_language
+            && let Some(uri) = self.get_buffer_uri()
+        {
+            // An answer for where the cursor rested before is no use now
+            if let Some(request) = self.idle_hover_request.take() {
+                request.handle.abort();
+            }
+            let manager = self.lsp_manager.clone();
+            let position = self.cursor_lsp_position();
+            self.idle_hover_request = Some(IdleHoverRequest {
+                position,
+                version: self.buffer.version,
+                handle: tokio::spawn(async move { manager.hover(language, &uri, position).await }),
+            });
+        }
+        false
+    }
+
+    /// Whether hover information asked for by the idle timer is on its way
+    pub fn is_waiting_for_idle_hover(&self) -> bool {
+        self.idle_hover_request.is_some()
+    }
+
+    /// Show the idle timer's hover information once the server has answered,
+    /// unless the cursor or text has moved on since. Failures stay quiet.
+    /// Returns true if it was shown.
+    pub async fn finish_idle_hover(&mut self) -> bool {
+        if !self.idle_hover_request.as_ref().is_some_and(|request| request.handle.is_finished()) {
+            return false;
+        }
+        let Some(request) = self.idle_hover_request.take() else {
+            return false;
+        };
+        let lines = match request.handle.await {
+            Ok(Ok(hover)) => hover_lines(hover),
+            Ok(Err(e)) => {
+                log::debug!("Hover failed: {}", e);
+                return false;
+            }
+            Err(_) => return false,
+        };
+        let moved_on = self.cursor_lsp_position() != request.position
+            || self.buffer.version != request.version
+            || self.mode != Mode::Normal
+            || self.hover_content.is_some()
+            || self.code_actions.is_some();
+        if lines.is_empty() || moved_on {
+            return false;
+        }
+        self.show_hover(lines);
+        self.idle_hover = true;
+        true
+    }
+
+    /// Close the window the idle timer opened, on the next input
+    pub fn dismiss_idle_hover(&mut self) -> bool {
+        if !self.idle_hover {
+            return false;
+        }
+        self.hide_hover();
+        true
+    }
+
+    /// Ask the server for code actions over `range` and open the menu
+    async fn request_code_actions(&mut self, language: LanguageId, range: lsp_types::Range) {
+        let Some(uri) = self.get_buffer_uri() else {
+            self.status_message = Some("Buffer has no file for the language server".to_string());
+            return;
+        };
+        let diagnostics: Vec<Diagnostic> = self
+            .diagnostics
+            .lock()
+            .ok()
+            .and_then(|diagnostics| {
+                diagnostics.get(&uri).map(|list| {
+                    list.iter()
+                        .filter(|d| {
+                            d.range.start.line <= range.end.line
+                                && d.range.end.line >= range.start.line
+                        })
+                        .cloned()
+                        .collect()
+                })
+            })
+            .unwrap_or_default();
+        match self
+            .lsp_manager
+            .code_actions(language, &uri, range, diagnostics, None)
+            .await
+        {
+            Ok(actions) if actions.is_empty() => {
+                self.status_message = Some("No code actions available".to_string());
+            }
+            Ok(actions) => self.show_code_actions(actions),
+            Err(e) => self.show_error(format!("Code actions failed: {}", e)),
+        }
+    }
+
+    /// Apply the code action chosen from the menu
+    async fn apply_code_action(&mut self, language: LanguageId, action: lsp_types::CodeAction) {
+        if let Some(disabled) = &action.disabled {
+            self.status_message = Some(format!("{}: {}", action.title, disabled.reason));
+            return;
+        }
+        let title = action.title.clone();
+        match self.perform_code_action(language, action).await {
+            Ok(()) => self.status_message = Some(format!("Applied: {}", title)),
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    /// Resolve a code action's edit if the server left it out, apply the
+    /// edit as one undo step, then run its command
+    async fn perform_code_action(
+        &mut self,
+        language: LanguageId,
+        mut action: lsp_types::CodeAction,
+    ) -> Result<(), String> {
+        if action.edit.is_none() && action.data.is_some() {
+            action = self
+                .lsp_manager
+                .resolve_code_action(language, &action)
+                .await
+                .map_err(|e| format!("Failed to resolve \"{}\": {}", action.title, e))?;
+        }
+        if let Some(edit) = &action.edit {
+            let checkpoint = self.undo_checkpoint();
+            let result = self.apply_workspace_edit(edit);
+            self.record_undo(checkpoint);
+            result.map_err(|e| format!("Failed to apply \"{}\": {}", action.title, e))?;
+        }
+        if let Some(command) = &action.command {
+            self.lsp_manager
+                .execute_command(language, command)
+                .await
+                .map_err(|e| format!("Failed to run \"{}\": {}", action.title, e))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the first action of `kind` the server offers for the whole
+    /// buffer. Returns its title, or None if the server had none.
+    async fn apply_source_action(
+        &mut self,
+        language: LanguageId,
+        kind: lsp_types::CodeActionKind,
+    ) -> Result<Option<String>, String> {
+        let uri = self
+            .get_buffer_uri()
+            .ok_or("Buffer has no file for the language server")?;
+        let rope = &self.buffer.rope;
+        let range = lsp_types::Range::new(
+            lsp_types::Position::new(0, 0),
+            lsp_position(rope, rope.len_chars()),
+        );
+        let diagnostics = self
+            .diagnostics
+            .lock()
+            .ok()
+            .and_then(|diagnostics| diagnostics.get(&uri).cloned())
+            .unwrap_or_default();
+        let actions = self
+            .lsp_manager
+            .code_actions(language, &uri, range, diagnostics, Some(vec![kind.clone()]))
+            .await
+            .map_err(|e| e.to_string())?;
+        // Servers may send more than was asked for; kinds nest with dots,
+        // so `source.organizeImports.rust` is a `source.organizeImports`
+        let Some(action) = actions.into_iter().find(|action| {
+            action.disabled.is_none()
+                && action.kind.as_ref().is_some_and(|action_kind| {
+                    action_kind == &kind
+                        || action_kind
+                            .as_str()
+                            .strip_prefix(kind.as_str())
+                            .is_some_and(|rest| rest.starts_with('.'))
+                })
+        }) else {
+            return Ok(None);
+        };
+        let title = action.title.clone();
+        let version = self.buffer.version;
+        self.perform_code_action(language, action).await?;
+        // The next request must see the edited text
+        if self.buffer.version != version {
+            let text = self.buffer.rope.to_string();
+            self.lsp_manager
+                .change_document(language, &uri, self.buffer.version as i32, &text)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(Some(title))
+    }
+
+    /// Format the buffer with the language server as one undo step.
+    /// Returns false if no running server can format.
+    async fn format_with_language_server(&mut self, language: LanguageId) -> Result<bool, String> {
+        let Some(uri) = self.get_buffer_uri() else {
+            return Ok(false);
+        };
+        if !self.lsp_manager.is_client_initialized(language).await {
+            return Ok(false);
+        }
+        // The server formats what it last heard about
+        let text = self.buffer.rope.to_string();
+        self.lsp_manager
+            .change_document(language, &uri, self.buffer.version as i32, &text)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let options = lsp_types::FormattingOptions {
+            tab_size: self.options.tab_width as u32,
+            insert_spaces: self.options.expand_tab,
+            trim_trailing_whitespace: Some(self.options.trim_trailing_whitespace),
+            insert_final_newline: Some(self.options.ensure_final_newline),
+            ..Default::default()
+        };
+        let Some(edits) = self
+            .lsp_manager
+            .formatting(language, &uri, options)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(false);
+        };
+
+        let checkpoint = self.undo_checkpoint();
+        let version = self.buffer.version;
+        let result = self.apply_format_edits(&edits);
+        self.record_undo(checkpoint);
+        result.map_err(|e| e.to_string())?;
+        if self.buffer.version != version {
+            let text = self.buffer.rope.to_string();
+            self.lsp_manager
+                .change_document(language, &uri, self.buffer.version as i32, &text)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(true)
+    }
+
+    /// Apply formatting edits from a language server, keeping the cursor on
+    /// the same text
+    fn apply_format_edits(&mut self, edits: &[lsp_types::TextEdit]) -> Result<(), BufferError> {
+        use crate::lsp::workspace_edit::char_index;
+        let rope = &self.buffer.rope;
+        let spans = edits
+            .iter()
+            .map(|edit| {
+                let start = char_index(rope, edit.range.start);
+                let end = char_index(rope, edit.range.end).max(start);
+                (
+                    self.buffer.char_to_position(start),
+                    self.buffer.char_to_position(end),
+                    edit.new_text.clone(),
+                )
+            })
+            .collect();
+        let (line, col) = self
+            .buffer
+            .apply_format_edits(spans, self.cursor.line, self.cursor.col)?;
+        self.cursor.line = line;
+        self.cursor.col = col;
+        self.notify_text_change();
+        Ok(())
+    }
+
+    /// Apply the on-save code actions in order, then format and write.
+    /// Failures are reported after the write message but never stop the
+    /// write.
+    async fn save_with_code_actions(&mut self, language: LanguageId, path: &str, quit: bool) {
+        let mut failures = Vec::new();
+        // Without a running server there is nothing to apply; just save
+        if self.lsp_manager.is_client_initialized(language).await {
+            for kind in self.options.save_code_action_kinds() {
+                if let Err(e) = self.apply_source_action(language, kind.clone()).await {
+                    failures.push(format!("{} failed: {}", kind.as_str(), e));
+                }
+            }
+        }
+        let written = self.write_buffer(path);
+        for failure in failures {
+            self.append_status(&failure);
+        }
+        self.quit_requested = quit && written;
+    }
+
+    /// Apply a workspace edit, editing the open buffer in place and other
+    /// files on disk. Stops at the first change that fails.
+    fn apply_workspace_edit(&mut self, edit: &lsp_types::WorkspaceEdit) -> Result<(), String> {
+        use crate::lsp::workspace_edit::{FileChange, apply_text_edits, file_changes, file_path};
+        let buffer_uri = self.get_buffer_uri();
+        let io_error = |path: &std::path::Path, e: std::io::Error| format!("{}: {}", path.display(), e);
+
+        for change in file_changes(edit) {
+            match change {
+                FileChange::Edit(uri, edits) if Some(&uri) == buffer_uri.as_ref() => {
+                    let mut rope = self.buffer.rope.clone();
+                    apply_text_edits(&mut rope, &edits);
+                    self.buffer.set_rope(rope);
+                    self.notify_text_change();
+                }
+                FileChange::Edit(uri, edits) => {
+                    let path = file_path(&uri)?;
+                    let content = std::fs::read_to_string(&path).map_err(|e| io_error(&path, e))?;
+                    let mut rope = ropey::Rope::from_str(&content);
+                    apply_text_edits(&mut rope, &edits);
+                    crate::buffer::write_atomic(&path, &rope.to_string(), false)
+                        .map_err(|e| io_error(&path, e))?;
+                }
+                FileChange::Create { uri, overwrite } => {
+                    let path = file_path(&uri)?;
+                    if overwrite || !path.exists() {
+                        std::fs::write(&path, "").map_err(|e| io_error(&path, e))?;
+                    }
+                }
+                FileChange::Rename { from, to, overwrite } => {
+                    let (from_path, to_path) = (file_path(&from)?, file_path(&to)?);
+                    if to_path.exists() && !overwrite {
+                        return Err(format!("{} already exists", to_path.display()));
+                    }
+                    std::fs::rename(&from_path, &to_path).map_err(|e| io_error(&from_path, e))?;
+                    if Some(&from) == buffer_uri.as_ref() {
+                        self.buffer.file_path = Some(to_path.to_string_lossy().into_owned());
+                    }
+                }
+                FileChange::Delete(uri) => {
+                    let path = file_path(&uri)?;
+                    std::fs::remove_file(&path).map_err(|e| io_error(&path, e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start restarting crashed language servers in the background, unless
+    /// the last check is still running
+    pub fn start_lsp_health_check(&mut self) {
+        if self.lsp_health_check.is_none() {
+            let manager = self.lsp_manager.clone();
+            self.lsp_health_check = Some(tokio::spawn(async move { manager.check_health().await }));
+        }
+    }
+
+    /// Report a finished health check's restarts and servers given up on in
+    /// the status line. Returns true if there was anything to report.
+    pub async fn finish_lsp_health_check(&mut self) -> bool {
+        if !self.lsp_health_check.as_ref().is_some_and(|check| check.is_finished()) {
+            return false;
+        }
+        let Some(check) = self.lsp_health_check.take() else {
+            return false;
+        };
+        // A check that panicked has nothing to report; the next one runs as usual
+        let events = check.await.unwrap_or_default();
+        for event in &events {
+            self.show_error(event.message());
+        }
+        !events.is_empty()
+    }
+
+    /// Move to the `count`th match of a character search on the cursor line.
+    /// The cursor stays put when there aren't that many matches.
+    fn find_char(&mut self, search: CharSearch, count: usize, repeat: bool) {
+        let pos = Position::new(self.cursor.line, self.cursor.col);
+        if let Some(found) = crate::motion::find_char(&self.buffer, pos, search, count, repeat) {
+            self.cursor.col = found.col;
+        }
+    }
+
+    /// First and last line covered by a linewise motion from the cursor
+    fn line_motion_range(&self, motion: LineMotion) -> (usize, usize) {
+        let line = self.cursor.line;
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        match motion {
+            LineMotion::Down(lines) => (line, (line + lines).min(last_line)),
+            LineMotion::Up(lines) => (line.saturating_sub(lines), line),
+            LineMotion::FileStart => (0, line),
+            LineMotion::FileEnd => (line, last_line),
+            LineMotion::ParagraphForward(count) => {
+                (line, crate::motion::paragraph_forward(&self.buffer, line, count))
+            }
+            LineMotion::ParagraphBackward(count) => {
+                (crate::motion::paragraph_backward(&self.buffer, line, count), line)
+            }
+        }
+    }
+
+    /// Toggle line comments on `start..=end` using the current language's
+    /// comment token, leaving the cursor on the first line. Returns false if
+    /// the language has no line comments.
+    fn toggle_comment(&mut self, start: usize, end: usize) -> bool {
+        let Some(language) = self.current_language else {
+            self.status_message = Some("No comment syntax for this file type".to_string());
+            return false;
+        };
+        let token = crate::syntax::get_language_config(language).comment_token;
+        if self.buffer.toggle_comment(start, end, token).is_err() {
+            return false;
+        }
+        self.cursor.line = start;
+        let line_len = self.line_char_count(start);
+        self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+        self.notify_text_change();
+        true
+    }
+
+    /// Set the indentation of `start..=end` from the syntax tree, one indent
+    /// unit a level, and put the cursor on the first line. Lines the indent
+    /// query leaves alone, like the inside of a multi-line string, keep theirs.
+    fn reindent_lines(&mut self, start: usize, end: usize) {
+        let engine = self.current_language.and_then(|language| {
+            IndentationEngine::new(&crate::syntax::get_language_config(language))
+        });
+        let Some(engine) = engine else {
+            self.show_error("No indent rules for this file type");
+            return;
+        };
+        let levels = engine.indent_levels(&self.buffer.rope.to_string());
+        let unit = self.options.indent_unit();
+        let mut changed = 0;
+        for line in start..=end {
+            let Some(Some(level)) = levels.get(line) else {
+                continue;
+            };
+            let content = self.buffer.get_line_content(line);
+            let text = content.trim_start();
+            let reindented = if text.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", unit.repeat(*level), text)
+            };
+            if reindented == content {
+                continue;
+            }
+            let end = Position::new(line, content.chars().count());
+            let _ = self
+                .buffer
+                .replace_range(Position::new(line, 0), end, &reindented);
+            changed += 1;
+        }
+        self.move_to_line_start(start);
+        if changed > 0 {
+            self.notify_text_change();
+        }
+        self.status_message = Some(format!(
+            "Reindented {} line{}",
+            changed,
+            if changed == 1 { "" } else { "s" }
+        ));
+    }
+
+    /// Describe the character under the cursor in the status line: its code
+    /// point in decimal and hex, and its UTF-8 bytes
+    fn show_char_info(&mut self) {
+        let line = self.buffer.get_line_content(self.cursor.line);
+        let Some(c) = line.chars().nth(self.cursor.col) else {
+            self.status_message = Some("NUL".to_string());
+            return;
+        };
+        let shown = match c {
+            // Control characters in caret notation, e.g. `^I` for a tab
+            '\0'..='\x1f' => format!("^{}", (c as u8 + b'@') as char),
+            '\x7f' => "^?".to_string(),
+            _ => c.to_string(),
+        };
+        let mut bytes = [0; 4];
+        let utf8: Vec<String> = c
+            .encode_utf8(&mut bytes)
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        self.status_message = Some(format!(
+            "<{}> {}, U+{:04X}, UTF-8 {}",
+            shown,
+            c as u32,
+            c as u32,
+            utf8.join(" ")
+        ));
+    }
+
+    /// Name the highlight captures of the token under the cursor, for theme
+    /// authors: the one drawn first, then any it covers
+    fn show_syntax_captures(&mut self) {
+        let pos = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+        let byte = self.buffer.rope.char_to_byte(self.buffer.position_to_char(pos));
+        let Some(highlighter) = &mut self.buffer.highlighter else {
+            self.status_message = Some("No syntax highlighting for this file".to_string());
+            return;
+        };
+        let captures = highlighter.captures_at(pos.line, byte);
+        self.status_message = Some(if captures.is_empty() {
+            "No highlight capture under the cursor".to_string()
+        } else {
+            let names: Vec<String> = captures.iter().map(|name| format!("@{}", name)).collect();
+            names.join(" ")
+        });
+    }
+
+    /// Delete before the cursor in insert mode: back to the start of the
+    /// previous word, or else to the indent and then the line start. At the
+    /// start of a line both join it onto the line above, like Backspace.
+    fn delete_back_insert(&mut self, word: bool) {
+        if self.mode != Mode::Insert {
+            return;
+        }
+        let line = self.cursor.line;
+        let col = self.cursor.col.min(self.line_char_count(line));
+        if col == 0 {
+            if line > 0 {
+                let prev_line_len = self.line_char_count(line - 1);
+                let _ = self.buffer.delete_char(line, 0);
+                self.cursor.line -= 1;
+                self.cursor.col = prev_line_len;
+                self.notify_text_change();
+            }
+            return;
+        }
+
+        let start = if word {
+            let target = crate::motion::word_backward_with(
+                &self.buffer,
+                Position::new(line, col),
+                &self.options.word_chars,
+            );
+            if target.line == line { target.col } else { 0 }
+        } else {
+            let indent = self
+                .buffer
+                .line(line)
+                .map(|text| text.chars().take_while(|c| *c == ' ' || *c == '\t').count())
+                .unwrap_or(0);
+            if col > indent { indent } else { 0 }
+        };
+        let _ = self
+            .buffer
+            .delete_range(Position::new(line, start), Position::new(line, col));
+        self.cursor.col = start;
+        self.notify_text_change();
+    }
+
+    /// Report line, word, char and byte counts in the status line: for the
+    /// selection in visual mode, otherwise the cursor's place in the buffer
+    fn show_counts(&mut self) {
+        use crate::motion::count_words_with;
+        let rope = &self.buffer.rope;
+        if rope.len_chars() == 0 {
+            self.status_message = Some("--No lines in buffer--".to_string());
+            return;
+        }
+        let text = rope.to_string();
+        let lines = self.buffer.last_content_line() + 1;
+        let words = count_words_with(&text, &self.options.word_chars);
+        let chars = rope.len_chars();
+        let bytes = rope.len_bytes();
+
+        if self.mode == Mode::Visual
+            && let Some((start, end)) = self.visual_selection()
+        {
+            let selected = self.buffer.get_range(start, end);
+            self.status_message = Some(format!(
+                "Selected {} of {} Lines; {} of {} Words; {} of {} Chars; {} of {} Bytes",
+                end.line - start.line + 1,
+                lines,
+                count_words_with(&selected, &self.options.word_chars),
+                words,
+                selected.chars().count(),
+                chars,
+                selected.len(),
+                bytes
+            ));
+            return;
+        }
+
+        let pos = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+        let char_idx = self.buffer.position_to_char(pos).min(chars - 1);
+        let line_len = self.line_char_count(pos.line);
+        self.status_message = Some(format!(
+            "Col {} of {}; Line {} of {}; Word {} of {}; Char {} of {}; Byte {} of {}",
+            (pos.col + 1).min(line_len.max(1)),
+            line_len,
+            pos.line + 1,
+            lines,
+            count_words_with(
+                &text[..rope.char_to_byte(char_idx + 1)],
+                &self.options.word_chars,
+            ),
+            words,
+            char_idx + 1,
+            chars,
+            rope.char_to_byte(char_idx) + 1,
+            bytes
+        ));
+    }
+
+    /// Move `count` git hunks forward or back from the cursor line, wrapping
+    /// around the ends of the file
+    fn jump_to_hunk(&mut self, count: usize, forward: bool) {
+        let starts: Vec<usize> = self
+            .git_signs
+            .hunks()
+            .iter()
+            .map(|hunk| hunk.first_line())
+            .collect();
+        if starts.is_empty() {
+            self.status_message = Some("No hunks".to_string());
+            return;
+        }
+
+        let mut index = None;
+        for _ in 0..count.max(1) {
+            let line = index.map_or(self.cursor.line, |i: usize| starts[i]);
+            index = Some(if forward {
+                starts.iter().position(|&start| start > line).unwrap_or(0)
+            } else {
+                starts
+                    .iter()
+                    .rposition(|&start| start < line)
+                    .unwrap_or(starts.len() - 1)
+            });
+        }
+        let index = index.unwrap_or(0);
+
+        self.cursor.line = starts[index];
+        self.cursor.col = 0;
+        self.status_message = Some(format!("hunk {} of {}", index + 1, starts.len()));
+    }
+
+    /// Replace the buffer with an empty one that has no file name
+    fn new_buffer(&mut self) {
+        self.buffer = Buffer::new();
+        self.git_signs = GitSigns::default();
+        self.undo_history.clear();
+        self.current_language = None;
+        self.file_watcher = None;
+        self.reload_prompt = false;
+        self.cursors.clear();
+        self.cursor.line = 0;
+        self.cursor.col = 0;
+        self.viewport.offset_line = 0;
+        self.viewport.offset_col = 0;
+    }
+
+    /// Async version of open_file - uses async file loading to avoid blocking UI
+    pub async fn open_file_async(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.buffer.load_from_file_async(path).await?;
+        self.undo_history.clear();
+        if self.mode != Mode::FuzzySearch {
+            self.recent_files.add(std::path::Path::new(path));
+        }
+        self.buffer.file_path = Some(path.to_string());
+
+        // Reset viewport and cursor to ensure clean rendering state
+        self.viewport.offset_line = 0;
+        self.viewport.offset_col = 0;
+        self.cursor.line = 0;
+        self.cursor.col = 0;
+
+        self.detect_language(path);
+        self.load_project_options(path);
+        self.update_git_signs();
+
+        self.watch_file(path);
+
+        // TODO: Notify LSP server about file open
+        // This is currently blocked by the async/sync boundary
+        // Async LSP operations need proper integration with sync UI
+
+        Ok(())
+    }
+
+    pub fn notify_text_change(&mut self) {
+        // Adjust viewport if it's out of bounds after buffer changes
+        let max_line = self.buffer.line_count().saturating_sub(1);
+        if self.viewport.offset_line > max_line {
+            self.viewport.offset_line = max_line;
+        }
+
+        // Adjust cursor if it's out of bounds
+        if self.cursor.line > max_line {
+            self.cursor.line = max_line;
+        }
+
+        // Adjust cursor column if it's beyond the end of the current line
+        if let Some(line) = self.buffer.line(self.cursor.line) {
+            let line_len = line.len();
+            if self.cursor.col > line_len {
+                self.cursor.col = line_len;
+            }
+        }
+
+        // Clear overlays when text changes
+        self.hide_hover();
+        self.hide_code_actions();
+
+        // TODO: Implement async text change notifications
+        // For now, LSP operations are handled asynchronously in command handlers
+    }
+
+    /// Show hover information at cursor position. It replaces the completion
+    /// popup and code action menu, which would cover it.
+    pub fn show_hover(&mut self, content: Vec<String>) {
+        self.completion_popup.hide();
+        self.hide_code_actions();
+        self.hover_content = Some(content);
+    }
+
+    /// Hide hover information
+    pub fn hide_hover(&mut self) {
+        self.hover_content = None;
+        self.idle_hover = false;
+    }
+
+    /// Show code actions menu, closing hover information and completions
+    pub fn show_code_actions(&mut self, actions: Vec<lsp_types::CodeAction>) {
+        self.completion_popup.hide();
+        self.hide_hover();
+        self.code_actions = Some(actions);
+        self.code_action_selected = 0;
+    }
+
+    /// Hide code actions menu
+    pub fn hide_code_actions(&mut self) {
+        self.code_actions = None;
+    }
+
+    /// Show the completion popup, closing hover information and the code
+    /// action menu
+    pub fn show_completions(&mut self, items: Vec<lsp_types::CompletionItem>) {
+        self.hide_hover();
+        self.hide_code_actions();
+        self.completion_popup.set_items(items);
+    }
+
+    /// Close every floating window and the quickfix window, leaving the mode,
+    /// selection and cursors alone
+    pub fn close_overlays(&mut self) {
+        self.completion_popup.hide();
+        self.hide_code_actions();
+        self.hide_hover();
+        self.listing = None;
+        self.quickfix_open = false;
+    }
+
+    /// Navigate code actions menu
+    pub fn select_next_code_action(&mut self) {
+        if let Some(actions) = &self.code_actions
+            && !actions.is_empty()
+        {
+            self.code_action_selected = (self.code_action_selected + 1) % actions.len();
+        }
+    }
+
+    pub fn select_prev_code_action(&mut self) {
+        if let Some(actions) = &self.code_actions
+            && !actions.is_empty()
+        {
+            self.code_action_selected = if self.code_action_selected == 0 {
+                actions.len() - 1
+            } else {
+                self.code_action_selected - 1
+            };
+        }
+    }
+
+    /// Get selected code action
+    pub fn get_selected_code_action(&self) -> Option<&lsp_types::CodeAction> {
+        self.code_actions.as_ref()?.get(self.code_action_selected)
+    }
+
+    /// Enter command mode
+    pub fn enter_command_mode(&mut self) {
+        self.mode = Mode::Command;
+        self.command_line.clear();
+        self.command_history_index = self.command_history.len();
+    }
+
+    /// Handle command line input
+    pub fn handle_command_input(&mut self, c: char) -> Result<bool, Box<dyn std::error::Error>> {
+        // Returns true if should quit
+        match c {
+            '\n' | '\r' => {
+                self.command_history.push(self.command_line.clone());
+                self.command_history_index = self.command_history.len();
+                // Execute command
+                let checkpoint = self.undo_checkpoint();
+                let result = self.execute_command_line();
+                self.record_undo(checkpoint);
+                let should_quit = result?;
+                // Commands such as `:recent` open a picker and keep its mode
+                if self.mode == Mode::Command {
+                    self.mode = Mode::Normal;
+                }
+                self.command_line.clear();
+                self.resume_insert();
+                return Ok(should_quit);
+            }
+            '\x08' | '\x7f' => {
+                // Backspace
+                self.command_line.pop();
+            }
+            '\x1b' => {
+                // Escape
+                self.mode = Mode::Normal;
+                self.command_line.clear();
+                self.resume_insert();
+            }
+            c if c.is_ascii_graphic() || c == ' ' => {
+                self.command_line.push(c);
+            }
+            _ => {}
+        }
+        Ok(false) // Don't quit for other inputs
+    }
+
+    /// Run the last command line `count` times (`@:`). Returns true if the
+    /// editor should quit.
+    fn repeat_ex_command(&mut self, count: usize) -> bool {
+        let last = self.command_history.iter().rev().find(|line| !line.trim().is_empty());
+        let Some(line) = last.cloned() else {
+            self.show_error("No previous command line");
+            return false;
+        };
+        self.last_executed = Some(':');
+        for _ in 0..count {
+            self.command_line = line.clone();
+            let quit = match self.execute_command_line() {
+                Ok(quit) => quit,
+                Err(e) => {
+                    self.show_error(e.to_string());
+                    false
+                }
+            };
+            self.command_line.clear();
+            if quit {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Execute command line
+    fn execute_command_line(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        // Returns true if should quit
+        let command_line = self.command_line.clone();
+        let trimmed = command_line.trim();
+        if trimmed.is_empty() {
+            return Ok(false);
+        }
+
+        // Commands that take a line range or a pattern
+        let last_line = self.buffer.last_content_line();
+        let ctx = ex::AddressContext {
+            current: self.cursor.line,
+            last: last_line,
+            visual: self
+                .last_visual
+                .map(|(start, end)| LineRange::new(start.line, end.line)),
+        };
+        let expanded = match ex::expand_user_command(trimmed, &self.command_aliases, ctx) {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => {
+                self.show_error(e);
+                return Ok(false);
+            }
+            None => trimmed.to_string(),
+        };
+        let trimmed = expanded.as_str();
+
+        let (range, rest) = ex::parse_range(trimmed, ctx);
+        if let Some(global) = ex::parse_global(rest, self.options.smart_case) {
+            let range = range.unwrap_or(LineRange::new(0, last_line));
+            if let Err(e) = global.and_then(|global| self.execute_global(&global, range)) {
+                self.show_error(e);
+            }
+            return Ok(false);
+        }
+        if let Some(sub) = ex::parse_substitute(rest, self.last_search.as_ref(), self.options.smart_case) {
+            let range = range.unwrap_or(LineRange::single(self.cursor.line));
+            let result = sub.and_then(|sub| {
+                self.remember_substitute(&sub);
+                self.execute_substitute(&sub, range)
+            });
+            if let Err(e) = result {
+                self.show_error(e);
+            }
+            return Ok(false);
+        }
+        if let Some(keep_flags) = ex::parse_repeat_substitute(rest) {
+            let range = range.unwrap_or(LineRange::single(self.cursor.line));
+            if let Err(e) = self.repeat_substitute(range, keep_flags) {
+                self.show_error(e);
+            }
+            return Ok(false);
+        }
+        if let Some(command) = ex::parse_bang(rest) {
+            let result = match range {
+                Some(range) => self.execute_filter(command, range),
+                None => self.execute_shell(command),
+            };
+            if let Err(e) = result {
+                self.show_error(e);
+            }
+            return Ok(false);
+        }
+        if let Some(options) = ex::parse_sort(rest) {
+            let range = range.unwrap_or(LineRange::new(0, last_line));
+            match options {
+                Ok(options) => self.execute_sort(options, range),
+                Err(e) => self.show_error(e),
+            }
+            return Ok(false);
+        }
+        if let Some(options) = ex::parse_retab(rest) {
+            let range = range.unwrap_or(LineRange::new(0, last_line));
+            match options {
+                Ok(options) => self.execute_retab(options, range),
+                Err(e) => self.show_error(e),
+            }
+            return Ok(false);
+        }
+
+        if let Some(command) = ex::parse_line_command(rest) {
+            let range = range.unwrap_or(LineRange::single(self.cursor.line));
+            match command {
+                Ok((command, reg)) => self.execute_line_command(command, reg, range),
+                Err(e) => self.show_error(e),
+            }
+            return Ok(false);
+        }
+
+        if rest == "reindent" {
+            let range = range.unwrap_or(LineRange::new(0, last_line));
+            self.reindent_lines(range.start, range.end);
+            return Ok(false);
+        }
+
+        // A bare address such as `:42` or `:+3` jumps to that line
+        if let Some(range) = range
+            && rest.is_empty()
+        {
+            self.execute_command(Command::GotoLine(range.end));
+            return Ok(false);
+        }
+
+        let parts: Vec<&str> = trimmed.split_whitespace().collect();
+        if parts.is_empty() {
+            return Ok(false);
+        }
+
+        match parts[0] {
+            "q" | "quit" => {
+                // Quit
+                Ok(true)
+            }
+            "x" | "wq" => {
+                // Save and quit
+                if let Some(path) = self.buffer.file_path.clone() {
+                    // Stay open if the write failed so nothing is lost
+                    return Ok(self.save_buffer(&path, true));
+                }
+                if let Some(filename) = parts.get(1) {
+                    return Ok(self.write_buffer(filename));
+                }
+                if self.buffer.modified {
+                    self.status_message = Some("No file name (use :w <file>)".to_string());
+                    return Ok(false);
+                }
+                Ok(true)
+            }
+            "fmt" | "format" => {
+                self.execute_command(Command::FormatBuffer);
+                Ok(false)
+            }
+            "set" | "se" => {
+                self.execute_set(&parts[1..]);
+                Ok(false)
+            }
+            "recent" | "ol" | "oldfiles" => {
+                self.open_recent_files();
+                Ok(false)
+            }
+            "A" => {
+                if self.buffer.modified {
+                    self.status_message =
+                        Some("No write since last change (add ! to override)".to_string());
+                    return Ok(false);
+                }
+                self.open_alternate_file();
+                Ok(false)
+            }
+            "A!" => {
+                self.open_alternate_file();
+                Ok(false)
+            }
+            "count" => {
+                self.show_counts();
+                Ok(false)
+            }
+            "as" | "ascii" => {
+                self.show_char_info();
+                Ok(false)
+            }
+            "mes" | "messages" => {
+                if parts.get(1) == Some(&"clear") {
+                    self.messages.clear();
+                } else {
+                    self.show_messages();
+                }
+                Ok(false)
+            }
+            "com" | "command" | "com!" | "command!" => {
+                let args = trimmed.split_once(char::is_whitespace).map_or("", |(_, args)| args);
+                if args.trim().is_empty() {
+                    self.show_user_commands();
+                } else {
+                    match ex::parse_user_command(args) {
+                        Ok((name, definition)) => {
+                            self.command_aliases.insert(name, definition);
+                        }
+                        Err(e) => self.show_error(e),
+                    }
+                }
+                Ok(false)
+            }
+            "delc" | "delcommand" => {
+                match parts.get(1) {
+                    Some(name) if self.command_aliases.remove(*name).is_some() => {}
+                    Some(name) => self.show_error(format!("No such user command: {}", name)),
+                    None => self.show_error("Argument required"),
+                }
+                Ok(false)
+            }
+            "reg" | "registers" | "di" | "display" => {
+                self.show_registers();
+                Ok(false)
+            }
+            "earlier" | "later" => {
+                let count = match parts.get(1).map(|n| n.parse::<usize>()) {
+                    None => 1,
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        self.show_error(format!("Invalid count: {}", parts[1]));
+                        return Ok(false);
+                    }
+                };
+                if parts[0] == "earlier" {
+                    self.undo(count);
+                } else {
+                    self.redo(count);
+                }
+                Ok(false)
+            }
+            "undol" | "undolist" => {
+                self.status_message = Some(format!(
+                    "{} changes to undo, {} to redo",
+                    self.undo_history.undo_len(),
+                    self.undo_history.redo_len()
+                ));
+                Ok(false)
+            }
+            "wa" | "wall" => {
+                self.write_all();
+                Ok(false)
+            }
+            "wqa" | "wqall" | "xa" | "xall" => {
+                // Stay open if anything couldn't be written
+                Ok(self.write_all())
+            }
+            "qa" | "qall" => {
+                if self.buffer.modified {
+                    self.status_message =
+                        Some("No write since last change (add ! to override)".to_string());
+                    return Ok(false);
+                }
+                Ok(true)
+            }
+            "qa!" | "qall!" => Ok(true),
+            "w" | "write" => {
+                // Save file
+                if let Some(path) = self.buffer.file_path.clone() {
+                    self.save_buffer(&path, false);
+                } else if parts.len() > 1 {
+                    // Save as new file, which also names the buffer
+                    let filename = parts[1].to_string();
+                    if self.write_buffer(&filename) {
+                        self.buffer.file_path = Some(filename.clone());
+                        self.detect_language(&filename);
+                        self.watch_file(&filename);
+                    }
+                } else {
+                    self.status_message = Some("No file name (use :w <file>)".to_string());
+                }
+                Ok(false)
+            }
+            "enew" | "ene" => {
+                if self.buffer.modified {
+                    self.status_message =
+                        Some("No write since last change (add ! to override)".to_string());
+                    return Ok(false);
+                }
+                self.new_buffer();
+                Ok(false)
+            }
+            "enew!" | "ene!" => {
+                self.new_buffer();
+                Ok(false)
+            }
+            "e!" | "edit!" if parts.len() == 1 => {
+                // Discard changes and reload the file from disk
+                let Some(path) = self.buffer.file_path.clone() else {
+                    self.status_message = Some("No file name".to_string());
+                    return Ok(false);
+                };
+                self.reload_prompt = false;
+                self.status_message = match self.reload_from_disk() {
+                    Ok(()) => Some(format!("\"{}\" reloaded", path)),
+                    Err(e) => Some(format!("Reload failed: {}", e)),
+                };
+                Ok(false)
+            }
+            "e" | "edit" | "e!" | "edit!" if parts.len() > 1 => {
+                // Open/edit file
+                let filename = parts[1].to_string();
+                self.open_file(&filename)?;
+                Ok(false)
+            }
+            "syntax" | "syn" => {
+                // Toggle syntax highlighting
+                if parts.len() > 1 {
+                    match parts[1] {
+                        "on" => {
+                            // Enable syntax highlighting
+                            if let Some(language_id) = self.current_language {
+                                let config =
+                                    crate::syntax::language::get_language_config(language_id);
+                                if let Ok(highlighter) =
+                                    crate::syntax::highlighter::SyntaxHighlighter::new(config)
+                                {
+                                    self.buffer.highlighter = Some(highlighter);
+                                }
+                            }
+                        }
+                        "off" => {
+                            // Disable syntax highlighting
+                            self.buffer.highlighter = None;
+                        }
+                        "info" => {
+                            self.execute_command(Command::ShowCurrentSyntaxCapture);
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(false)
+            }
+            "copen" | "cope" | "cw" | "cwindow" => {
+                self.execute_command(Command::OpenQuickfix);
+                Ok(false)
+            }
+            "cclose" | "ccl" => {
+                self.quickfix_open = false;
+                Ok(false)
+            }
+            "cnext" | "cn" | "cprevious" | "cprev" | "cp" | "cNext" | "cN" => {
+                let count = parts.get(1).and_then(|n| n.parse().ok()).unwrap_or(1);
+                self.execute_command(if parts[0].starts_with("cn") {
+                    Command::QuickfixNext(count)
+                } else {
+                    Command::QuickfixPrev(count)
+                });
+                Ok(false)
+            }
+            "grep" | "gr" => {
+                let pattern = trimmed.split_once(char::is_whitespace).map_or("", |(_, p)| p.trim());
+                self.grep_to_quickfix(pattern);
+                Ok(false)
+            }
+            "make" | "mak" => {
+                let args = trimmed.split_once(char::is_whitespace).map_or("", |(_, a)| a.trim());
+                self.make_to_quickfix(args);
+                Ok(false)
+            }
+            "symbols" | "sym" => {
+                self.open_workspace_symbols();
+                if let Some(fuzzy) = &mut self.fuzzy_search {
+                    let query = trimmed.split_once(char::is_whitespace).map_or("", |(_, q)| q.trim());
+                    fuzzy.update_query(query.to_string());
+                }
+                Ok(false)
+            }
+            "diagnostics" | "diag" => {
+                self.diagnostics_to_quickfix();
+                Ok(false)
+            }
+            "lsp" => {
+                match parts.get(1).copied() {
+                    Some("restart") => self.pending_lsp_action = Some(LspAction::Restart),
+                    Some("stop") => self.pending_lsp_action = Some(LspAction::Stop),
+                    Some("status") | None => self.pending_lsp_action = Some(LspAction::Status),
+                    Some("organize-imports") => {
+                        self.execute_command(Command::OrganizeImports);
+                    }
+                    Some("log") => {
+                        self.execute_command(Command::ShowLspLog);
+                    }
+                    Some(other) => {
+                        self.show_error(format!("Unknown :lsp command: {}", other));
+                    }
+                }
+                Ok(false)
+            }
+            _ => {
+                // Unknown command - could show error message
+                Ok(false)
+            }
+        }
+    }
+
+    /// Run `:s` on every line in `range`
+    fn execute_substitute(&mut self, sub: &Substitute, range: LineRange) -> Result<(), String> {
+        let mut substitutions = 0;
+        let mut lines_changed = 0;
+        let mut last_changed = None;
+        // Bottom-to-top so replacements containing newlines don't shift pending lines
+        for line in (range.start..=range.end).rev() {
+            let count = self.substitute_line(sub, line);
+            if count > 0 {
+                substitutions += count;
+                lines_changed += 1;
+                last_changed.get_or_insert(line);
+            }
+        }
+
+        let Some(line) = last_changed else {
+            return Err(format!("Pattern not found: {}", sub.pattern.as_str()));
+        };
+        self.move_to_line_start(line);
+        self.notify_text_change();
+        self.status_message = Some(format!(
+            "{} substitutions on {} lines",
+            substitutions, lines_changed
+        ));
+        Ok(())
+    }
+
+    /// Keep a substitution for `&`, and its pattern for the next empty one
+    fn remember_substitute(&mut self, sub: &Substitute) {
+        self.last_search = Some(sub.pattern.clone());
+        self.last_substitute = Some(sub.clone());
+    }
+
+    /// Run the last `:s` again on `range`. Like Vim, its flags are dropped
+    /// unless `keep_flags` (`:&&`).
+    fn repeat_substitute(&mut self, range: LineRange, keep_flags: bool) -> Result<(), String> {
+        let Some(mut sub) = self.last_substitute.clone() else {
+            return Err("No previous substitute regular expression".to_string());
+        };
+        if !keep_flags {
+            sub.global = false;
+        }
+        self.execute_substitute(&sub, range)
+    }
+
+    /// Apply a substitution to one line, returning the number of replacements made
+    fn substitute_line(&mut self, sub: &Substitute, line: usize) -> usize {
+        let content = self.buffer.get_line_content(line);
+        let Some(replaced) = sub.apply(&content) else {
+            return 0;
+        };
+        let count = if sub.global {
+            sub.pattern.find_iter(&content).count()
+        } else {
+            1
+        };
+        let end = Position::new(line, content.chars().count());
+        let _ = self
+            .buffer
+            .replace_range(Position::new(line, 0), end, &replaced);
+        count
+    }
+
+    /// Run `:{range}!cmd`: pipe the lines through `cmd` and replace them with its output.
+    /// The buffer is left untouched if the command fails.
+    fn execute_filter(&mut self, command: &str, range: LineRange) -> Result<(), String> {
+        if command.is_empty() {
+            return Err("No filter command given".to_string());
+        }
+
+        let mut input: String = (range.start..=range.end)
+            .map(|line| self.buffer.get_line_content(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        input.push('\n');
+
+        let output = crate::formatter::external::filter_text(command, &input)
+            .map_err(|e| format!("Filter failed: {}", e))?;
+        let output = output.strip_suffix('\n').unwrap_or(&output);
+
+        if output.is_empty() {
+            for line in (range.start..=range.end).rev() {
+                self.delete_ex_line(line);
+            }
+        } else {
+            let end = Position::new(range.end, self.line_char_count(range.end));
+            let _ = self
+                .buffer
+                .replace_range(Position::new(range.start, 0), end, output);
+        }
+
+        self.move_to_line_start(range.start);
+        self.notify_text_change();
+        self.status_message = Some(format!(
+            "{} lines filtered",
+            range.end - range.start + 1
+        ));
+        Ok(())
+    }
+
+    /// Run `:!cmd` without a range: show the command's output, leaving the buffer alone
+    fn execute_shell(&mut self, command: &str) -> Result<(), String> {
+        if command.is_empty() {
+            return Err("No shell command given".to_string());
+        }
+        let output = crate::formatter::external::filter_text(command, "")
+            .map_err(|e| format!("Shell command failed: {}", e))?;
+        let output = output.trim_end();
+        self.status_message = Some(if output.is_empty() {
+            format!("!{} finished", command)
+        } else {
+            output.lines().collect::<Vec<_>>().join(" | ")
+        });
+        Ok(())
+    }
+
+    /// Run `:sort` over `range`, keeping the newline after the last line as it was
+    fn execute_sort(&mut self, options: SortOptions, range: LineRange) {
+        let mut lines: Vec<String> = (range.start..=range.end)
+            .map(|line| self.buffer.get_line_content(line))
+            .collect();
+        ex::sort_lines(&mut lines, options);
+
+        let end_col = self.buffer.get_line_content(range.end).chars().count();
+        let _ = self.buffer.replace_range(
+            Position::new(range.start, 0),
+            Position::new(range.end, end_col),
+            &lines.join("\n"),
+        );
+        self.move_to_line_start(range.start);
+        self.notify_text_change();
+    }
+
+    /// Run `:retab` over `range`, rewriting only the lines whose blanks change
+    fn execute_retab(&mut self, options: RetabOptions, range: LineRange) {
+        for line in range.start..=range.end {
+            let content = self.buffer.get_line_content(line);
+            let retabbed = ex::retab_line(&content, options, self.options.tab_width);
+            if retabbed == content {
+                continue;
+            }
+            let end = Position::new(line, content.chars().count());
+            let _ = self
+                .buffer
+                .replace_range(Position::new(line, 0), end, &retabbed);
+        }
+        let pos = self.clamp_position(Position::new(self.cursor.line, self.cursor.col));
+        self.cursor.col = pos.col;
+        self.notify_text_change();
+    }
+
+    /// Run `:d` / `:y` over `range`, into `reg` or else the register a
+    /// delete or yank goes to
+    fn execute_line_command(&mut self, command: LineCommand, reg: Option<char>, range: LineRange) {
+        let count = range.end - range.start + 1;
+        let lines = format!("{} line{}", count, if count == 1 { "" } else { "s" });
+        match command {
+            LineCommand::Delete => {
+                self.delete_lines_to_register(range, reg.unwrap_or('"'));
+                self.status_message = Some(format!("Deleted {}", lines));
+            }
+            LineCommand::Yank => {
+                let text = self.buffer.get_lines(range.start, count);
+                self.yank_to_register(text, reg.unwrap_or('0'), true);
+                self.status_message = Some(format!("Yanked {}", lines));
+            }
+        }
+    }
+
+    /// Delete the lines of `range` into `reg` and put the cursor on the first
+    /// non-blank of the line that takes their place, for `dd`, `Vd` and `:d`
+    fn delete_lines_to_register(&mut self, range: LineRange, reg: char) {
+        let deleted = self.delete_line_range(range);
+        self.registers.yank_lines(deleted, reg);
+        self.move_to_line_start(range.start.min(self.buffer.last_content_line()));
+        self.notify_text_change();
+    }
+
+    /// Delete the lines of `range` and return their text. When they run to
+    /// the end of a buffer without a final newline, the line break before
+    /// them goes too, so no empty line is left behind.
+    fn delete_line_range(&mut self, range: LineRange) -> String {
+        let count = range.end - range.start + 1;
+        let text = self.buffer.get_lines(range.start, count);
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        if range.end >= last_line && range.start > 0 {
+            let prev_end = self.line_char_count(range.start - 1);
+            let end = self.line_char_count(last_line);
+            let _ = self
+                .buffer
+                .delete_range(Position::new(range.start - 1, prev_end), Position::new(last_line, end));
+        } else {
+            let _ = self.buffer.delete_lines(range.start, count);
+        }
+        text
+    }
+
+    /// Run `:g/pat/cmd` (or `:v`) over `range`
+    fn execute_global(&mut self, global: &Global, range: LineRange) -> Result<(), String> {
+        self.last_search = Some(global.pattern.clone());
+        let lines: Vec<usize> = (range.start..=range.end)
+            .filter(|&line| global.selects(&self.buffer.get_line_content(line)))
+            .collect();
+        if lines.is_empty() {
+            return Err(format!("Pattern not found: {}", global.pattern.as_str()));
+        }
+
+        // Edits are applied bottom-to-top so earlier line numbers stay valid
+        match global.command.as_str() {
+            "d" | "delete" => {
+                for &line in lines.iter().rev() {
+                    self.delete_ex_line(line);
+                }
+                self.status_message = Some(format!("{} fewer lines", lines.len()));
+            }
+            "p" | "print" => {
+                self.status_message = Some(format!("{} matching lines", lines.len()));
+                return Ok(());
+            }
+            command => {
+                let sub = match ex::parse_substitute(command, self.last_search.as_ref(), self.options.smart_case) {
+                    Some(sub) => sub?,
+                    None => return Err(format!("Not supported in :g: {}", command)),
+                };
+                self.remember_substitute(&sub);
+                let mut substitutions = 0;
+                let mut lines_changed = 0;
+                for &line in lines.iter().rev() {
+                    let count = self.substitute_line(&sub, line);
+                    if count > 0 {
+                        substitutions += count;
+                        lines_changed += 1;
+                    }
+                }
+                self.status_message = Some(format!(
+                    "{} substitutions on {} lines",
+                    substitutions, lines_changed
+                ));
+            }
+        }
+
+        self.move_to_line_start(lines[0]);
+        self.notify_text_change();
+        Ok(())
+    }
+
+    /// Delete a whole line for ex commands, consuming the preceding newline
+    /// when it's the last line so no empty line is left behind
+    fn delete_ex_line(&mut self, line: usize) {
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        if line == last_line && line > 0 {
+            let prev_end = self.buffer.get_line_content(line - 1).chars().count();
+            let end = self.buffer.get_line_content(line).chars().count();
+            let _ = self
+                .buffer
+                .delete_range(Position::new(line - 1, prev_end), Position::new(line, end));
+        } else {
+            let _ = self.buffer.delete_line(line);
+        }
+    }
+
+    /// Number of characters on `line`, excluding the newline
+    fn line_char_count(&self, line: usize) -> usize {
+        self.buffer.get_line_content(line).chars().count()
+    }
+
+    /// Swap the character under the cursor with the next one and move onto
+    /// it, like `xp`; on the last character, swap it with the one before. In
+    /// insert mode the pair is the characters around the cursor, as Emacs'
+    /// `C-t` does, and the cursor ends up after both.
+    fn transpose_chars(&mut self) {
+        let len = self.line_char_count(self.cursor.line);
+        let insert = self.mode == Mode::Insert;
+        if len < 2 || (insert && self.cursor.col == 0) {
+            return;
+        }
+        let first = if insert { self.cursor.col - 1 } else { self.cursor.col };
+        let first = first.min(len - 2);
+        if let Ok(true) = self.buffer.swap_chars(self.cursor.line, first) {
+            self.cursor.col = if insert { first + 2 } else { first + 1 };
+            self.notify_text_change();
+        }
+    }
+
+    /// Join `count` lines (at least two) starting at the cursor's line, as
+    /// many as there are. The cursor ends up where the last join happened.
+    fn join_lines(&mut self, count: usize, spaced: bool) {
+        let line = self.cursor.line;
+        let joins = (count.max(2) - 1).min(self.buffer.last_content_line().saturating_sub(line));
+        for _ in 0..joins {
+            let join_col = self.line_char_count(line);
+            let result = if spaced {
+                self.buffer.join_lines(line)
+            } else {
+                self.buffer.join_lines_no_space(line)
+            };
+            if result.is_err() {
+                break;
+            }
+            self.cursor.col = join_col.min(self.line_char_count(line).saturating_sub(1));
+        }
+        if joins > 0 {
+            self.notify_text_change();
+        }
+    }
+
+    /// Replace each character between `start` and `end` (exclusive) with `ch`,
+    /// leaving line breaks alone and the cursor at `start`
+    fn replace_selection(&mut self, start: Position, end: Position, ch: char) {
+        for line in start.line..=end.line {
+            let len = self.line_char_count(line);
+            let from = if line == start.line { start.col } else { 0 };
+            let to = if line == end.line { end.col.min(len) } else { len };
+            for col in from..to {
+                let _ = self.buffer.replace_char(line, col, ch);
+            }
+        }
+        self.cursor.line = start.line;
+        self.cursor.col = start.col;
+        self.notify_text_change();
+    }
+
+    /// Change case between `start` and `end` (exclusive), leaving the cursor at `start`
+    fn change_case(&mut self, start: Position, end: Position, case: CaseChange) {
+        if self.buffer.change_case(start, end, case).is_ok() {
+            self.cursor.line = start.line;
+            self.cursor.col = start.col;
+            self.notify_text_change();
+        }
+    }
+
+    /// Start and exclusive end of the active visual selection. Linewise
+    /// selections cover whole lines; charwise ones include the cursor character.
+    fn visual_selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.visual_start?;
+        let cursor = Position::new(self.cursor.line, self.cursor.col);
+        let range = crate::motion::Range::new(anchor, cursor).normalized();
+        if self.visual_line {
+            let end_col = self.line_char_count(range.end.line);
+            Some((
+                Position::new(range.start.line, 0),
+                Position::new(range.end.line, end_col),
+            ))
+        } else {
+            let end_col = (range.end.col + 1).min(self.line_char_count(range.end.line));
+            Some((range.start, Position::new(range.end.line, end_col)))
+        }
+    }
+
+    /// Size of the active visual selection for the status bar: lines for a
+    /// linewise selection, characters (line breaks included) otherwise
+    pub fn selection_size(&self) -> Option<String> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        let (start, end) = self.visual_selection()?;
+        let plural = |count: usize, unit: &str| {
+            format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+        };
+        Some(if self.visual_line {
+            plural(end.line - start.line + 1, "line")
+        } else {
+            let chars = self.buffer.position_to_char(end) - self.buffer.position_to_char(start);
+            plural(chars, "char")
+        })
+    }
+
+    /// Lines covered by the active visual selection
+    fn visual_line_range(&self) -> Option<LineRange> {
+        self.visual_start
+            .map(|start| LineRange::new(start.line, self.cursor.line))
+    }
+
+    /// Drop keys typed towards a command, then close whatever Escape should
+    /// close first: the completion popup, the code action menu, hover
+    /// information, the fuzzy finder, the visual selection and then extra
+    /// cursors, in that order. Only one of them goes per press.
+    pub fn escape(&mut self) {
+        self.vim_parser.reset();
+        if self.completion_popup.is_visible() {
+            self.completion_popup.hide();
+        } else if self.code_actions.is_some() {
+            self.hide_code_actions();
+        } else if self.quickfix_open {
+            self.quickfix_open = false;
+        } else if self.hover_content.is_some() {
+            self.hide_hover();
+        } else if self.fuzzy_search.is_some() {
+            self.fuzzy_search = None;
+            if self.mode == Mode::FuzzySearch {
+                self.mode = Mode::Normal;
+            }
+        } else if self.mode == Mode::Visual {
+            self.exit_visual();
+            self.mode = Mode::Normal;
+        } else {
+            self.cursors.clear();
+        }
+    }
+
+    /// Leave visual mode, remembering the selection for `'<,'>`
+    fn exit_visual(&mut self) {
+        if let Some(start) = self.visual_start.take() {
+            let end = Position::new(self.cursor.line, self.cursor.col);
+            self.last_visual = Some((start, end));
+        }
+    }
+
+    /// Put the cursor on the first non-blank of `line`, clamped to the buffer
+    fn move_to_line_start(&mut self, line: usize) {
+        self.cursor.line = line.min(self.buffer.line_count().saturating_sub(1));
+        self.cursor.col =
+            crate::motion::first_non_blank(&self.buffer, Position::new(self.cursor.line, 0)).col;
+    }
+
+    /// Get command line display text
+    pub fn get_command_line_display(&self) -> String {
+        if self.mode == Mode::Command {
+            format!(":{}", self.command_line)
+        } else {
+            String::new()
+        }
+    }
+
+    fn open_fuzzy_search(&mut self) {
+        let mut fuzzy_state = FuzzySearchState::new();
+        fuzzy_state.smart_case = self.options.smart_case;
+        fuzzy_state.current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+        // Scan directory and populate items
+        fuzzy_state.rescan_current_directory();
+
+        self.fuzzy_search = Some(fuzzy_state);
+        self.mode = Mode::FuzzySearch;
+    }
+
+    /// Open the fuzzy search over recently opened files instead of the current directory
+    fn open_recent_files(&mut self) {
+        let files = self.recent_files.existing();
+        if files.is_empty() {
+            self.status_message = Some("No recent files".to_string());
+            return;
+        }
+
+        let mut fuzzy_state = FuzzySearchState::new_recent(files);
+        fuzzy_state.smart_case = self.options.smart_case;
+
+        fuzzy_state.current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        fuzzy_state.rescan_current_directory();
+
+        self.fuzzy_search = Some(fuzzy_state);
+        self.mode = Mode::FuzzySearch;
+    }
+
+    /// Open the current file's companion, such as the header of a C source.
+    /// Several candidates are offered in the file picker instead.
+    fn open_alternate_file(&mut self) {
+        let Some(path) = self.buffer.file_path.clone() else {
+            self.status_message = Some("No file name".to_string());
+            return;
+        };
+        let candidates = crate::alternate::alternate_files(
+            std::path::Path::new(&path),
+            &self.options.alternate_extensions,
+        );
+        match candidates.as_slice() {
+            [] => self.status_message = Some(format!("No alternate file for {}", path)),
+            [alternate] => {
+                let alternate = alternate.to_string_lossy().to_string();
+                if let Err(e) = self.open_file(&alternate) {
+                    self.show_error(format!("Failed to open {}: {}", alternate, e));
+                }
+            }
+            _ => {
+                let mut fuzzy_state = FuzzySearchState::new_recent(candidates);
+                fuzzy_state.smart_case = self.options.smart_case;
+                fuzzy_state.current_path =
+                    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                fuzzy_state.rescan_current_directory();
+
+                self.fuzzy_search = Some(fuzzy_state);
+                self.mode = Mode::FuzzySearch;
+            }
+        }
+    }
+
+    /// Open the picker over the language server's workspace symbols
+    fn open_workspace_symbols(&mut self) {
+        if self.current_language.is_none() {
+            self.status_message = Some("No language server for this file type".to_string());
+            return;
+        }
+        let mut fuzzy_state = FuzzySearchState::new_symbols();
+        fuzzy_state.smart_case = self.options.smart_case;
+        fuzzy_state.current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        self.fuzzy_search = Some(fuzzy_state);
+        self.mode = Mode::FuzzySearch;
+    }
+
+    /// Start fuzzy search in a specific directory
+    pub fn start_fuzzy_search_in_dir(&mut self, dir_path: &std::path::Path) {
+        let mut fuzzy_state = FuzzySearchState::new_in_directory(dir_path);
+        fuzzy_state.smart_case = self.options.smart_case;
+
+        // Scan directory and populate items
+        fuzzy_state.rescan_current_directory();
+
+        self.fuzzy_search = Some(fuzzy_state);
+        self.mode = Mode::FuzzySearch;
+    }
+}
+
+/// Entries for the symbol picker from a `workspace/symbol` answer. Symbols
+/// outside local files are left out.
+fn symbol_items(response: lsp_types::WorkspaceSymbolResponse) -> Vec<SymbolItem> {
+    let symbols: Vec<_> = match response {
+        lsp_types::WorkspaceSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .map(|s| (s.name, s.kind, s.container_name, s.location.uri, s.location.range.start))
+            .collect(),
+        lsp_types::WorkspaceSymbolResponse::Nested(symbols) => symbols
+            .into_iter()
+            .map(|s| {
+                // Servers may leave the range to be resolved later
+                let (uri, start) = match s.location {
+                    lsp_types::OneOf::Left(location) => (location.uri, location.range.start),
+                    lsp_types::OneOf::Right(location) => (location.uri, Default::default()),
+                };
+                (s.name, s.kind, s.container_name, uri, start)
+            })
+            .collect(),
+    };
+    symbols
+        .into_iter()
+        .filter_map(|(name, kind, container, uri, start)| {
+            Some(SymbolItem {
+                name,
+                kind: symbol_kind_name(kind),
+                container: container.filter(|c| !c.is_empty()),
+                path: crate::lsp::workspace_edit::file_path(&uri).ok()?,
+                line: start.line as usize,
+                character: start.character as usize,
+            })
+        })
+        .collect()
+}
+
+/// Lowercase words for a symbol kind, such as `enum member`
+fn symbol_kind_name(kind: lsp_types::SymbolKind) -> String {
+    // Debug prints the kind in PascalCase
+    let mut name = String::new();
+    for c in format!("{:?}", kind).chars() {
+        if c.is_uppercase() && !name.is_empty() {
+            name.push(' ');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name
+}
+
+/// Lines for the hover window; none when the server had nothing to show
+fn hover_lines(hover: Option<lsp_types::Hover>) -> Vec<String> {
+    hover
+        .map(|hover| crate::ui::widgets::hover::hover_lines(&hover.contents))
+        .unwrap_or_default()
+}
+
+/// Lowercase name of a diagnostic's severity, as shown before its message
+fn severity_label(diagnostic: &Diagnostic) -> &'static str {
+    match diagnostic.severity {
+        Some(lsp_types::DiagnosticSeverity::ERROR) => "error",
+        Some(lsp_types::DiagnosticSeverity::WARNING) => "warning",
+        Some(lsp_types::DiagnosticSeverity::HINT) => "hint",
+        _ => "info",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_down_in_empty_buffer() {
+        let mut editor = Editor::new();
+        // Clear buffer to make it truly empty
+        editor.buffer.rope = ropey::Rope::from("");
+
+        // Try to move down - should not crash
+        editor.execute_command(Command::MoveDown);
+        assert_eq!(editor.cursor.line, 0);
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_move_up_from_top() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::MoveUp);
+        assert_eq!(editor.cursor.line, 0);
+    }
+
+    #[test]
+    fn test_move_left_from_start() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::MoveLeft);
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_move_right_at_end_of_line() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.insert_char('b', 0, 1).unwrap();
+        editor.cursor.col = 2; // Move to end
+
+        editor.execute_command(Command::MoveRight);
+        assert_eq!(editor.cursor.col, 2);
+    }
+
+    #[test]
+    fn test_move_down_to_last_line() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.insert_char('\n', 0, 1).unwrap();
+        editor.buffer.insert_char('b', 1, 0).unwrap();
+
+        editor.cursor.line = 0;
+        editor.execute_command(Command::MoveDown);
+        assert_eq!(editor.cursor.line, 1);
+    }
+
+    #[test]
+    fn test_delete_char_from_start() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::DeleteChar);
+        // Should not crash when deleting at col 0
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_insert_mode_movement() {
+        let mut editor = Editor::new();
+        editor.mode = Mode::Insert;
+        editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.insert_char('b', 0, 1).unwrap();
+
+        editor.cursor.col = 0;
+        editor.execute_command(Command::MoveRight);
+        assert_eq!(editor.cursor.col, 1);
+
+        editor.execute_command(Command::MoveLeft);
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_viewport_scrolling_on_move() {
+        let mut editor = Editor::new();
+        // Add many lines
+        for i in 0..30 {
+            if i > 0 {
+                editor.buffer.insert_char('\n', 0, 0).unwrap();
+            }
+            editor.buffer.insert_char('a', 0, 0).unwrap();
+        }
+
+        // Set viewport smaller than content
+        editor.viewport.rows = 10;
+        editor.viewport.cols = 80;
+
+        // Move cursor to line 25
+        editor.cursor.line = 25;
+        editor
+            .viewport
+            .scroll_to_cursor(editor.cursor.line, editor.cursor.col);
+
+        // Offset should be adjusted to keep cursor visible
+        assert!(editor.cursor.line >= editor.viewport.offset_line);
+        assert!(editor.cursor.line < editor.viewport.offset_line + editor.viewport.rows);
+    }
+
+    #[test]
+    fn test_cursor_col_adjustment_after_text_change() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.insert_char('b', 0, 1).unwrap();
+        editor.buffer.insert_char('c', 0, 2).unwrap();
+
+        editor.cursor.col = 3; // Beyond line length
+
+        // Text change should clamp cursor
+        editor.notify_text_change();
+        let line_len = editor.buffer.line_len(0);
+        assert!(editor.cursor.col <= line_len);
+    }
+
+    #[test]
+    fn test_multiple_rapid_movements() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.insert_char('b', 0, 1).unwrap();
+        editor.buffer.insert_char('c', 0, 2).unwrap();
+        editor.buffer.insert_char('d', 0, 3).unwrap();
+
+        // Rapid movements should not overflow
+        for _ in 0..100 {
+            editor.execute_command(Command::MoveRight);
+        }
+        assert_eq!(editor.cursor.col, 4); // Should be clamped to line length
+
+        for _ in 0..100 {
+            editor.execute_command(Command::MoveLeft);
+        }
+        assert_eq!(editor.cursor.col, 0); // Should be clamped to 0
+    }
+
+    fn feed_keys(editor: &mut Editor, keys: &str) {
+        use crate::vim_parser::ParseResult;
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        for c in keys.chars() {
+            let key = match c {
+                '\x1b' => KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+                _ => KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+            };
+            let result = if editor.mode == Mode::Visual {
+                editor.vim_parser.process_visual_key(key)
+            } else {
+                editor.vim_parser.process_key(key)
+            };
+            if let ParseResult::Command(cmd) = result {
+                editor.execute_command(cmd);
+            }
+        }
+    }
+
+    #[test]
+    fn test_append_linewise_yanks_and_paste() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo\nthree", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "\"ayy");
+        feed_keys(&mut editor, "jj\"Ayy");
+        assert_eq!(editor.registers.get('a'), Some("one\nthree\n"));
+
+        editor.cursor.line = 0;
+        feed_keys(&mut editor, "\"ap");
+        assert_eq!(editor.buffer.rope.to_string(), "one\none\nthree\ntwo\nthree");
+        assert_eq!(editor.cursor.line, 1);
+    }
+
+    #[test]
+    fn test_append_charwise_yank_to_linewise_register() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("alpha beta\ngamma", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "\"byy");
+        feed_keys(&mut editor, "\"Byw");
+        assert_eq!(editor.registers.get('b'), Some("alpha beta\nalpha \n"));
+        assert!(editor.registers.is_linewise('b'));
+    }
+
+    #[test]
+    fn test_paste_linewise_below_last_line() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\nb", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        feed_keys(&mut editor, "yyp");
+        assert_eq!(editor.buffer.rope.to_string(), "a\nb\nb");
+        assert_eq!(editor.cursor.line, 2);
+    }
+
+    fn run_ex(editor: &mut Editor, command: &str) -> bool {
+        editor.enter_command_mode();
+        editor.command_line = command.to_string();
+        editor.handle_command_input('\n').unwrap()
+    }
+
+    #[test]
+    fn test_resize_to_no_rows() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo\nthree\n", 0, 0).unwrap();
+        editor.handle_resize(0, 0);
+        assert_eq!(editor.viewport.rows, 0);
+        feed_keys(&mut editor, "jjk");
+        assert_eq!(editor.cursor.line, 1);
+    }
+
+    #[test]
+    fn test_goto_line_command() {
+        let mut editor = Editor::new();
+        let text: String = (0..50).map(|i| format!("    line {}\n", i)).collect();
+        editor.buffer.insert_text(&text, 0, 0).unwrap();
+        editor.handle_resize(10, 80);
+
+        run_ex(&mut editor, "42");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (41, 4));
+        assert!(editor.viewport.offset_line <= 41);
+        assert!(editor.viewport.offset_line + editor.viewport.rows > 41);
+
+        run_ex(&mut editor, "-10");
+        assert_eq!(editor.cursor.line, 31);
+        run_ex(&mut editor, "+");
+        assert_eq!(editor.cursor.line, 32);
+
+        // Past either end goes to the first or last line with text
+        run_ex(&mut editor, "999");
+        assert_eq!(editor.cursor.line, 49);
+        run_ex(&mut editor, "0");
+        assert_eq!(editor.cursor.line, 0);
+    }
+
+    #[test]
+    fn test_goto_percent_and_matching_pair() {
+        let mut editor = Editor::new();
+        let text: String = (0..200).map(|i| format!("  f(x[{}]);\n", i)).collect();
+        editor.buffer.insert_text(&text, 0, 0).unwrap();
+        editor.handle_resize(10, 80);
+
+        feed_keys(&mut editor, "50%");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (99, 2));
+        assert!(editor.viewport.offset_line <= 99);
+        assert!(editor.viewport.offset_line + editor.viewport.rows > 99);
+        feed_keys(&mut editor, "1%");
+        assert_eq!(editor.cursor.line, 1);
+        feed_keys(&mut editor, "150%");
+        assert_eq!(editor.cursor.line, 199);
+        feed_keys(&mut editor, "gg100000000000000000%");
+        assert_eq!(editor.cursor.line, 199);
+
+        // Without a count, from before the first bracket to its match and back
+        editor.cursor.col = 0;
+        feed_keys(&mut editor, "%");
+        assert_eq!(editor.cursor.col, 10);
+        feed_keys(&mut editor, "%");
+        assert_eq!(editor.cursor.col, 3);
+    }
+
+    #[test]
+    fn test_user_commands() {
+        let mut editor = Editor::new();
+        let config: crate::config::TextyConfig =
+            toml::from_str("[commands]\nSub = \"s/a/z/\"\nbad = \"d\"").unwrap();
+        editor.apply_config(&config);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Invalid user command name in config: bad")
+        );
+        editor.buffer.insert_text("a\nb\nc\n", 0, 0).unwrap();
+
+        run_ex(&mut editor, "command Del d");
+        run_ex(&mut editor, "2,3Del x");
+        assert_eq!(editor.buffer.rope, "a\n");
+        assert_eq!(editor.registers.get('x'), Some("b\nc\n"));
+
+        run_ex(&mut editor, "command! Del Sub");
+        run_ex(&mut editor, "Del");
+        assert_eq!(editor.buffer.rope, "z\n");
+
+        run_ex(&mut editor, "command");
+        let listing = editor.listing.take().unwrap();
+        assert_eq!(listing.lines.len(), 2);
+        assert!(listing.lines[0].starts_with("Del "));
+
+        run_ex(&mut editor, "command Again Again");
+        run_ex(&mut editor, "Again");
+        let status = editor.status_message.as_deref().unwrap_or_default();
+        assert!(status.starts_with("User commands nested too deeply"));
+        run_ex(&mut editor, "delcommand Again");
+        assert!(!editor.command_aliases.contains_key("Again"));
+    }
+
+    #[test]
+    fn test_global_delete() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("keep\nTODO one\nkeep too\nTODO two", 0, 0).unwrap();
+
+        run_ex(&mut editor, "g/TODO/d");
+        assert_eq!(editor.buffer.rope.to_string(), "keep\nkeep too");
+        assert_eq!(editor.status_message.as_deref(), Some("2 fewer lines"));
+    }
+
+    #[test]
+    fn test_global_substitute_and_invert() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a1\nb1\na2", 0, 0).unwrap();
+
+        run_ex(&mut editor, "g/^a/s/\\d/N/");
+        assert_eq!(editor.buffer.rope.to_string(), "aN\nb1\naN");
+
+        run_ex(&mut editor, "v/^a/d");
+        assert_eq!(editor.buffer.rope.to_string(), "aN\naN");
+    }
+
+    #[test]
+    fn test_global_pattern_not_found() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("abc", 0, 0).unwrap();
+
+        run_ex(&mut editor, "g/xyz/d");
+        assert_eq!(editor.buffer.rope.to_string(), "abc");
+        assert_eq!(editor.status_message.as_deref(), Some("Pattern not found: xyz"));
+    }
+
+    #[test]
+    fn test_substitute_whole_buffer() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("foo foo\nbar\nfoo", 0, 0).unwrap();
+
+        run_ex(&mut editor, "%s/foo/baz/g");
+        assert_eq!(editor.buffer.rope.to_string(), "baz baz\nbar\nbaz");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("3 substitutions on 2 lines")
+        );
+    }
+
+    #[test]
+    fn test_repeat_substitute() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a a\na a\na a", 0, 0).unwrap();
+        editor.execute_command(Command::RepeatSubstitute);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No previous substitute regular expression")
+        );
+
+        run_ex(&mut editor, "s/a/b/g");
+        assert_eq!(editor.buffer.rope.to_string(), "b b\na a\na a");
+
+        // `&` drops the `g` flag, `:&&` keeps it
+        editor.cursor.line = 1;
+        feed_keys(&mut editor, "&");
+        assert_eq!(editor.buffer.rope.to_string(), "b b\nb a\na a");
+        run_ex(&mut editor, "3&&");
+        assert_eq!(editor.buffer.rope.to_string(), "b b\nb a\nb b");
+    }
+
+    #[test]
+    fn test_substitute_empty_pattern() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\ntwo one", 0, 0).unwrap();
+        run_ex(&mut editor, "s//x/");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No previous regular expression")
+        );
+
+        run_ex(&mut editor, "s/one/1/");
+        run_ex(&mut editor, "2s//ONE/");
+        assert_eq!(editor.buffer.rope.to_string(), "1 two\ntwo ONE");
+
+        // `:g` sets the pattern too
+        run_ex(&mut editor, "g/two/s//2/");
+        assert_eq!(editor.buffer.rope.to_string(), "1 2\n2 ONE");
+    }
+
+    #[test]
+    fn test_sort_whole_buffer() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("pear\napple\nfig\napple\n", 0, 0).unwrap();
+        editor.cursor.line = 3;
+
+        run_ex(&mut editor, "sort u");
+        assert_eq!(editor.buffer.rope.to_string(), "apple\nfig\npear\n");
+        assert_eq!(editor.cursor.line, 0);
+    }
+
+    #[test]
+    fn test_retab() {
+        let mut editor = Editor::new();
+        editor.options.tab_width = 4;
+        editor
+            .buffer
+            .insert_text("\tif x {\n\t\ty = \"\t\";\n\t}\n", 0, 0)
+            .unwrap();
+
+        run_ex(&mut editor, "retab");
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "    if x {\n        y = \"\t\";\n    }\n"
+        );
+
+        run_ex(&mut editor, "2,3retab!");
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "    if x {\n\t\ty = \"\t\";\n\t}\n"
+        );
+
+        run_ex(&mut editor, "retab a");
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "    if x {\n        y = \"   \";\n    }\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_range_reverse_numeric() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("header\nv2\nv10\nv1\nfooter", 0, 0).unwrap();
+
+        run_ex(&mut editor, "2,4sort! n");
+        assert_eq!(editor.buffer.rope.to_string(), "header\nv10\nv2\nv1\nfooter");
+        assert_eq!(editor.cursor.line, 1);
+    }
+
+    #[test]
+    fn test_sort_visual_selection() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("z\nc\nb\na", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        feed_keys(&mut editor, "Vj:");
+        assert_eq!(editor.mode, Mode::Command);
+        assert_eq!(editor.command_line, "'<,'>");
+        for c in "sort\n".chars() {
+            editor.handle_command_input(c).unwrap();
+        }
+        assert_eq!(editor.buffer.rope.to_string(), "z\nb\nc\na");
+    }
+
+    #[test]
+    fn test_visual_indent_keeps_selection() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\nb\nc\nd", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        feed_keys(&mut editor, "Vj>");
+        assert_eq!(editor.buffer.rope.to_string(), "a\n    b\n    c\nd");
+        assert_eq!(editor.mode, Mode::Visual);
+
+        feed_keys(&mut editor, ">");
+        assert_eq!(editor.buffer.rope.to_string(), "a\n        b\n        c\nd");
+
+        feed_keys(&mut editor, "<\x1b");
+        assert_eq!(editor.buffer.rope.to_string(), "a\n    b\n    c\nd");
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_visual_indent_repeats_with_dot() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\nb\nc", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "Vj>\x1b");
+        editor.cursor.line = 1;
+        feed_keys(&mut editor, ".");
+        assert_eq!(editor.buffer.rope.to_string(), "    a\n        b\n    c");
+    }
+
+    #[test]
+    fn test_shift_line_repeat_with_dot() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("x\ny", 0, 0).unwrap();
+
+        feed_keys(&mut editor, ">>..");
+        assert_eq!(editor.buffer.rope.to_string(), "            x\ny");
+        feed_keys(&mut editor, "<<");
+        assert_eq!(editor.buffer.rope.to_string(), "        x\ny");
+        assert_eq!(editor.last_change, Some(Command::UnindentLine(1)));
+    }
+
+    #[test]
+    fn test_shift_respects_expand_tab() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("\t  x", 0, 0).unwrap();
+        editor.options.expand_tab = false;
+        editor.options.tab_width = 4;
+
+        feed_keys(&mut editor, ">>");
+        assert_eq!(editor.buffer.rope.to_string(), "\t\t  x");
+        editor.options.tab_width = 2;
+        feed_keys(&mut editor, "<<");
+        assert_eq!(editor.buffer.rope.to_string(), "\t\tx");
+    }
+
+    #[test]
+    fn test_toggle_case_char_advances() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("aB-ç", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "~~~~");
+        assert_eq!(editor.buffer.rope.to_string(), "Ab-Ç");
+        assert_eq!(editor.cursor.col, 3);
+
+        editor.cursor.col = 0;
+        feed_keys(&mut editor, "3~");
+        assert_eq!(editor.buffer.rope.to_string(), "aB-Ç");
+        assert_eq!(editor.cursor.col, 3);
+    }
+
+    #[test]
+    fn test_transpose_chars() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("abcd\nx", 0, 0).unwrap();
+
+        editor.cursor.col = 1;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.get_line_content(0), "acbd");
+        assert_eq!(editor.cursor.col, 2);
+
+        // On the last character, swap with the one before and stay
+        editor.cursor.col = 3;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.get_line_content(0), "acdb");
+        assert_eq!(editor.cursor.col, 3);
+
+        // A single character has nothing to swap with
+        editor.cursor.line = 1;
+        editor.cursor.col = 0;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.rope.to_string(), "acdb\nx");
+
+        // The swap is one undo step and leaves the registers alone
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.get_line_content(0), "acbd");
+        assert_eq!(editor.registers.get('"'), Some(""));
+    }
+
+    #[test]
+    fn test_transpose_chars_in_insert_mode() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("teh", 0, 0).unwrap();
+        editor.execute_command(Command::InsertMode(1));
+
+        // At the end of the line, swap the last two
+        editor.cursor.col = 3;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.rope.to_string(), "the");
+        assert_eq!(editor.cursor.col, 3);
+
+        editor.cursor.col = 1;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.rope.to_string(), "hte");
+        assert_eq!(editor.cursor.col, 2);
+
+        editor.cursor.col = 0;
+        editor.execute_command(Command::TransposeChars);
+        assert_eq!(editor.buffer.rope.to_string(), "hte");
+    }
+
+    #[test]
+    fn test_case_operators_with_motions() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("hello Wörld\nMixed Case", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "gUw");
+        assert_eq!(editor.buffer.get_line_content(0), "HELLO Wörld");
+
+        feed_keys(&mut editor, "wgU$");
+        assert_eq!(editor.buffer.get_line_content(0), "HELLO WÖRLD");
+        assert_eq!(editor.cursor.col, 6);
+
+        feed_keys(&mut editor, "jg~~");
+        assert_eq!(editor.buffer.get_line_content(1), "mIXED cASE");
+
+        feed_keys(&mut editor, "kguiw");
+        assert_eq!(editor.buffer.get_line_content(0), "HELLO wörld");
+    }
+
+    #[test]
+    fn test_case_to_word_end_is_inclusive() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("ab cd.ef gh", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "gUe");
+        assert_eq!(editor.buffer.get_line_content(0), "AB cd.ef gh");
+        assert_eq!(editor.cursor.col, 0);
+
+        // From a blank, `e` goes on through the next word
+        feed_keys(&mut editor, "llgUe");
+        assert_eq!(editor.buffer.get_line_content(0), "AB CD.ef gh");
+        assert_eq!(editor.cursor.col, 2);
+
+        feed_keys(&mut editor, "$b.");
+        assert_eq!(editor.buffer.get_line_content(0), "AB CD.ef GH");
+        feed_keys(&mut editor, "0gue");
+        assert_eq!(editor.buffer.get_line_content(0), "ab CD.ef GH");
+    }
+
+    #[test]
+    fn test_case_line_with_count_and_dot() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("Ab\nCd\nEf", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "2guu");
+        assert_eq!(editor.buffer.rope.to_string(), "ab\ncd\nEf");
+
+        feed_keys(&mut editor, "jj.");
+        assert_eq!(editor.buffer.rope.to_string(), "ab\ncd\nef");
+    }
+
+    #[test]
+    fn test_toggle_comment_per_language() {
+        for (language, token) in [
+            (LanguageId::Rust, "//"),
+            (LanguageId::Python, "#"),
+            (LanguageId::JavaScript, "//"),
+            (LanguageId::TypeScript, "//"),
+        ] {
+            let mut editor = Editor::new();
+            editor.current_language = Some(language);
+            editor.buffer.insert_text("a\n  b\n\nc", 0, 0).unwrap();
+
+            feed_keys(&mut editor, "gcc");
+            assert_eq!(editor.buffer.rope, format!("{token} a\n  b\n\nc"));
+            feed_keys(&mut editor, "gcc");
+            assert_eq!(editor.buffer.rope, "a\n  b\n\nc");
+            feed_keys(&mut editor, "gc}");
+            assert_eq!(editor.buffer.rope, format!("{token} a\n  {token} b\n\nc"));
+            feed_keys(&mut editor, "gc}");
+            assert_eq!(editor.buffer.rope, "a\n  b\n\nc");
+            feed_keys(&mut editor, "jgcG");
+            assert_eq!(
+                editor.buffer.rope,
+                format!("a\n  {token} b\n\n{token} c")
+            );
+        }
+    }
+
+    #[test]
+    fn test_toggle_comment_selection_and_repeat() {
+        let mut editor = Editor::new();
+        editor.current_language = Some(LanguageId::Python);
+        editor.buffer.insert_text("# x = 1\ny = 2\nz = 3\nw = 4", 0, 0).unwrap();
+
+        // A mix of commented and plain lines is commented as a whole
+        feed_keys(&mut editor, "Vjgc");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.buffer.rope, "# # x = 1\n# y = 2\nz = 3\nw = 4");
+
+        feed_keys(&mut editor, "jj.");
+        assert_eq!(editor.buffer.rope, "# # x = 1\n# y = 2\n# z = 3\n# w = 4");
+
+        editor.current_language = None;
+        feed_keys(&mut editor, "gcc");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No comment syntax for this file type")
+        );
+    }
+
+    #[test]
+    fn test_reindent_from_syntax_tree() {
+        let mut editor = Editor::new();
+        editor.current_language = Some(LanguageId::Rust);
+        editor.options.tab_width = 2;
+        editor
+            .buffer
+            .insert_text("fn f() {\nif x {\n  a();\n      }\n}\n", 0, 0)
+            .unwrap();
+        editor.cursor.line = 1;
+
+        feed_keys(&mut editor, "=j");
+        assert_eq!(editor.buffer.rope, "fn f() {\n  if x {\n    a();\n      }\n}\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 2));
+        feed_keys(&mut editor, "j.");
+        assert_eq!(editor.buffer.rope, "fn f() {\n  if x {\n    a();\n  }\n}\n");
+
+        editor.options.expand_tab = false;
+        run_ex(&mut editor, "reindent");
+        assert_eq!(editor.buffer.rope, "fn f() {\n\tif x {\n\t\ta();\n\t}\n}\n");
+        assert_eq!(editor.status_message.as_deref(), Some("Reindented 3 lines"));
+
+        // A partial selection is reindented rather than formatted
+        editor.options.expand_tab = true;
+        editor.cursor.line = 0;
+        feed_keys(&mut editor, "Vj=");
+        assert_eq!(editor.buffer.rope, "fn f() {\n  if x {\n\t\ta();\n\t}\n}\n");
+
+        editor.current_language = None;
+        feed_keys(&mut editor, "=G");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No indent rules for this file type")
+        );
+    }
+
+    #[test]
+    fn test_multiline_text_objects() {
+        let mut editor = Editor::new();
+        editor
+            .buffer
+            .insert_text("fn f() {\n    a();\n    b(1, 2);\n}\n", 0, 0)
+            .unwrap();
+        editor.cursor.line = 2;
+        editor.cursor.col = 7;
+
+        feed_keys(&mut editor, "di(");
+        assert_eq!(editor.buffer.get_line_content(2), "    b();");
+        assert_eq!(editor.registers.get('"'), Some("1, 2"));
+        // An empty object succeeds without deleting anything
+        feed_keys(&mut editor, "di(");
+        assert_eq!(editor.buffer.get_line_content(2), "    b();");
+
+        feed_keys(&mut editor, "yi{");
+        assert_eq!(editor.registers.get('0'), Some("    a();\n    b();\n"));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+
+        feed_keys(&mut editor, "ci{");
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.buffer.rope, "fn f() {\n\n}\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn test_indent_text_object_with_operators_and_visual() {
+        let mut editor = Editor::new();
+        let text = "def f():\n    a = 1\n    b = 2\nx = 3\n";
+        editor.buffer.insert_text(text, 0, 0).unwrap();
+        editor.cursor.line = 2;
+
+        feed_keys(&mut editor, "yii");
+        assert_eq!(editor.registers.get('0'), Some("    a = 1\n    b = 2\n"));
+        assert!(editor.registers.is_linewise('0'));
+        assert_eq!(editor.status_message.as_deref(), Some("Yanked 2 lines"));
+
+        feed_keys(&mut editor, "vii");
+        assert_eq!(editor.selection_size().as_deref(), Some("2 lines"));
+        feed_keys(&mut editor, "\x1bvai");
+        assert_eq!(editor.selection_size().as_deref(), Some("3 lines"));
+        feed_keys(&mut editor, "\x1b");
+
+        feed_keys(&mut editor, "dai");
+        assert_eq!(editor.buffer.rope, "x = 3\n");
+        assert_eq!(editor.registers.get('"'), Some(text.strip_suffix("x = 3\n").unwrap()));
+    }
+
+    #[test]
+    fn test_change_inside_quotes() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("let s = \"old\";", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "ci\"");
+        for c in "new".chars() {
+            editor.execute_command(Command::InsertChar(c));
+        }
+        editor.execute_command(Command::NormalMode);
+        assert_eq!(editor.buffer.rope, "let s = \"new\";");
+        editor.cursor.col = 0;
+        feed_keys(&mut editor, "da\"");
+        assert_eq!(editor.buffer.rope, "let s = ;");
+    }
+
+    #[test]
+    fn test_show_char_info() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("é\t\u{200b}", 0, 0).unwrap();
+        let version = editor.buffer.version;
+
+        feed_keys(&mut editor, "ga");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("<é> 233, U+00E9, UTF-8 c3 a9")
+        );
+        editor.cursor.col = 1;
+        run_ex(&mut editor, "ascii");
+        assert_eq!(editor.status_message.as_deref(), Some("<^I> 9, U+0009, UTF-8 09"));
+        editor.cursor.col = 2;
+        run_ex(&mut editor, "as");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("<\u{200b}> 8203, U+200B, UTF-8 e2 80 8b")
+        );
+        assert_eq!(editor.buffer.version, version);
+
+        editor.buffer.file_path = None;
+        run_ex(&mut editor, "enew!");
+        feed_keys(&mut editor, "ga");
+        assert_eq!(editor.status_message.as_deref(), Some("NUL"));
+    }
+
+    #[test]
+    fn test_show_counts() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\nthrée four.\n", 0, 0).unwrap();
+        editor.cursor.line = 1;
+        editor.cursor.col = 6;
+
+        run_ex(&mut editor, "count");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Col 7 of 11; Line 2 of 2; Word 4 of 5; Char 15 of 20; Byte 16 of 21")
+        );
+
+        editor.cursor.line = 0;
+        editor.cursor.col = 4;
+        feed_keys(&mut editor, "vj");
+        editor.execute_command(Command::ShowCounts);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Selected 2 of 2 Lines; 2 of 5 Words; 9 of 20 Chars; 10 of 21 Bytes")
+        );
+
+        run_ex(&mut editor, "enew!");
+        run_ex(&mut editor, "count");
+        assert_eq!(editor.status_message.as_deref(), Some("--No lines in buffer--"));
+    }
+
+    #[test]
+    fn test_visual_case_change() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\nthree", 0, 0).unwrap();
+        editor.cursor.col = 4;
+
+        feed_keys(&mut editor, "vlU");
+        assert_eq!(editor.buffer.get_line_content(0), "one TWo");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.cursor.col, 4);
+
+        feed_keys(&mut editor, "Vj~");
+        assert_eq!(editor.buffer.rope.to_string(), "ONE twO\nTHREE");
+    }
+
+    #[test]
+    fn test_join_lines_counts() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\n  b\nc\nd\n  e\nf\n", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "J");
+        assert_eq!(editor.buffer.rope.to_string(), "a b\nc\nd\n  e\nf\n");
+        assert_eq!(editor.cursor.col, 1);
+
+        feed_keys(&mut editor, "3J");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c d\n  e\nf\n");
+        assert_eq!(editor.cursor.col, 5);
+
+        feed_keys(&mut editor, "jgJ");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c d\n  ef\n");
+
+        // A count past the end joins what is left
+        editor.cursor.line = 0;
+        feed_keys(&mut editor, "9J");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c d ef\n");
+    }
+
+    #[test]
+    fn test_visual_join_joins_the_selected_lines() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\n  b\nc\nd\n  e\nf\n", 0, 0).unwrap();
+        editor.cursor.line = 2;
+
+        // Selected upwards; the join starts at the top line
+        feed_keys(&mut editor, "vkkJ");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c\nd\n  e\nf\n");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 3));
+
+        // One selected line joins with the next, like `J`
+        feed_keys(&mut editor, "jVJ");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c\nd e\nf\n");
+
+        feed_keys(&mut editor, "VjgJ");
+        assert_eq!(editor.buffer.rope.to_string(), "a b c\nd ef\n");
+    }
+
+    #[test]
+    fn test_visual_replace_across_lines() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\nthree\nfour five\n", 0, 0).unwrap();
+        editor.cursor.col = 4;
+
+        feed_keys(&mut editor, "vjjhrx");
+        assert_eq!(editor.buffer.rope.to_string(), "one xxx\nxxxxx\nxxxx five\n");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 4));
+
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "one two\nthree\nfour five\n");
+
+        editor.cursor.line = 0;
+        feed_keys(&mut editor, "Vr-");
+        assert_eq!(editor.buffer.rope.to_string(), "-------\nthree\nfour five\n");
+    }
+
+    #[test]
+    fn test_indent_boundary_jumps() {
+        let mut editor = Editor::new();
+        editor
+            .buffer
+            .insert_text("def f():\n    if x:\n        a()\n    return 1\n", 0, 0)
+            .unwrap();
+        editor.cursor.line = 2;
+        editor.cursor.col = 8;
+
+        feed_keys(&mut editor, "[i");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 4));
+        feed_keys(&mut editor, "j]i");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (3, 4));
+        // No enclosing block below: stay put
+        feed_keys(&mut editor, "]i");
+        assert_eq!(editor.cursor.line, 3);
+        feed_keys(&mut editor, "[i");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+    }
+
+    #[test]
+    fn test_visual_yank() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\nthree\n", 0, 0).unwrap();
+        editor.cursor.col = 4;
+
+        feed_keys(&mut editor, "vey");
+        assert_eq!(editor.registers.get('"'), Some("two"));
+        assert_eq!(editor.registers.get('0'), Some("two"));
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.status_message.as_deref(), Some("Yanked 3 chars"));
+
+        feed_keys(&mut editor, "Vj\"ay");
+        assert_eq!(editor.registers.get('a'), Some("one two\nthree\n"));
+        assert!(editor.registers.is_linewise('a'));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+        assert_eq!(editor.status_message.as_deref(), Some("Yanked 2 lines"));
+        // Nothing goes to the clipboard unless asked for
+        assert!(editor.registers.clipboard.is_empty());
+
+        editor.options.yank_to_clipboard = true;
+        feed_keys(&mut editor, "jyy");
+        assert_eq!(editor.registers.get('+'), Some("three\n"));
+        assert!(editor.registers.is_linewise('+'));
+        assert_eq!(editor.registers.get('0'), Some("three\n"));
+        // A named register is left to itself
+        feed_keys(&mut editor, "\"by$");
+        assert_eq!(editor.registers.get('+'), Some("three\n"));
+    }
+
+    #[test]
+    fn test_select_all_and_visual_delete() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo\nthree", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        editor.execute_command(Command::SelectAll);
+        assert_eq!(editor.selection_size().as_deref(), Some("3 lines"));
+        feed_keys(&mut editor, "y");
+        assert_eq!(editor.registers.get('0'), Some("one\ntwo\nthree\n"));
+
+        editor.cursor.col = 1;
+        feed_keys(&mut editor, "vld");
+        assert_eq!(editor.buffer.rope.to_string(), "o\ntwo\nthree");
+        assert_eq!(editor.registers.get('"'), Some("ne"));
+        // The last lines take the line break before them along
+        feed_keys(&mut editor, "jVjd");
+        assert_eq!(editor.buffer.rope.to_string(), "o");
+        assert_eq!(editor.registers.get('"'), Some("two\nthree\n"));
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_substitute_with_smart_case() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("Foo foo\nFoo foo", 0, 0).unwrap();
+        run_ex(&mut editor, "s/foo/x/g");
+        assert_eq!(editor.buffer.get_line_content(0), "x x");
+        run_ex(&mut editor, "2s/Foo/x/g");
+        assert_eq!(editor.buffer.get_line_content(1), "x foo");
+
+        run_ex(&mut editor, "set nosmartcase");
+        assert_eq!(editor.status_message.as_deref(), Some("nosmartcase"));
+        run_ex(&mut editor, "2s/foo/y/");
+        assert_eq!(editor.buffer.get_line_content(1), "x y");
+    }
+
+    #[test]
+    fn test_messages_keep_what_scrolled_past() {
+        let mut editor = Editor::new();
+        run_ex(&mut editor, "messages");
+        assert_eq!(editor.status_message.as_deref(), Some("No messages"));
+        editor.record_status();
+        assert!(editor.messages.is_empty());
+
+        editor.buffer.insert_text("one\ntwo", 0, 0).unwrap();
+        run_ex(&mut editor, "set ft=cobol");
+        feed_keys(&mut editor, "Vy");
+        run_ex(&mut editor, "messages");
+        let listing = editor.listing.clone().unwrap();
+        let texts: Vec<&str> = listing
+            .lines
+            .iter()
+            .map(|line| line.split_once("] ").unwrap().1)
+            .collect();
+        assert_eq!(texts, ["Unknown filetype: cobol", "Yanked line (3 chars)"]);
+        assert_eq!(listing.errors, [true, false]);
+
+        run_ex(&mut editor, "messages clear");
+        assert!(editor.messages.is_empty());
+    }
+
+    #[test]
+    fn test_ex_delete_and_yank_lines() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo\nthree\n", 0, 0).unwrap();
+
+        run_ex(&mut editor, "%y");
+        assert_eq!(editor.registers.get('0'), Some("one\ntwo\nthree\n"));
+        assert_eq!(editor.status_message.as_deref(), Some("Yanked 3 lines"));
+        run_ex(&mut editor, "2,3d a");
+        assert_eq!(editor.buffer.rope.to_string(), "one\n");
+        assert_eq!(editor.registers.get('a'), Some("two\nthree\n"));
+        run_ex(&mut editor, "%d");
+        assert_eq!(editor.buffer.rope.to_string(), "");
+        assert_eq!(editor.status_message.as_deref(), Some("Deleted 1 line"));
+    }
+
+    #[test]
+    fn test_visual_paste_replaces_selection() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo three\n", 0, 0).unwrap();
+
+        // The replaced word can be pasted back over the next one
+        feed_keys(&mut editor, "y$jvep");
+        assert_eq!(editor.buffer.rope.to_string(), "one\none three\n");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.cursor.col, 2);
+        feed_keys(&mut editor, "wvep");
+        assert_eq!(editor.buffer.rope.to_string(), "one\none two\n");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "one\none three\n");
+
+        // Lines over lines, then lines into the middle of a line
+        feed_keys(&mut editor, "kyyjVp");
+        assert_eq!(editor.buffer.rope.to_string(), "one\none\n");
+        assert!(editor.registers.is_linewise('"'));
+        feed_keys(&mut editor, "0lvlp");
+        assert_eq!(editor.buffer.rope.to_string(), "one\no\none three\n\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 0));
+        assert_eq!(editor.registers.get('"'), Some("ne"));
+
+        // `P` leaves the register alone
+        feed_keys(&mut editor, "gg\"ay$jjwve\"aP");
+        assert_eq!(editor.buffer.rope.to_string(), "one\no\none one\n\n");
+        assert_eq!(editor.registers.get('"'), Some("one"));
+    }
+
+    fn mouse(kind: MouseEventKind, row: u16, column: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn test_screen_to_buffer() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("\tx\n日本語\nend", 0, 0).unwrap();
+        editor.options.tab_width = 4;
+        let text = Gutter::WIDTH;
+
+        // Anywhere on the tab's cells is the tab
+        assert_eq!(editor.screen_to_buffer(0, text + 3), Some(Position::new(0, 0)));
+        assert_eq!(editor.screen_to_buffer(0, text + 4), Some(Position::new(0, 1)));
+        // Both cells of a wide character
+        assert_eq!(editor.screen_to_buffer(1, text + 3), Some(Position::new(1, 1)));
+        // Gutter, past the end of the line, below the last line
+        assert_eq!(editor.screen_to_buffer(1, 0), Some(Position::new(1, 0)));
+        assert_eq!(editor.screen_to_buffer(1, text + 40), Some(Position::new(1, 2)));
+        assert_eq!(editor.screen_to_buffer(9, text), Some(Position::new(2, 0)));
+        assert_eq!(editor.screen_to_buffer(editor.viewport.rows as u16, text), None);
+
+        editor.viewport.offset_line = 1;
+        editor.viewport.offset_col = 2;
+        assert_eq!(editor.screen_to_buffer(0, text), Some(Position::new(1, 1)));
+    }
+
+    #[test]
+    fn test_mouse_click_drag_and_scroll() {
+        let mut editor = Editor::new();
+        let text: String = (0..50).map(|i| format!("line {}\n", i)).collect();
+        editor.buffer.insert_text(&text, 0, 0).unwrap();
+        editor.viewport.rows = 10;
+        let column = Gutter::WIDTH;
+
+        editor.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 2, column + 1));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 1));
+
+        editor.handle_mouse(mouse(MouseEventKind::Drag(MouseButton::Left), 3, column + 3));
+        assert_eq!(editor.mode, Mode::Visual);
+        assert_eq!(editor.visual_start, Some(Position::new(2, 1)));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (3, 3));
+
+        // A click ends the selection
+        editor.handle_mouse(mouse(MouseEventKind::Down(MouseButton::Left), 0, column));
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.last_visual, Some((Position::new(2, 1), Position::new(3, 3))));
+
+        // Scrolling keeps the cursor on screen
+        for _ in 0..2 {
+            editor.handle_mouse(mouse(MouseEventKind::ScrollDown, 0, 0));
+        }
+        assert_eq!(editor.viewport.offset_line, 6);
+        assert_eq!(editor.cursor.line, 6);
+        editor.handle_mouse(mouse(MouseEventKind::ScrollUp, 0, 0));
+        assert_eq!(editor.viewport.offset_line, 3);
+        assert_eq!(editor.cursor.line, 6);
+    }
+
+    #[test]
+    fn test_visual_swap_ends() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two\nthree", 0, 0).unwrap();
+        editor.cursor.col = 1;
+
+        feed_keys(&mut editor, "vjl");
+        let selection = editor.visual_selection();
+        feed_keys(&mut editor, "o");
+        assert_eq!(editor.visual_start, Some(Position::new(1, 2)));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 1));
+        assert_eq!(editor.visual_selection(), selection);
+
+        // Extending now moves the start of the selection
+        feed_keys(&mut editor, "hU");
+        assert_eq!(editor.buffer.rope.to_string(), "ONE TWO\nTHRee");
+    }
+
+    #[test]
+    fn test_filter_whole_buffer() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("c\na\nb\n", 0, 0).unwrap();
+
+        run_ex(&mut editor, "%!sort");
+        assert_eq!(editor.buffer.rope.to_string(), "a\nb\nc\n");
+        assert_eq!(editor.status_message.as_deref(), Some("3 lines filtered"));
+    }
+
+    #[test]
+    fn test_filter_range_with_visual_selection() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("keep\nfoo\nbar\nkeep", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        feed_keys(&mut editor, "Vj:");
+        for c in "!tr a-z A-Z\n".chars() {
+            editor.handle_command_input(c).unwrap();
+        }
+        assert_eq!(editor.buffer.rope.to_string(), "keep\nFOO\nBAR\nkeep");
+    }
+
+    #[test]
+    fn test_filter_failure_leaves_buffer_intact() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("data", 0, 0).unwrap();
+
+        run_ex(&mut editor, "%!echo broken >&2; exit 3");
+        assert_eq!(editor.buffer.rope.to_string(), "data");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Filter failed: broken")
+        );
+    }
+
+    #[test]
+    fn test_filter_empty_output_removes_lines() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\nb\nc", 0, 0).unwrap();
+
+        run_ex(&mut editor, "2,3!true");
+        assert_eq!(editor.buffer.rope.to_string(), "a");
+    }
+
+    fn wait_for_disk_change(editor: &mut Editor) -> bool {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while std::time::Instant::now() < deadline {
+            if editor.check_file_changes() {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_reload_unmodified_buffer_on_disk_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        editor.cursor.line = 2;
+        editor.cursor.col = 3;
+
+        std::fs::write(&path, "one\nTWO\n").unwrap();
+        assert!(wait_for_disk_change(&mut editor));
+        assert_eq!(editor.buffer.rope.to_string(), "one\nTWO\n");
+        assert!(!editor.buffer.modified);
+        assert_eq!(editor.cursor.line, 2);
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_modified_buffer_prompts_before_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "disk\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        editor.buffer.insert_text("local ", 0, 0).unwrap();
+
+        std::fs::write(&path, "changed\n").unwrap();
+        assert!(wait_for_disk_change(&mut editor));
+        assert!(editor.reload_prompt);
+        assert_eq!(editor.buffer.rope.to_string(), "local disk\n");
+
+        assert!(editor.handle_reload_prompt('k'));
+        assert!(!editor.reload_prompt);
+        assert_eq!(editor.buffer.rope.to_string(), "local disk\n");
+
+        std::fs::write(&path, "changed again\n").unwrap();
+        assert!(wait_for_disk_change(&mut editor));
+        assert!(editor.handle_reload_prompt('r'));
+        assert_eq!(editor.buffer.rope.to_string(), "changed again\n");
+        assert!(!editor.buffer.modified);
+    }
+
+    #[test]
+    fn test_edit_bang_discards_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        editor.buffer.insert_text("x\ny\nz\n", 2, 0).unwrap();
+        editor.cursor.line = 4;
+        editor.cursor.col = 0;
+        assert!(editor.buffer.modified);
+
+        run_ex(&mut editor, "e!");
+        assert_eq!(editor.buffer.rope.to_string(), "one\ntwo\n");
+        assert!(!editor.buffer.modified);
+        assert_eq!(editor.buffer.version, 0);
+        assert_eq!(editor.cursor.line, 2);
+
+        editor.buffer.insert_text("more ", 0, 0).unwrap();
+        run_ex(&mut editor, "edit!");
+        assert_eq!(editor.buffer.rope.to_string(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_edit_bang_without_file() {
+        let mut editor = Editor::new();
+        editor.buffer.file_path = None;
+        editor.buffer.insert_text("scratch", 0, 0).unwrap();
+        run_ex(&mut editor, "e!");
+        assert_eq!(editor.buffer.rope.to_string(), "scratch");
+        assert_eq!(editor.status_message.as_deref(), Some("No file name"));
+    }
+
+    #[test]
+    fn test_write_can_ensure_final_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eol.txt");
+        std::fs::write(&path, "no newline").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "no newline");
+
+        editor.options.ensure_final_newline = true;
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "no newline\n");
+    }
+
+    #[test]
+    fn test_write_all_and_quit_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("all.txt");
+
+        let mut editor = Editor::new();
+        editor.buffer.file_path = Some(path.to_string_lossy().to_string());
+        editor.buffer.insert_text("changed", 0, 0).unwrap();
+
+        // :qa refuses to drop unsaved changes, :qa! doesn't
+        assert!(!run_ex(&mut editor, "qa"));
+        assert!(run_ex(&mut editor, "qa!"));
+
+        assert!(!run_ex(&mut editor, "wa"));
+        assert!(!editor.buffer.modified);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "changed");
+        assert_eq!(editor.status_message.as_deref(), Some("1 buffer written"));
+        assert!(run_ex(&mut editor, "qa"));
+        assert!(run_ex(&mut editor, "wqa"));
+    }
+
+    #[test]
+    fn test_write_all_without_file_name_stays_open() {
+        let mut editor = Editor::new();
+        editor.buffer.file_path = None;
+        editor.buffer.insert_text("scratch", 0, 0).unwrap();
+        assert!(!run_ex(&mut editor, "wqa"));
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No file name for modified buffer")
+        );
+    }
+
+    #[test]
+    fn test_counted_delete_and_yank_lines() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("1\n2\n3\n4\n5\n", 0, 0).unwrap();
+        editor.cursor.line = 1;
+
+        editor.execute_command(Command::YankLine(3));
+        assert_eq!(editor.registers.get('0'), Some("2\n3\n4\n"));
+        assert!(editor.registers.is_linewise('0'));
+        assert_eq!(editor.status_message.as_deref(), Some("Yanked 3 lines"));
+
+        editor.execute_command(Command::DeleteLine(3));
+        assert_eq!(editor.buffer.rope.to_string(), "1\n5\n");
+        assert_eq!(editor.registers.get('"'), Some("2\n3\n4\n"));
+        assert!(editor.registers.is_linewise('"'));
+
+        // A count past the end stops at the last line
+        editor.execute_command(Command::DeleteLine(10));
+        assert_eq!(editor.buffer.rope.to_string(), "1\n");
+    }
+
+    #[test]
+    fn test_line_deletes_agree_at_the_end_of_the_buffer() {
+        for keys in ["5dd", "Vjd", ":2,3d"] {
+            let mut editor = Editor::new();
+            editor.buffer.insert_text("one\ntwo\nthree", 0, 0).unwrap();
+            editor.cursor.line = 1;
+            match keys.strip_prefix(':') {
+                Some(cmd) => {
+                    run_ex(&mut editor, cmd);
+                }
+                None => feed_keys(&mut editor, keys),
+            }
+            assert_eq!(editor.buffer.rope.to_string(), "one", "{}", keys);
+            assert_eq!(editor.registers.get('"'), Some("two\nthree\n"), "{}", keys);
+            assert_eq!(editor.cursor.line, 0, "{}", keys);
+        }
+    }
+
+    #[test]
+    fn test_insert_is_one_undo_step() {
+        let mut editor = Editor::new();
+        type_insert(&mut editor, Command::InsertMode(1), "hello");
+        type_insert(&mut editor, Command::OpenLineBelow(1), "world");
+        assert_eq!(editor.buffer.rope.to_string(), "hello\nworld");
+
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "hello");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.status_message.as_deref(), Some("Already at oldest change"));
+
+        editor.execute_command(Command::Redo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "hello");
+    }
+
+    #[test]
+    fn test_insert_undo_breaks_at_newline_and_movement() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::InsertMode(1));
+        for c in "one\ntwo".chars() {
+            editor.execute_command(Command::InsertChar(c));
+        }
+        editor.execute_command(Command::MoveLeft);
+        editor.execute_command(Command::InsertChar('X'));
+        editor.execute_command(Command::NormalMode);
+        assert_eq!(editor.buffer.rope.to_string(), "one\ntwXo");
+
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "one\ntwo");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "one");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "");
+    }
+
+    #[test]
+    fn test_insert_delete_word_and_line_start() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::InsertMode(1));
+        for c in "    let foo = bar.baz".chars() {
+            editor.execute_command(Command::InsertChar(c));
+        }
+        editor.execute_command(Command::DeleteWordBackInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = bar.");
+        editor.execute_command(Command::DeleteWordBackInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = bar");
+        editor.execute_command(Command::InsertChar('x'));
+
+        // Ctrl-U keeps the indent, then removes it
+        editor.execute_command(Command::DeleteToLineStartInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "    ");
+        assert_eq!(editor.cursor.col, 4);
+        editor.execute_command(Command::DeleteToLineStartInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "");
+
+        // Each deletion is undone on its own
+        editor.execute_command(Command::NormalMode);
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "    ");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = barx");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = bar");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = bar.");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "    let foo = bar.baz");
+    }
+
+    #[test]
+    fn test_insert_code_point() {
+        let mut editor = Editor::new();
+        let type_keys = |editor: &mut Editor, keys: &str| {
+            for c in keys.chars() {
+                editor.execute_command(Command::InsertChar(c));
+            }
+        };
+        editor.execute_command(Command::InsertMode(1));
+        editor.execute_command(Command::InsertCodePoint);
+        type_keys(&mut editor, "u00e9");
+        editor.execute_command(Command::InsertCodePoint);
+        type_keys(&mut editor, "065!");
+        // A short code ends at the first key that is not a digit
+        editor.execute_command(Command::InsertCodePoint);
+        type_keys(&mut editor, "u3bb.");
+        assert_eq!(editor.buffer.rope.to_string(), "éA!λ.");
+        assert_eq!(editor.cursor.col, 5);
+
+        editor.execute_command(Command::InsertCodePoint);
+        type_keys(&mut editor, "ud800");
+        assert_eq!(editor.buffer.rope.to_string(), "éA!λ.");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Invalid code point: U+D800")
+        );
+        type_keys(&mut editor, "x");
+        assert_eq!(editor.buffer.rope.to_string(), "éA!λ.x");
+    }
+
+    #[test]
+    fn test_replay_last_insert() {
+        let mut editor = Editor::new();
+        let type_keys = |editor: &mut Editor, keys: &str| {
+            for c in keys.chars() {
+                editor.execute_command(Command::InsertChar(c));
+            }
+        };
+        editor.execute_command(Command::InsertMode(1));
+        editor.execute_command(Command::ReplayLastInsert);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No previously inserted text")
+        );
+        type_keys(&mut editor, "ab\ncd");
+        editor.execute_command(Command::NormalMode);
+
+        editor.execute_command(Command::AppendLineEnd(1));
+        type_keys(&mut editor, "-");
+        editor.execute_command(Command::ReplayLastInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "ab\ncd-ab\ncd");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 2));
+        type_keys(&mut editor, "!");
+
+        // The replayed text is one undo step of its own
+        editor.execute_command(Command::NormalMode);
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "ab\ncd-ab\ncd");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "ab\ncd-");
+
+        // The whole last insert is kept, replayed text included
+        assert_eq!(editor.registers.get_inserted(), "-ab\ncd!");
+    }
+
+    #[test]
+    fn test_insert_delete_word_joins_lines_at_line_start() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo", 0, 0).unwrap();
+        editor.cursor.line = 1;
+        editor.execute_command(Command::InsertMode(1));
+        editor.execute_command(Command::DeleteWordBackInsert);
+        assert_eq!(editor.buffer.rope.to_string(), "onetwo");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 3));
+    }
+
+    #[test]
+    fn test_counted_undo_and_redo() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("1\n2\n3\n4\n", 0, 0).unwrap();
+        for _ in 0..3 {
+            editor.execute_command(Command::DeleteLine(1));
+        }
+        assert_eq!(editor.buffer.rope.to_string(), "4\n");
+
+        editor.execute_command(Command::Undo(2));
+        assert_eq!(editor.buffer.rope.to_string(), "2\n3\n4\n");
+        assert_eq!(editor.status_message.as_deref(), Some("2 changes undone"));
+        // Stops at the end of the history
+        editor.execute_command(Command::Undo(5));
+        assert_eq!(editor.buffer.rope.to_string(), "1\n2\n3\n4\n");
+        assert_eq!(editor.status_message.as_deref(), Some("1 change undone"));
+
+        run_ex(&mut editor, "later 2");
+        assert_eq!(editor.buffer.rope.to_string(), "3\n4\n");
+        run_ex(&mut editor, "undolist");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("2 changes to undo, 1 to redo")
+        );
+
+        // A new change drops what could be redone
+        editor.execute_command(Command::DeleteLine(1));
+        editor.execute_command(Command::Redo(1));
+        assert_eq!(editor.status_message.as_deref(), Some("Already at newest change"));
+    }
+
+    #[test]
+    fn test_registers_listing() {
+        let mut editor = Editor::new();
+        run_ex(&mut editor, "reg");
+        assert!(editor.listing.is_none());
+        assert_eq!(editor.status_message.as_deref(), Some("All registers are empty"));
+
+        editor.registers.yank_lines("first\tline".to_string(), 'a');
+        editor.registers.yank("word".to_string(), '0');
+        run_ex(&mut editor, "registers");
+        let lines = editor.listing.clone().unwrap().lines;
+        assert_eq!(
+            lines,
+            vec![
+                "Type Name Content",
+                "  c  \"\"   word",
+                "  c  \"0   word",
+                "  l  \"a   first^Iline^J",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_filetype() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("def f():\n    pass\n", 0, 0).unwrap();
+        editor.set_language(None);
+        assert!(editor.formatter.is_none());
+
+        run_ex(&mut editor, "set ft=python");
+        assert_eq!(editor.current_language, Some(LanguageId::Python));
+        assert!(editor.buffer.highlighter.is_some());
+        assert_eq!(editor.status_message.as_deref(), Some("filetype=python"));
+
+        run_ex(&mut editor, "set filetype=cobol");
+        assert_eq!(editor.status_message.as_deref(), Some("Unknown filetype: cobol"));
+        assert_eq!(editor.current_language, Some(LanguageId::Python));
+        run_ex(&mut editor, "set ft?");
+        assert_eq!(editor.status_message.as_deref(), Some("filetype=python"));
+
+        run_ex(&mut editor, "set ft=");
+        assert_eq!(editor.current_language, None);
+        assert!(editor.buffer.highlighter.is_none());
+        run_ex(&mut editor, "set wrap");
+        assert_eq!(editor.status_message.as_deref(), Some("Unknown option: wrap"));
+    }
+
+    #[test]
+    fn test_set_colorcolumn() {
+        let mut editor = Editor::new();
+        run_ex(&mut editor, "set cc=100,80");
+        assert_eq!(editor.options.color_columns, [80, 100]);
+        assert_eq!(editor.status_message.as_deref(), Some("colorcolumn=80,100"));
+
+        run_ex(&mut editor, "set colorcolumn=x");
+        assert_eq!(editor.status_message.as_deref(), Some("Invalid colorcolumn: x"));
+        assert_eq!(editor.options.color_columns, [80, 100]);
+
+        run_ex(&mut editor, "set cc=");
+        assert!(editor.options.color_columns.is_empty());
+        run_ex(&mut editor, "set cc?");
+        assert_eq!(editor.status_message.as_deref(), Some("colorcolumn="));
+    }
+
+    #[test]
+    fn test_repeat_last_command_line() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a a a a\na\n", 0, 0).unwrap();
+        feed_keys(&mut editor, "@@");
+        assert_eq!(editor.status_message.as_deref(), Some("No previous register executed"));
+        feed_keys(&mut editor, "@:");
+        assert_eq!(editor.status_message.as_deref(), Some("No previous command line"));
+
+        run_ex(&mut editor, "s/a/b/");
+        feed_keys(&mut editor, "@:");
+        assert_eq!(editor.buffer.rope.to_string(), "b b a a\na\n");
+        feed_keys(&mut editor, "2@@");
+        assert_eq!(editor.buffer.rope.to_string(), "b b b b\na\n");
+        // It isn't added to the history again
+        assert_eq!(editor.command_history, ["s/a/b/"]);
+        feed_keys(&mut editor, "u");
+        assert_eq!(editor.buffer.rope.to_string(), "b b a a\na\n");
+    }
+
+    #[test]
+    fn test_syntax_info_names_captures() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("fn main() {\n    let x = 1;\n}", 0, 0).unwrap();
+        editor.set_language(None);
+        run_ex(&mut editor, "syntax info");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No syntax highlighting for this file")
+        );
+
+        editor.set_language(Some(LanguageId::Rust));
+        editor.cursor.col = 4;
+        run_ex(&mut editor, "syntax info");
+        assert_eq!(editor.status_message.as_deref(), Some("@variable @function"));
+        editor.cursor.col = 2;
+        run_ex(&mut editor, "syn info");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No highlight capture under the cursor")
+        );
+    }
+
+    #[test]
+    fn test_set_iskeyword() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("let $el = foo-bar;", 0, 0).unwrap();
+        editor.set_language(Some(LanguageId::JavaScript));
+        run_ex(&mut editor, "set isk?");
+        assert_eq!(editor.status_message.as_deref(), Some("iskeyword=_$"));
+        feed_keys(&mut editor, "wdw");
+        assert_eq!(editor.buffer.rope.to_string(), "let = foo-bar;");
+
+        run_ex(&mut editor, "set iskeyword=_-");
+        feed_keys(&mut editor, "wdiw");
+        assert_eq!(editor.buffer.rope.to_string(), "let = ;");
+        assert_eq!(editor.options.word_chars.extra(), "_-");
+
+        // A new file type brings its own
+        run_ex(&mut editor, "set ft=rust");
+        assert_eq!(editor.options.word_chars.extra(), "_");
+    }
+
+    #[test]
+    fn test_alternate_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src/list.c");
+        let header = dir.path().join("include/list.h");
+        let lone = dir.path().join("src/main.c");
+        for path in [&source, &header, &lone] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "int x;\n").unwrap();
+        }
+
+        let mut editor = Editor::new();
+        editor.buffer.file_path = None;
+        run_ex(&mut editor, "A");
+        assert_eq!(editor.status_message.as_deref(), Some("No file name"));
+
+        editor.open_file(source.to_str().unwrap()).unwrap();
+        run_ex(&mut editor, "A");
+        assert_eq!(editor.buffer.file_path.as_deref(), header.to_str());
+        run_ex(&mut editor, "A");
+        assert_eq!(editor.buffer.file_path.as_deref(), source.to_str());
+
+        // Unsaved changes need `!`
+        editor.buffer.insert_char('x', 0, 0).unwrap();
+        run_ex(&mut editor, "A");
+        assert_eq!(editor.buffer.file_path.as_deref(), source.to_str());
+        run_ex(&mut editor, "A!");
+        assert_eq!(editor.buffer.file_path.as_deref(), header.to_str());
+
+        editor.open_file(lone.to_str().unwrap()).unwrap();
+        run_ex(&mut editor, "A");
+        assert_eq!(
+            editor.status_message,
+            Some(format!("No alternate file for {}", lone.display()))
+        );
+
+        // Several candidates go to the picker
+        std::fs::write(dir.path().join("src/list.h"), "").unwrap();
+        editor.open_file(source.to_str().unwrap()).unwrap();
+        editor.execute_command(Command::OpenAlternateFile);
+        assert_eq!(editor.mode, Mode::FuzzySearch);
+        let fuzzy = editor.fuzzy_search.as_ref().unwrap();
+        assert_eq!(fuzzy.filtered_items.len(), 2);
+    }
+
+    #[test]
+    fn test_recent_files_picker() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.txt");
+        let second = dir.path().join("second.txt");
+        std::fs::write(&first, "1\n").unwrap();
+        std::fs::write(&second, "2\n").unwrap();
+
+        let mut editor = Editor::new();
+        run_ex(&mut editor, "recent");
+        assert_eq!(editor.status_message.as_deref(), Some("No recent files"));
+        assert!(editor.fuzzy_search.is_none());
+
+        editor.open_file(first.to_str().unwrap()).unwrap();
+        editor.open_file(second.to_str().unwrap()).unwrap();
+        editor.execute_command(Command::OpenRecentFiles);
+        assert_eq!(editor.mode, Mode::FuzzySearch);
+        let fuzzy = editor.fuzzy_search.as_mut().unwrap();
+        let names: Vec<_> = fuzzy
+            .filtered_items
+            .iter()
+            .map(|item| item.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["second.txt", "first.txt"]);
+
+        fuzzy.update_query("fir".to_string());
+        assert_eq!(fuzzy.filtered_items.len(), 1);
+        fuzzy.update_query(String::new());
+
+        // Previewing while browsing doesn't reorder the list
+        editor.execute_command(Command::FuzzySearchDown);
+        assert!(editor.recent_files.entries()[0].ends_with("second.txt"));
+        editor.execute_command(Command::FuzzySearchSelect);
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.buffer.line(0).as_deref(), Some("1"));
+        assert!(editor.recent_files.entries()[0].ends_with("first.txt"));
+    }
+
+    #[test]
+    fn test_add_cursor_at_next_word_match() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("foo bar\nfoobar foo\n", 0, 0).unwrap();
+        editor.cursor.col = 1;
+
+        editor.execute_command(Command::AddCursor);
+        assert_eq!((editor.cursors[0].line, editor.cursors[0].col), (1, 8));
+        assert_eq!(editor.status_message.as_deref(), Some("2 cursors"));
+        // Wrapping around only finds the primary cursor's word again
+        editor.execute_command(Command::AddCursor);
+        assert_eq!(editor.cursors.len(), 1);
+        assert_eq!(editor.status_message.as_deref(), Some("No more matches"));
+    }
+
+    #[test]
+    fn test_typing_at_multiple_cursors() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("  a\n  b\n  c", 0, 0).unwrap();
+        editor.execute_command(Command::AddCursor);
+        editor.execute_command(Command::AddCursor);
+        assert_eq!(editor.cursors.len(), 2);
+
+        type_insert(&mut editor, Command::InsertMode(1), "x-");
+        assert_eq!(editor.buffer.rope.to_string(), "x-  a\nx-  b\nx-  c");
+        // Escape collapses to the primary cursor
+        assert!(editor.cursors.is_empty());
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 2));
+
+        editor.execute_command(Command::AddCursor);
+        editor.execute_command(Command::InsertMode(1));
+        editor.execute_command(Command::DeleteChar);
+        editor.execute_command(Command::NormalMode);
+        assert_eq!(editor.buffer.rope.to_string(), "x  a\nx  b\nx-  c");
+    }
+
+    fn type_insert(editor: &mut Editor, entry: Command, text: &str) {
+        editor.execute_command(entry);
+        for c in text.chars() {
+            editor.execute_command(Command::InsertChar(c));
+        }
+        editor.execute_command(Command::NormalMode);
+    }
+
+    #[test]
+    fn test_enter_continues_comments() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("fn f() {\n    // one\n}", 0, 0).unwrap();
+        editor.set_language(Some(LanguageId::Rust));
+        editor.cursor.line = 1;
+        type_insert(&mut editor, Command::AppendLineEnd(1), "\ntwo\n\nx");
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "fn f() {\n    // one\n    // two\n    x\n}"
+        );
+
+        editor.buffer = Buffer::new();
+        editor.buffer.insert_text("/**\nlet", 0, 0).unwrap();
+        editor.cursor = Cursor::default();
+        type_insert(&mut editor, Command::AppendLineEnd(1), "\nDocs.\n");
+        assert_eq!(editor.buffer.rope.to_string(), "/**\n * Docs.\n * \nlet");
+
+        // A line starting with `*` outside a block comment is code
+        editor.buffer = Buffer::new();
+        editor.buffer.insert_text("/* a */\nlet x = total\n    * rate;", 0, 0).unwrap();
+        editor.cursor = Cursor::default();
+        editor.cursor.line = 2;
+        type_insert(&mut editor, Command::AppendLineEnd(1), "\nx");
+        assert_eq!(editor.buffer.line(3).as_deref(), Some("x"));
+
+        // Splitting a line carries the rest into the comment
+        editor.buffer = Buffer::new();
+        editor.buffer.insert_text("# a b", 0, 0).unwrap();
+        editor.set_language(Some(LanguageId::Python));
+        editor.cursor = Cursor::default();
+        editor.cursor.col = 3;
+        type_insert(&mut editor, Command::InsertMode(1), "\n");
+        assert_eq!(editor.buffer.rope.to_string(), "# a\n#  b");
+
+        run_ex(&mut editor, "set nocontinuecomments");
+        editor.cursor.col = 3;
+        type_insert(&mut editor, Command::InsertMode(1), "\n");
+        assert_eq!(editor.buffer.rope.to_string(), "# a\n#  \nb");
+    }
+
+    #[test]
+    fn test_insert_mode_ctrl_o_runs_one_command() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two three", 0, 0).unwrap();
+        editor.execute_command(Command::AppendLineEnd(1));
+        editor.execute_command(Command::InsertChar('!'));
+
+        // A command that doesn't move the cursor leaves it after the line
+        editor.execute_command(Command::InsertNormalCommand);
+        assert_eq!((editor.mode, editor.cursor.col), (Mode::Normal, 13));
+        assert!(editor.in_insert_normal_command());
+        feed_keys(&mut editor, "ga");
+        assert_eq!((editor.mode, editor.cursor.col), (Mode::Insert, 14));
+        assert_eq!(editor.registers.get_inserted(), "!");
+
+        editor.execute_command(Command::InsertNormalCommand);
+        feed_keys(&mut editor, "0");
+        assert_eq!((editor.mode, editor.cursor.col), (Mode::Insert, 0));
+
+        // Keys of a longer command are all read before inserting again
+        editor.execute_command(Command::InsertNormalCommand);
+        feed_keys(&mut editor, "d");
+        assert_eq!(editor.mode, Mode::Normal);
+        feed_keys(&mut editor, "w");
+        assert_eq!(editor.mode, Mode::Insert);
+        editor.execute_command(Command::InsertChar('1'));
+        assert_eq!(editor.buffer.rope, "1two three!");
+
+        // The command line counts as the one command
+        editor.execute_command(Command::InsertNormalCommand);
+        feed_keys(&mut editor, ":");
+        assert_eq!(editor.mode, Mode::Command);
+        editor.command_line = "s/two/2/".to_string();
+        editor.handle_command_input('\n').unwrap();
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.buffer.rope, "12 three!");
+
+        // Escape stays in normal mode
+        editor.execute_command(Command::InsertNormalCommand);
+        feed_keys(&mut editor, "\x1b");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert!(!editor.in_insert_normal_command());
+    }
+
+    #[test]
+    fn test_auto_pairs_close_and_type_over() {
+        let mut editor = Editor::new();
+        type_insert(&mut editor, Command::InsertMode(1), "f(x) + [\"it's\"]");
+        assert_eq!(editor.buffer.rope.to_string(), "f(x) + [\"it's\"]");
+
+        // Left open, the closer stays after the cursor
+        editor.buffer = Buffer::new();
+        editor.cursor.col = 0;
+        type_insert(&mut editor, Command::InsertMode(1), "{a");
+        assert_eq!(editor.buffer.rope.to_string(), "{a}");
+
+        // No pair before a word, and an empty pair is deleted whole
+        editor.buffer = Buffer::new();
+        editor.buffer.insert_text("word", 0, 0).unwrap();
+        editor.cursor.col = 0;
+        type_insert(&mut editor, Command::InsertMode(1), "\"(");
+        assert_eq!(editor.buffer.rope.to_string(), "\"(word");
+        editor.cursor.col = 6;
+        editor.execute_command(Command::InsertMode(1));
+        editor.execute_command(Command::InsertChar('['));
+        assert_eq!(editor.buffer.rope.to_string(), "\"(word[]");
+        editor.execute_command(Command::DeleteChar);
+        editor.execute_command(Command::DeleteChar);
+        editor.execute_command(Command::NormalMode);
+        assert_eq!(editor.buffer.rope.to_string(), "\"(wor");
+
+        editor.options.auto_pairs.clear();
+        editor.cursor.col = 0;
+        type_insert(&mut editor, Command::InsertMode(1), "(");
+        assert_eq!(editor.buffer.rope.to_string(), "(\"(wor");
+    }
+
+    #[test]
+    fn test_counted_insert() {
+        let mut editor = Editor::new();
+        type_insert(&mut editor, Command::InsertMode(3), "x");
+        assert_eq!(editor.buffer.rope.to_string(), "xxx");
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_counted_append() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("ab", 0, 0).unwrap();
+        type_insert(&mut editor, Command::AppendAfter(3), "-");
+        assert_eq!(editor.buffer.rope.to_string(), "a---b");
+    }
 
-        // Scan directory and populate items
-        fuzzy_state.rescan_current_directory();
+    #[test]
+    fn test_counted_open_line() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("top\nbottom", 0, 0).unwrap();
+        type_insert(&mut editor, Command::OpenLineBelow(3), "hello");
+        assert_eq!(editor.buffer.rope.to_string(), "top\nhello\nhello\nhello\nbottom");
+        assert_eq!(editor.cursor.line, 3);
 
-        self.fuzzy_search = Some(fuzzy_state);
-        self.mode = Mode::FuzzySearch;
+        type_insert(&mut editor, Command::OpenLineAbove(2), "up");
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "top\nhello\nhello\nup\nup\nhello\nbottom"
+        );
     }
 
-    /// Start fuzzy search in a specific directory
-    pub fn start_fuzzy_search_in_dir(&mut self, dir_path: &std::path::Path) {
-        let mut fuzzy_state = FuzzySearchState::new_in_directory(dir_path);
+    #[test]
+    fn test_moving_during_insert_drops_the_count() {
+        let mut editor = Editor::new();
+        editor.execute_command(Command::InsertMode(3));
+        editor.execute_command(Command::InsertChar('a'));
+        editor.execute_command(Command::MoveLeft);
+        editor.execute_command(Command::InsertChar('b'));
+        editor.execute_command(Command::NormalMode);
+        assert_eq!(editor.buffer.rope.to_string(), "ba");
+    }
 
-        // Scan directory and populate items
-        fuzzy_state.rescan_current_directory();
+    #[test]
+    fn test_delete_to_previous_word_end() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two three", 0, 0).unwrap();
+        editor.cursor.col = 9;
+        editor.execute_command(Command::MoveWordEndBackward(1));
+        assert_eq!(editor.cursor.col, 6);
 
-        self.fuzzy_search = Some(fuzzy_state);
-        self.mode = Mode::FuzzySearch;
+        // `dge` is inclusive of the character under the cursor
+        editor.cursor.col = 9;
+        editor.execute_command(Command::DeleteWordEndBackward(1));
+        assert_eq!(editor.buffer.line(0).unwrap(), "one twree");
+        assert_eq!(editor.cursor.col, 6);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
-    fn test_move_down_in_empty_buffer() {
+    fn test_horizontal_scroll_uses_display_width() {
         let mut editor = Editor::new();
-        // Clear buffer to make it truly empty
-        editor.buffer.rope = ropey::Rope::from("");
+        editor.viewport.cols = 10;
+        editor.options.tab_width = 4;
+        editor.buffer.insert_text("\t\t\tabc", 0, 0).unwrap();
 
-        // Try to move down - should not crash
-        editor.execute_command(Command::MoveDown);
-        assert_eq!(editor.cursor.line, 0);
-        assert_eq!(editor.cursor.col, 0);
+        // Three tabs put 'a' at screen column 12, past the 10-column view
+        editor.cursor.col = 3;
+        assert_eq!(editor.cursor_display_col(), 12);
+        editor.execute_command(Command::MoveRight);
+        assert_eq!(editor.cursor_display_col(), 13);
+        assert_eq!(editor.viewport.offset_col, 4);
     }
 
     #[test]
-    fn test_move_up_from_top() {
+    fn test_write_failure_keeps_editor_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("file.txt");
+
         let mut editor = Editor::new();
-        editor.execute_command(Command::MoveUp);
-        assert_eq!(editor.cursor.line, 0);
+        editor.buffer.file_path = Some(path.to_string_lossy().to_string());
+        editor.buffer.insert_text("unsaved", 0, 0).unwrap();
+
+        editor.enter_command_mode();
+        editor.command_line = "wq".to_string();
+        assert!(!editor.handle_command_input('\n').unwrap());
+        assert!(editor.buffer.modified);
+        assert!(editor.status_message.unwrap().starts_with("Error writing"));
     }
 
     #[test]
-    fn test_move_left_from_start() {
+    fn test_enew_scratch_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("draft.rs");
+
         let mut editor = Editor::new();
-        editor.execute_command(Command::MoveLeft);
-        assert_eq!(editor.cursor.col, 0);
+        editor.buffer.insert_text("unsaved", 0, 0).unwrap();
+        run_ex(&mut editor, "enew");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No write since last change (add ! to override)")
+        );
+
+        run_ex(&mut editor, "enew!");
+        assert_eq!(editor.buffer.file_path, None);
+        assert_eq!(editor.buffer.rope, "");
+        assert_eq!(editor.current_language, None);
+
+        editor.buffer.insert_text("fn main() {}", 0, 0).unwrap();
+        run_ex(&mut editor, "w");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No file name (use :w <file>)")
+        );
+        assert!(!run_ex(&mut editor, "wq"));
+
+        // Writing gives the buffer its name and language
+        run_ex(&mut editor, &format!("w {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fn main() {}");
+        assert_eq!(
+            editor.buffer.file_path.as_deref(),
+            Some(path.to_str().unwrap())
+        );
+        assert!(!editor.buffer.modified);
+        assert_eq!(editor.current_language, Some(LanguageId::Rust));
     }
 
     #[test]
-    fn test_move_right_at_end_of_line() {
+    fn test_repeat_find_char() {
         let mut editor = Editor::new();
-        editor.buffer.insert_char('a', 0, 0).unwrap();
-        editor.buffer.insert_char('b', 0, 1).unwrap();
-        editor.cursor.col = 2; // Move to end
+        editor.buffer.insert_text("f(a, b, c, d)", 0, 0).unwrap();
+        let till_comma = CharSearch {
+            ch: ',',
+            till: true,
+            forward: true,
+        };
+        let repeat = |reverse, count| Command::RepeatFindChar { reverse, count };
 
-        editor.execute_command(Command::MoveRight);
+        editor.execute_command(Command::FindChar(till_comma, 1));
         assert_eq!(editor.cursor.col, 2);
+        // `;` gets past the comma the cursor is stopped before
+        editor.execute_command(repeat(false, 1));
+        assert_eq!(editor.cursor.col, 5);
+        editor.execute_command(repeat(false, 5));
+        assert_eq!(editor.cursor.col, 5);
+        editor.execute_command(repeat(true, 1));
+        assert_eq!(editor.cursor.col, 4);
+        editor.execute_command(repeat(false, 2));
+        assert_eq!(editor.cursor.col, 8);
     }
 
     #[test]
-    fn test_move_down_to_last_line() {
+    fn test_jump_between_hunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(args)
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q"]) {
+            // git isn't installed
+            return;
+        }
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "a\nb\nc\nd\ne\nf\n").unwrap();
+        assert!(git(&["add", "file.txt"]));
+        std::fs::write(&path, "a\nB\nc\nd\nE\nf\n").unwrap();
+
         let mut editor = Editor::new();
-        editor.buffer.insert_char('a', 0, 0).unwrap();
-        editor.buffer.insert_char('\n', 0, 1).unwrap();
-        editor.buffer.insert_char('b', 1, 0).unwrap();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        editor.execute_command(Command::NextHunk(1));
+        assert_eq!(editor.cursor.line, 1);
+        assert_eq!(editor.status_message.as_deref(), Some("hunk 1 of 2"));
+        editor.execute_command(Command::NextHunk(1));
+        assert_eq!(editor.cursor.line, 4);
 
-        editor.cursor.line = 0;
-        editor.execute_command(Command::MoveDown);
+        // Both directions wrap around the ends
+        editor.execute_command(Command::NextHunk(1));
         assert_eq!(editor.cursor.line, 1);
+        editor.execute_command(Command::PrevHunk(1));
+        assert_eq!(editor.cursor.line, 4);
+        editor.execute_command(Command::PrevHunk(2));
+        assert_eq!(editor.cursor.line, 4);
+        assert_eq!(editor.status_message.as_deref(), Some("hunk 2 of 2"));
     }
 
     #[test]
-    fn test_delete_char_from_start() {
+    fn test_write_with_backup_option() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "before").unwrap();
+
         let mut editor = Editor::new();
-        editor.execute_command(Command::DeleteChar);
-        // Should not crash when deleting at col 0
-        assert_eq!(editor.cursor.col, 0);
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        editor.options.write_backup = true;
+        editor.buffer.insert_text("after ", 0, 0).unwrap();
+        run_ex(&mut editor, "w");
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after before");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt~")).unwrap(),
+            "before"
+        );
+        assert!(!editor.buffer.modified);
     }
 
-    #[test]
-    fn test_insert_mode_movement() {
+    #[tokio::test]
+    async fn test_lsp_commands() {
         let mut editor = Editor::new();
-        editor.mode = Mode::Insert;
-        editor.buffer.insert_char('a', 0, 0).unwrap();
-        editor.buffer.insert_char('b', 0, 1).unwrap();
+        editor.current_language = Some(LanguageId::Python);
 
-        editor.cursor.col = 0;
-        editor.execute_command(Command::MoveRight);
-        assert_eq!(editor.cursor.col, 1);
+        run_ex(&mut editor, "lsp");
+        assert!(editor.run_pending_lsp_action().await);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No language servers running")
+        );
+        assert!(!editor.run_pending_lsp_action().await);
 
-        editor.execute_command(Command::MoveLeft);
-        assert_eq!(editor.cursor.col, 0);
+        run_ex(&mut editor, "lsp stop");
+        assert!(editor.run_pending_lsp_action().await);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("pyright-langserver is not running")
+        );
+        run_ex(&mut editor, "lsp status");
+        editor.run_pending_lsp_action().await;
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("pyright-langserver: down")
+        );
+
+        run_ex(&mut editor, "lsp frobnicate");
+        assert_eq!(editor.pending_lsp_action, None);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Unknown :lsp command: frobnicate")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_format_falls_back_to_language_server() {
+        use lsp_types::{Position as LspPosition, Range as LspRange, TextEdit};
+        let mut editor = Editor::new();
+        editor.buffer.file_path = Some("/tmp/script.py".to_string());
+        editor.buffer.insert_text("x=1\n", 0, 0).unwrap();
+        editor.current_language = Some(LanguageId::Python);
+        editor.formatter = None;
+
+        // No formatter program: ask the language server, which isn't running
+        run_ex(&mut editor, "fmt");
+        assert_eq!(editor.pending_lsp_action, Some(LspAction::Format));
+        assert!(editor.run_pending_lsp_action().await);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No formatter available for this file type")
+        );
+
+        // What the server sends back is applied around the cursor on `1`
+        editor.cursor.col = 2;
+        let edit = |col: u32, text: &str| {
+            let at = LspPosition::new(0, col);
+            TextEdit::new(LspRange::new(at, LspPosition::new(0, col + 1)), text.to_string())
+        };
+        editor.apply_format_edits(&[edit(1, " = ")]).unwrap();
+        assert_eq!(editor.buffer.rope.to_string(), "x = 1\n");
+        assert_eq!(editor.cursor.col, 4);
+
+        editor.current_language = None;
+        run_ex(&mut editor, "fmt");
+        assert_eq!(editor.pending_lsp_action, None);
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No formatter available for this file type")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_code_action_applies_workspace_edit() {
+        use lsp_types::{Position as LspPosition, Range as LspRange, TextEdit, WorkspaceEdit};
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        let other = dir.path().join("lib.rs");
+        std::fs::write(&other, "pub fn helper() {}\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.buffer.file_path = Some(path.to_string_lossy().into_owned());
+        editor.buffer.insert_text("fn main() {\n    helper();\n}\n", 0, 0).unwrap();
+        editor.cursor.line = 1;
+        editor.cursor.col = 4;
+
+        editor.execute_command(Command::CodeAction);
+        let point = LspPosition::new(1, 4);
+        assert_eq!(
+            editor.pending_lsp_action,
+            Some(LspAction::CodeActions(LspRange::new(point, point)))
+        );
+        editor.pending_lsp_action = None;
+
+        let edit = |line, col, text: &str| {
+            let at = LspPosition::new(line, col);
+            TextEdit::new(LspRange::new(at, at), text.to_string())
+        };
+        let action = lsp_types::CodeAction {
+            title: "Import helper".to_string(),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([
+                    (editor.get_buffer_uri().unwrap(), vec![edit(0, 0, "use lib::helper;\n")]),
+                    (Url::from_file_path(&other).unwrap(), vec![edit(0, 0, "// lib\n")]),
+                ])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        editor.show_code_actions(vec![action]);
+        editor.execute_command(Command::CodeActionAccept);
+        assert!(editor.code_actions.is_none());
+        assert!(editor.run_pending_lsp_action().await);
+
+        assert_eq!(editor.status_message.as_deref(), Some("Applied: Import helper"));
+        assert_eq!(
+            editor.buffer.rope.to_string(),
+            "use lib::helper;\nfn main() {\n    helper();\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&other).unwrap(),
+            "// lib\npub fn helper() {}\n"
+        );
+
+        // The buffer change is one undo step
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "fn main() {\n    helper();\n}\n");
+    }
+
+    #[tokio::test]
+    async fn test_save_with_code_actions_queues_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        let mut editor = Editor::new();
+        editor.buffer.file_path = Some(path.to_string_lossy().into_owned());
+        editor.current_language = Some(LanguageId::Rust);
+        editor.options.organize_imports_on_save = true;
+        editor.buffer.insert_text("use b;\nuse a;\n", 0, 0).unwrap();
+
+        // The write waits for the server's edits
+        assert!(!run_ex(&mut editor, "w"));
+        assert!(!path.exists());
+        assert_eq!(
+            editor.pending_lsp_action,
+            Some(LspAction::Save {
+                path: path.to_string_lossy().into_owned(),
+                quit: false
+            })
+        );
+
+        // No server is running, so it just saves
+        assert!(editor.run_pending_lsp_action().await);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "use b;\nuse a;\n");
+        assert!(!editor.quit_requested);
+
+        assert!(!run_ex(&mut editor, "wq"));
+        editor.run_pending_lsp_action().await;
+        assert!(editor.quit_requested);
     }
 
     #[test]
-    fn test_viewport_scrolling_on_move() {
+    fn test_format_on_save() {
+        use crate::formatter::external::FormatterConfig;
+        let formatter = |command: &str, args: &[&str]| {
+            Formatter::new(FormatterConfig {
+                command: command.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                stdin_mode: true,
+            })
+            .ok()
+        };
+        let Some(upper) = formatter("tr", &["a-z", "A-Z"]) else {
+            // tr isn't installed
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
         let mut editor = Editor::new();
-        // Add many lines
-        for i in 0..30 {
-            if i > 0 {
-                editor.buffer.insert_char('\n', 0, 0).unwrap();
-            }
-            editor.buffer.insert_char('a', 0, 0).unwrap();
+        editor.buffer.file_path = Some(path.to_string_lossy().into_owned());
+        editor.buffer.insert_text("hello\n", 0, 0).unwrap();
+        editor.formatter = Some(upper);
+        editor.options.format_on_save = true;
+
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "HELLO\n");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "hello\n");
+
+        // A failing formatter doesn't stop the save
+        editor.formatter = formatter("bash", &["-c", "exit 1"]);
+        if editor.formatter.is_none() {
+            return;
         }
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+        let message = editor.status_message.clone().unwrap();
+        assert!(message.contains("written; format failed:"), "{}", message);
+    }
 
-        // Set viewport smaller than content
-        editor.viewport.rows = 10;
-        editor.viewport.cols = 80;
+    #[test]
+    fn test_nested_commands_are_one_undo_step() {
+        use crate::formatter::external::FormatterConfig;
+        let Ok(upper) = Formatter::new(FormatterConfig {
+            command: "tr".to_string(),
+            args: vec!["a-z".to_string(), "A-Z".to_string()],
+            stdin_mode: true,
+        }) else {
+            // tr isn't installed
+            return;
+        };
+        let mut editor = Editor::new();
+        editor.formatter = Some(upper);
+        editor.buffer.insert_text("hello", 0, 0).unwrap();
+        feed_keys(&mut editor, "x");
+        assert_eq!(editor.buffer.rope.to_string(), "ello");
 
-        // Move cursor to line 25
-        editor.cursor.line = 25;
-        editor
-            .viewport
-            .scroll_to_cursor(editor.cursor.line, editor.cursor.col);
+        // `:fmt` runs FormatBuffer inside the command line's own step
+        run_ex(&mut editor, "fmt");
+        assert_eq!(editor.buffer.rope.to_string(), "ELLO");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "ello");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "hello");
+        assert_eq!(editor.status_message.as_deref(), Some("1 change undone"));
 
-        // Offset should be adjusted to keep cursor visible
-        assert!(editor.cursor.line >= editor.viewport.offset_line);
-        assert!(editor.cursor.line < editor.viewport.offset_line + editor.viewport.rows);
+        // `.` repeats the change as one step too
+        feed_keys(&mut editor, "g~~.");
+        assert_eq!(editor.buffer.rope.to_string(), "hello");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "HELLO");
+        editor.execute_command(Command::Undo(1));
+        assert_eq!(editor.buffer.rope.to_string(), "hello");
+        assert_eq!(editor.status_message.as_deref(), Some("1 change undone"));
     }
 
     #[test]
-    fn test_cursor_col_adjustment_after_text_change() {
+    fn test_editorconfig_applies_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".editorconfig"),
+            "root = true\n[*.txt]\nindent_style = tab\nindent_size = 8\n\
+             trim_trailing_whitespace = true\nend_of_line = crlf\n",
+        )
+        .unwrap();
+        let notes = dir.path().join("notes.txt");
+        let other = dir.path().join("other.md");
+        std::fs::write(&notes, "a  \nb\t\n").unwrap();
+        std::fs::write(&other, "").unwrap();
+
         let mut editor = Editor::new();
-        editor.buffer.insert_char('a', 0, 0).unwrap();
-        editor.buffer.insert_char('b', 0, 1).unwrap();
-        editor.buffer.insert_char('c', 0, 2).unwrap();
+        let config: crate::config::TextyConfig =
+            toml::from_str("ensure_final_newline = true").unwrap();
+        editor.apply_config(&config);
+        editor.open_file(notes.to_str().unwrap()).unwrap();
+        assert!(!editor.options.expand_tab);
+        assert_eq!(editor.options.tab_width, 8);
+        assert!(editor.options.ensure_final_newline);
 
-        editor.cursor.col = 3; // Beyond line length
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "a\r\nb\r\n");
+        // Only the file gets the CRLF line breaks
+        assert_eq!(editor.buffer.rope.to_string(), "a\nb\n");
 
-        // Text change should clamp cursor
-        editor.notify_text_change();
-        let line_len = editor.buffer.line_len(0);
-        assert!(editor.cursor.col <= line_len);
+        // An edited line is saved whole
+        type_insert(&mut editor, Command::AppendLineEnd(1), "X");
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "aX\r\nb\r\n");
+        editor.execute_command(Command::Undo(2));
+        assert_eq!(editor.buffer.rope.to_string(), "a  \nb\t\n");
+
+        // The next file starts from the user's options again
+        editor.open_file(other.to_str().unwrap()).unwrap();
+        assert_eq!(editor.options.tab_width, 4);
+        assert!(editor.options.expand_tab);
+        assert_eq!(editor.options.line_ending, None);
     }
 
     #[test]
-    fn test_multiple_rapid_movements() {
+    fn test_diagnostics_columns_are_utf16() {
+        use lsp_types::{Position as LspPosition, Range as LspRange};
+        let dir = tempfile::tempdir().unwrap();
+        let other = dir.path().join("other.rs");
+        std::fs::write(&other, "// é😀 x\n").unwrap();
         let mut editor = Editor::new();
-        editor.buffer.insert_char('a', 0, 0).unwrap();
-        editor.buffer.insert_char('b', 0, 1).unwrap();
-        editor.buffer.insert_char('c', 0, 2).unwrap();
-        editor.buffer.insert_char('d', 0, 3).unwrap();
+        editor.buffer.file_path = Some(dir.path().join("main.rs").to_string_lossy().into_owned());
+        editor.buffer.insert_text("let 😀 = y;\n", 0, 0).unwrap();
+        let at = |character| Diagnostic {
+            range: LspRange::new(LspPosition::new(0, character), LspPosition::new(0, character)),
+            message: "here".to_string(),
+            ..Default::default()
+        };
+        {
+            let mut diagnostics = editor.diagnostics.lock().unwrap();
+            diagnostics.insert(editor.get_buffer_uri().unwrap(), vec![at(9)]);
+            diagnostics.insert(Url::from_file_path(&other).unwrap(), vec![at(7)]);
+        }
 
-        // Rapid movements should not overflow
-        for _ in 0..100 {
-            editor.execute_command(Command::MoveRight);
+        run_ex(&mut editor, "diagnostics");
+        let columns: Vec<_> = editor
+            .quickfix
+            .entries
+            .iter()
+            .map(|entry| (entry.path.file_name().unwrap().to_str().unwrap(), entry.col))
+            .collect();
+        assert_eq!(columns, [("main.rs", 8), ("other.rs", 6)]);
+    }
+
+    #[tokio::test]
+    async fn test_lsp_health_check_runs_in_the_background() {
+        let mut editor = Editor::new();
+        editor.start_lsp_health_check();
+        assert!(editor.lsp_health_check.is_some());
+        while !editor.lsp_health_check.as_ref().unwrap().is_finished() {
+            tokio::task::yield_now().await;
         }
-        assert_eq!(editor.cursor.col, 4); // Should be clamped to line length
+        // Nothing is running, so nothing was restarted
+        assert!(!editor.finish_lsp_health_check().await);
+        assert!(editor.lsp_health_check.is_none());
+        assert!(!editor.finish_lsp_health_check().await);
+    }
 
-        for _ in 0..100 {
-            editor.execute_command(Command::MoveLeft);
+    #[tokio::test]
+    async fn test_idle_hover_reply_is_dropped_once_the_cursor_moves() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("let x = y;\n", 0, 0).unwrap();
+        let answer = |editor: &mut Editor, col| {
+            let hover = lsp_types::Hover {
+                contents: lsp_types::HoverContents::Scalar(lsp_types::MarkedString::String(
+                    "i32".to_string(),
+                )),
+                range: None,
+            };
+            editor.idle_hover_request = Some(IdleHoverRequest {
+                position: lsp_types::Position::new(0, col),
+                version: editor.buffer.version,
+                handle: tokio::spawn(async move { Ok(Some(hover)) }),
+            });
+        };
+
+        answer(&mut editor, 4);
+        editor.cursor.col = 8;
+        while !editor.idle_hover_request.as_ref().unwrap().handle.is_finished() {
+            tokio::task::yield_now().await;
         }
-        assert_eq!(editor.cursor.col, 0); // Should be clamped to 0
+        assert!(!editor.finish_idle_hover().await);
+        assert!(editor.hover_content.is_none());
+
+        answer(&mut editor, 8);
+        while !editor.idle_hover_request.as_ref().unwrap().handle.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        assert!(editor.finish_idle_hover().await);
+        assert_eq!(editor.hover_content, Some(vec!["i32".to_string()]));
+        assert!(editor.dismiss_idle_hover());
+    }
+
+    #[tokio::test]
+    async fn test_idle_info() {
+        use lsp_types::{Position as LspPosition, Range as LspRange};
+        let dir = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new();
+        editor.buffer.file_path = Some(dir.path().join("main.rs").to_string_lossy().into_owned());
+        editor.buffer.insert_text("let x = y;\n", 0, 0).unwrap();
+        editor.current_language = Some(LanguageId::Rust);
+        let diagnostic = Diagnostic {
+            range: LspRange::new(LspPosition::new(0, 8), LspPosition::new(0, 9)),
+            severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+            message: "cannot find value `y`\nin this scope".to_string(),
+            ..Default::default()
+        };
+        editor
+            .diagnostics
+            .lock()
+            .unwrap()
+            .insert(editor.get_buffer_uri().unwrap(), vec![diagnostic]);
+
+        editor.cursor.col = 8;
+        assert!(editor.show_idle_info());
+        assert_eq!(
+            editor.hover_content,
+            Some(vec!["error: cannot find value `y`".to_string(), "in this scope".to_string()])
+        );
+        assert!(editor.dismiss_idle_hover());
+        assert!(editor.hover_content.is_none());
+        assert!(!editor.dismiss_idle_hover());
+
+        // Elsewhere the server is asked in the background, and without one
+        // nothing is shown
+        editor.cursor.col = 4;
+        assert!(!editor.show_idle_info());
+        assert_eq!(editor.pending_lsp_action, None);
+        let request = editor.idle_hover_request.as_ref().unwrap();
+        assert_eq!(request.position, LspPosition::new(0, 4));
+        while !editor.idle_hover_request.as_ref().unwrap().handle.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        assert!(!editor.finish_idle_hover().await);
+        assert!(!editor.is_waiting_for_idle_hover());
+        assert!(editor.hover_content.is_none());
+        assert_eq!(editor.status_message, None);
+
+        // Asking explicitly reports the failure
+        editor.execute_command(Command::Hover);
+        editor.run_pending_lsp_action().await;
+        assert!(editor.status_message.as_deref().unwrap().starts_with("Hover failed"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_without_servers() {
+        let mut editor = Editor::new();
+        editor.current_language = Some(LanguageId::Rust);
+        editor.shutdown().await;
     }
 
     #[test]
@@ -1338,4 +7777,338 @@ mod tests {
             assert_eq!(editor.cursor.line, i - 1);
         }
     }
+
+    #[test]
+    fn test_escape_always_drops_pending_keys() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("abcdef\n", 0, 0).unwrap();
+
+        feed_keys(&mut editor, "v3\x1bx");
+        assert_eq!(editor.buffer.rope.to_string(), "bcdef\n");
+
+        editor.hover_content = Some(vec!["fn main()".to_string()]);
+        feed_keys(&mut editor, "3\x1b");
+        assert!(editor.hover_content.is_none());
+        assert!(!editor.vim_parser.is_pending());
+        feed_keys(&mut editor, "x");
+        assert_eq!(editor.buffer.rope.to_string(), "cdef\n");
+    }
+
+    #[test]
+    fn test_escape_closes_one_thing_at_a_time() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one two one", 0, 0).unwrap();
+        editor.execute_command(Command::AddCursor);
+        editor.execute_command(Command::VisualChar);
+        feed_keys(&mut editor, "2");
+        editor.fuzzy_search = Some(FuzzySearchState::new_recent(Vec::new()));
+        // Opening one of these closes the others, so they are set directly
+        editor.hover_content = Some(vec!["fn main()".to_string()]);
+        editor.code_actions = Some(vec![lsp_types::CodeAction::default()]);
+        editor.completion_popup.set_items(vec![lsp_types::CompletionItem::default()]);
+
+        feed_keys(&mut editor, "\x1b");
+        assert!(!editor.completion_popup.is_visible());
+        assert!(!editor.vim_parser.is_pending());
+        assert!(editor.code_actions.is_some());
+
+        feed_keys(&mut editor, "\x1b");
+        assert!(editor.code_actions.is_none());
+        assert!(editor.hover_content.is_some());
+
+        feed_keys(&mut editor, "\x1b");
+        assert!(editor.hover_content.is_none());
+        assert!(editor.fuzzy_search.is_some());
+
+        feed_keys(&mut editor, "\x1b");
+        assert!(editor.fuzzy_search.is_none());
+        assert_eq!(editor.mode, Mode::Visual);
+
+        feed_keys(&mut editor, "\x1b");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert!(!editor.vim_parser.is_pending());
+        assert!(!editor.cursors.is_empty());
+
+        feed_keys(&mut editor, "\x1b");
+        assert!(editor.cursors.is_empty());
+
+        // Nothing left to close
+        feed_keys(&mut editor, "\x1b");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.buffer.rope.to_string(), "one two one");
+    }
+
+    #[test]
+    fn test_overlays_replace_each_other_and_close_together() {
+        let mut editor = Editor::new();
+        editor.show_hover(vec!["fn main()".to_string()]);
+        editor.show_completions(vec![lsp_types::CompletionItem::default()]);
+        assert!(editor.hover_content.is_none());
+        assert!(editor.completion_popup.is_visible());
+
+        editor.show_code_actions(vec![lsp_types::CodeAction::default()]);
+        assert!(!editor.completion_popup.is_visible());
+        editor.show_hover(vec!["fn main()".to_string()]);
+        assert!(editor.code_actions.is_none());
+
+        editor.show_completions(vec![lsp_types::CompletionItem::default()]);
+        editor.quickfix_open = true;
+        editor.show_error("Format failed");
+        run_ex(&mut editor, "messages");
+        assert!(editor.listing.is_some());
+        editor.execute_command(Command::VisualChar);
+        editor.execute_command(Command::CloseAllOverlays);
+        assert!(!editor.completion_popup.is_visible());
+        assert!(editor.listing.is_none());
+        assert!(!editor.quickfix_open);
+        // Unlike Escape, it leaves the selection alone
+        assert_eq!(editor.mode, Mode::Visual);
+    }
+
+    #[test]
+    fn test_escape_leaves_fuzzy_search_mode() {
+        let mut editor = Editor::new();
+        editor.fuzzy_search = Some(FuzzySearchState::new_recent(Vec::new()));
+        editor.mode = Mode::FuzzySearch;
+        editor.execute_command(Command::Escape);
+        assert!(editor.fuzzy_search.is_none());
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_quickfix_steps_through_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "one\ntwo match\n").unwrap();
+        std::fs::write(&b, "match here\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(a.to_str().unwrap()).unwrap();
+        editor.set_quickfix(
+            "grep match",
+            vec![
+                QuickfixEntry::new(a.clone(), 1, 4, "two match"),
+                QuickfixEntry::new(b.clone(), 0, 0, "match here"),
+            ],
+        );
+        assert!(editor.quickfix_open);
+        editor.execute_command(Command::Escape);
+        assert!(!editor.quickfix_open);
+
+        feed_keys(&mut editor, "]q");
+        assert!(editor.buffer.file_path.as_deref().unwrap().ends_with("b.txt"));
+        assert_eq!(editor.status_message.as_deref(), Some("(2 of 2) match here"));
+
+        // An unsaved buffer is not replaced by the jump
+        feed_keys(&mut editor, "ix\x1b[q");
+        assert!(editor.buffer.file_path.as_deref().unwrap().ends_with("b.txt"));
+        editor.buffer.modified = false;
+
+        run_ex(&mut editor, "copen");
+        editor.execute_command(Command::QuickfixSelectPrev);
+        editor.execute_command(Command::QuickfixSelectPrev);
+        editor.execute_command(Command::QuickfixAccept);
+        assert!(!editor.quickfix_open);
+        assert!(editor.buffer.file_path.as_deref().unwrap().ends_with("a.txt"));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 4));
+
+        run_ex(&mut editor, "grep (");
+        assert!(editor.status_message.unwrap().starts_with("Invalid pattern"));
+    }
+
+    #[test]
+    fn test_make_lists_build_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        std::fs::write(&source, "int main() {\n  return x;\n}\n").unwrap();
+
+        // The build runs in the background until it is waited for
+        let make = |editor: &mut Editor, command: &str| {
+            run_ex(editor, command);
+            while editor.is_making() {
+                editor.finish_make();
+                std::thread::yield_now();
+            }
+        };
+
+        let mut editor = Editor::new();
+        editor.options.make_command = "sleep 0.2; printf '%s\\n' building".to_string();
+        run_ex(&mut editor, "make");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("Running sleep 0.2; printf '%s\\n' building...")
+        );
+        run_ex(&mut editor, "make");
+        assert!(editor.status_message.as_deref().unwrap().ends_with(": still running"));
+        while !editor.finish_make() {
+            std::thread::yield_now();
+        }
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("sleep 0.2; printf '%s\\n' building: finished")
+        );
+        assert!(!editor.quickfix_open);
+
+        // Arguments are added to the command, and errors go to stderr
+        editor.options.make_command = "printf '%s\\n' >&2".to_string();
+        let error = format!("'{}:2:10: error: x undeclared'; exit 1", source.display());
+        make(&mut editor, &format!("make {}", error));
+        assert!(editor.quickfix_open);
+        assert_eq!(
+            editor.quickfix.entries,
+            [QuickfixEntry::new(source.clone(), 1, 9, "error: x undeclared")]
+        );
+        assert!(editor.status_message.as_deref().unwrap().ends_with(": 1 error"));
+
+        feed_keys(&mut editor, "\x1b[q");
+        assert_eq!(editor.buffer.file_path.as_deref(), source.to_str());
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 9));
+
+        editor.options.make_command = "exit 2".to_string();
+        make(&mut editor, "make");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("exit 2: failed, but no errors were found")
+        );
+        editor.options.error_format = "(".to_string();
+        run_ex(&mut editor, "make");
+        assert!(!editor.is_making());
+        assert!(editor.status_message.unwrap().starts_with("Invalid error_format"));
+    }
+
+    #[test]
+    fn test_references_fill_quickfix_with_their_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.rs");
+        std::fs::write(&a, "fn main() {}\n").unwrap();
+        std::fs::write(&b, "// 😀 main\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(a.to_str().unwrap()).unwrap();
+        editor.execute_command(Command::FindReferences);
+        assert_eq!(
+            editor.pending_lsp_action,
+            Some(LspAction::References(lsp_types::Position::new(0, 0)))
+        );
+
+        // The open buffer is read rather than the file, which may be stale
+        editor.buffer.insert_text("\n", 0, 0).unwrap();
+        let location = |path: &std::path::Path, line, character| {
+            let start = lsp_types::Position::new(line, character);
+            let range = lsp_types::Range::new(start, start);
+            lsp_types::Location::new(Url::from_file_path(path).unwrap(), range)
+        };
+        editor.set_quickfix_locations("References", &[location(&a, 1, 3), location(&b, 0, 6)]);
+
+        let found: Vec<_> = editor
+            .quickfix
+            .entries
+            .iter()
+            .map(|e| (e.line, e.col, e.text.as_str()))
+            .collect();
+        // The emoji is two UTF-16 units but one char
+        assert_eq!(found, [(1, 3, "fn main() {}"), (0, 5, "// 😀 main")]);
+        assert!(editor.quickfix_open);
+    }
+
+    #[test]
+    fn test_workspace_symbol_picker_jumps_to_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.rs");
+        let lib = dir.path().join("lib.rs");
+        std::fs::write(&main, "fn main() {}\n").unwrap();
+        std::fs::write(&lib, "// 😀\nconst 😀: u8 = 0; fn parse() {}\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.current_language = None;
+        run_ex(&mut editor, "symbols");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No language server for this file type")
+        );
+        assert!(editor.fuzzy_search.is_none());
+
+        editor.open_file(main.to_str().unwrap()).unwrap();
+        run_ex(&mut editor, "symbols par");
+        assert_eq!(editor.mode, Mode::FuzzySearch);
+
+        let uri = Url::from_file_path(&lib).unwrap();
+        #[allow(deprecated)]
+        let symbol = lsp_types::SymbolInformation {
+            name: "parse".to_string(),
+            kind: lsp_types::SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: lsp_types::Location::new(
+                uri,
+                lsp_types::Range::new(lsp_types::Position::new(1, 22), lsp_types::Position::new(1, 27)),
+            ),
+            container_name: Some("lib".to_string()),
+        };
+        let items = symbol_items(lsp_types::WorkspaceSymbolResponse::Flat(vec![symbol]));
+        assert_eq!(items[0].kind, "function");
+        assert_eq!(symbol_kind_name(lsp_types::SymbolKind::ENUM_MEMBER), "enum member");
+        editor.fuzzy_search.as_mut().unwrap().set_symbols(items);
+
+        editor.execute_command(Command::FuzzySearchSelect);
+        assert!(editor.fuzzy_search.is_none());
+        assert_eq!(editor.mode, Mode::Normal);
+        assert!(editor.buffer.file_path.as_deref().unwrap().ends_with("lib.rs"));
+        // The emoji before it is two UTF-16 units but one char
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 21));
+    }
+
+    #[tokio::test]
+    async fn test_inlay_hints_shift_the_display_but_not_the_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "let x = f(1);\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.open_file(path.to_str().unwrap()).unwrap();
+        // Asked for once the view has stayed put for a moment
+        assert!(!editor.poll_inlay_hints());
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(editor.poll_inlay_hints());
+        let request = editor.inlay_hint_request.as_mut().unwrap();
+        assert_eq!(request.lines, 0..2);
+        // Answer in place of a server
+        let hint = |character, label: &str| lsp_types::InlayHint {
+            position: lsp_types::Position::new(0, character),
+            label: lsp_types::InlayHintLabel::String(label.to_string()),
+            kind: None,
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: None,
+        };
+        let hints = vec![hint(5, ": i32"), hint(10, "n: ")];
+        request.handle.abort();
+        request.handle = tokio::spawn(async move { Some(hints) });
+        while !editor.inlay_hint_request.as_ref().unwrap().handle.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        assert!(editor.finish_inlay_hints().await);
+        assert!(!editor.is_waiting_for_inlay_hints());
+
+        // `f` is drawn after the type hint, `1` after the parameter name
+        editor.cursor.col = 8;
+        assert_eq!(editor.cursor_display_col(), 13);
+        editor.cursor.col = 10;
+        assert_eq!(editor.cursor_display_col(), 18);
+        let text = Gutter::WIDTH;
+        assert_eq!(editor.screen_to_buffer(0, text + 13), Some(Position::new(0, 8)));
+        // Clicking a hint lands after it
+        assert_eq!(editor.screen_to_buffer(0, text + 6), Some(Position::new(0, 5)));
+
+        // The hints are never written, and an edit drops them
+        run_ex(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "let x = f(1);\n");
+        feed_keys(&mut editor, "x");
+        assert!(!editor.poll_inlay_hints());
+        assert!(editor.inlay_hints.line(0).is_empty());
+    }
 }
@@ -0,0 +1,382 @@
+// src/editorconfig.rs - Per-project settings from `.editorconfig` files
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How line breaks are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// `text` with every line break written as this ending
+    pub fn convert(self, text: &str) -> String {
+        let mut converted = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    converted.push_str(self.as_str());
+                }
+                '\n' => converted.push_str(self.as_str()),
+                c => converted.push(c),
+            }
+        }
+        converted
+    }
+}
+
+/// Settings for one file. `None` leaves the user's own setting in place.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditorConfig {
+    /// `indent_style`: spaces (true) or tabs
+    pub expand_tab: Option<bool>,
+    /// `indent_size`, or `tab_width` when indenting by tabs
+    pub indent_size: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfig {
+    /// Settings for `path` from the `.editorconfig` files in its directory
+    /// and the ones above, stopping at one marked `root = true`
+    pub fn for_file(path: &Path) -> Self {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let Ok(content) = std::fs::read_to_string(dir.join(".editorconfig")) else {
+                continue;
+            };
+            let file = ConfigFile::parse(&content);
+            let root = file.root;
+            files.push((dir.to_path_buf(), file));
+            if root {
+                break;
+            }
+        }
+
+        // Files nearer to `path` override the ones further up
+        let mut properties = HashMap::new();
+        for (dir, file) in files.iter().rev() {
+            if let Ok(relative) = path.strip_prefix(dir) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                file.apply(&relative, &mut properties);
+            }
+        }
+        Self::from_properties(&properties)
+    }
+
+    fn from_properties(properties: &HashMap<String, String>) -> Self {
+        let get = |key: &str| properties.get(key).map(String::as_str);
+        let size = |key: &str| get(key).and_then(|v| v.parse().ok()).filter(|&n: &usize| n > 0);
+        let flag = |key: &str| match get(key) {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        };
+        Self {
+            expand_tab: match get("indent_style") {
+                Some("space") => Some(true),
+                Some("tab") => Some(false),
+                _ => None,
+            },
+            // `indent_size = tab` isn't a number, so the tab width is used
+            indent_size: size("indent_size").or_else(|| size("tab_width")),
+            end_of_line: match get("end_of_line") {
+                Some("lf") => Some(LineEnding::Lf),
+                Some("crlf") => Some(LineEnding::CrLf),
+                Some("cr") => Some(LineEnding::Cr),
+                _ => None,
+            },
+            trim_trailing_whitespace: flag("trim_trailing_whitespace"),
+            insert_final_newline: flag("insert_final_newline"),
+        }
+    }
+}
+
+/// One parsed `.editorconfig` file
+#[derive(Debug, Default)]
+struct ConfigFile {
+    /// Set in the preamble to stop looking in parent directories
+    root: bool,
+    /// Section globs with their properties, in file order
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl ConfigFile {
+    fn parse(content: &str) -> Self {
+        let mut file = ConfigFile::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                file.sections.push((glob.to_string(), Vec::new()));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            // Keys and the values of the supported properties are case-insensitive
+            let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+            match file.sections.last_mut() {
+                Some((_, properties)) => properties.push((key, value)),
+                None if key == "root" => file.root = value == "true",
+                None => {}
+            }
+        }
+        file
+    }
+
+    /// Merge the properties of the sections matching `path`, which is
+    /// relative to this file's directory. Later sections win.
+    fn apply(&self, path: &str, properties: &mut HashMap<String, String>) {
+        for (glob, section) in &self.sections {
+            if !glob_matches(glob, path) {
+                continue;
+            }
+            for (key, value) in section {
+                if value == "unset" {
+                    properties.remove(key);
+                } else {
+                    properties.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Whether `path` matches a section glob. A glob without a `/` matches the
+/// file name in any directory; one with a `/` matches from the directory of
+/// the `.editorconfig`.
+fn glob_matches(glob: &str, path: &str) -> bool {
+    let target = if glob.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    let target: Vec<char> = target.chars().collect();
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    expand_braces(glob).iter().any(|pattern| {
+        let pattern: Vec<char> = pattern.chars().collect();
+        pattern_matches(&pattern, &target)
+    })
+}
+
+/// Expand `{a,b}` alternatives, nested or not, into plain patterns. Braces
+/// without a comma or without a closing brace are taken literally.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let mut depth = 0;
+    let mut close = None;
+    for (i, c) in glob[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return vec![glob.to_string()];
+    };
+
+    let (prefix, body, suffix) = (&glob[..open], &glob[open + 1..close], &glob[close + 1..]);
+    let alternatives: Vec<String> = match split_alternatives(body) {
+        parts if parts.len() > 1 => parts.iter().flat_map(|part| expand_braces(part)).collect(),
+        _ => vec![format!("{{{}}}", body)],
+    };
+    let suffixes = expand_braces(suffix);
+    alternatives
+        .iter()
+        .flat_map(|alternative| {
+            suffixes
+                .iter()
+                .map(move |suffix| format!("{}{}{}", prefix, alternative, suffix))
+        })
+        .collect()
+}
+
+/// Split a brace body at its top-level commas
+fn split_alternatives(body: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in body.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&body[start..]);
+    parts
+}
+
+/// Match a brace-free pattern: `*` within a path component, `**` across
+/// components, `?` one character, `[abc]`/`[a-z]`/`[!abc]` a set
+fn pattern_matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| pattern_matches(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            for i in 0..=text.len() {
+                if pattern_matches(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    return false;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => {
+            text.first().is_some_and(|&c| c != '/') && pattern_matches(rest, &text[1..])
+        }
+        ['[', set @ ..] if set.contains(&']') => {
+            let close = set.iter().position(|&c| c == ']').unwrap_or_default();
+            let (negated, members) = match &set[..close] {
+                ['!', members @ ..] => (true, members),
+                members => (false, members),
+            };
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            set_contains(members, c) != negated && pattern_matches(&set[close + 1..], &text[1..])
+        }
+        ['\\', escaped, rest @ ..] => {
+            text.first() == Some(escaped) && pattern_matches(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && pattern_matches(rest, &text[1..]),
+    }
+}
+
+/// Whether a `[...]` set, with `a-z` style ranges, contains `c`
+fn set_contains(members: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < members.len() {
+        if members.get(i + 1) == Some(&'-') && i + 2 < members.len() {
+            if (members[i]..=members[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if members[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "src/main.rs"));
+        assert!(glob_matches("*.rs", "src/main.rs"));
+        assert!(!glob_matches("*.rs", "src/main.py"));
+        assert!(glob_matches("*.{js,ts}", "web/app.ts"));
+        assert!(glob_matches("{Makefile,*.mk}", "build/rules.mk"));
+        assert!(glob_matches("file?.[ch]", "file1.c"));
+        assert!(!glob_matches("file?.[!ch]", "file1.c"));
+        assert!(glob_matches("[a-c]*", "beta"));
+        // Globs with a slash start at the .editorconfig's directory
+        assert!(glob_matches("src/*.rs", "src/main.rs"));
+        assert!(glob_matches("/src/*.rs", "src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "src/lsp/client.rs"));
+        assert!(glob_matches("src/**.rs", "src/lsp/client.rs"));
+        assert!(!glob_matches("src/*.rs", "other/src/main.rs"));
+        // Braces without alternatives are literal
+        assert!(glob_matches("{a}.txt", "{a}.txt"));
+    }
+
+    #[test]
+    fn test_sections_merge_in_order() {
+        let file = ConfigFile::parse(
+            "root = true\n\n[*]\nindent_style = space\nindent_size = 4\n\n\
+             # Makefiles need tabs\n[Makefile]\nindent_style = Tab\nindent_size = unset\n",
+        );
+        assert!(file.root);
+
+        let mut properties = HashMap::new();
+        file.apply("Makefile", &mut properties);
+        let config = EditorConfig::from_properties(&properties);
+        assert_eq!(config.expand_tab, Some(false));
+        assert_eq!(config.indent_size, None);
+
+        let mut properties = HashMap::new();
+        file.apply("src/main.rs", &mut properties);
+        let config = EditorConfig::from_properties(&properties);
+        assert_eq!(config.expand_tab, Some(true));
+        assert_eq!(config.indent_size, Some(4));
+    }
+
+    #[test]
+    fn test_for_file_walks_up_to_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        // Above the root file, so never read
+        std::fs::write(dir.path().join(".editorconfig"), "[*]\ninsert_final_newline = false\n")
+            .unwrap();
+        std::fs::write(
+            project.join(".editorconfig"),
+            "root = true\n[*]\nindent_size = 4\nend_of_line = crlf\ninsert_final_newline = true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            project.join("src/.editorconfig"),
+            "[*.rs]\nindent_size = 2\ntrim_trailing_whitespace = true\n",
+        )
+        .unwrap();
+
+        let config = EditorConfig::for_file(&project.join("src/main.rs"));
+        assert_eq!(
+            config,
+            EditorConfig {
+                expand_tab: None,
+                indent_size: Some(2),
+                end_of_line: Some(LineEnding::CrLf),
+                trim_trailing_whitespace: Some(true),
+                insert_final_newline: Some(true),
+            }
+        );
+        let config = EditorConfig::for_file(&project.join("README.md"));
+        assert_eq!(config.indent_size, Some(4));
+        assert_eq!(config.trim_trailing_whitespace, None);
+    }
+
+    #[test]
+    fn test_convert_line_endings() {
+        assert_eq!(LineEnding::CrLf.convert("a\nb\r\nc\rd"), "a\r\nb\r\nc\r\nd");
+        assert_eq!(LineEnding::Lf.convert("a\r\nb\r"), "a\nb\n");
+    }
+}
@@ -0,0 +1,865 @@
+// src/ex.rs - Parsing helpers for ex command lines (ranges, :s, :g, :sort, :retab, :command)
+
+use crate::display::char_width;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// An inclusive range of 0-based buffer lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    pub fn new(start: usize, end: usize) -> Self {
+        if start <= end {
+            Self { start, end }
+        } else {
+            Self {
+                start: end,
+                end: start,
+            }
+        }
+    }
+
+    pub fn single(line: usize) -> Self {
+        Self {
+            start: line,
+            end: line,
+        }
+    }
+}
+
+/// Line positions that ex addresses are resolved against (all 0-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressContext {
+    /// Cursor line, for `.`
+    pub current: usize,
+    /// Last buffer line, for `$`
+    pub last: usize,
+    /// Last visual selection, for `'<` and `'>`
+    pub visual: Option<LineRange>,
+}
+
+/// Parse a leading line range (`%`, `N`, `.`, `$`, `'<`, `'>`, `N,M`) off an ex command.
+/// Addresses may carry `+N`/`-N` offsets (`.+2`, `$-1`, or `-3` from the cursor line).
+///
+/// Returns the range (if one was given) and the remainder of the command line.
+pub fn parse_range(input: &str, ctx: AddressContext) -> (Option<LineRange>, &str) {
+    if let Some(rest) = input.strip_prefix('%') {
+        return (Some(LineRange::new(0, ctx.last)), rest.trim_start());
+    }
+
+    let (start, rest) = match parse_address(input, ctx) {
+        Some(parsed) => parsed,
+        None => return (None, input),
+    };
+
+    if let Some(after_comma) = rest.strip_prefix(',')
+        && let Some((end, rest)) = parse_address(after_comma, ctx)
+    {
+        return (Some(LineRange::new(start, end)), rest.trim_start());
+    }
+
+    (Some(LineRange::single(start)), rest.trim_start())
+}
+
+/// Parse a single line address with any offsets, returning the 0-based line
+/// (clamped to the buffer) and the remainder
+fn parse_address(input: &str, ctx: AddressContext) -> Option<(usize, &str)> {
+    let (base, mut rest) = match parse_base_address(input, ctx) {
+        Some((line, rest)) => (Some(line), rest),
+        None => (None, input),
+    };
+
+    // A bare offset counts from the cursor line; `+` alone means `+1`
+    let mut line = base.unwrap_or(ctx.current) as isize;
+    let mut has_offset = false;
+    while let Some(sign) = rest.chars().next().filter(|&c| c == '+' || c == '-') {
+        let digits = rest[1..].chars().take_while(|c| c.is_ascii_digit()).count();
+        let amount: isize = if digits == 0 {
+            1
+        } else {
+            rest[1..=digits].parse().ok()?
+        };
+        line += if sign == '+' { amount } else { -amount };
+        rest = &rest[1 + digits..];
+        has_offset = true;
+    }
+    if base.is_none() && !has_offset {
+        return None;
+    }
+    Some((line.clamp(0, ctx.last as isize) as usize, rest))
+}
+
+/// Parse the line an address starts from, without offsets
+fn parse_base_address(input: &str, ctx: AddressContext) -> Option<(usize, &str)> {
+    let last = ctx.last;
+    if let Some(rest) = input.strip_prefix('.') {
+        return Some((ctx.current, rest));
+    }
+    if let Some(rest) = input.strip_prefix('$') {
+        return Some((last, rest));
+    }
+    if let Some(rest) = input.strip_prefix("'<") {
+        return ctx.visual.map(|range| (range.start.min(last), rest));
+    }
+    if let Some(rest) = input.strip_prefix("'>") {
+        return ctx.visual.map(|range| (range.end.min(last), rest));
+    }
+
+    let digits = input.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let number: usize = input[..digits].parse().ok()?;
+    Some((number.saturating_sub(1).min(last), &input[digits..]))
+}
+
+/// Split `text` on an unescaped `delim`. `\<delim>` yields a literal delimiter,
+/// any other escape is kept as-is for the regex engine.
+pub fn split_delimited(text: &str, delim: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delim => fields.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let field = fields.last_mut().unwrap();
+                    field.push('\\');
+                    field.push(next);
+                }
+                None => fields.last_mut().unwrap().push('\\'),
+            }
+        } else if c == delim {
+            fields.push(String::new());
+        } else {
+            fields.last_mut().unwrap().push(c);
+        }
+    }
+    fields
+}
+
+/// A parsed `:s/pattern/replacement/flags` command
+#[derive(Debug, Clone)]
+pub struct Substitute {
+    pub pattern: Regex,
+    /// Replacement in `regex` crate syntax (`$1`, `$0`)
+    pub replacement: String,
+    /// `g` flag: replace every match on the line, not only the first
+    pub global: bool,
+}
+
+impl Substitute {
+    /// Apply the substitution to one line, returning the new text if it changed
+    pub fn apply(&self, line: &str) -> Option<String> {
+        if !self.pattern.is_match(line) {
+            return None;
+        }
+        let replaced = if self.global {
+            self.pattern.replace_all(line, self.replacement.as_str())
+        } else {
+            self.pattern.replace(line, self.replacement.as_str())
+        };
+        Some(replaced.into_owned())
+    }
+}
+
+/// Parse the body of a substitute command, e.g. `s/foo/bar/g`. An empty
+/// pattern (`s//bar/`) reuses `last_pattern`, the last one searched for.
+/// Returns `None` if the input is not a substitute command.
+pub fn parse_substitute(
+    input: &str,
+    last_pattern: Option<&Regex>,
+    smart_case: bool,
+) -> Option<Result<Substitute, String>> {
+    let body = input
+        .strip_prefix("substitute")
+        .or_else(|| input.strip_prefix("s"))?;
+    let delim = body.chars().next()?;
+    if delim.is_alphanumeric() || delim.is_whitespace() || delim == '\\' {
+        return None;
+    }
+
+    let fields = split_delimited(&body[delim.len_utf8()..], delim);
+    let pattern = fields[0].as_str();
+    let replacement = fields.get(1).map(String::as_str).unwrap_or("");
+    let flags = fields.get(2).map(String::as_str).unwrap_or("");
+
+    let pattern = if pattern.is_empty() {
+        last_pattern
+            .cloned()
+            .ok_or_else(|| "No previous regular expression".to_string())
+    } else {
+        compile_pattern(pattern, smart_case)
+    };
+    Some(pattern.map(|pattern| Substitute {
+        pattern,
+        replacement: convert_replacement(replacement),
+        global: flags.contains('g'),
+    }))
+}
+
+/// Parse `:&&`, `:&` or a bare `:s`, which repeat the last substitution.
+/// Returns whether its flags are kept; only `:&&` keeps them.
+pub fn parse_repeat_substitute(input: &str) -> Option<bool> {
+    match input.trim_end() {
+        "&&" => Some(true),
+        "&" | "s" | "substitute" => Some(false),
+        _ => None,
+    }
+}
+
+/// A parsed `:g/pattern/cmd` (or `:v/pattern/cmd`) command
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub pattern: Regex,
+    /// `:v` / `:g!`: run on lines that do NOT match
+    pub invert: bool,
+    /// Ex command to run on each selected line
+    pub command: String,
+}
+
+impl Global {
+    pub fn selects(&self, line: &str) -> bool {
+        self.pattern.is_match(line) != self.invert
+    }
+}
+
+/// Parse a global command, e.g. `g/TODO/d` or `v/^$/s/x/y/`.
+/// Returns `None` if the input is not a global command.
+pub fn parse_global(input: &str, smart_case: bool) -> Option<Result<Global, String>> {
+    let (body, invert) = if let Some(rest) = input.strip_prefix("g!") {
+        (rest, true)
+    } else if let Some(rest) = input
+        .strip_prefix("global")
+        .or_else(|| input.strip_prefix("g"))
+    {
+        (rest, false)
+    } else if let Some(rest) = input
+        .strip_prefix("vglobal")
+        .or_else(|| input.strip_prefix("v"))
+    {
+        (rest, true)
+    } else {
+        return None;
+    };
+
+    let delim = body.chars().next()?;
+    if delim.is_alphanumeric() || delim.is_whitespace() || delim == '\\' {
+        return None;
+    }
+    let rest = &body[delim.len_utf8()..];
+
+    // Only the first unescaped delimiter ends the pattern; the command keeps its own
+    let mut end = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == delim {
+            end = Some(i);
+            break;
+        }
+    }
+    let (pattern, command) = match end {
+        Some(i) => (&rest[..i], rest[i + delim.len_utf8()..].trim()),
+        None => (rest, ""),
+    };
+    let pattern = split_delimited(pattern, delim).concat();
+
+    Some(compile_pattern(&pattern, smart_case).map(|pattern| Global {
+        pattern,
+        invert,
+        command: if command.is_empty() {
+            "p".to_string()
+        } else {
+            command.to_string()
+        },
+    }))
+}
+
+/// Parse a `!cmd` filter/shell command, returning the command text
+pub fn parse_bang(input: &str) -> Option<&str> {
+    input.strip_prefix('!').map(str::trim)
+}
+
+/// `:d` / `:y`: what to do with the lines of a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCommand {
+    Delete,
+    Yank,
+}
+
+/// Parse `d [x]` / `y [x]` (or `delete`, `yank`, ...) with the register to
+/// put the lines in. Returns `None` if the input is not one of them.
+pub fn parse_line_command(input: &str) -> Option<Result<(LineCommand, Option<char>), String>> {
+    let (name, arg) = input.split_once(' ').unwrap_or((input, ""));
+    let command = if !name.is_empty() && "delete".starts_with(name) {
+        LineCommand::Delete
+    } else if !name.is_empty() && "yank".starts_with(name) {
+        LineCommand::Yank
+    } else {
+        return None;
+    };
+    let mut chars = arg.trim().chars();
+    Some(match (chars.next(), chars.next()) {
+        (None, _) => Ok((command, None)),
+        (Some(reg), None) if reg.is_ascii_alphanumeric() || "\"-+*".contains(reg) => {
+            Ok((command, Some(reg)))
+        }
+        _ => Err(format!("Invalid register: {}", arg.trim())),
+    })
+}
+
+/// Most user commands expanded one after another, so two that use each
+/// other can't loop forever
+const MAX_USER_COMMAND_DEPTH: usize = 10;
+
+/// Parse `{Name} {definition}`, the arguments of `:command`. Names are
+/// letters and digits starting with an uppercase letter, so they can't hide
+/// the built-in commands.
+pub fn parse_user_command(input: &str) -> Result<(String, String), String> {
+    let input = input.trim();
+    let (name, definition) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    if !is_user_command_name(name) {
+        return Err(format!(
+            "User command names start with an uppercase letter: {}",
+            name
+        ));
+    }
+    let definition = definition.trim();
+    if definition.is_empty() {
+        return Err(format!("Missing definition for {}", name));
+    }
+    Ok((name.to_string(), definition.to_string()))
+}
+
+/// Whether `name` can be given to `:command`
+pub fn is_user_command_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Replace the user command at the start of `input`, after any range, with
+/// its definition, adding the arguments typed after the name. Definitions
+/// may use other user commands. Returns `None` if `input` doesn't run one.
+pub fn expand_user_command(
+    input: &str,
+    commands: &HashMap<String, String>,
+    ctx: AddressContext,
+) -> Option<Result<String, String>> {
+    let mut line = input.to_string();
+    for _ in 0..MAX_USER_COMMAND_DEPTH {
+        let (_, rest) = parse_range(&line, ctx);
+        let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let Some(definition) = commands.get(name) else {
+            return (line != input).then_some(Ok(line));
+        };
+        let range = &line[..line.len() - rest.len()];
+        let args = args.trim();
+        line = if args.is_empty() {
+            format!("{}{}", range, definition)
+        } else {
+            format!("{}{} {}", range, definition, args)
+        };
+    }
+    Some(Err(format!("User commands nested too deeply: {}", input)))
+}
+
+/// Flags for `:sort`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SortOptions {
+    /// `!`: reverse the order
+    pub reverse: bool,
+    /// `u`: keep only the first of a run of equal lines
+    pub unique: bool,
+    /// `n`: sort on the first decimal number in each line. Lines without one
+    /// sort first and count as equal, as in Vim.
+    pub numeric: bool,
+}
+
+/// Parse `sort[!] [nu]`. Returns `None` if the input is not a sort command.
+pub fn parse_sort(input: &str) -> Option<Result<SortOptions, String>> {
+    let body = input
+        .strip_prefix("sort")
+        .or_else(|| input.strip_prefix("sor"))?;
+    let (body, reverse) = match body.strip_prefix('!') {
+        Some(rest) => (rest, true),
+        None => (body, false),
+    };
+    if body.starts_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+
+    let mut options = SortOptions {
+        reverse,
+        ..SortOptions::default()
+    };
+    for flag in body.chars().filter(|c| !c.is_whitespace()) {
+        match flag {
+            'u' => options.unique = true,
+            'n' => options.numeric = true,
+            other => return Some(Err(format!("Invalid sort flag: {}", other))),
+        }
+    }
+    Some(Ok(options))
+}
+
+/// Sort lines in place according to `options`. The sort is stable.
+pub fn sort_lines(lines: &mut Vec<String>, options: SortOptions) {
+    let compare = |a: &String, b: &String| {
+        if options.numeric {
+            // Lines without a number sort before all numbered lines
+            first_number(a).cmp(&first_number(b))
+        } else {
+            a.cmp(b)
+        }
+    };
+
+    if options.reverse {
+        lines.sort_by(|a, b| compare(b, a));
+    } else {
+        lines.sort_by(compare);
+    }
+    if options.unique {
+        lines.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+    }
+}
+
+/// Flags for `:retab`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetabOptions {
+    /// `!`: turn runs of spaces into tabs instead of tabs into spaces
+    pub to_tabs: bool,
+    /// `a`: convert whitespace anywhere in the line, not just the indent
+    pub all: bool,
+}
+
+/// Parse `retab[!] [a]`. Returns `None` if the input is not a retab command.
+pub fn parse_retab(input: &str) -> Option<Result<RetabOptions, String>> {
+    let body = input
+        .strip_prefix("retab")
+        .or_else(|| input.strip_prefix("ret"))?;
+    let (body, to_tabs) = match body.strip_prefix('!') {
+        Some(rest) => (rest, true),
+        None => (body, false),
+    };
+    if body.starts_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+
+    let mut options = RetabOptions {
+        to_tabs,
+        ..RetabOptions::default()
+    };
+    for flag in body.chars().filter(|c| !c.is_whitespace()) {
+        match flag {
+            'a' => options.all = true,
+            other => return Some(Err(format!("Invalid retab flag: {}", other))),
+        }
+    }
+    Some(Ok(options))
+}
+
+/// Rewrite the blanks of `line` as spaces, or as tabs wherever a tab reaches
+/// the next tab stop, keeping every other character at the same display
+/// column. Only the indent is touched unless `options.all` is set.
+pub fn retab_line(line: &str, options: RetabOptions, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut out = String::with_capacity(line.len());
+    let mut col = 0;
+    let mut in_indent = true;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != ' ' && c != '\t' {
+            in_indent = false;
+            out.push(c);
+            col += char_width(c, col, tab_width);
+            continue;
+        }
+
+        let mut run = String::from(c);
+        let start = col;
+        col += char_width(c, col, tab_width);
+        while let Some(&next) = chars.peek()
+            && (next == ' ' || next == '\t')
+        {
+            run.push(next);
+            col += char_width(next, col, tab_width);
+            chars.next();
+        }
+
+        if !(in_indent || options.all) {
+            out.push_str(&run);
+        } else if options.to_tabs {
+            let mut at = start;
+            loop {
+                let stop = (at / tab_width + 1) * tab_width;
+                if stop > col {
+                    break;
+                }
+                // A tab filling a single column is just a space
+                out.push(if stop - at == 1 { ' ' } else { '\t' });
+                at = stop;
+            }
+            out.push_str(&" ".repeat(col - at));
+        } else {
+            out.push_str(&" ".repeat(col - start));
+        }
+    }
+    out
+}
+
+/// First (optionally negative) decimal number in a line, used by `:sort n`
+fn first_number(line: &str) -> Option<i64> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = line[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let value = digits.parse::<i64>().unwrap_or(i64::MAX);
+    if line[..start].ends_with('-') {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
+
+/// Compile a search pattern. With `smart_case`, a pattern without
+/// uppercase letters matches either case.
+pub fn compile_pattern(pattern: &str, smart_case: bool) -> Result<Regex, String> {
+    if pattern.is_empty() {
+        return Err("Empty search pattern".to_string());
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(smart_case && !has_uppercase(pattern))
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+/// Whether `pattern` has an uppercase letter, not counting escapes such
+/// as `\S` or `\W`
+fn has_uppercase(pattern: &str) -> bool {
+    let mut escaped = false;
+    pattern.chars().any(|c| {
+        let literal = !escaped;
+        escaped = !escaped && c == '\\';
+        literal && c.is_uppercase()
+    })
+}
+
+/// Convert a Vim replacement string (`&`, `\1`) into `regex` crate syntax
+fn convert_replacement(replacement: &str) -> String {
+    let mut out = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => out.push_str(&format!("${{{}}}", d)),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(current: usize, last: usize) -> AddressContext {
+        AddressContext {
+            current,
+            last,
+            visual: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("s/a/b/", ctx(3, 9)), (None, "s/a/b/"));
+        assert_eq!(
+            parse_range("%s/a/b/", ctx(3, 9)),
+            (Some(LineRange::new(0, 9)), "s/a/b/")
+        );
+        assert_eq!(
+            parse_range("2,4d", ctx(0, 9)),
+            (Some(LineRange::new(1, 3)), "d")
+        );
+        assert_eq!(
+            parse_range(".,$d", ctx(5, 9)),
+            (Some(LineRange::new(5, 9)), "d")
+        );
+        assert_eq!(
+            parse_range("42", ctx(0, 9)),
+            (Some(LineRange::single(9)), "")
+        );
+    }
+
+    #[test]
+    fn test_parse_address_offsets() {
+        assert_eq!(parse_range("+3", ctx(2, 9)), (Some(LineRange::single(5)), ""));
+        assert_eq!(parse_range("-", ctx(2, 9)), (Some(LineRange::single(1)), ""));
+        assert_eq!(parse_range("$-1", ctx(0, 9)), (Some(LineRange::single(8)), ""));
+        assert_eq!(
+            parse_range(".,+2d", ctx(4, 9)),
+            (Some(LineRange::new(4, 6)), "d")
+        );
+        // Clamped to the buffer
+        assert_eq!(parse_range("-20", ctx(2, 9)), (Some(LineRange::single(0)), ""));
+        assert_eq!(parse_range("5+20", ctx(2, 9)), (Some(LineRange::single(9)), ""));
+    }
+
+    #[test]
+    fn test_parse_visual_marks() {
+        let with_visual = AddressContext {
+            visual: Some(LineRange::new(2, 4)),
+            ..ctx(0, 9)
+        };
+        assert_eq!(
+            parse_range("'<,'>sort", with_visual),
+            (Some(LineRange::new(2, 4)), "sort")
+        );
+        assert_eq!(parse_range("'<,'>sort", ctx(0, 9)), (None, "'<,'>sort"));
+    }
+
+    #[test]
+    fn test_parse_bang() {
+        assert_eq!(parse_bang("!jq ."), Some("jq ."));
+        assert_eq!(parse_bang("! sort -u "), Some("sort -u"));
+        assert_eq!(parse_bang("sort"), None);
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        assert_eq!(parse_sort("sort").unwrap(), Ok(SortOptions::default()));
+        let options = parse_sort("sort! un").unwrap().unwrap();
+        assert!(options.reverse && options.unique && options.numeric);
+        assert!(parse_sort("sort x").unwrap().is_err());
+        assert!(parse_sort("sorted").is_none());
+        assert!(parse_sort("s/a/b/").is_none());
+    }
+
+    #[test]
+    fn test_smart_case_patterns() {
+        assert!(compile_pattern("readme", true).unwrap().is_match("README.md"));
+        assert!(!compile_pattern("README", true).unwrap().is_match("readme.md"));
+        assert!(compile_pattern("README", true).unwrap().is_match("README.md"));
+        // Escapes such as `\S` aren't uppercase letters
+        assert!(compile_pattern(r"\Sx", true).unwrap().is_match("aX"));
+        assert!(!compile_pattern("readme", false).unwrap().is_match("README.md"));
+    }
+
+    #[test]
+    fn test_parse_line_command() {
+        assert_eq!(parse_line_command("d"), Some(Ok((LineCommand::Delete, None))));
+        assert_eq!(
+            parse_line_command("yank a"),
+            Some(Ok((LineCommand::Yank, Some('a'))))
+        );
+        assert_eq!(
+            parse_line_command("del +"),
+            Some(Ok((LineCommand::Delete, Some('+'))))
+        );
+        assert!(parse_line_command("y ab").unwrap().is_err());
+        assert_eq!(parse_line_command("diag"), None);
+        assert_eq!(parse_line_command("yy"), None);
+    }
+
+    #[test]
+    fn test_parse_retab() {
+        assert_eq!(parse_retab("retab").unwrap(), Ok(RetabOptions::default()));
+        let options = parse_retab("ret! a").unwrap().unwrap();
+        assert!(options.to_tabs && options.all);
+        assert!(parse_retab("retab x").unwrap().is_err());
+        assert!(parse_retab("return").is_none());
+    }
+
+    #[test]
+    fn test_retab_line() {
+        let spaces = RetabOptions::default();
+        let tabs = RetabOptions {
+            to_tabs: true,
+            ..RetabOptions::default()
+        };
+        assert_eq!(retab_line("\tx\t= 1;", spaces, 4), "    x\t= 1;");
+        // Alignment is kept when a tab follows spaces
+        assert_eq!(retab_line("  \tx", spaces, 4), "    x");
+        assert_eq!(retab_line("          x  y", tabs, 4), "\t\t  x  y");
+        assert_eq!(retab_line("   x", tabs, 4), "   x");
+
+        let all = RetabOptions {
+            all: true,
+            ..RetabOptions::default()
+        };
+        assert_eq!(retab_line("\tab\tc", all, 4), "    ab  c");
+        let all_tabs = RetabOptions {
+            to_tabs: true,
+            all: true,
+        };
+        assert_eq!(retab_line("ab      c d", all_tabs, 4), "ab\t\tc d");
+    }
+
+    #[test]
+    fn test_sort_lines() {
+        let mut lines: Vec<String> = ["b", "a", "c", "a"].iter().map(|s| s.to_string()).collect();
+        sort_lines(&mut lines, SortOptions::default());
+        assert_eq!(lines, ["a", "a", "b", "c"]);
+
+        sort_lines(
+            &mut lines,
+            SortOptions {
+                reverse: true,
+                unique: true,
+                ..SortOptions::default()
+            },
+        );
+        assert_eq!(lines, ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_sort_lines_numeric() {
+        let mut lines: Vec<String> = ["item 10", "item 9", "none", "item -1", "x100"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        sort_lines(
+            &mut lines,
+            SortOptions {
+                numeric: true,
+                ..SortOptions::default()
+            },
+        );
+        assert_eq!(lines, ["none", "item -1", "item 9", "item 10", "x100"]);
+    }
+
+    #[test]
+    fn test_sort_lines_numeric_unique_treats_lines_without_numbers_as_equal() {
+        let mut lines: Vec<String> = ["b 2", "none", "a 2", "other", "other", "c 1"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        sort_lines(
+            &mut lines,
+            SortOptions {
+                numeric: true,
+                unique: true,
+                ..SortOptions::default()
+            },
+        );
+        // Like Vim, they all sort as the same key and only the first is kept
+        assert_eq!(lines, ["none", "c 1", "b 2"]);
+    }
+
+    #[test]
+    fn test_parse_substitute() {
+        let sub = parse_substitute("s/foo/bar/g", None, false).unwrap().unwrap();
+        assert!(sub.global);
+        assert_eq!(sub.apply("foo foo").as_deref(), Some("bar bar"));
+        assert_eq!(sub.apply("baz"), None);
+
+        let sub = parse_substitute("s#a/b#[&]#", None, false).unwrap().unwrap();
+        assert!(!sub.global);
+        assert_eq!(sub.apply("a/b a/b").as_deref(), Some("[a/b] a/b"));
+    }
+
+    #[test]
+    fn test_substitute_groups_and_escaped_delimiter() {
+        let sub = parse_substitute(r"s/(\w+)\/(\w+)/\2\/\1/", None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sub.apply("left/right").as_deref(), Some("right/left"));
+    }
+
+    #[test]
+    fn test_not_a_substitute() {
+        assert!(parse_substitute("syntax on", None, false).is_none());
+        assert!(parse_substitute("set", None, false).is_none());
+    }
+
+    #[test]
+    fn test_substitute_empty_pattern_reuses_last() {
+        assert_eq!(
+            parse_substitute("s//x/", None, false).unwrap().unwrap_err(),
+            "No previous regular expression"
+        );
+        let last = Regex::new("b+").unwrap();
+        let sub = parse_substitute("s//x/g", Some(&last), false).unwrap().unwrap();
+        assert_eq!(sub.pattern.as_str(), "b+");
+        assert_eq!(sub.apply("abba bb").as_deref(), Some("axa x"));
+    }
+
+    #[test]
+    fn test_parse_repeat_substitute() {
+        assert_eq!(parse_repeat_substitute("&"), Some(false));
+        assert_eq!(parse_repeat_substitute("&&"), Some(true));
+        assert_eq!(parse_repeat_substitute("s"), Some(false));
+        assert_eq!(parse_repeat_substitute("s/a/b/"), None);
+    }
+
+    #[test]
+    fn test_parse_user_command() {
+        assert_eq!(
+            parse_user_command("Jq  %!jq . "),
+            Ok(("Jq".to_string(), "%!jq .".to_string()))
+        );
+        assert!(parse_user_command("w write").is_err());
+        assert!(parse_user_command("W-x w").is_err());
+        assert!(parse_user_command("W").is_err());
+    }
+
+    #[test]
+    fn test_expand_user_command() {
+        let commands: HashMap<String, String> =
+            [("W", "w"), ("Del", "d"), ("D2", "Del b"), ("Loop", "Loop")]
+                .into_iter()
+                .map(|(name, definition)| (name.to_string(), definition.to_string()))
+                .collect();
+        let expand = |input| expand_user_command(input, &commands, ctx(0, 9));
+
+        assert_eq!(expand("W"), Some(Ok("w".to_string())));
+        assert_eq!(expand("W  out.txt"), Some(Ok("w out.txt".to_string())));
+        // The range stays in front, and definitions can use other commands
+        assert_eq!(expand("2,3D2"), Some(Ok("2,3d b".to_string())));
+        assert_eq!(expand("w"), None);
+        assert_eq!(expand("Wq"), None);
+        assert!(matches!(expand("Loop"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_global() {
+        let global = parse_global("g/TODO/d", false).unwrap().unwrap();
+        assert!(!global.invert);
+        assert_eq!(global.command, "d");
+        assert!(global.selects("// TODO: fix"));
+        assert!(!global.selects("done"));
+
+        let global = parse_global("v/^$/s/a/b/g", false).unwrap().unwrap();
+        assert!(global.invert);
+        assert_eq!(global.command, "s/a/b/g");
+        assert!(global.selects("text"));
+        assert!(!global.selects(""));
+
+        assert!(parse_global("g!/x/d", false).unwrap().unwrap().invert);
+    }
+
+    #[test]
+    fn test_parse_global_invalid_pattern() {
+        assert!(parse_global("g/(/d", false).unwrap().is_err());
+        assert!(parse_global("go", false).is_none());
+    }
+}
@@ -0,0 +1,102 @@
+// src/file_watcher.rs - Detect changes to the open file made by other programs
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+
+/// Watches a single file for modifications on disk.
+///
+/// The parent directory is watched rather than the file itself so that tools
+/// which replace the file (atomic saves, `git checkout`) are still noticed.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    path: PathBuf,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Drain pending events, returning true if any touched the watched file
+    pub fn has_changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            let relevant = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+            );
+            if relevant && event.paths.iter().any(|p| p == &self.path) {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_for_change(watcher: &FileWatcher) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if watcher.has_changed() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_detects_modification() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("watched.txt");
+        std::fs::write(&file, "one").unwrap();
+
+        let watcher = FileWatcher::new(&file).unwrap();
+        assert!(!watcher.has_changed());
+
+        std::fs::write(&file, "two").unwrap();
+        assert!(wait_for_change(&watcher));
+    }
+
+    #[test]
+    fn test_ignores_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("watched.txt");
+        std::fs::write(&file, "one").unwrap();
+
+        let watcher = FileWatcher::new(&file).unwrap();
+        std::fs::write(dir.path().join("other.txt"), "x").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!watcher.has_changed());
+    }
+}
@@ -50,6 +50,42 @@ impl Formatter {
     }
 }
 
+/// Run a shell command with `text` on stdin and return its stdout.
+/// A non-zero exit status is an error carrying the command's stderr.
+pub fn filter_text(command: &str, text: &str) -> Result<String, std::io::Error> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Feed stdin from another thread so a command that writes before it
+    // finishes reading can't deadlock on a full pipe
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = text.to_string();
+        std::thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        // A command that ignores its input closes the pipe early; that's not an error
+        let _ = writer.join();
+    }
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = match stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => line.trim().to_string(),
+            None => format!("{} exited with {}", command, output.status),
+        };
+        Err(std::io::Error::other(message))
+    }
+}
+
 pub fn get_formatter_config(language: LanguageId) -> Option<FormatterConfig> {
     match language {
         LanguageId::Rust => Some(FormatterConfig {
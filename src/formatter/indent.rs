@@ -1,35 +1,223 @@
-// src/formatter/indent.rs - Smart indentation
+// src/formatter/indent.rs - Smart indentation from the syntax tree
 
-use crate::syntax::LanguageId;
+use std::collections::HashMap;
 
+use tree_sitter::{Language, Parser, Point, Query, QueryCursor};
+
+use crate::syntax::{LanguageConfig, QueryLoader};
+
+/// Works out how deep each line is nested using the language's indent query.
+/// Its captures are:
+/// - `@indent`: the lines after the node's first one are a level deeper. Nodes
+///   starting on the same line only add one level between them.
+/// - `@outdent`: a line starting with the node is a level shallower, like a
+///   closing bracket
+/// - `@ignore`: the lines after the node's first one are left alone, like the
+///   inside of a multi-line string
 #[derive(Debug)]
 pub struct IndentationEngine {
-    pub indent_width: usize,
-    pub use_spaces: bool,
+    language: Language,
+    query: Query,
 }
 
 impl IndentationEngine {
-    pub fn new(language: LanguageId) -> Self {
-        let indent_width = match language {
-            LanguageId::Rust | LanguageId::Python => 4,
-            LanguageId::JavaScript | LanguageId::TypeScript => 2,
+    /// Engine for `config`'s language, or `None` if it has no indent query
+    pub fn new(config: &LanguageConfig) -> Option<Self> {
+        let language = (config.tree_sitter_language)();
+        let path = config.indent_query_path.as_deref().unwrap_or("");
+        let query = QueryLoader::new()
+            .load_query(language, path, config.indent_query_fallback)
+            .ok()?;
+        Some(Self { language, query })
+    }
+
+    /// Indent level of every line of `text`, or `None` for lines whose
+    /// whitespace is kept as it is. Blank lines are at level 0.
+    pub fn indent_levels(&self, text: &str) -> Vec<Option<usize>> {
+        let line_count = text.split('\n').count();
+        let mut parser = Parser::new();
+        let tree = match parser.set_language(self.language) {
+            Ok(()) => parser.parse(text, None),
+            Err(_) => None,
         };
-        Self {
-            indent_width,
-            use_spaces: true,
+        let Some(tree) = tree else {
+            return vec![None; line_count];
+        };
+
+        // Last line each indenting line covers, so nodes starting on the same
+        // line count once; lines kept as they are; where outdents start
+        let mut indents: HashMap<usize, usize> = HashMap::new();
+        let mut ignored = vec![false; line_count];
+        let mut outdents = Vec::new();
+        let mut cursor = QueryCursor::new();
+        for found in cursor.matches(&self.query, tree.root_node(), text.as_bytes()) {
+            for capture in found.captures {
+                let node = capture.node;
+                let start = node.start_position();
+                let last = last_line(node.end_position());
+                match self.query.capture_names()[capture.index as usize].as_str() {
+                    "indent" if last > start.row => {
+                        let covered = indents.entry(start.row).or_default();
+                        *covered = (*covered).max(last);
+                    }
+                    "outdent" => outdents.push((start.row, start.column)),
+                    "ignore" => {
+                        for line in ignored.iter_mut().take(last + 1).skip(start.row + 1) {
+                            *line = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Running depth: each indenting line adds a level from the next line
+        // through the last one it covers
+        let mut changes = vec![0isize; line_count + 1];
+        for (start, last) in indents {
+            changes[start + 1] += 1;
+            changes[(last + 1).min(line_count)] -= 1;
         }
+
+        let mut depth = 0isize;
+        text.split('\n')
+            .enumerate()
+            .map(|(row, line)| {
+                depth += changes[row];
+                if ignored[row] {
+                    return None;
+                }
+                let Some(col) = line.find(|c: char| !c.is_whitespace()) else {
+                    return Some(0);
+                };
+                let outdent = outdents.contains(&(row, col)) as isize;
+                Some((depth - outdent).max(0) as usize)
+            })
+            .collect()
+    }
+}
+
+/// Last line a node ending at `end` has text on
+fn last_line(end: Point) -> usize {
+    if end.column == 0 {
+        end.row.saturating_sub(1)
+    } else {
+        end.row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{LanguageId, get_language_config};
+
+    /// `text` reindented with four spaces a level
+    fn reindent(language: LanguageId, text: &str) -> String {
+        let engine = IndentationEngine::new(&get_language_config(language)).unwrap();
+        let levels = engine.indent_levels(text);
+        text.split('\n')
+            .zip(levels)
+            .map(|(line, level)| match level {
+                Some(0) if line.trim().is_empty() => String::new(),
+                Some(level) => format!("{}{}", "    ".repeat(level), line.trim_start()),
+                None => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
-    pub fn get_indent_level(&self, _line: &str, _context: &str) -> usize {
-        // TODO: Use tree-sitter queries to determine indent level
-        0
+    fn flatten(text: &str) -> String {
+        text.lines().map(str::trim_start).collect::<Vec<_>>().join("\n")
     }
 
-    pub fn create_indent_string(&self, level: usize) -> String {
-        if self.use_spaces {
-            " ".repeat(level * self.indent_width)
-        } else {
-            "\t".repeat(level)
+    #[test]
+    fn test_every_language_has_an_indent_query() {
+        for id in LanguageId::ALL {
+            assert!(IndentationEngine::new(&get_language_config(id)).is_some(), "{:?}", id);
         }
     }
+
+    #[test]
+    fn test_rust_nested_blocks() {
+        let expected = "\
+impl Foo {
+    fn run(&self, items: &[u32]) -> u32 {
+        let total = items
+            .iter()
+            .sum();
+        for item in items {
+            if *item > 1 {
+                match item {
+                    2 => {
+                        call(
+                            *item,
+                            total,
+                        );
+                    }
+                    _ => {}
+                }
+            } else {
+                return 0;
+            }
+        }
+
+        total
+    }
+}";
+        assert_eq!(reindent(LanguageId::Rust, &flatten(expected)), expected);
+    }
+
+    #[test]
+    fn test_rust_keeps_string_and_comment_lines() {
+        let text = "fn main() {\nlet s = \"one\n  two\";\n/*\n   kept\n*/\n}";
+        assert_eq!(
+            reindent(LanguageId::Rust, text),
+            "fn main() {\n    let s = \"one\n  two\";\n    /*\n   kept\n*/\n}"
+        );
+    }
+
+    #[test]
+    fn test_python_clauses_line_up() {
+        let expected = "\
+def f(a,
+    b):
+    if a:
+        try:
+            g(
+                b,
+            )
+        except E:
+            pass
+    elif b:
+        pass
+    else:
+        return [
+            1,
+        ]";
+        // Python's blocks are its indentation, so only lines inside brackets
+        // can be moved without changing the program
+        let text = expected
+            .replace("\n    b):", "\nb):")
+            .replace("            1,", "1,");
+        assert_eq!(reindent(LanguageId::Python, &text), expected);
+    }
+
+    #[test]
+    fn test_javascript_switch_and_objects() {
+        let expected = "\
+function f(x) {
+    switch (x) {
+        case 1:
+            return {
+                a: [
+                    1,
+                ],
+            };
+        default:
+            break;
+    }
+}";
+        assert_eq!(reindent(LanguageId::JavaScript, &flatten(expected)), expected);
+        assert_eq!(reindent(LanguageId::TypeScript, &flatten(expected)), expected);
+    }
 }
@@ -2,7 +2,7 @@ use rayon::prelude::*;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::ui::widgets::preview::{PreviewBuffer, PreviewCache};
 
@@ -144,6 +144,16 @@ pub struct FzfResult {
     pub positions: Option<Vec<usize>>,
 }
 
+/// Whether two ASCII characters are the same, ignoring case unless
+/// `case_sensitive`
+fn chars_match(text_char: char, pattern_char: char, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        text_char == pattern_char
+    } else {
+        text_char.eq_ignore_ascii_case(&pattern_char)
+    }
+}
+
 // Optimized ASCII string search for pattern occurrence
 fn ascii_fuzzy_index(
     text: &[char],
@@ -169,22 +179,7 @@ fn ascii_fuzzy_index(
         for (idx, &text_char) in text[text_pos..].iter().enumerate() {
             let current_idx = text_pos + idx;
 
-            // Case-insensitive matching for ASCII
-            let text_char_code = text_char as u8;
-            let pattern_char_code = pattern_char as u8;
-
-            let matches = if case_sensitive {
-                text_char_code == pattern_char_code
-            } else {
-                // Fast case-insensitive comparison for ASCII
-                text_char_code == pattern_char_code
-                    || text_char_code == pattern_char_code - 32
-                        && (97..=122).contains(&text_char_code)
-                    || text_char_code == pattern_char_code + 32
-                        && (65..=90).contains(&text_char_code)
-            };
-
-            if matches {
+            if chars_match(text_char, pattern_char, case_sensitive) {
                 if pidx == 0 && current_idx > 0 {
                     first_idx = current_idx - 1;
                 }
@@ -286,7 +281,7 @@ fn fuzzy_match_v1(text: &str, pattern: &str, case_sensitive: bool) -> Option<Fzf
             let class = char_class_of(text_char);
 
             if let Some(&pattern_char) = pattern_chars.get(positions.len()) {
-                if text_char == pattern_char {
+                if chars_match(text_char, pattern_char, case_sensitive) {
                     positions.push(global_idx);
                     score += SCORE_MATCH as i32;
 
@@ -656,6 +651,41 @@ pub struct FileItem {
     pub is_binary: bool,
 }
 
+/// How long typing must pause before the symbol picker asks the server
+const SYMBOL_QUERY_DELAY: Duration = Duration::from_millis(150);
+
+/// Lines of context above a symbol in the preview
+const SYMBOL_PREVIEW_CONTEXT: usize = 3;
+
+/// A symbol from the language server's `workspace/symbol` answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolItem {
+    pub name: String,
+    /// Kind such as `function` or `struct`
+    pub kind: String,
+    /// The module, type or other symbol it is declared in
+    pub container: Option<String>,
+    pub path: PathBuf,
+    /// 0-based line
+    pub line: usize,
+    /// UTF-16 column, as the server reports it
+    pub character: usize,
+}
+
+/// Workspace symbol picker. The server does the searching, so the query is
+/// sent once typing pauses and its answer is ranked against the query here.
+#[derive(Debug, Default)]
+pub struct SymbolSearch {
+    /// Symbols from the last answer
+    pub items: Vec<SymbolItem>,
+    /// Those matching the query, best first
+    pub filtered: Vec<SymbolItem>,
+    /// Query of the last request
+    pub sent_query: Option<String>,
+    /// When the query last changed, until it is sent
+    pub query_changed: Option<Instant>,
+}
+
 /// State for fuzzy file search
 #[derive(Debug)]
 pub struct FuzzySearchState {
@@ -682,6 +712,18 @@ pub struct FuzzySearchState {
 
     // Gitignore filtering
     pub follow_gitignore: bool,
+
+    /// Include dotfiles and the contents of dot-directories such as `.git/`
+    pub show_hidden: bool,
+
+    /// Search these files instead of scanning `current_path`
+    pub recent_files: Option<Vec<PathBuf>>,
+
+    /// Pick from the language server's workspace symbols instead of files
+    pub symbols: Option<SymbolSearch>,
+
+    /// Match case only once the query has an uppercase letter
+    pub smart_case: bool,
 }
 
 impl Default for FuzzySearchState {
@@ -704,6 +746,10 @@ impl Default for FuzzySearchState {
             preview_cache: PreviewCache::default(),
             current_preview: None,
             follow_gitignore: true,
+            show_hidden: false,
+            recent_files: None,
+            symbols: None,
+            smart_case: true,
         }
     }
 }
@@ -721,6 +767,26 @@ impl FuzzySearchState {
         }
     }
 
+    /// Create fuzzy search state over a list of recently opened files
+    pub fn new_recent(files: Vec<PathBuf>) -> Self {
+        Self {
+            recent_files: Some(files),
+            ..Default::default()
+        }
+    }
+
+    /// Create the workspace symbol picker. The first request, for every
+    /// symbol, goes out after the usual pause.
+    pub fn new_symbols() -> Self {
+        Self {
+            symbols: Some(SymbolSearch {
+                query_changed: Some(Instant::now()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
     /// Update the current search query, adjust cached or recomputed results, and refresh the preview.
     ///
     /// This saves the previous non-empty query to the state's backtracking history, attempts to load
@@ -738,6 +804,13 @@ impl FuzzySearchState {
     /// // filtered_items, displayed_count, and current_preview are updated by the call
     /// ```
     pub fn update_query(&mut self, new_query: String) {
+        if self.symbols.is_some() {
+            self.query = new_query;
+            self.update_filter();
+            self.update_preview();
+            return;
+        }
+
         let old_query = self.query.clone();
         self.query = new_query.clone();
 
@@ -800,12 +873,13 @@ impl FuzzySearchState {
 
     /// Apply early termination filter for short queries on large datasets
     fn apply_early_termination_filter(&self, query: &str) -> Vec<(FileItem, i32, MatchType)> {
+        let case_sensitive = self.case_sensitive(query);
         let target_results = 50;
         let mut scored_items: Vec<(FileItem, i32, MatchType)> = Vec::new();
 
         for item in &self.all_items {
             let result = if self.recursive_search {
-                fuzzy_match_with_priority_optimized(query, item)
+                fuzzy_match_with_priority_optimized(query, item, case_sensitive)
             } else {
                 let filename = if let Some(last_sep) = item.name.rfind(['/', '\\']) {
                     &item.name[last_sep + 1..]
@@ -814,7 +888,7 @@ impl FuzzySearchState {
                 };
 
                 let total_bonus = calculate_file_bonus(&item.path, filename, &item.modified);
-                fuzzy_match_optimized(query, filename)
+                fuzzy_match_optimized(query, filename, case_sensitive)
                     .map(|score| (score + total_bonus, MatchType::FilenameFuzzy))
             };
 
@@ -876,13 +950,20 @@ impl FuzzySearchState {
         self.result_cache.insert(self.query.clone(), all_sorted_items);
     }
 
+    /// Whether `query` must match case: with smart case, once it has an
+    /// uppercase letter
+    fn case_sensitive(&self, query: &str) -> bool {
+        self.smart_case && query.chars().any(char::is_uppercase)
+    }
+
     /// Filter items for non-empty query with optimized fzf-style scoring
     fn filter_items_with_query(&self) -> Vec<(FileItem, i32, MatchType)> {
+        let case_sensitive = self.case_sensitive(&self.query);
         self.all_items
             .par_iter()
             .filter_map(|item| {
                 let result = if self.recursive_search {
-                    fuzzy_match_with_priority_optimized(&self.query, item)
+                    fuzzy_match_with_priority_optimized(&self.query, item, case_sensitive)
                 } else {
                     let filename = if let Some(last_sep) = item.name.rfind(['/', '\\']) {
                         &item.name[last_sep + 1..]
@@ -891,7 +972,7 @@ impl FuzzySearchState {
                     };
 
                     let total_bonus = calculate_file_bonus(&item.path, filename, &item.modified);
-                    fuzzy_match_optimized(&self.query, filename)
+                    fuzzy_match_optimized(&self.query, filename, case_sensitive)
                         .map(|score| (score + total_bonus, MatchType::FilenameFuzzy))
                 };
 
@@ -902,14 +983,15 @@ impl FuzzySearchState {
 
     /// Filter all items for caching (uses non-optimized version for consistency)
     fn filter_all_items_for_cache(&self) -> Vec<FileItem> {
+        let case_sensitive = self.case_sensitive(&self.query);
         self.all_items
             .iter()
             .filter_map(|item| {
                 let result = if self.recursive_search {
-                    fuzzy_match_with_priority(&self.query, item)
+                    fuzzy_match_with_priority(&self.query, item, case_sensitive)
                 } else {
                     let total_bonus = calculate_file_bonus(&item.path, &item.name, &item.modified);
-                    fuzzy_match(&self.query, &item.name)
+                    fuzzy_match(&self.query, &item.name, case_sensitive)
                         .map(|score| (score + total_bonus, MatchType::FilenameFuzzy))
                 };
 
@@ -923,14 +1005,15 @@ impl FuzzySearchState {
         &self,
         all_filtered_items: &[FileItem],
     ) -> Vec<(FileItem, i32, MatchType)> {
+        let case_sensitive = self.case_sensitive(&self.query);
         all_filtered_items
             .iter()
             .filter_map(|item| {
                 let result = if self.recursive_search {
-                    fuzzy_match_with_priority(&self.query, item)
+                    fuzzy_match_with_priority(&self.query, item, case_sensitive)
                 } else {
                     let total_bonus = calculate_file_bonus(&item.path, &item.name, &item.modified);
-                    fuzzy_match(&self.query, &item.name)
+                    fuzzy_match(&self.query, &item.name, case_sensitive)
                         .map(|score| (score + total_bonus, MatchType::FilenameFuzzy))
                 };
 
@@ -944,6 +1027,11 @@ impl FuzzySearchState {
         self.selected_index = 0;
         self.scroll_offset = 0;
 
+        if self.symbols.is_some() {
+            self.filter_symbols();
+            return;
+        }
+
         // Filter and sort items based on query with priority scoring
         if self.query.is_empty() {
             self.filtered_items = self.all_items.clone();
@@ -972,8 +1060,74 @@ impl FuzzySearchState {
         }
     }
 
+    /// Rank the server's symbols by how well their names match the query, and
+    /// have the query sent if the answer isn't for it
+    fn filter_symbols(&mut self) {
+        let case_sensitive = self.case_sensitive(&self.query);
+        let Some(symbols) = &mut self.symbols else {
+            return;
+        };
+        let mut scored: Vec<(i32, &SymbolItem)> = symbols
+            .items
+            .iter()
+            .filter_map(|item| {
+                fuzzy_match(&self.query, &item.name, case_sensitive).map(|score| (score, item))
+            })
+            .collect();
+        // Best score first, then shorter names
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.len().cmp(&b.1.name.len())));
+        symbols.filtered = scored.into_iter().map(|(_, item)| item.clone()).collect();
+        self.result_count = symbols.filtered.len();
+        self.displayed_count = symbols.filtered.len();
+        self.has_more_results = false;
+
+        if symbols.sent_query.as_deref() != Some(self.query.as_str()) {
+            symbols.query_changed = Some(Instant::now());
+        }
+    }
+
+    /// The query to send to the server, once typing has paused long enough
+    /// since it changed
+    pub fn take_due_symbol_query(&mut self) -> Option<String> {
+        let symbols = self.symbols.as_mut()?;
+        if symbols.query_changed?.elapsed() < SYMBOL_QUERY_DELAY {
+            return None;
+        }
+        symbols.query_changed = None;
+        symbols.sent_query = Some(self.query.clone());
+        Some(self.query.clone())
+    }
+
+    /// Whether a symbol query is waiting for typing to pause
+    pub fn has_pending_symbol_query(&self) -> bool {
+        self.symbols
+            .as_ref()
+            .is_some_and(|symbols| symbols.query_changed.is_some())
+    }
+
+    /// Show the symbols the server answered with
+    pub fn set_symbols(&mut self, items: Vec<SymbolItem>) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.items = items;
+            self.update_filter();
+            self.update_preview();
+        }
+    }
+
+    pub fn get_selected_symbol(&self) -> Option<&SymbolItem> {
+        self.symbols.as_ref()?.filtered.get(self.selected_index)
+    }
+
+    /// Number of entries in the list, files or symbols
+    fn result_len(&self) -> usize {
+        match &self.symbols {
+            Some(symbols) => symbols.filtered.len(),
+            None => self.filtered_items.len(),
+        }
+    }
+
     pub fn select_next(&mut self) -> Option<FileItem> {
-        if self.selected_index < self.filtered_items.len().saturating_sub(1) {
+        if self.selected_index < self.result_len().saturating_sub(1) {
             self.selected_index += 1;
             self.update_preview();
             self.get_selected_item().cloned()
@@ -1005,10 +1159,19 @@ impl FuzzySearchState {
     }
 
     pub fn rescan_current_directory(&mut self) {
-        self.all_items = if self.recursive_search {
-            scan_directory_recursive(&self.current_path, self.max_depth, self.follow_gitignore)
+        self.all_items = if self.symbols.is_some() {
+            Vec::new()
+        } else if let Some(files) = &self.recent_files {
+            recent_file_items(files)
+        } else if self.recursive_search {
+            scan_directory_recursive(
+                &self.current_path,
+                self.max_depth,
+                self.follow_gitignore,
+                self.show_hidden,
+            )
         } else {
-            scan_directory(&self.current_path, self.follow_gitignore)
+            scan_directory(&self.current_path, self.follow_gitignore, self.show_hidden)
         };
         self.update_filter();
     }
@@ -1037,50 +1200,90 @@ impl FuzzySearchState {
         self.rescan_current_directory();
     }
 
+    /// Toggle whether hidden files and directories are listed, then rescan
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.result_cache.clear();
+        self.rescan_current_directory();
+    }
+
     pub fn update_preview(&mut self) {
-        if let Some(selected_item) = self.filtered_items.get(self.selected_index) {
-            if !selected_item.is_dir {
-                if let Some(mut cached) = self.preview_cache.get(&selected_item.path) {
-                    cached.ensure_highlighted(0, 100);
-                    self.preview_cache
-                        .put(selected_item.path.clone(), cached.clone());
-                    self.current_preview = Some(cached);
-                    return;
-                }
+        if self.symbols.is_some() {
+            // The symbol's file, scrolled to it
+            let selected = self
+                .get_selected_symbol()
+                .map(|symbol| (symbol.path.clone(), symbol.line));
+            self.current_preview = selected.and_then(|(path, line)| {
+                let mut preview = self.load_preview(&path)?;
+                preview.top_line = line.saturating_sub(SYMBOL_PREVIEW_CONTEXT);
+                Some(preview)
+            });
+            return;
+        }
 
-                match PreviewBuffer::load_from_file(&selected_item.path) {
-                    Ok(mut preview_buffer) => {
-                        preview_buffer.ensure_highlighted(0, 100);
-                        self.preview_cache
-                            .put(selected_item.path.clone(), preview_buffer.clone());
-                        self.current_preview = Some(preview_buffer);
-                    }
-                    Err(_) => {
-                        self.current_preview = None;
-                    }
-                }
-            } else {
-                self.current_preview = None;
-            }
-        } else {
-            self.current_preview = None;
+        let selected = self
+            .filtered_items
+            .get(self.selected_index)
+            .filter(|item| !item.is_dir)
+            .map(|item| item.path.clone());
+        self.current_preview = selected.and_then(|path| self.load_preview(&path));
+    }
+
+    /// Preview of `path`, from the cache or read from disk
+    fn load_preview(&mut self, path: &PathBuf) -> Option<PreviewBuffer> {
+        // Highlighting is done when the preview is drawn, once the visible
+        // height is known
+        if let Some(cached) = self.preview_cache.get(path) {
+            return Some(cached);
         }
+        let preview_buffer = PreviewBuffer::load_from_file(path).ok()?;
+        self.preview_cache.put(path.clone(), preview_buffer.clone());
+        Some(preview_buffer)
     }
 }
 
+/// Build items for files that still exist, keeping the order of `files`.
+///
+/// # Examples
+///
+/// ```
+/// let items = texty::fuzzy_search::recent_file_items(&[std::path::PathBuf::from("Cargo.toml")]);
+/// assert_eq!(items.len(), 1);
+/// ```
+pub fn recent_file_items(files: &[PathBuf]) -> Vec<FileItem> {
+    files
+        .iter()
+        .filter_map(|path| {
+            let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+            Some(FileItem {
+                name: path.display().to_string(),
+                path: path.clone(),
+                is_dir: false,
+                is_hidden: path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.')),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                size: Some(metadata.len()),
+                is_binary: false,
+            })
+        })
+        .collect()
+}
+
 /// Scan a directory and return all files and directories.
 ///
 /// # Arguments
 ///
 /// * `path` - The directory path to scan
-/// * `follow_gitignore` - If true, exclude files matching patterns in `.gitignore` and hidden files
+/// * `follow_gitignore` - If true, exclude files matching patterns in `.gitignore`
+/// * `show_hidden` - If false, exclude files and directories whose name starts with `.`
 ///
 /// # Examples
 ///
 /// ```
-/// let items = texty::fuzzy_search::scan_directory(&std::path::PathBuf::from("."), true);
+/// let items = texty::fuzzy_search::scan_directory(&std::path::PathBuf::from("."), true, false);
 /// ```
-pub fn scan_directory(path: &PathBuf, follow_gitignore: bool) -> Vec<FileItem> {
+pub fn scan_directory(path: &PathBuf, follow_gitignore: bool, show_hidden: bool) -> Vec<FileItem> {
     let mut items = Vec::new();
 
     let gitignore = if follow_gitignore {
@@ -1108,14 +1311,11 @@ pub fn scan_directory(path: &PathBuf, follow_gitignore: bool) -> Vec<FileItem> {
                     )
                 };
 
-                if follow_gitignore {
-                    if is_path_ignored(&full_path, path, &gitignore) {
-                        continue;
-                    }
-
-                    if is_hidden {
-                        continue;
-                    }
+                if is_hidden && !show_hidden {
+                    continue;
+                }
+                if follow_gitignore && is_path_ignored(&full_path, path, &gitignore) {
+                    continue;
                 }
 
                 items.push(FileItem {
@@ -1146,17 +1346,19 @@ pub fn scan_directory(path: &PathBuf, follow_gitignore: bool) -> Vec<FileItem> {
 ///
 /// * `path` - The directory path to scan
 /// * `max_depth` - Maximum recursion depth (0 for unlimited)
-/// * `follow_gitignore` - If true, exclude files matching patterns in `.gitignore` and hidden files
+/// * `follow_gitignore` - If true, exclude files matching patterns in `.gitignore`
+/// * `show_hidden` - If false, exclude dotfiles and don't descend into dot-directories
 ///
 /// # Examples
 ///
 /// ```
-/// let items = texty::fuzzy_search::scan_directory_recursive(&std::path::PathBuf::from("."), 0, true);
+/// let items = texty::fuzzy_search::scan_directory_recursive(&std::path::PathBuf::from("."), 0, true, false);
 /// ```
 pub fn scan_directory_recursive(
     path: &PathBuf,
     max_depth: usize,
     follow_gitignore: bool,
+    show_hidden: bool,
 ) -> Vec<FileItem> {
     let mut items = Vec::new();
 
@@ -1172,7 +1374,8 @@ pub fn scan_directory_recursive(
         });
     }
 
-    let all_items = scan_recursive_helper_parallel(path, max_depth, 0, follow_gitignore);
+    let all_items =
+        scan_recursive_helper_parallel(path, max_depth, 0, follow_gitignore, show_hidden);
 
     items.extend(all_items);
     items.sort_by(|a, b| match (a.is_dir, b.is_dir) {
@@ -1190,6 +1393,7 @@ fn scan_recursive_helper_parallel(
     max_depth: usize,
     current_depth: usize,
     follow_gitignore: bool,
+    show_hidden: bool,
 ) -> Vec<FileItem> {
     let mut items = Vec::new();
 
@@ -1231,14 +1435,11 @@ fn scan_recursive_helper_parallel(
                         )
                     };
 
-                    if follow_gitignore {
-                        if is_path_ignored(&full_path, path, &gitignore) {
-                            return None;
-                        }
-
-                        if is_hidden {
-                            return None;
-                        }
+                    if is_hidden && !show_hidden {
+                        return None;
+                    }
+                    if follow_gitignore && is_path_ignored(&full_path, path, &gitignore) {
+                        return None;
                     }
 
                     if is_dir {
@@ -1271,7 +1472,13 @@ fn scan_recursive_helper_parallel(
     let sub_items: Vec<Vec<FileItem>> = dirs_to_scan
         .par_iter()
         .map(|dir_path| {
-            scan_recursive_helper_parallel(dir_path, max_depth, current_depth + 1, follow_gitignore)
+            scan_recursive_helper_parallel(
+                dir_path,
+                max_depth,
+                current_depth + 1,
+                follow_gitignore,
+                show_hidden,
+            )
         })
         .collect();
 
@@ -1282,19 +1489,25 @@ fn scan_recursive_helper_parallel(
     items
 }
 
-/// Optimized fuzzy matching with fzf-style algorithm
-fn fuzzy_match_optimized(query: &str, target: &str) -> Option<i32> {
+/// Optimized fuzzy matching with fzf-style algorithm; case is ignored
+/// unless `case_sensitive`
+fn fuzzy_match_optimized(query: &str, target: &str, case_sensitive: bool) -> Option<i32> {
     if query.is_empty() {
         return Some(0);
     }
 
     // Try exact match first
-    if query.to_lowercase() == target.to_lowercase() {
+    let exact = if case_sensitive {
+        query == target
+    } else {
+        query.to_lowercase() == target.to_lowercase()
+    };
+    if exact {
         return Some(100); // Highest score for exact match
     }
 
     // Use fzf-style V1 algorithm for performance
-    if let Some(result) = fuzzy_match_v1(target, query, false) {
+    if let Some(result) = fuzzy_match_v1(target, query, case_sensitive) {
         Some(result.score)
     } else {
         None
@@ -1302,7 +1515,11 @@ fn fuzzy_match_optimized(query: &str, target: &str) -> Option<i32> {
 }
 
 /// Enhanced fuzzy matching with priority scoring (optimized)
-fn fuzzy_match_with_priority_optimized(query: &str, item: &FileItem) -> Option<(i32, MatchType)> {
+fn fuzzy_match_with_priority_optimized(
+    query: &str,
+    item: &FileItem,
+    case_sensitive: bool,
+) -> Option<(i32, MatchType)> {
     if query.is_empty() {
         return Some((0, MatchType::PathFuzzy));
     }
@@ -1325,12 +1542,12 @@ fn fuzzy_match_with_priority_optimized(query: &str, item: &FileItem) -> Option<(
     }
 
     // Priority 2: Filename fuzzy match
-    if let Some(score) = fuzzy_match_optimized(query, filename) {
+    if let Some(score) = fuzzy_match_optimized(query, filename, case_sensitive) {
         return Some((score + 100 + total_bonus, MatchType::FilenameFuzzy));
     }
 
     // Priority 3: Full path fuzzy match
-    if let Some(score) = fuzzy_match_optimized(query, &full_path) {
+    if let Some(score) = fuzzy_match_optimized(query, &full_path, case_sensitive) {
         return Some((score + total_bonus, MatchType::PathFuzzy));
     }
 
@@ -1346,7 +1563,11 @@ pub enum MatchType {
 }
 
 /// Enhanced fuzzy matching with priority scoring
-pub fn fuzzy_match_with_priority(query: &str, item: &FileItem) -> Option<(i32, MatchType)> {
+pub fn fuzzy_match_with_priority(
+    query: &str,
+    item: &FileItem,
+    case_sensitive: bool,
+) -> Option<(i32, MatchType)> {
     if query.is_empty() {
         return Some((0, MatchType::PathFuzzy));
     }
@@ -1369,35 +1590,38 @@ pub fn fuzzy_match_with_priority(query: &str, item: &FileItem) -> Option<(i32, M
     }
 
     // Priority 2: Filename fuzzy match
-    if let Some(score) = fuzzy_match(query, filename) {
+    if let Some(score) = fuzzy_match(query, filename, case_sensitive) {
         return Some((score + 100 + total_bonus, MatchType::FilenameFuzzy));
     }
 
     // Priority 3: Full path fuzzy match
-    if let Some(score) = fuzzy_match(query, &full_path) {
+    if let Some(score) = fuzzy_match(query, &full_path, case_sensitive) {
         return Some((score + total_bonus, MatchType::PathFuzzy));
     }
 
     None
 }
 
-/// Improved fuzzy matching algorithm
+/// Improved fuzzy matching algorithm; case is ignored unless `case_sensitive`
 /// Returns Some(score) if query matches target, None otherwise
-fn fuzzy_match(query: &str, target: &str) -> Option<i32> {
-    let query_lower = query.to_lowercase();
-    let target_lower = target.to_lowercase();
+fn fuzzy_match(query: &str, target: &str, case_sensitive: bool) -> Option<i32> {
+    let (query, target) = if case_sensitive {
+        (query.to_string(), target.to_string())
+    } else {
+        (query.to_lowercase(), target.to_lowercase())
+    };
 
-    if query_lower.is_empty() {
+    if query.is_empty() {
         return Some(0);
     }
 
     // Check for exact match first
-    if query_lower == target_lower {
+    if query == target {
         return Some(100); // Highest score for exact match
     }
 
     // Improved fuzzy matching
-    improved_fuzzy_match(&query_lower, &target_lower)
+    improved_fuzzy_match(&query, &target)
 }
 
 /// Advanced fuzzy matching with better scoring
@@ -1548,6 +1772,10 @@ pub fn benchmark_fuzzy_search_performance() {
         preview_cache: PreviewCache::default(),
         current_preview: None,
         follow_gitignore: true,
+        show_hidden: false,
+        recent_files: None,
+        symbols: None,
+        smart_case: true,
     };
 
     // Benchmark old algorithm
@@ -1579,111 +1807,111 @@ mod tests {
 
     #[test]
     fn test_fuzzy_match() {
-        assert!(fuzzy_match("abc", "abc").unwrap() > 95); // Exact match - high score
-        assert!(fuzzy_match("abc", "axbycz").is_some()); // Fuzzy match - all chars found
-        assert_eq!(fuzzy_match("abc", "xyz"), None); // No match
-        assert_eq!(fuzzy_match("", "abc"), Some(0)); // Empty query
+        assert!(fuzzy_match("abc", "abc", false).unwrap() > 95); // Exact match - high score
+        assert!(fuzzy_match("abc", "axbycz", false).is_some()); // Fuzzy match - all chars found
+        assert_eq!(fuzzy_match("abc", "xyz", false), None); // No match
+        assert_eq!(fuzzy_match("", "abc", false), Some(0)); // Empty query
     }
 
     #[test]
     fn test_fuzzy_match_non_sequential() {
         // Should find "abc" even if characters are not sequential
-        let score1 = fuzzy_match("abc", "axbycz").unwrap();
-        let score2 = fuzzy_match("abc", "a_bc").unwrap();
+        let score1 = fuzzy_match("abc", "axbycz", false).unwrap();
+        let score2 = fuzzy_match("abc", "a_bc", false).unwrap();
 
         assert!(score1 > 0);
         assert!(score2 >= score1); // Word boundary bonus or at least not worse
 
         // Should find "mlb" in "my_lib.rs"
-        assert!(fuzzy_match("mlb", "my_lib.rs").is_some());
+        assert!(fuzzy_match("mlb", "my_lib.rs", false).is_some());
 
         // Should find "ad" in "README.md"
-        assert!(fuzzy_match("ad", "README.md").is_some());
+        assert!(fuzzy_match("ad", "README.md", false).is_some());
 
         // Should find "ab" in "about.txt"
-        assert!(fuzzy_match("ab", "about.txt").is_some());
+        assert!(fuzzy_match("ab", "about.txt", false).is_some());
 
         // Should find characters in different order
-        assert!(fuzzy_match("da", "README.md").is_some()); // 'd' then 'a' in README.md
-        assert!(fuzzy_match("em", "README.md").is_some()); // 'e' then 'm' in README.md
+        assert!(fuzzy_match("da", "README.md", false).is_some()); // 'd' then 'a' in README.md
+        assert!(fuzzy_match("em", "README.md", false).is_some()); // 'e' then 'm' in README.md
 
         // Test that short queries work with common filenames - using realistic examples
-        assert!(fuzzy_match("mn", "main.rs").is_some()); // m-a-i-n from main.rs
-        assert!(fuzzy_match("tt", "tests").is_some()); // t-e-t-s from tests
-        assert!(fuzzy_match("mai", "main.rs").is_some()); // m-a-i from main.rs
-        assert!(fuzzy_match("lib", "lib.rs").is_some()); // l-i-b from lib.rs
+        assert!(fuzzy_match("mn", "main.rs", false).is_some()); // m-a-i-n from main.rs
+        assert!(fuzzy_match("tt", "tests", false).is_some()); // t-e-t-s from tests
+        assert!(fuzzy_match("mai", "main.rs", false).is_some()); // m-a-i from main.rs
+        assert!(fuzzy_match("lib", "lib.rs", false).is_some()); // l-i-b from lib.rs
     }
 
     #[test]
     fn test_word_boundary_bonuses() {
         // Word start bonus - "main" should match better at start
-        let start_score = fuzzy_match("main", "main.rs").unwrap();
-        let middle_score = fuzzy_match("main", "my_main.rs").unwrap();
+        let start_score = fuzzy_match("main", "main.rs", false).unwrap();
+        let middle_score = fuzzy_match("main", "my_main.rs", false).unwrap();
         assert!(start_score >= middle_score); // Should be better or equal
 
         // CamelCase bonus - "mf" should match MyFunction better than myfunction
-        let camel_score = fuzzy_match("mf", "MyFunction").unwrap();
-        let regular_score = fuzzy_match("mf", "myfunction").unwrap();
+        let camel_score = fuzzy_match("mf", "MyFunction", false).unwrap();
+        let regular_score = fuzzy_match("mf", "myfunction", false).unwrap();
         assert!(camel_score >= regular_score); // Should be better or equal
 
         // Snake case bonus - "my" should match my_function better than myfunction
-        let snake_score = fuzzy_match("my", "my_function.rs").unwrap();
-        let no_boundary_score = fuzzy_match("my", "myfunction.rs").unwrap();
+        let snake_score = fuzzy_match("my", "my_function.rs", false).unwrap();
+        let no_boundary_score = fuzzy_match("my", "myfunction.rs", false).unwrap();
         assert!(snake_score >= no_boundary_score); // Should be better or equal
 
         // Test underscore boundary specifically
-        let underscore_score = fuzzy_match("func", "my_function.rs").unwrap();
-        let direct_score = fuzzy_match("func", "myfunction.rs").unwrap();
+        let underscore_score = fuzzy_match("func", "my_function.rs", false).unwrap();
+        let direct_score = fuzzy_match("func", "myfunction.rs", false).unwrap();
         assert!(underscore_score >= direct_score);
     }
 
     #[test]
     fn test_position_scoring() {
         // Earlier characters should get higher scores
-        let early_score = fuzzy_match("abc", "abcxyz").unwrap();
-        let late_score = fuzzy_match("abc", "xyzabc").unwrap();
+        let early_score = fuzzy_match("abc", "abcxyz", false).unwrap();
+        let late_score = fuzzy_match("abc", "xyzabc", false).unwrap();
         assert!(early_score > late_score);
     }
 
     #[test]
     fn test_levenshtein_fallback() {
         // Should match with typos for short queries
-        assert!(fuzzy_match("man", "main").is_some()); // 1 substitution
-        assert!(fuzzy_match("mian", "main").is_some()); // 1 transposition
+        assert!(fuzzy_match("man", "main", false).is_some()); // 1 substitution
+        assert!(fuzzy_match("mian", "main", false).is_some()); // 1 transposition
 
         // Should not match for very different strings
-        assert_eq!(fuzzy_match("abc", "xyz"), None);
+        assert_eq!(fuzzy_match("abc", "xyz", false), None);
 
         // Should not apply Levenshtein to long queries
         assert_eq!(
-            fuzzy_match("verylongquerythatshouldnotuselevenshtein", "short"),
+            fuzzy_match("verylongquerythatshouldnotuselevenshtein", "short", false),
             None
         );
     }
 
     #[test]
     fn test_case_insensitive() {
-        assert_eq!(fuzzy_match("abc", "ABC"), fuzzy_match("ABC", "abc"));
+        assert_eq!(fuzzy_match("abc", "ABC", false), fuzzy_match("ABC", "abc", false));
         assert_eq!(
-            fuzzy_match("Main", "main.rs"),
-            fuzzy_match("main", "MAIN.rs")
+            fuzzy_match("Main", "main.rs", false),
+            fuzzy_match("main", "MAIN.rs", false)
         );
 
         // Case variations should match
-        assert!(fuzzy_match("mlb", "MY_LIB.RS").is_some());
-        assert!(fuzzy_match("MLB", "my_lib.rs").is_some());
+        assert!(fuzzy_match("mlb", "MY_LIB.RS", false).is_some());
+        assert!(fuzzy_match("MLB", "my_lib.rs", false).is_some());
     }
 
     #[test]
     fn test_length_penalties() {
         // Exact length match should be preferred
-        let exact_match = fuzzy_match("main", "main").unwrap();
-        let longer_target = fuzzy_match("main", "main_extended").unwrap();
+        let exact_match = fuzzy_match("main", "main", false).unwrap();
+        let longer_target = fuzzy_match("main", "main_extended", false).unwrap();
         assert!(exact_match > longer_target);
 
         // Very short queries on long targets should be penalized
-        let short_query = fuzzy_match("a", "very_long_filename.rs").unwrap();
-        let reasonable_query = fuzzy_match("very", "very_long_filename.rs").unwrap();
+        let short_query = fuzzy_match("a", "very_long_filename.rs", false).unwrap();
+        let reasonable_query = fuzzy_match("very", "very_long_filename.rs", false).unwrap();
         assert!(reasonable_query > short_query);
     }
 
@@ -1717,6 +1945,41 @@ mod tests {
         assert_eq!(state.filtered_items.len(), 2);
     }
 
+    #[test]
+    fn test_smart_case() {
+        let mut state = FuzzySearchState::new();
+        state.all_items = ["README.md", "readme_test.rs"]
+            .into_iter()
+            .map(|name| FileItem {
+                name: name.to_string(),
+                path: PathBuf::from(name),
+                is_dir: false,
+                is_hidden: false,
+                modified: SystemTime::UNIX_EPOCH,
+                size: Some(100),
+                is_binary: false,
+            })
+            .collect();
+        let names = |state: &FuzzySearchState| {
+            let mut names: Vec<String> =
+                state.filtered_items.iter().map(|item| item.name.clone()).collect();
+            names.sort();
+            names
+        };
+
+        state.query = "readme".to_string();
+        state.update_filter();
+        assert_eq!(names(&state), ["README.md", "readme_test.rs"]);
+        state.query = "README".to_string();
+        state.update_filter();
+        assert_eq!(names(&state), ["README.md"]);
+
+        // Without smart case, case never matters
+        state.smart_case = false;
+        state.update_filter();
+        assert_eq!(names(&state), ["README.md", "readme_test.rs"]);
+    }
+
     #[test]
     fn test_benchmark_performance() {
         benchmark_fuzzy_search_performance();
@@ -1796,20 +2059,20 @@ mod tests {
         };
 
         // Test filename matching (should have higher score)
-        let result = fuzzy_match_with_priority("main", &item);
+        let result = fuzzy_match_with_priority("main", &item, false);
         assert!(result.is_some());
         let (score, match_type) = result.unwrap();
         assert!(score > 100); // Should have bonus for filename match
         assert_eq!(match_type, MatchType::FilenameFuzzy);
 
         // Test exact filename match
-        let result = fuzzy_match_with_priority("main.rs", &item);
+        let result = fuzzy_match_with_priority("main.rs", &item, false);
         assert!(result.is_some());
         let (_, match_type) = result.unwrap();
         assert_eq!(match_type, MatchType::ExactFilename);
 
         // Test no match
-        let result = fuzzy_match_with_priority("xyz", &item);
+        let result = fuzzy_match_with_priority("xyz", &item, false);
         assert!(result.is_none());
     }
 
@@ -1960,7 +2223,7 @@ mod tests {
         let mut scored_items: Vec<(FileItem, i32, MatchType)> = items
             .into_iter()
             .filter_map(|item| {
-                fuzzy_match_with_priority("main", &item)
+                fuzzy_match_with_priority("main", &item, false)
                     .map(|(score, match_type)| (item, score, match_type))
             })
             .collect();
@@ -2169,8 +2432,8 @@ mod tests {
         std::fs::write(root.join("target/test"), "").unwrap();
         std::fs::write(root.join("debug.log"), "").unwrap();
 
-        let items_without_filter = scan_directory(&root.to_path_buf(), false);
-        let items_with_filter = scan_directory(&root.to_path_buf(), true);
+        let items_without_filter = scan_directory(&root.to_path_buf(), false, true);
+        let items_with_filter = scan_directory(&root.to_path_buf(), true, true);
 
         let names_without: Vec<_> = items_without_filter
             .iter()
@@ -2184,9 +2447,86 @@ mod tests {
 
         assert!(!names_with.iter().any(|n| n.contains("target")));
         assert!(!names_with.iter().any(|n| n.contains("debug.log")));
-        assert!(!names_with.iter().any(|n| n == ".gitignore"));
         assert!(
             names_with.iter().any(|n| n == "src") || names_with.iter().any(|n| n.ends_with("src"))
         );
     }
+
+    #[test]
+    fn test_hidden_files_filtering() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        std::fs::create_dir_all(root.join(".git/objects")).unwrap();
+        std::fs::write(root.join(".git/objects/abc"), "").unwrap();
+        std::fs::write(root.join(".env"), "").unwrap();
+        std::fs::write(root.join("main.rs"), "").unwrap();
+
+        let names = |items: Vec<FileItem>| -> Vec<String> {
+            items.into_iter().map(|i| i.name).collect()
+        };
+
+        let hidden_off = names(scan_directory(&root, false, false));
+        assert_eq!(hidden_off, vec!["main.rs".to_string()]);
+        let hidden_on = names(scan_directory(&root, false, true));
+        assert!(hidden_on.iter().any(|n| n == ".env"));
+        assert!(hidden_on.iter().any(|n| n == ".git"));
+
+        // Dot-directories aren't descended into either
+        let recursive = names(scan_directory_recursive(&root, 0, false, false));
+        assert!(recursive.iter().any(|n| n == "main.rs"));
+        assert!(!recursive.iter().any(|n| n == "abc" || n == ".env" || n.contains(".git")));
+        let recursive = names(scan_directory_recursive(&root, 0, false, true));
+        assert!(recursive.iter().any(|n| n == "abc"));
+
+        let mut state = FuzzySearchState::new_in_directory(&root);
+        state.recursive_search = false;
+        state.rescan_current_directory();
+        assert_eq!(state.all_items.len(), 1);
+        state.toggle_hidden();
+        assert!(state.show_hidden);
+        assert_eq!(state.all_items.len(), 3);
+    }
+
+    #[test]
+    fn test_symbol_picker_ranks_answer_and_debounces_query() {
+        let symbol = |name: &str| SymbolItem {
+            name: name.to_string(),
+            kind: "function".to_string(),
+            container: None,
+            path: PathBuf::from("src/lib.rs"),
+            line: 0,
+            character: 0,
+        };
+        let mut state = FuzzySearchState::new_symbols();
+        // The first request waits for the pause like any other
+        assert!(state.has_pending_symbol_query());
+        assert_eq!(state.take_due_symbol_query(), None);
+        let long_ago = Instant::now() - SYMBOL_QUERY_DELAY;
+        state.symbols.as_mut().unwrap().query_changed = Some(long_ago);
+        assert_eq!(state.take_due_symbol_query(), Some(String::new()));
+        assert!(!state.has_pending_symbol_query());
+
+        state.set_symbols(vec![symbol("parse_config"), symbol("render"), symbol("parse")]);
+        assert!(!state.has_pending_symbol_query());
+        state.update_query("parse".to_string());
+        let names: Vec<_> = state
+            .symbols
+            .as_ref()
+            .unwrap()
+            .filtered
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(names, ["parse", "parse_config"]);
+        assert_eq!(state.result_count, 2);
+
+        // The new query goes out once typing pauses
+        assert!(state.has_pending_symbol_query());
+        state.symbols.as_mut().unwrap().query_changed = Some(long_ago);
+        assert_eq!(state.take_due_symbol_query(), Some("parse".to_string()));
+        state.select_next();
+        assert_eq!(state.get_selected_symbol().unwrap().name, "parse_config");
+    }
 }
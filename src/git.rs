@@ -0,0 +1,306 @@
+// src/git.rs - Per-line change markers against the file's git index version
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Signs are recomputed once edits have paused for this long
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Above this many line pairs a changed region isn't diffed line by line but
+/// marked modified as a whole
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// How a line differs from the version in the git index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines were deleted just below this one
+    Removed,
+}
+
+impl LineChange {
+    pub fn sign(self) -> char {
+        match self {
+            LineChange::Added => '+',
+            LineChange::Modified => '~',
+            LineChange::Removed => '_',
+        }
+    }
+}
+
+/// A run of changed lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    /// First line of the hunk in the buffer
+    pub start: usize,
+    /// Lines of the index version it replaces
+    pub removed: usize,
+    /// Lines of the buffer it covers
+    pub added: usize,
+}
+
+impl Hunk {
+    /// Line the hunk is shown on. A pure deletion is marked on the line above
+    /// the deleted lines.
+    pub fn first_line(&self) -> usize {
+        if self.added == 0 {
+            self.start.saturating_sub(1)
+        } else {
+            self.start
+        }
+    }
+
+    fn signs(&self) -> Vec<(usize, LineChange)> {
+        if self.added == 0 {
+            return vec![(self.first_line(), LineChange::Removed)];
+        }
+        (0..self.added)
+            .map(|offset| {
+                let change = if offset < self.removed {
+                    LineChange::Modified
+                } else {
+                    LineChange::Added
+                };
+                (self.start + offset, change)
+            })
+            .collect()
+    }
+}
+
+/// Change markers for the open buffer
+#[derive(Debug, Default)]
+pub struct GitSigns {
+    /// The file as staged in the index; `None` outside a repository or for
+    /// untracked files, which show no signs
+    base: Option<String>,
+    hunks: Vec<Hunk>,
+    signs: HashMap<usize, LineChange>,
+    /// Buffer version the signs describe
+    computed_version: usize,
+    /// Buffer version of the last edit seen, and when it was seen
+    seen_version: usize,
+    seen_at: Option<Instant>,
+}
+
+impl GitSigns {
+    /// Read the index version of `path` and compute signs for `text`
+    pub fn load(path: &Path, text: &str, version: usize) -> Self {
+        let mut signs = Self {
+            base: index_content(path),
+            ..Default::default()
+        };
+        signs.update(text, version);
+        signs
+    }
+
+    /// Recompute the signs for `text` right away, e.g. after a save
+    pub fn update(&mut self, text: &str, version: usize) {
+        self.hunks = match &self.base {
+            Some(base) => diff_hunks(base, text),
+            None => Vec::new(),
+        };
+        self.signs = self.hunks.iter().flat_map(Hunk::signs).collect();
+        self.computed_version = version;
+        self.seen_version = version;
+        self.seen_at = None;
+    }
+
+    /// Whether an edit hasn't been reflected in the signs yet
+    pub fn is_stale(&self, version: usize) -> bool {
+        self.base.is_some() && version != self.computed_version
+    }
+
+    /// Recompute the signs once edits have paused. `text` is only called
+    /// when they are. Returns true if the signs were recomputed.
+    pub fn poll(&mut self, version: usize, text: impl FnOnce() -> String) -> bool {
+        if !self.is_stale(version) {
+            return false;
+        }
+        if version != self.seen_version || self.seen_at.is_none() {
+            self.seen_version = version;
+            self.seen_at = Some(Instant::now());
+            return false;
+        }
+        if self.seen_at.is_some_and(|at| at.elapsed() < DEBOUNCE) {
+            return false;
+        }
+        self.update(&text(), version);
+        true
+    }
+
+    pub fn sign(&self, line: usize) -> Option<LineChange> {
+        self.signs.get(&line).copied()
+    }
+
+    /// Changed regions, top to bottom
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+}
+
+/// Contents of `path` as staged in its repository's index
+fn index_content(path: &Path) -> Option<String> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let name = path.file_name()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir.unwrap_or(Path::new(".")))
+        .arg("show")
+        .arg(format!(":./{}", name.to_string_lossy()))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Find the regions of `new` that differ from `old`
+pub fn diff_hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Only the region between the common prefix and suffix needs diffing
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut hunks = Vec::new();
+    let mut mark_hunk = |start: usize, removed: usize, added: usize| {
+        if removed > 0 || added > 0 {
+            hunks.push(Hunk {
+                start,
+                removed,
+                added,
+            });
+        }
+    };
+
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        mark_hunk(prefix, old_mid.len(), new_mid.len());
+        return hunks;
+    }
+
+    // Longest common subsequence table, filled from the end
+    let width = new_mid.len() + 1;
+    let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    // Walk the table, grouping unmatched lines between matches into hunks
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut added, mut hunk_start) = (0, 0, prefix);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            mark_hunk(hunk_start, removed, added);
+            i += 1;
+            j += 1;
+            (removed, added, hunk_start) = (0, 0, prefix + j);
+        } else if j < new_mid.len()
+            && (i == old_mid.len() || lcs[i * width + j + 1] >= lcs[(i + 1) * width + j])
+        {
+            added += 1;
+            j += 1;
+        } else {
+            removed += 1;
+            i += 1;
+        }
+    }
+    mark_hunk(hunk_start, removed, added);
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signs(old: &str, new: &str) -> Vec<(usize, char)> {
+        let mut signs: Vec<_> = diff_hunks(old, new)
+            .iter()
+            .flat_map(Hunk::signs)
+            .map(|(line, change)| (line, change.sign()))
+            .collect();
+        signs.sort();
+        signs
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let old = "a\nb\nc\nd\n";
+        assert_eq!(signs(old, old), []);
+        assert_eq!(signs(old, "a\nx\nb\nc\nd\n"), [(1, '+')]);
+        assert_eq!(signs(old, "a\nB\nc\nd\ne\n"), [(1, '~'), (4, '+')]);
+        assert_eq!(signs(old, "a\nd\n"), [(0, '_')]);
+        assert_eq!(signs(old, "b\nc\nd\n"), [(0, '_')]);
+        // A replacement longer than what it replaced
+        assert_eq!(
+            signs(old, "a\nX\nY\nZ\nd\n"),
+            [(1, '~'), (2, '~'), (3, '+')]
+        );
+
+        let hunks = diff_hunks(old, "x\na\nb\nd\n");
+        let lines: Vec<_> = hunks.iter().map(Hunk::first_line).collect();
+        assert_eq!(lines, [0, 2]);
+    }
+
+    #[test]
+    fn test_untracked_file_has_no_signs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "a\n").unwrap();
+
+        let mut signs = GitSigns::load(&path, "a\nb\n", 1);
+        assert_eq!(signs.sign(1), None);
+        assert!(!signs.is_stale(2));
+        assert!(!signs.poll(2, || unreachable!()));
+    }
+
+    #[test]
+    fn test_signs_against_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(args)
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q"]) {
+            // git isn't installed
+            return;
+        }
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        assert!(git(&["add", "file.txt"]));
+
+        let mut signs = GitSigns::load(&path, "one\n2\nthree\n", 1);
+        assert_eq!(signs.sign(0), None);
+        assert_eq!(signs.sign(1), Some(LineChange::Modified));
+        assert_eq!(signs.sign(2), Some(LineChange::Added));
+
+        // Edits are picked up once they pause
+        assert!(!signs.poll(2, || "one\ntwo\n".to_string()));
+        signs.seen_at = Some(Instant::now() - DEBOUNCE);
+        assert!(signs.poll(2, || "one\ntwo\n".to_string()));
+        assert_eq!(signs.sign(1), None);
+    }
+}
@@ -0,0 +1,210 @@
+// src/inlay_hints.rs - Inlay hints from the language server, drawn between the characters of a line
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::display::VirtualText;
+
+/// How long the text and view must stay the same before hints are asked for
+const INLAY_HINT_DELAY: Duration = Duration::from_millis(300);
+
+/// How long to wait before asking again after a request failed
+const INLAY_HINT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Text and lines hints are asked for: the buffer version and visible lines
+type Request = (usize, Range<usize>);
+
+/// Inlay hints for the visible lines. They aren't part of the buffer: the
+/// cursor skips them and they are never saved.
+#[derive(Debug, Default)]
+pub struct InlayHints {
+    /// Hints by line, sorted by column
+    hints: HashMap<usize, Vec<VirtualText>>,
+    /// What the shown hints were asked for
+    fetched: Option<Request>,
+    /// A changed view or text waiting for typing and scrolling to pause
+    pending: Option<(Request, Instant)>,
+}
+
+impl InlayHints {
+    /// Hints drawn on `line`
+    pub fn line(&self, line: usize) -> &[VirtualText] {
+        self.hints.get(&line).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether hints should be asked for `lines` of the text at `version`
+    /// now. They are once the view and text have stayed the same for a moment
+    /// after changing. Hints are dropped as soon as the text changes, since
+    /// edits move the text under them.
+    pub fn due(&mut self, version: usize, lines: Range<usize>) -> bool {
+        let wanted = (version, lines);
+        if self.fetched.as_ref() == Some(&wanted) {
+            self.pending = None;
+            return false;
+        }
+        if self.fetched.as_ref().is_some_and(|(fetched, _)| *fetched != version) {
+            self.hints.clear();
+            self.fetched = None;
+        }
+        match &self.pending {
+            Some((request, since)) if *request == wanted => {
+                if since.elapsed() < INLAY_HINT_DELAY {
+                    return false;
+                }
+                self.pending = None;
+                self.fetched = Some(wanted);
+                true
+            }
+            _ => {
+                self.pending = Some((wanted, Instant::now()));
+                false
+            }
+        }
+    }
+
+    /// The request `due` made for `lines` of the text at `version` got no
+    /// answer. It is made again after a while, unless the view or text moves
+    /// on first.
+    pub fn failed(&mut self, version: usize, lines: Range<usize>) {
+        let request = (version, lines);
+        if self.fetched.as_ref() == Some(&request) {
+            self.fetched = None;
+            // `due` waits for the delay after this point in time
+            let retry_at = Instant::now() + INLAY_HINT_RETRY_DELAY - INLAY_HINT_DELAY;
+            self.pending = Some((request, retry_at));
+        }
+    }
+
+    /// Buffer version the shown hints were asked for
+    pub fn fetched_version(&self) -> Option<usize> {
+        self.fetched.as_ref().map(|(version, _)| *version)
+    }
+
+    /// Whether a request waits for typing or scrolling to pause
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Show the hints answered for the text at `version`, unless it has
+    /// changed since they were asked for
+    pub fn set(&mut self, version: usize, hints: Vec<(usize, VirtualText)>) {
+        if self.fetched.as_ref().is_none_or(|(fetched, _)| *fetched != version) {
+            return;
+        }
+        self.hints.clear();
+        for (line, hint) in hints {
+            self.hints.entry(line).or_default().push(hint);
+        }
+        for line in self.hints.values_mut() {
+            line.sort_by_key(|hint| hint.col);
+        }
+    }
+
+    /// Forget the hints, such as when another file is opened or the server stops
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Text of a hint, with the padding the server asks for. Line breaks in
+/// labels are drawn as spaces.
+pub fn hint_text(hint: &lsp_types::InlayHint) -> String {
+    let label = match &hint.label {
+        lsp_types::InlayHintLabel::String(label) => label.clone(),
+        lsp_types::InlayHintLabel::LabelParts(parts) => {
+            parts.iter().map(|part| part.value.as_str()).collect()
+        }
+    };
+    let mut text = String::new();
+    if hint.padding_left == Some(true) {
+        text.push(' ');
+    }
+    text.extend(label.chars().map(|c| if c == '\n' { ' ' } else { c }));
+    if hint.padding_right == Some(true) {
+        text.push(' ');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(col: usize, text: &str) -> VirtualText {
+        VirtualText {
+            col,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_hints_are_asked_for_once_changes_pause() {
+        let mut hints = InlayHints::default();
+        assert!(!hints.due(1, 0..10));
+        assert!(hints.is_pending());
+        // Scrolling again restarts the wait
+        assert!(!hints.due(1, 5..15));
+        hints.pending.as_mut().unwrap().1 -= INLAY_HINT_DELAY;
+        assert!(hints.due(1, 5..15));
+        assert!(!hints.is_pending());
+        assert!(!hints.due(1, 5..15));
+
+        hints.set(1, vec![(6, hint(4, ": i32")), (6, hint(0, "x: "))]);
+        assert_eq!(hints.line(6), [hint(0, "x: "), hint(4, ": i32")]);
+
+        // An edit hides them, and an answer for the old text isn't shown
+        assert!(!hints.due(2, 5..15));
+        assert!(hints.line(6).is_empty());
+        hints.set(1, vec![(6, hint(4, ": i32"))]);
+        assert!(hints.line(6).is_empty());
+    }
+
+    #[test]
+    fn test_failed_requests_are_made_again() {
+        let mut hints = InlayHints::default();
+        hints.due(1, 0..10);
+        hints.pending.as_mut().unwrap().1 -= INLAY_HINT_DELAY;
+        assert!(hints.due(1, 0..10));
+
+        // An answer for another view doesn't count
+        hints.failed(1, 5..15);
+        assert!(!hints.due(1, 0..10));
+        assert!(!hints.is_pending());
+
+        hints.failed(1, 0..10);
+        assert_eq!(hints.fetched_version(), None);
+        assert!(!hints.due(1, 0..10));
+        assert!(hints.is_pending());
+        hints.pending.as_mut().unwrap().1 -= INLAY_HINT_RETRY_DELAY;
+        assert!(hints.due(1, 0..10));
+    }
+
+    #[test]
+    fn test_hint_text_adds_padding() {
+        let mut inlay_hint = lsp_types::InlayHint {
+            position: lsp_types::Position::new(0, 5),
+            label: lsp_types::InlayHintLabel::String("i32".to_string()),
+            kind: Some(lsp_types::InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: Some(true),
+            padding_right: None,
+            data: None,
+        };
+        assert_eq!(hint_text(&inlay_hint), " i32");
+        inlay_hint.label = lsp_types::InlayHintLabel::LabelParts(vec![
+            lsp_types::InlayHintLabelPart {
+                value: "count".to_string(),
+                ..Default::default()
+            },
+            lsp_types::InlayHintLabelPart {
+                value: ":".to_string(),
+                ..Default::default()
+            },
+        ]);
+        inlay_hint.padding_left = None;
+        inlay_hint.padding_right = Some(true);
+        assert_eq!(hint_text(&inlay_hint), "count: ");
+    }
+}
@@ -1,22 +1,35 @@
 // lib.rs - Library root for texty editor
 
+pub mod alternate;
 pub mod buffer;
 pub mod cli;
 #[cfg(feature = "clipboard")]
 pub mod clipboard;
+pub mod code_point;
 pub mod command;
 pub mod config;
 pub mod cursor;
+pub mod display;
 pub mod editor;
+pub mod editorconfig;
+pub mod ex;
+pub mod file_watcher;
 pub mod formatter;
 pub mod fuzzy_search;
+pub mod git;
+pub mod inlay_hints;
 pub mod keymap;
 pub mod lsp;
+pub mod messages;
 pub mod mode;
 pub mod motion;
+pub mod options;
+pub mod quickfix;
+pub mod recent_files;
 pub mod registers;
 pub mod syntax;
 pub mod theme_discovery;
 pub mod ui;
+pub mod undo;
 pub mod viewport;
 pub mod vim_parser;
@@ -1,5 +1,6 @@
 // src/lsp/client.rs - LSP Client implementation (Helix-style async architecture)
 
+use super::server_log::ServerLog;
 use super::transport::{Transport, TransportError};
 use log::warn;
 use lsp_types::*;
@@ -19,8 +20,15 @@ use lsp_types::{
 };
 use std::process::{Command, Stdio};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+/// Restarts tried in a row before a crashing server is given up on
+pub const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// How long a restarted server must stay up before its crashes are forgotten
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
 #[derive(thiserror::Error, Debug)]
 pub enum LspError {
     #[error("IO error: {0}")]
@@ -35,6 +43,10 @@ pub enum LspError {
     NotInitialized,
     #[error("Server process error")]
     ProcessError,
+    #[error("Server gave up after {0} restarts")]
+    RestartsExhausted(u32),
+    #[error("Server is down")]
+    ServerDown,
 }
 
 #[derive(Clone)]
@@ -46,20 +58,42 @@ pub struct LspClient {
     server_command: String,
     server_args: Vec<String>,
     connection_attempts: Arc<Mutex<u32>>,
+    /// When the server was last restarted after a crash
+    restarted_at: Arc<Mutex<Option<Instant>>>,
+    /// Where the server's stderr goes, across restarts
+    log: ServerLog,
 }
 
-impl LspClient {
-    pub async fn new(server_command: &str, args: &[String]) -> Result<Self, LspError> {
-        let child = Command::new(server_command)
-            .args(args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null()) // Redirect stderr to /dev/null to suppress LSP server output
-            .spawn()?;
-
-        let (connection, _io_threads) = lsp_server::Connection::stdio();
+/// Start a server with piped stdio and connect to its stdin and stdout,
+/// sending its stderr to `log`
+fn spawn_server(
+    command: &str,
+    args: &[String],
+    log: &ServerLog,
+) -> std::io::Result<(std::process::Child, Transport)> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(stderr) = child.stderr.take() {
+        log.capture(stderr);
+    }
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        let _ = child.kill();
+        return Err(std::io::Error::other("server stdio is not piped"));
+    };
+    Ok((child, Transport::spawn(stdin, stdout)))
+}
 
-        let transport = Transport::new(connection);
+impl LspClient {
+    pub async fn new(
+        server_command: &str,
+        args: &[String],
+        log: ServerLog,
+    ) -> Result<Self, LspError> {
+        let (child, transport) = spawn_server(server_command, args, &log)?;
 
         Ok(Self {
             transport: Arc::new(Mutex::new(Some(transport))),
@@ -68,7 +102,9 @@ impl LspClient {
             process_handle: Arc::new(Mutex::new(Some(child))),
             server_command: server_command.to_string(),
             server_args: args.to_vec(),
-            connection_attempts: Arc::new(Mutex::new(1)),
+            connection_attempts: Arc::new(Mutex::new(0)),
+            restarted_at: Arc::new(Mutex::new(None)),
+            log,
         })
     }
 
@@ -80,10 +116,16 @@ impl LspClient {
         let transport = self.transport.lock().await;
         let transport = transport.as_ref().ok_or(LspError::NotInitialized)?;
 
-        // Client capabilities - currently using defaults (empty capabilities object)
+        // Client capabilities - mostly defaults (empty capabilities object)
         // TODO: Add proper LSP client capabilities declaration to inform servers what features we support
         // This affects what the server sends us (e.g., completion, diagnostics, etc.)
-        let capabilities = ClientCapabilities::default();
+        let capabilities = ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                inlay_hint: Some(InlayHintClientCapabilities::default()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
 
         #[allow(deprecated)]
         let params = InitializeParams {
@@ -293,6 +335,54 @@ impl LspClient {
         Ok(response)
     }
 
+    /// Edits that format the whole document, if the server can format
+    pub async fn formatting(
+        &self,
+        uri: &Url,
+        options: lsp_types::FormattingOptions,
+    ) -> Result<Option<Vec<lsp_types::TextEdit>>, LspError> {
+        let params = lsp_types::DocumentFormattingParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+            options,
+            work_done_progress_params: Default::default(),
+        };
+
+        self.send_request::<lsp_types::request::Formatting>("textDocument/formatting", &params)
+            .await
+    }
+
+    /// Whether the server advertised `textDocument/formatting`
+    pub fn supports_formatting(&self) -> bool {
+        self.server_capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.document_formatting_provider.as_ref())
+            .is_some_and(|provider| !matches!(provider, OneOf::Left(false)))
+    }
+
+    pub fn supports_inlay_hints(&self) -> bool {
+        self.server_capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.inlay_hint_provider.as_ref())
+            .is_some_and(|provider| !matches!(provider, OneOf::Left(false)))
+    }
+
+    pub async fn inlay_hints(
+        &self,
+        uri: &Url,
+        range: lsp_types::Range,
+    ) -> Result<Option<Vec<lsp_types::InlayHint>>, LspError> {
+        let params = lsp_types::InlayHintParams {
+            work_done_progress_params: Default::default(),
+            text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+            range,
+        };
+
+        let response: Option<Vec<lsp_types::InlayHint>> = self
+            .send_request::<lsp_types::request::InlayHintRequest>("textDocument/inlayHint", &params)
+            .await?;
+        Ok(response)
+    }
+
     pub async fn workspace_symbols(
         &self,
         query: String,
@@ -332,15 +422,65 @@ impl LspClient {
         Ok(response)
     }
 
+    /// Fill in the lazily computed parts of a code action, usually its edit
+    pub async fn resolve_code_action(
+        &self,
+        action: &lsp_types::CodeAction,
+    ) -> Result<lsp_types::CodeAction, LspError> {
+        self.send_request::<lsp_types::request::CodeActionResolveRequest>(
+            "codeAction/resolve",
+            action,
+        )
+        .await
+    }
+
+    pub async fn execute_command(
+        &self,
+        command: &lsp_types::Command,
+    ) -> Result<Option<serde_json::Value>, LspError> {
+        let params = lsp_types::ExecuteCommandParams {
+            command: command.command.clone(),
+            arguments: command.arguments.clone().unwrap_or_default(),
+            work_done_progress_params: Default::default(),
+        };
+        self.send_request::<lsp_types::request::ExecuteCommand>(
+            "workspace/executeCommand",
+            &params,
+        )
+        .await
+    }
+
+    /// Whether the connection is up and the server process hasn't exited
     pub async fn is_healthy(&self) -> bool {
-        let transport = self.transport.lock().await;
-        transport
-            .as_ref()
-            .map(|t| t.is_connected())
-            .unwrap_or(false)
+        let connected = {
+            let transport = self.transport.lock().await;
+            transport
+                .as_ref()
+                .map(|t| t.is_connected())
+                .unwrap_or(false)
+        };
+        let running = match self.process_handle.lock().await.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        };
+        connected && running
+    }
+
+    /// Restarts tried since the server last stayed up for a while
+    pub async fn restart_attempts(&self) -> u32 {
+        *self.connection_attempts.lock().await
+    }
+
+    /// Whether `other` is a copy of this client, talking to the same server
+    pub fn same_server(&self, other: &LspClient) -> bool {
+        Arc::ptr_eq(&self.process_handle, &other.process_handle)
     }
 
-    pub async fn restart_if_needed(&mut self) -> Result<(), LspError> {
+    /// Restart the server if it has crashed, up to `MAX_RESTART_ATTEMPTS`
+    /// times in a row. The count starts over once a restarted server has
+    /// stayed up for `STABLE_UPTIME`. Returns the attempt number if a restart
+    /// was made.
+    pub async fn restart_if_needed(&mut self) -> Result<Option<u32>, LspError> {
         if !self.is_healthy().await {
             let attempts = {
                 let mut attempts = self.connection_attempts.lock().await;
@@ -348,8 +488,7 @@ impl LspClient {
                 *attempts
             };
 
-            if attempts <= 3 {
-                // Max 3 restart attempts
+            if attempts <= MAX_RESTART_ATTEMPTS {
                 warn!(
                     "LSP server unhealthy, attempting restart (attempt {})",
                     attempts
@@ -362,18 +501,13 @@ impl LspClient {
                 }
 
                 // Create new connection
-                let child = Command::new(&self.server_command)
-                    .args(&self.server_args)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()?;
-
-                let (connection, _io_threads) = lsp_server::Connection::stdio();
-                let transport = Transport::new(connection);
+                self.log.push(format!("texty: restarting (attempt {})", attempts));
+                let (child, transport) =
+                    spawn_server(&self.server_command, &self.server_args, &self.log)?;
 
                 *self.transport.lock().await = Some(transport);
                 *self.process_handle.lock().await = Some(child);
+                *self.restarted_at.lock().await = Some(Instant::now());
                 self.initialized = false;
 
                 // Re-initialize
@@ -381,25 +515,34 @@ impl LspClient {
                 let root_uri = None;
                 self.initialize(workspace_folders, root_uri).await?;
 
-                Ok(())
+                Ok(Some(attempts))
             } else {
-                Err(LspError::Protocol(
-                    "Max restart attempts exceeded".to_string(),
-                ))
+                Err(LspError::RestartsExhausted(MAX_RESTART_ATTEMPTS))
             }
         } else {
-            // Reset attempts on successful connection
-            *self.connection_attempts.lock().await = 0;
-            Ok(())
+            // A server that crashes soon after each restart keeps counting
+            // towards the limit
+            if has_settled(*self.restarted_at.lock().await) {
+                *self.connection_attempts.lock().await = 0;
+            }
+            Ok(None)
         }
     }
 }
 
+/// Whether a server restarted at `restarted_at`, if ever, has been up long
+/// enough for its earlier crashes not to count
+fn has_settled(restarted_at: Option<Instant>) -> bool {
+    restarted_at.is_none_or(|at| at.elapsed() >= STABLE_UPTIME)
+}
+
 impl Drop for LspClient {
     fn drop(&mut self) {
         // Note: shutdown() is async, so we can't call it here
-        // The transport will be dropped automatically
-        if let Ok(mut guard) = self.process_handle.try_lock()
+        // The transport will be dropped automatically. Copies share the
+        // process, so only the last one stops it.
+        if Arc::strong_count(&self.process_handle) == 1
+            && let Ok(mut guard) = self.process_handle.try_lock()
             && let Some(mut child) = guard.take()
         {
             let _ = child.kill();
@@ -493,4 +636,13 @@ mod tests {
 
         assert_eq!(params.text_document.uri, uri);
     }
+
+    #[test]
+    fn test_crashes_are_forgotten_once_the_server_settles() {
+        assert!(has_settled(None));
+        assert!(!has_settled(Some(Instant::now())));
+        if let Some(long_ago) = Instant::now().checked_sub(STABLE_UPTIME) {
+            assert!(has_settled(Some(long_ago)));
+        }
+    }
 }
@@ -1,13 +1,20 @@
 // src/lsp/manager.rs - Multi-server LSP management
 
 use super::client::LspClient;
-use super::client::LspError;
+use super::client::{LspError, MAX_RESTART_ATTEMPTS};
 use super::progress::ProgressManager;
+use super::server_log::ServerLog;
+use log::warn;
 use crate::syntax::LanguageId;
-use std::collections::HashMap;
+use lsp_types::Url;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex as AsyncMutex;
 
+/// How long a server gets to answer each step of the shutdown handshake
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone)]
 pub struct LspConfig {
     pub command: String,
@@ -15,11 +22,66 @@ pub struct LspConfig {
     pub trigger_characters: Vec<String>,
 }
 
+/// A change in a server's health worth telling the user about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// The server crashed and restart `attempt` of `MAX_RESTART_ATTEMPTS` was made
+    Restarting { server: String, attempt: u32 },
+    /// The server kept crashing and is left stopped until restarted by hand
+    Down { server: String },
+}
+
+/// How a language's server is doing, for `:lsp status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ServerStatus {
+    Running,
+    /// Started, but its process has exited
+    Unhealthy,
+    /// Stopped by hand or given up on after repeated crashes
+    Down,
+}
+
+impl ServerStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            ServerStatus::Running => "running",
+            ServerStatus::Unhealthy => "not responding",
+            ServerStatus::Down => "down",
+        }
+    }
+}
+
+impl HealthEvent {
+    /// Status line text for the event
+    pub fn message(&self) -> String {
+        match self {
+            HealthEvent::Restarting { server, attempt } => format!(
+                "{} crashed, restarting ({}/{})",
+                server, attempt, MAX_RESTART_ATTEMPTS
+            ),
+            HealthEvent::Down { server } => format!(
+                "{} is down after {} restarts; use :lsp restart",
+                server, MAX_RESTART_ATTEMPTS
+            ),
+        }
+    }
+}
+
+/// Language, version and text of an open document
+type OpenDocument = (LanguageId, i32, String);
+
 #[derive(Clone)]
 pub struct LspManager {
     clients: Arc<AsyncMutex<HashMap<LanguageId, LspClient>>>,
     configs: HashMap<LanguageId, LspConfig>,
     progress_manager: Arc<ProgressManager>,
+    /// Languages whose server was stopped or ran out of restarts; they aren't
+    /// started again until restarted by hand
+    down: Arc<AsyncMutex<HashSet<LanguageId>>>,
+    /// Stderr of each language's server, kept when it stops or fails to start
+    logs: Arc<std::sync::Mutex<HashMap<LanguageId, ServerLog>>>,
+    /// Open documents, to open again on a server restarted after a crash
+    documents: Arc<std::sync::Mutex<HashMap<Url, OpenDocument>>>,
 }
 
 impl Default for LspManager {
@@ -68,20 +130,38 @@ impl LspManager {
             clients: Arc::new(AsyncMutex::new(HashMap::new())),
             configs,
             progress_manager: Arc::new(ProgressManager::new()),
+            down: Arc::new(AsyncMutex::new(HashSet::new())),
+            logs: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            documents: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
 
     pub async fn get_or_start_client(&self, language: LanguageId) -> Result<(), LspError> {
+        if self.is_down(language).await {
+            return Err(LspError::ServerDown);
+        }
         let mut clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
             self.clients.lock().await;
         if let std::collections::hash_map::Entry::Vacant(e) = clients.entry(language) {
             if let Some(config) = self.configs.get(&language) {
-                let mut client = LspClient::new(&config.command, &config.args).await?;
-                // Initialize the client
-                let workspace_folders = None; // Could be made configurable
-                let root_uri = None; // Could be set to project root
-                client.initialize(workspace_folders, root_uri).await?;
-                e.insert(client);
+                let log = self.server_log(language);
+                let started = async {
+                    let mut client = LspClient::new(&config.command, &config.args, log.clone()).await?;
+                    // Initialize the client
+                    let workspace_folders = None; // Could be made configurable
+                    let root_uri = None; // Could be set to project root
+                    client.initialize(workspace_folders, root_uri).await?;
+                    Ok::<_, LspError>(client)
+                };
+                match started.await {
+                    Ok(client) => {
+                        e.insert(client);
+                    }
+                    Err(err) => {
+                        log.push(format!("texty: failed to start {}: {}", config.command, err));
+                        return Err(err);
+                    }
+                }
             } else {
                 return Err(LspError::Protocol(format!(
                     "No config for language {:?}",
@@ -107,13 +187,364 @@ impl LspManager {
             .unwrap_or(false)
     }
 
-    pub async fn shutdown_all(&self) -> Result<(), LspError> {
-        let mut clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+    /// Whether the language's server was stopped or given up on
+    pub async fn is_down(&self, language: LanguageId) -> bool {
+        self.down.lock().await.contains(&language)
+    }
+
+    /// The log of the language's server, created empty the first time
+    fn server_log(&self, language: LanguageId) -> ServerLog {
+        self.logs.lock().unwrap().entry(language).or_default().clone()
+    }
+
+    /// What the language's server has written to stderr, oldest line first
+    pub fn log_lines(&self, language: LanguageId) -> Vec<String> {
+        self.logs
+            .lock()
+            .unwrap()
+            .get(&language)
+            .map(ServerLog::lines)
+            .unwrap_or_default()
+    }
+
+    /// Command that runs the language's server, naming it in messages
+    pub fn server_name(&self, language: LanguageId) -> String {
+        self.configs
+            .get(&language)
+            .map(|config| config.command.clone())
+            .unwrap_or_else(|| format!("{:?} server", language))
+    }
+
+    /// Shut down the language's server and keep it from starting again until
+    /// `restart`. Returns false if it wasn't running. A server that doesn't
+    /// shut down cleanly is killed.
+    pub async fn stop(&self, language: LanguageId) -> bool {
+        self.down.lock().await.insert(language);
+        let Some(mut client) = self.clients.lock().await.remove(&language) else {
+            return false;
+        };
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, client.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("{} did not shut down cleanly: {}", self.server_name(language), e),
+            Err(_) => warn!("{} did not answer shutdown", self.server_name(language)),
+        }
+        true
+    }
+
+    /// Stop the language's server if it's running and start a fresh one
+    pub async fn restart(&self, language: LanguageId) -> Result<(), LspError> {
+        self.stop(language).await;
+        self.down.lock().await.remove(&language);
+        self.get_or_start_client(language).await
+    }
+
+    /// Send `textDocument/didOpen` for `uri` to the language's server, if one is running
+    pub async fn open_document(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        version: i32,
+        text: &str,
+    ) -> Result<(), LspError> {
+        self.documents
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), (language, version, text.to_string()));
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
             self.clients.lock().await;
-        for client in clients.values_mut() {
-            client.shutdown().await?;
+        match clients.get(&language) {
+            Some(client) => {
+                client
+                    .text_document_did_open(uri, language.name(), version, text)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Send the whole new text of `uri` to the language's server, if one is running
+    pub async fn change_document(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        version: i32,
+        text: &str,
+    ) -> Result<(), LspError> {
+        if let Some(document) = self.documents.lock().unwrap().get_mut(uri) {
+            *document = (language, version, text.to_string());
+        }
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        match clients.get(&language) {
+            Some(client) => {
+                let change = lsp_types::TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: text.to_string(),
+                };
+                client.text_document_did_change(uri, version, vec![change]).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Hover information at `position` of `uri`
+    pub async fn hover(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        position: lsp_types::Position,
+    ) -> Result<Option<lsp_types::Hover>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        client.hover(uri, position).await
+    }
+
+    /// Locations referring to the symbol at `position`, its declaration included
+    pub async fn references(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        position: lsp_types::Position,
+    ) -> Result<Vec<lsp_types::Location>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        Ok(client.find_references(uri, position, true).await?.unwrap_or_default())
+    }
+
+    /// Inlay hints for `range` of `uri`; none if the server doesn't provide them
+    pub async fn inlay_hints(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        range: lsp_types::Range,
+    ) -> Result<Vec<lsp_types::InlayHint>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        if !client.supports_inlay_hints() {
+            return Ok(Vec::new());
+        }
+        Ok(client.inlay_hints(uri, range).await?.unwrap_or_default())
+    }
+
+    /// Symbols anywhere in the workspace matching `query`
+    pub async fn workspace_symbols(
+        &self,
+        language: LanguageId,
+        query: String,
+    ) -> Result<Option<lsp_types::WorkspaceSymbolResponse>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        client.workspace_symbols(query).await
+    }
+
+    /// Edits formatting all of `uri`, or None if the server can't format
+    pub async fn formatting(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        options: lsp_types::FormattingOptions,
+    ) -> Result<Option<Vec<lsp_types::TextEdit>>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        if !client.supports_formatting() {
+            return Ok(None);
+        }
+        Ok(Some(client.formatting(uri, options).await?.unwrap_or_default()))
+    }
+
+    /// Code actions for `range` of `uri`, limited to the `only` kinds when
+    /// given. Bare commands are wrapped as actions that only run the command.
+    pub async fn code_actions(
+        &self,
+        language: LanguageId,
+        uri: &Url,
+        range: lsp_types::Range,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        only: Option<Vec<lsp_types::CodeActionKind>>,
+    ) -> Result<Vec<lsp_types::CodeAction>, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        let context = lsp_types::CodeActionContext {
+            diagnostics,
+            only,
+            trigger_kind: Some(lsp_types::CodeActionTriggerKind::INVOKED),
+        };
+        let response = client.code_actions(uri, range, context).await?;
+        Ok(response
+            .unwrap_or_default()
+            .into_iter()
+            .map(|action| match action {
+                lsp_types::CodeActionOrCommand::CodeAction(action) => action,
+                lsp_types::CodeActionOrCommand::Command(command) => lsp_types::CodeAction {
+                    title: command.title.clone(),
+                    command: Some(command),
+                    ..Default::default()
+                },
+            })
+            .collect())
+    }
+
+    pub async fn resolve_code_action(
+        &self,
+        language: LanguageId,
+        action: &lsp_types::CodeAction,
+    ) -> Result<lsp_types::CodeAction, LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        client.resolve_code_action(action).await
+    }
+
+    pub async fn execute_command(
+        &self,
+        language: LanguageId,
+        command: &lsp_types::Command,
+    ) -> Result<(), LspError> {
+        let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+            self.clients.lock().await;
+        let client = clients.get(&language).ok_or(LspError::NotInitialized)?;
+        client.execute_command(command).await.map(|_| ())
+    }
+
+    /// Every server that is running or down, by name
+    pub async fn status(&self) -> Vec<(String, ServerStatus)> {
+        let mut statuses = Vec::new();
+        {
+            let clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+                self.clients.lock().await;
+            for (&language, client) in clients.iter() {
+                let status = if client.is_healthy().await {
+                    ServerStatus::Running
+                } else {
+                    ServerStatus::Unhealthy
+                };
+                statuses.push((self.server_name(language), status));
+            }
+        }
+        for &language in self.down.lock().await.iter() {
+            statuses.push((self.server_name(language), ServerStatus::Down));
+        }
+        statuses.sort();
+        statuses.dedup_by(|a, b| a.0 == b.0);
+        statuses
+    }
+
+    /// Restart every server that has crashed. Meant to run periodically.
+    pub async fn check_health(&self) -> Vec<HealthEvent> {
+        let languages: Vec<LanguageId> = self.clients.lock().await.keys().copied().collect();
+        let mut events = Vec::new();
+        for language in languages {
+            events.extend(self.check_language(language).await);
+        }
+        events
+    }
+
+    /// Restart the language's server if it has crashed, e.g. after a request
+    /// to it failed with a transport error. Once restarts run out the server
+    /// is dropped and marked down.
+    pub async fn check_language(&self, language: LanguageId) -> Option<HealthEvent> {
+        // Check a copy, so other requests can use the clients meanwhile
+        let mut client = self.clients.lock().await.get(&language)?.clone();
+        let server = self.server_name(language);
+        let result = client.restart_if_needed().await;
+        if let Ok(Some(_)) = result {
+            self.reopen_documents(language, &client).await;
+        }
+
+        // The server may have been stopped or restarted by hand meanwhile
+        let mut clients = self.clients.lock().await;
+        let current = clients
+            .get(&language)
+            .is_some_and(|other| other.same_server(&client));
+        match result {
+            Ok(None) => None,
+            Ok(Some(attempt)) => {
+                if current {
+                    clients.insert(language, client);
+                }
+                Some(HealthEvent::Restarting { server, attempt })
+            }
+            Err(LspError::RestartsExhausted(_)) => {
+                warn!("{} keeps crashing, giving up", server);
+                if current {
+                    clients.remove(&language);
+                    self.down.lock().await.insert(language);
+                }
+                Some(HealthEvent::Down { server })
+            }
+            Err(e) => {
+                // The next check tries again until the attempts run out
+                warn!("Failed to restart {}: {}", server, e);
+                let attempt = client.restart_attempts().await;
+                Some(HealthEvent::Restarting { server, attempt })
+            }
+        }
+    }
+
+    /// Open the language's documents on `client`, a server just restarted
+    /// that knows nothing about them
+    async fn reopen_documents(&self, language: LanguageId, client: &LspClient) {
+        let documents: Vec<(Url, i32, String)> = self
+            .documents
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (document_language, _, _))| *document_language == language)
+            .map(|(uri, (_, version, text))| (uri.clone(), *version, text.clone()))
+            .collect();
+        for (uri, version, text) in documents {
+            let opened = client.text_document_did_open(&uri, language.name(), version, &text);
+            if let Err(e) = opened.await {
+                warn!("Failed to reopen {} after a restart: {}", uri, e);
+            }
+        }
+    }
+
+    /// Send `textDocument/didClose` for `uri` to the language's server, if one is running
+    pub async fn close_document(&self, language: LanguageId, uri: &Url) -> Result<(), LspError> {
+        self.documents.lock().unwrap().remove(uri);
+        let Some(client) = self.get_client(language).await else {
+            return Ok(());
+        };
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, client.text_document_did_close(uri)).await {
+            Ok(result) => result,
+            Err(_) => Err(LspError::Protocol("didClose timed out".to_string())),
+        }
+    }
+
+    /// Run the `shutdown`/`exit` handshake with every server and drop them.
+    /// A server that errors or doesn't answer within the timeout is killed
+    /// when its client is dropped, so a hung server never blocks exit.
+    pub async fn shutdown_all(&self) -> Result<(), LspError> {
+        let clients: Vec<LspClient> = {
+            let mut clients: tokio::sync::MutexGuard<'_, HashMap<LanguageId, LspClient>> =
+                self.clients.lock().await;
+            clients.drain().map(|(_, client)| client).collect()
+        };
+
+        let mut first_error = None;
+        for mut client in clients {
+            let result = match tokio::time::timeout(SHUTDOWN_TIMEOUT, client.shutdown()).await {
+                Ok(result) => result,
+                Err(_) => Err(LspError::Protocol("shutdown timed out".to_string())),
+            };
+            if let Err(e) = result {
+                warn!("LSP server did not shut down cleanly: {}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     pub fn progress_manager(&self) -> Arc<ProgressManager> {
@@ -167,6 +598,34 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_open_documents_are_kept_for_restarts() {
+        let manager = LspManager::new();
+        let uri = Url::parse("file:///tmp/main.rs").unwrap();
+        let documents = || manager.documents.lock().unwrap().get(&uri).cloned();
+
+        // Kept even with no server running, for the one started later
+        manager.open_document(LanguageId::Rust, &uri, 1, "fn main() {}").await.unwrap();
+        assert_eq!(documents(), Some((LanguageId::Rust, 1, "fn main() {}".to_string())));
+        manager.change_document(LanguageId::Rust, &uri, 2, "").await.unwrap();
+        assert_eq!(documents(), Some((LanguageId::Rust, 2, String::new())));
+        manager.close_document(LanguageId::Rust, &uri).await.unwrap();
+        assert_eq!(documents(), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_is_logged() {
+        let mut manager = LspManager::new();
+        manager.configs.get_mut(&LanguageId::Rust).unwrap().command =
+            "texty-no-such-server".to_string();
+        assert!(manager.log_lines(LanguageId::Rust).is_empty());
+
+        assert!(manager.get_or_start_client(LanguageId::Rust).await.is_err());
+        let lines = manager.log_lines(LanguageId::Rust);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("texty: failed to start texty-no-such-server: "));
+    }
+
     #[test]
     fn test_lsp_config_rust() {
         let manager = LspManager::new();
@@ -227,6 +686,56 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_health_event_messages() {
+        let restarting = HealthEvent::Restarting {
+            server: "rust-analyzer".to_string(),
+            attempt: 2,
+        };
+        assert_eq!(restarting.message(), "rust-analyzer crashed, restarting (2/3)");
+        let down = HealthEvent::Down {
+            server: "rust-analyzer".to_string(),
+        };
+        assert_eq!(
+            down.message(),
+            "rust-analyzer is down after 3 restarts; use :lsp restart"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_down_server_is_not_started() {
+        let manager = LspManager::new();
+        assert!(manager.check_health().await.is_empty());
+        assert_eq!(manager.check_language(LanguageId::Rust).await, None);
+
+        manager.down.lock().await.insert(LanguageId::Rust);
+        assert!(manager.is_down(LanguageId::Rust).await);
+        assert!(matches!(
+            manager.get_or_start_client(LanguageId::Rust).await,
+            Err(LspError::ServerDown)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stop_and_status() {
+        let manager = LspManager::new();
+        assert!(manager.status().await.is_empty());
+
+        assert!(!manager.stop(LanguageId::Python).await);
+        assert!(manager.is_down(LanguageId::Python).await);
+        assert_eq!(
+            manager.status().await,
+            vec![("pyright-langserver".to_string(), ServerStatus::Down)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_document_without_client() {
+        let manager = LspManager::new();
+        let uri = Url::parse("file:///tmp/test.rs").unwrap();
+        assert!(manager.close_document(LanguageId::Rust, &uri).await.is_ok());
+    }
+
     #[test]
     fn test_lsp_config_creation() {
         let config = LspConfig {
@@ -5,4 +5,6 @@ pub mod completion;
 pub mod diagnostics;
 pub mod manager;
 pub mod progress;
+pub mod server_log;
 pub mod transport;
+pub mod workspace_edit;
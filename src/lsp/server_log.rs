@@ -0,0 +1,76 @@
+// src/lsp/server_log.rs - What language servers write to stderr, for `:lsp log`
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::{Arc, Mutex};
+
+/// Lines kept per server; older ones are dropped
+pub const SERVER_LOG_LINES: usize = 1000;
+
+/// The last `SERVER_LOG_LINES` lines a server wrote to stderr, oldest first.
+/// Clones share the same lines, so one log can follow a server across restarts.
+#[derive(Debug, Clone, Default)]
+pub struct ServerLog {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl ServerLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, line: impl Into<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == SERVER_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.into());
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Read `stderr` into the log on a background thread until it closes
+    pub fn capture(&self, stderr: impl Read + Send + 'static) {
+        let log = self.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                match line {
+                    Ok(line) => log.push(line),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_newest_lines() {
+        let log = ServerLog::new();
+        for i in 0..SERVER_LOG_LINES + 2 {
+            log.push(i.to_string());
+        }
+        let lines = log.lines();
+        assert_eq!(lines.len(), SERVER_LOG_LINES);
+        assert_eq!(lines[0], "2");
+        assert_eq!(lines.last().unwrap(), &(SERVER_LOG_LINES + 1).to_string());
+    }
+
+    #[test]
+    fn test_capture() {
+        let log = ServerLog::new();
+        log.capture(std::io::Cursor::new(b"starting\nindexing 3 files\n".to_vec()));
+        for _ in 0..100 {
+            if log.lines().len() == 2 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(log.lines(), ["starting", "indexing 3 files"]);
+    }
+}
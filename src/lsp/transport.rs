@@ -4,9 +4,15 @@
 // use futures::StreamExt;  // Future: for async message handling
 use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
 use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::process::{ChildStdin, ChildStdout};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::oneshot;
 
+/// Requests waiting for the server's response, by id
+type PendingRequests = Arc<Mutex<HashMap<RequestId, oneshot::Sender<Response>>>>;
+
 // Future: Transport event types for async message handling
 // #[derive(Debug, Clone)]
 // pub enum TransportEvent {
@@ -35,8 +41,10 @@ pub enum TransportError {
 
 pub struct Transport {
     connection: Connection,
-    pending_requests: Arc<Mutex<HashMap<RequestId, oneshot::Sender<Response>>>>,
+    pending_requests: PendingRequests,
     next_id: Arc<Mutex<i64>>,
+    /// Cleared once the server closes its stdout
+    connected: Arc<AtomicBool>,
 }
 
 impl Transport {
@@ -45,9 +53,60 @@ impl Transport {
             connection,
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(0)),
+            connected: Arc::new(AtomicBool::new(true)),
         }
     }
 
+    /// Talk to a server process over its stdin and stdout. A thread writes
+    /// the messages sent, and another reads the server's: responses go to
+    /// the requests waiting for them, and requests from the server are
+    /// refused, since none are supported yet.
+    pub fn spawn(stdin: ChildStdin, stdout: ChildStdout) -> Self {
+        let (connection, server) = Connection::memory();
+        let transport = Self::new(connection);
+
+        std::thread::spawn(move || {
+            let mut stdin = stdin;
+            for message in server.receiver {
+                if message.write(&mut stdin).and_then(|()| stdin.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let pending = Arc::clone(&transport.pending_requests);
+        let connected = Arc::clone(&transport.connected);
+        let replies = transport.connection.sender.clone();
+        std::thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            while let Ok(Some(message)) = Message::read(&mut stdout) {
+                match message {
+                    Message::Response(response) => {
+                        let waiting = pending.lock().unwrap().remove(&response.id);
+                        if let Some(sender) = waiting {
+                            let _ = sender.send(response);
+                        }
+                    }
+                    Message::Request(request) => {
+                        let _ = replies.send(Message::Response(Response::new_err(
+                            request.id,
+                            ErrorCode::MethodNotFound as i32,
+                            format!("{} is not supported", request.method),
+                        )));
+                    }
+                    Message::Notification(notification) => {
+                        log::debug!("Ignoring LSP notification {}", notification.method);
+                    }
+                }
+            }
+            connected.store(false, Ordering::SeqCst);
+            // Nothing more will arrive for the requests still waiting
+            pending.lock().unwrap().clear();
+        });
+
+        transport
+    }
+
     // Future: Async message processing
     // async fn process_messages(
     //     connection: Connection,
@@ -118,9 +177,9 @@ impl Transport {
             .map_err(|_| TransportError::ConnectionClosed)
     }
 
+    /// Whether the server's stdout is still open
     pub fn is_connected(&self) -> bool {
-        // TODO: Implement connection health check
-        true
+        self.connected.load(Ordering::SeqCst)
     }
 }
 
@@ -137,3 +196,36 @@ impl Drop for Transport {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[tokio::test]
+    async fn test_messages_go_through_the_server_pipes() {
+        // `cat` sends every message back, so a request comes back as a
+        // request from the server, which is refused with a response to it
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let transport = Transport::spawn(stdin, stdout);
+
+        let error = transport
+            .send_request("initialize".to_string(), serde_json::Value::Null)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("initialize is not supported"));
+        assert!(transport.is_connected());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        while transport.is_connected() {
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// src/lsp/workspace_edit.rs - Applying LSP text edits and workspace edits
+
+use lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, ResourceOp, TextEdit, Url, WorkspaceEdit,
+};
+use ropey::Rope;
+use std::path::PathBuf;
+
+/// One step of a workspace edit, in the order it must be applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileChange {
+    Edit(Url, Vec<TextEdit>),
+    /// Create an empty file; `overwrite` truncates an existing one
+    Create { uri: Url, overwrite: bool },
+    Rename { from: Url, to: Url, overwrite: bool },
+    Delete(Url),
+}
+
+/// Flatten a workspace edit into the steps to apply. `documentChanges` wins
+/// over `changes` when a server sends both, as the spec asks.
+pub fn file_changes(edit: &WorkspaceEdit) -> Vec<FileChange> {
+    let text_edits = |edits: &[OneOf<TextEdit, lsp_types::AnnotatedTextEdit>]| {
+        edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(annotated) => annotated.text_edit.clone(),
+            })
+            .collect()
+    };
+
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits
+            .iter()
+            .map(|edit| FileChange::Edit(edit.text_document.uri.clone(), text_edits(&edit.edits)))
+            .collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter()
+            .map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => {
+                    FileChange::Edit(edit.text_document.uri.clone(), text_edits(&edit.edits))
+                }
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => FileChange::Create {
+                    uri: create.uri.clone(),
+                    overwrite: create.options.as_ref().and_then(|o| o.overwrite) == Some(true),
+                },
+                DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => FileChange::Rename {
+                    from: rename.old_uri.clone(),
+                    to: rename.new_uri.clone(),
+                    overwrite: rename.options.as_ref().and_then(|o| o.overwrite) == Some(true),
+                },
+                DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                    FileChange::Delete(delete.uri.clone())
+                }
+            })
+            .collect(),
+        None => {
+            let mut changes: Vec<FileChange> = edit
+                .changes
+                .iter()
+                .flatten()
+                .map(|(uri, edits)| FileChange::Edit(uri.clone(), edits.clone()))
+                .collect();
+            // The map has no order of its own; keep runs reproducible
+            changes.sort_by(|a, b| match (a, b) {
+                (FileChange::Edit(a, _), FileChange::Edit(b, _)) => a.as_str().cmp(b.as_str()),
+                _ => std::cmp::Ordering::Equal,
+            });
+            changes
+        }
+    }
+}
+
+/// Local path of a `file://` URI
+pub fn file_path(uri: &Url) -> Result<PathBuf, String> {
+    uri.to_file_path()
+        .map_err(|_| format!("Not a local file: {}", uri))
+}
+
+/// Char index of an LSP position, whose `character` counts UTF-16 code
+/// units. Positions past the end of a line or the text are clamped to it.
+pub fn char_index(rope: &Rope, position: lsp_types::Position) -> usize {
+    let line = position.line as usize;
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+    let line_start = rope.line_to_char(line);
+    let mut units = 0;
+    let mut col = 0;
+    for c in rope.line(line).chars() {
+        if units >= position.character as usize || c == '\n' || c == '\r' {
+            break;
+        }
+        units += c.len_utf16();
+        col += 1;
+    }
+    line_start + col
+}
+
+/// LSP position of a char index
+pub fn lsp_position(rope: &Rope, char_idx: usize) -> lsp_types::Position {
+    let char_idx = char_idx.min(rope.len_chars());
+    let line = rope.char_to_line(char_idx);
+    let line_start = rope.line_to_char(line);
+    let character: usize = rope
+        .slice(line_start..char_idx)
+        .chars()
+        .map(char::len_utf16)
+        .sum();
+    lsp_types::Position::new(line as u32, character as u32)
+}
+
+/// Apply `edits` to `rope`. They are applied back to front so each edit's
+/// positions still refer to the original text; edits inserting at the same
+/// place end up in the order they were given.
+pub fn apply_text_edits(rope: &mut Rope, edits: &[TextEdit]) {
+    let mut ranges: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let start = char_index(rope, edit.range.start);
+            let end = char_index(rope, edit.range.end).max(start);
+            (start, end, edit.new_text.as_str())
+        })
+        .collect();
+    // Stable, so equal starts keep their order before being reversed
+    ranges.sort_by_key(|&(start, _, _)| start);
+    for (start, end, text) in ranges.into_iter().rev() {
+        rope.remove(start..end);
+        rope.insert(start, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+    use std::collections::HashMap;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextEdit {
+        TextEdit::new(
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            text.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let rope = Rope::from_str("a😀b\nçd\n");
+        // The emoji takes two UTF-16 units but one char
+        assert_eq!(char_index(&rope, Position::new(0, 3)), 2);
+        assert_eq!(lsp_position(&rope, 2), Position::new(0, 3));
+        assert_eq!(char_index(&rope, Position::new(1, 1)), 5);
+        // Past the end of a line or the text
+        assert_eq!(char_index(&rope, Position::new(0, 99)), 3);
+        assert_eq!(char_index(&rope, Position::new(9, 0)), rope.len_chars());
+    }
+
+    #[test]
+    fn test_apply_text_edits_back_to_front() {
+        let mut rope = Rope::from_str("use b;\nfn main() {}\n");
+        apply_text_edits(
+            &mut rope,
+            &[
+                edit((1, 3), (1, 7), "start"),
+                edit((0, 0), (0, 0), "use a;\n"),
+                edit((0, 0), (0, 0), "use c;\n"),
+            ],
+        );
+        assert_eq!(rope.to_string(), "use a;\nuse c;\nuse b;\nfn start() {}\n");
+    }
+
+    #[test]
+    fn test_file_changes() {
+        let uri = Url::parse("file:///tmp/a.rs").unwrap();
+        let workspace_edit = WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit((0, 0), (0, 1), "x")])])),
+            ..Default::default()
+        };
+        assert_eq!(
+            file_changes(&workspace_edit),
+            vec![FileChange::Edit(uri, vec![edit((0, 0), (0, 1), "x")])]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crossterm::{
-    event::{Event, KeyCode, KeyModifiers, read},
+    cursor::SetCursorStyle,
+    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseEventKind, read},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use std::time::{Duration, Instant};
@@ -16,7 +17,7 @@ static mut LAST_SPACE_TIME: Option<Instant> = None;
 /// The function performs terminal setup (raw mode, alternate screen), constructs the UI renderer
 /// according to CLI flags or the `TEXTY_TERMINAL_PALETTE` environment variable, and drives input
 /// events until the editor requests shutdown. On exit it leaves the alternate screen and disables
-/// raw mode.
+/// raw mode, as it also does when an error is returned or something panics.
 ///
 /// # Returns
 ///
@@ -45,13 +46,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .filter_level(log::LevelFilter::Info)
         .init();
 
-    // Enable raw mode and enter alternate screen
+    // Put the terminal back before a panic message is printed, so a crash
+    // doesn't leave it in raw mode on the alternate screen. Only a panic in
+    // this thread, which runs the UI, ends the editor; background threads
+    // such as a server's log reader can panic while it keeps running.
+    let ui_thread = std::thread::current().id();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() == ui_thread {
+            restore_terminal();
+        }
+        default_hook(info);
+    }));
+
+    // Enable raw mode, enter alternate screen and receive mouse events. The
+    // guard restores the terminal however `main` returns, errors included.
     enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    crossterm::execute!(stdout, EnterAlternateScreen)?;
+    let terminal = TerminalGuard;
+    crossterm::execute!(std::io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
 
     // Initialize editor
     let mut editor = Editor::new();
+    let (cols, rows) = crossterm::terminal::size()?;
+    editor.handle_resize(rows, cols);
+    if let Some(config_path) = texty::theme_discovery::find_config_file()
+        && let Ok(config) = texty::config::TextyConfig::from_file(&config_path)
+    {
+        editor.apply_config(&config);
+    }
+    if let Some(store) = texty::recent_files::default_path() {
+        editor.recent_files = texty::recent_files::RecentFiles::load(&store);
+    }
 
     // Handle file/directory argument if specified
     if let Some(path) = &cli_args.file {
@@ -69,10 +94,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
+    if let Some(language) = cli_args.filetype {
+        editor.set_language(Some(language));
+    }
 
     // Handle --list-themes flag
     if cli_args.list_themes {
         let themes = texty::theme_discovery::list_builtin_themes();
+        drop(terminal);
         println!("Available built-in themes:");
         for theme in themes {
             println!("  {}", theme);
@@ -90,10 +119,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Frame rate limiting constants
     const TARGET_FPS: u64 = 60;
     const FRAME_DURATION: Duration = Duration::from_micros(1_000_000 / TARGET_FPS);
+    // How often language servers are checked for crashes
+    const LSP_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
     // Event loop with frame rate limiting
     let mut last_frame_time = Instant::now();
+    let mut last_health_check = Instant::now();
     let mut needs_redraw = true;
+    // For the hover idle timer: when the last key or click came in, and
+    // whether the timer already went off since
+    let mut last_input = Instant::now();
+    let mut idle_fired = false;
 
     loop {
         // Only render if needed and enough time has elapsed since last frame
@@ -103,6 +139,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             needs_redraw = false;
         }
 
+        // Time left until the cursor counts as resting, if the idle timer is on
+        let idle_wait = editor
+            .options
+            .hover_delay
+            .filter(|_| !idle_fired)
+            .map(|delay| delay.saturating_sub(last_input.elapsed()));
+
         // Read event (blocking, with timeout for periodic redraws)
         let event = if last_frame_time.elapsed() < FRAME_DURATION {
             // Use poll with timeout to respect frame rate
@@ -112,11 +155,87 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             } else {
                 None
             }
-        } else {
+        } else if editor.buffer.has_pending_highlight()
+            || editor.git_signs.is_stale(editor.buffer.version)
+            || editor.has_pending_symbol_query()
+            || editor.inlay_hints.is_pending()
+            || editor.is_waiting_for_inlay_hints()
+            || editor.is_waiting_for_idle_hover()
+            || editor.is_making()
+        {
+            // Wake up once typing pauses so the deferred highlight, git
+            // signs, symbol search and inlay hints are brought up to date,
+            // and soon after hints, hover information or a build finish
+            if crossterm::event::poll(FRAME_DURATION)? {
+                Some(read()?)
+            } else {
+                None
+            }
+        } else if crossterm::event::poll(
+            idle_wait.map_or(LSP_HEALTH_CHECK_INTERVAL, |wait| wait.min(LSP_HEALTH_CHECK_INTERVAL)),
+        )? {
             Some(read()?)
+        } else {
+            None
+        };
+
+        // Any key or click restarts the idle timer and closes what it showed
+        let is_input = match &event {
+            Some(Event::Key(_)) => true,
+            Some(Event::Mouse(mouse_event)) => mouse_event.kind != MouseEventKind::Moved,
+            _ => false,
         };
+        if is_input {
+            last_input = Instant::now();
+            idle_fired = false;
+            if editor.dismiss_idle_hover() {
+                needs_redraw = true;
+            }
+        }
+
+        if editor.check_file_changes() {
+            needs_redraw = true;
+        }
+        if last_health_check.elapsed() >= LSP_HEALTH_CHECK_INTERVAL {
+            last_health_check = Instant::now();
+            editor.start_lsp_health_check();
+        }
+        if editor.finish_lsp_health_check().await {
+            needs_redraw = true;
+        }
+        if editor.finish_make() {
+            needs_redraw = true;
+        }
 
         match event {
+            Some(Event::Key(key_event)) if editor.reload_prompt => {
+                let answer = match key_event.code {
+                    KeyCode::Char(c) => Some(c),
+                    KeyCode::Esc => Some('\x1b'),
+                    _ => None,
+                };
+                if let Some(c) = answer {
+                    editor.handle_reload_prompt(c);
+                    needs_redraw = true;
+                }
+            }
+            Some(Event::Key(key_event)) if editor.code_actions.is_some() => {
+                if let Some(cmd) = code_action_menu_command(key_event) {
+                    editor.execute_command(cmd);
+                }
+                needs_redraw = true;
+            }
+            Some(Event::Key(key_event)) if editor.quickfix_open => {
+                if let Some(cmd) = quickfix_window_command(key_event) {
+                    editor.execute_command(cmd);
+                }
+                needs_redraw = true;
+            }
+            Some(Event::Key(_)) if editor.listing.is_some() => {
+                // Any key closes the `:reg`, `:messages` or `:lsp log` listing
+                editor.listing = None;
+                needs_redraw = true;
+            }
             Some(Event::Key(key_event)) => {
                 match &editor.mode {
                     Mode::Command => {
@@ -157,7 +276,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             }
                         } else {
                             // Use Vim parser for multi-key command sequences
-                            match editor.vim_parser.process_key(key_event) {
+                            let result = if editor.mode == Mode::Visual {
+                                editor.vim_parser.process_visual_key(key_event)
+                            } else {
+                                editor.vim_parser.process_key(key_event)
+                            };
+                            match result {
                                 ParseResult::Command(cmd) => {
                                     if editor.execute_command(cmd) {
                                         break; // Quit
@@ -171,7 +295,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 ParseResult::Invalid => {
                                     // Invalid sequence, reset parser
                                     editor.vim_parser.reset();
-                                    editor.status_message = Some("Invalid command".to_string());
+                                    editor.show_error("Invalid command");
+                                    editor.resume_insert();
                                     needs_redraw = true;
                                 }
                             }
@@ -189,23 +314,118 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
             }
-            Some(Event::Resize(rows, cols)) => {
+            Some(Event::Mouse(mouse_event)) => {
+                editor.handle_mouse(mouse_event);
+                needs_redraw = true;
+            }
+            Some(Event::Resize(cols, rows)) => {
                 editor.handle_resize(rows, cols);
                 needs_redraw = true;
             }
             None => {
-                // Timeout - no event, continue loop
+                // Idle: apply highlighting and git signs deferred while typing
+                if editor.buffer.check_pending_highlight() {
+                    needs_redraw = true;
+                }
+                if editor.poll_git_signs() {
+                    needs_redraw = true;
+                }
             }
             Some(_) => {}
         }
+
+        if let Some(delay) = editor.options.hover_delay
+            && !idle_fired
+            && last_input.elapsed() >= delay
+        {
+            idle_fired = true;
+            if editor.show_idle_info() {
+                needs_redraw = true;
+            }
+        }
+        if editor.finish_idle_hover().await {
+            needs_redraw = true;
+        }
+
+        // The symbol picker and inlay hints ask the server once typing pauses
+        editor.poll_symbol_query();
+        editor.poll_inlay_hints();
+        if editor.finish_inlay_hints().await {
+            needs_redraw = true;
+        }
+
+        // `:lsp` commands talk to the servers asynchronously
+        if editor.run_pending_lsp_action().await {
+            needs_redraw = true;
+        }
+        // Messages from async work and file watching go to `:messages` too
+        editor.record_status();
+        if editor.quit_requested {
+            break;
+        }
     }
 
+    // Tell language servers we're done so they don't linger after exit
+    editor.shutdown().await;
+
     // Leave alternate screen and disable raw mode
-    crossterm::execute!(stdout, LeaveAlternateScreen)?;
-    disable_raw_mode()?;
+    drop(terminal);
     Ok(())
 }
 
+/// Restores the terminal when dropped, on every way out of `main`
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Leave the alternate screen, stop mouse capture and disable raw mode.
+/// Errors are ignored: this runs while exiting, often after something failed.
+fn restore_terminal() {
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        SetCursorStyle::DefaultUserShape,
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    );
+    let _ = disable_raw_mode();
+}
+
+/// Keys for the code action menu while it is open
+fn code_action_menu_command(key_event: crossterm::event::KeyEvent) -> Option<Command> {
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => Some(Command::CodeActionNext),
+        KeyCode::Char('n') if ctrl => Some(Command::CodeActionNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(Command::CodeActionPrev),
+        KeyCode::Char('p') if ctrl => Some(Command::CodeActionPrev),
+        KeyCode::Enter => Some(Command::CodeActionAccept),
+        KeyCode::Esc => Some(Command::Escape),
+        KeyCode::Char('l') if ctrl => Some(Command::CloseAllOverlays),
+        // Toggles the open menu closed
+        KeyCode::Char('q') => Some(Command::CodeAction),
+        _ => None,
+    }
+}
+
+/// Keys for the quickfix window while it is open
+fn quickfix_window_command(key_event: crossterm::event::KeyEvent) -> Option<Command> {
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => Some(Command::QuickfixSelectNext),
+        KeyCode::Char('n') if ctrl => Some(Command::QuickfixSelectNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(Command::QuickfixSelectPrev),
+        KeyCode::Char('p') if ctrl => Some(Command::QuickfixSelectPrev),
+        KeyCode::Enter => Some(Command::QuickfixAccept),
+        KeyCode::Esc | KeyCode::Char('q') => Some(Command::Escape),
+        KeyCode::Char('l') if ctrl => Some(Command::CloseAllOverlays),
+        _ => None,
+    }
+}
+
 fn key_to_command(key_event: crossterm::event::KeyEvent, mode: &Mode) -> Option<Command> {
     match mode {
         Mode::Normal => match key_event.code {
@@ -219,7 +439,7 @@ fn key_to_command(key_event: crossterm::event::KeyEvent, mode: &Mode) -> Option<
             KeyCode::Down => Some(Command::MoveDown),
             KeyCode::Up => Some(Command::MoveUp),
             KeyCode::Right => Some(Command::MoveRight),
-            KeyCode::Char('i') => Some(Command::InsertMode),
+            KeyCode::Char('i') => Some(Command::InsertMode(1)),
             KeyCode::Char(':') => Some(Command::EnterCommandMode),
             KeyCode::Char('f') => Some(Command::FormatBuffer),
             KeyCode::Char('c') => Some(Command::Completion),
@@ -258,6 +478,27 @@ fn key_to_command(key_event: crossterm::event::KeyEvent, mode: &Mode) -> Option<
         },
         Mode::Insert => match key_event.code {
             KeyCode::Esc => Some(Command::NormalMode),
+            KeyCode::Char('w') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::DeleteWordBackInsert)
+            }
+            KeyCode::Char('u') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::DeleteToLineStartInsert)
+            }
+            KeyCode::Char('v') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::InsertCodePoint)
+            }
+            KeyCode::Char('t') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::TransposeChars)
+            }
+            KeyCode::Char('a') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::ReplayLastInsert)
+            }
+            KeyCode::Char('o') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::InsertNormalCommand)
+            }
+            KeyCode::Char('l') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::CloseAllOverlays)
+            }
             KeyCode::Char(c) => Some(Command::InsertChar(c)),
             KeyCode::Enter => Some(Command::InsertChar('\n')),
             KeyCode::Backspace => Some(Command::DeleteChar),
@@ -269,7 +510,7 @@ fn key_to_command(key_event: crossterm::event::KeyEvent, mode: &Mode) -> Option<
             _ => None,
         },
         Mode::FuzzySearch => match key_event.code {
-            KeyCode::Esc => Some(Command::FuzzySearchCancel),
+            KeyCode::Esc => Some(Command::Escape),
             KeyCode::Enter => Some(Command::FuzzySearchSelect),
             KeyCode::Up | KeyCode::Char('k') => Some(Command::FuzzySearchUp),
             KeyCode::Down | KeyCode::Char('j') => Some(Command::FuzzySearchDown),
@@ -280,6 +521,9 @@ fn key_to_command(key_event: crossterm::event::KeyEvent, mode: &Mode) -> Option<
             KeyCode::Char('g') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
                 Some(Command::FuzzySearchToggleGitignore)
             }
+            KeyCode::Char('d') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Command::FuzzySearchToggleHidden)
+            }
             KeyCode::Char(c)
                 if c.is_alphanumeric() || c == ' ' || c == '.' || c == '_' || c == '-' =>
             {
@@ -0,0 +1,146 @@
+// src/messages.rs - History of status line messages, reviewed with `:messages`
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Most messages kept; older ones are dropped
+const MAX_MESSAGES: usize = 200;
+
+/// Whether a message reports a failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+    pub at: Instant,
+}
+
+impl Message {
+    /// `[12s ago] text` for the `:messages` listing
+    pub fn display(&self) -> String {
+        format!("[{}] {}", age_text(self.at.elapsed()), self.text)
+    }
+}
+
+/// Messages shown in the status line, oldest first
+#[derive(Debug, Default)]
+pub struct MessageHistory {
+    messages: VecDeque<Message>,
+    /// Status message last recorded, so one left on screen is kept only once
+    seen: Option<String>,
+}
+
+impl MessageHistory {
+    pub fn push(&mut self, text: impl Into<String>, severity: Severity) {
+        let text = text.into();
+        self.seen = Some(text.clone());
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            text,
+            severity,
+            at: Instant::now(),
+        });
+    }
+
+    /// Keep the status line's `status` as an info message if it is new.
+    /// Mode names such as `-- VISUAL --` aren't kept.
+    pub fn note_status(&mut self, status: Option<&str>) {
+        match status {
+            Some(text) if self.seen.as_deref() != Some(text) => {
+                if text.starts_with("-- ") {
+                    self.seen = Some(text.to_string());
+                } else {
+                    self.push(text, Severity::Info);
+                }
+            }
+            Some(_) => {}
+            None => self.seen = None,
+        }
+    }
+
+    /// Leave `status` out of the history while it stays on screen, for
+    /// replies about the history itself
+    pub fn skip_status(&mut self, status: &str) {
+        self.seen = Some(status.to_string());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Message> {
+        self.messages.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+/// How long ago something happened, in the largest whole unit
+fn age_text(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        _ => format!("{}h ago", secs / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(history: &MessageHistory) -> Vec<(&str, Severity)> {
+        history
+            .iter()
+            .map(|message| (message.text.as_str(), message.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_status_is_kept_once_until_cleared() {
+        let mut history = MessageHistory::default();
+        history.note_status(Some("Yanked 3 lines"));
+        history.note_status(Some("Yanked 3 lines"));
+        history.note_status(Some("-- VISUAL --"));
+        history.note_status(None);
+        history.note_status(Some("Yanked 3 lines"));
+        history.push("Format failed: no rustfmt", Severity::Error);
+        history.note_status(Some("Format failed: no rustfmt"));
+        history.skip_status("No messages");
+        history.note_status(Some("No messages"));
+        assert_eq!(
+            texts(&history),
+            [
+                ("Yanked 3 lines", Severity::Info),
+                ("Yanked 3 lines", Severity::Info),
+                ("Format failed: no rustfmt", Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oldest_messages_are_dropped() {
+        let mut history = MessageHistory::default();
+        for i in 0..MAX_MESSAGES + 5 {
+            history.push(i.to_string(), Severity::Info);
+        }
+        assert_eq!(history.iter().count(), MAX_MESSAGES);
+        assert_eq!(history.iter().next().unwrap().text, "5");
+    }
+
+    #[test]
+    fn test_age_text() {
+        assert_eq!(age_text(Duration::from_secs(5)), "5s ago");
+        assert_eq!(age_text(Duration::from_secs(125)), "2m ago");
+        assert_eq!(age_text(Duration::from_secs(7300)), "2h ago");
+    }
+}
@@ -53,15 +53,66 @@ pub enum WordKind {
     Other,
 }
 
-/// Determine the kind of character at a position
-fn char_kind(c: char) -> WordKind {
-    if c.is_alphanumeric() || c == '_' {
-        WordKind::Alphanumeric
-    } else if c.is_whitespace() {
-        WordKind::Whitespace
-    } else {
-        WordKind::Other
+/// Characters besides letters and digits that count as part of a word (Vim's
+/// `iskeyword`). Word motions, text objects and word lookups all go by it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordChars(String);
+
+impl Default for WordChars {
+    fn default() -> Self {
+        Self("_".to_string())
+    }
+}
+
+impl WordChars {
+    /// Word characters made of letters, digits and `extra`, or `None` if
+    /// `extra` has whitespace in it
+    pub fn new(extra: &str) -> Option<Self> {
+        if extra.chars().any(char::is_whitespace) {
+            return None;
+        }
+        Some(Self(extra.to_string()))
+    }
+
+    /// The characters besides letters and digits
+    pub fn extra(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.0.contains(c)
     }
+
+    /// Determine the kind of character at a position
+    fn kind(&self, c: char) -> WordKind {
+        if self.is_word_char(c) {
+            WordKind::Alphanumeric
+        } else if c.is_whitespace() {
+            WordKind::Whitespace
+        } else {
+            WordKind::Other
+        }
+    }
+}
+
+/// Number of words in `text`, split the way `w` moves: runs of word
+/// characters and runs of other non-blank characters each count as one
+pub fn count_words(text: &str) -> usize {
+    count_words_with(text, &WordChars::default())
+}
+
+/// [`count_words`] with the given word characters
+pub fn count_words_with(text: &str, words: &WordChars) -> usize {
+    let mut count = 0;
+    let mut previous = WordKind::Whitespace;
+    for c in text.chars() {
+        let kind = words.kind(c);
+        if kind != WordKind::Whitespace && kind != previous {
+            count += 1;
+        }
+        previous = kind;
+    }
+    count
 }
 
 /// Move forward by one word (Vim's `w` motion)
@@ -69,8 +120,13 @@ fn char_kind(c: char) -> WordKind {
 /// Rules:
 /// - If on whitespace, skip to next word
 /// - If on a word, go to start of next word
-/// - Word boundaries are alphanumeric/underscore vs other vs whitespace
+/// - Word boundaries are word characters vs other vs whitespace
 pub fn word_forward(buffer: &Buffer, pos: Position) -> Position {
+    word_forward_with(buffer, pos, &WordChars::default())
+}
+
+/// [`word_forward`] with the given word characters
+pub fn word_forward_with(buffer: &Buffer, pos: Position, words: &WordChars) -> Position {
     let line_count = buffer.line_count();
     if line_count == 0 {
         return pos;
@@ -93,13 +149,13 @@ pub fn word_forward(buffer: &Buffer, pos: Position) -> Position {
 
         // Skip to end of current word/whitespace
         let start_kind = if col < chars.len() {
-            char_kind(chars[col])
+            words.kind(chars[col])
         } else {
             WordKind::Whitespace
         };
 
         // Move forward to different character kind
-        while col < chars.len() && char_kind(chars[col]) == start_kind {
+        while col < chars.len() && words.kind(chars[col]) == start_kind {
             col += 1;
         }
 
@@ -125,6 +181,11 @@ pub fn word_forward(buffer: &Buffer, pos: Position) -> Position {
 
 /// Move forward to end of word (Vim's `e` motion)
 pub fn word_end(buffer: &Buffer, pos: Position) -> Position {
+    word_end_with(buffer, pos, &WordChars::default())
+}
+
+/// [`word_end`] with the given word characters
+pub fn word_end_with(buffer: &Buffer, pos: Position, words: &WordChars) -> Position {
     let line_count = buffer.line_count();
     if line_count == 0 {
         return pos;
@@ -154,18 +215,18 @@ pub fn word_end(buffer: &Buffer, pos: Position) -> Position {
 
     // Move to the end of the current word
     let _start_kind = if col < chars.len() {
-        char_kind(chars[col])
+        words.kind(chars[col])
     } else {
         WordKind::Whitespace
     };
 
     // If we're on whitespace, skip it
-    while col < chars.len() && char_kind(chars[col]) == WordKind::Whitespace {
+    while col < chars.len() && words.kind(chars[col]) == WordKind::Whitespace {
         col += 1;
     }
 
     // Find end of current word
-    while col < chars.len() && char_kind(chars[col]) == WordKind::Alphanumeric {
+    while col < chars.len() && words.kind(chars[col]) == WordKind::Alphanumeric {
         col += 1;
     }
 
@@ -177,6 +238,11 @@ pub fn word_end(buffer: &Buffer, pos: Position) -> Position {
 
 /// Move backward by one word (Vim's `b` motion)
 pub fn word_backward(buffer: &Buffer, pos: Position) -> Position {
+    word_backward_with(buffer, pos, &WordChars::default())
+}
+
+/// [`word_backward`] with the given word characters
+pub fn word_backward_with(buffer: &Buffer, pos: Position, words: &WordChars) -> Position {
     let mut line = pos.line;
     let mut col = pos.col;
 
@@ -227,8 +293,8 @@ pub fn word_backward(buffer: &Buffer, pos: Position) -> Position {
         }
 
         // Find the word boundary
-        let start_kind = char_kind(chars[col - 1]);
-        while col > 0 && char_kind(chars[col - 1]) == start_kind {
+        let start_kind = words.kind(chars[col - 1]);
+        while col > 0 && words.kind(chars[col - 1]) == start_kind {
             col -= 1;
         }
 
@@ -237,6 +303,55 @@ pub fn word_backward(buffer: &Buffer, pos: Position) -> Position {
     }
 }
 
+/// Move backward to the end of the previous word (Vim's `ge` motion)
+pub fn word_end_backward(buffer: &Buffer, pos: Position) -> Position {
+    word_end_backward_with(buffer, pos, &WordChars::default())
+}
+
+/// [`word_end_backward`] with the given word characters
+pub fn word_end_backward_with(buffer: &Buffer, pos: Position, words: &WordChars) -> Position {
+    end_backward(buffer, pos, |c| words.kind(c))
+}
+
+/// Move backward to the end of the previous WORD, where only whitespace
+/// separates WORDs (Vim's `gE` motion)
+pub fn big_word_end_backward(buffer: &Buffer, pos: Position) -> Position {
+    end_backward(buffer, pos, |c| {
+        if c.is_whitespace() {
+            WordKind::Whitespace
+        } else {
+            WordKind::Other
+        }
+    })
+}
+
+/// Find the last word end before `pos`: a non-blank character followed by the
+/// end of the line or a character of another kind. Empty lines count as words.
+fn end_backward(buffer: &Buffer, pos: Position, kind: impl Fn(char) -> WordKind) -> Position {
+    let mut line = pos.line.min(buffer.line_count().saturating_sub(1));
+
+    loop {
+        let chars: Vec<char> = buffer.line(line).unwrap_or_default().chars().collect();
+        if line != pos.line && chars.is_empty() {
+            return Position::new(line, 0);
+        }
+
+        let before = if line == pos.line { pos.col.min(chars.len()) } else { chars.len() };
+        for col in (0..before).rev() {
+            let c = chars[col];
+            let at_end = chars.get(col + 1).is_none_or(|&next| kind(next) != kind(c));
+            if kind(c) != WordKind::Whitespace && at_end {
+                return Position::new(line, col);
+            }
+        }
+
+        if line == 0 {
+            return Position::new(0, 0);
+        }
+        line -= 1;
+    }
+}
+
 /// Move to start of line (Vim's `0` motion)
 pub fn line_start(_pos: Position) -> Position {
     Position::new(_pos.line, 0)
@@ -259,6 +374,39 @@ pub fn line_end(buffer: &Buffer, pos: Position) -> Position {
     Position::new(pos.line, line_len.saturating_sub(1))
 }
 
+/// Line of the `count`th blank line below `line` that ends a paragraph
+/// (Vim's `}` motion), or the last line
+pub fn paragraph_forward(buffer: &Buffer, line: usize, count: usize) -> usize {
+    let last_line = buffer.line_count().saturating_sub(1);
+    let is_blank = |line: usize| buffer.get_line_content(line).trim().is_empty();
+    let mut line = line;
+    for _ in 0..count.max(1) {
+        while line < last_line && is_blank(line) {
+            line += 1;
+        }
+        while line < last_line && !is_blank(line) {
+            line += 1;
+        }
+    }
+    line
+}
+
+/// Line of the `count`th blank line above `line` that starts a paragraph
+/// (Vim's `{` motion), or the first line
+pub fn paragraph_backward(buffer: &Buffer, line: usize, count: usize) -> usize {
+    let is_blank = |line: usize| buffer.get_line_content(line).trim().is_empty();
+    let mut line = line;
+    for _ in 0..count.max(1) {
+        while line > 0 && is_blank(line) {
+            line -= 1;
+        }
+        while line > 0 && !is_blank(line) {
+            line -= 1;
+        }
+    }
+    line
+}
+
 /// Move to start of file (Vim's `gg` motion)
 pub fn file_start(_pos: Position) -> Position {
     Position::new(0, 0)
@@ -270,6 +418,334 @@ pub fn file_end(buffer: &Buffer, _pos: Position) -> Position {
     Position::new(last_line, 0)
 }
 
+/// Bounds of the run of same-kind characters under the cursor (Vim's `iw`).
+/// Returns the start and the exclusive end on the cursor's line.
+pub fn inner_word(buffer: &Buffer, pos: Position) -> (Position, Position) {
+    inner_word_with(buffer, pos, &WordChars::default())
+}
+
+/// [`inner_word`] with the given word characters
+pub fn inner_word_with(
+    buffer: &Buffer,
+    pos: Position,
+    words: &WordChars,
+) -> (Position, Position) {
+    let chars: Vec<char> = buffer.line(pos.line).unwrap_or_default().chars().collect();
+    if chars.is_empty() {
+        return (Position::new(pos.line, 0), Position::new(pos.line, 0));
+    }
+
+    let col = pos.col.min(chars.len() - 1);
+    let kind = words.kind(chars[col]);
+    let mut start = col;
+    while start > 0 && words.kind(chars[start - 1]) == kind {
+        start -= 1;
+    }
+    let mut end = col + 1;
+    while end < chars.len() && words.kind(chars[end]) == kind {
+        end += 1;
+    }
+    (Position::new(pos.line, start), Position::new(pos.line, end))
+}
+
+/// A character search within the line: `f`, `t`, `F` or `T`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharSearch {
+    pub ch: char,
+    /// Stop just before the match (`t`/`T`) rather than on it (`f`/`F`)
+    pub till: bool,
+    pub forward: bool,
+}
+
+impl CharSearch {
+    /// The same search in the other direction, for `,`
+    pub fn reversed(self) -> Self {
+        Self {
+            forward: !self.forward,
+            ..self
+        }
+    }
+}
+
+/// Position of the `count`th match of `search` on the cursor's line, or `None`
+/// if there are fewer matches.
+///
+/// When repeating a `t`/`T` search, a match right next to the cursor is skipped,
+/// since stopping before it again would leave the cursor where it is.
+pub fn find_char(
+    buffer: &Buffer,
+    pos: Position,
+    search: CharSearch,
+    count: usize,
+    repeat: bool,
+) -> Option<Position> {
+    let chars: Vec<char> = buffer.line(pos.line)?.chars().collect();
+    let skip = usize::from(search.till && repeat);
+    let matches = |&i: &usize| chars.get(i) == Some(&search.ch);
+    let count = count.max(1);
+
+    let col = if search.forward {
+        let found = (pos.col + 1 + skip..chars.len())
+            .filter(matches)
+            .nth(count - 1)?;
+        if search.till { found - 1 } else { found }
+    } else {
+        let found = (0..pos.col.checked_sub(skip)?)
+            .rev()
+            .filter(matches)
+            .nth(count - 1)?;
+        if search.till { found + 1 } else { found }
+    };
+    Some(Position::new(pos.line, col))
+}
+
+/// What bounds a text object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    /// A pair of characters: `i(`, `a"` and so on
+    Pair { open: char, close: char },
+    /// The lines around the cursor indented at least as far as it (`ii`, `ai`)
+    Indent,
+}
+
+/// A text object named after `i` or `a`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextObject {
+    pub kind: ObjectKind,
+    /// Only what is between the delimiters (`i`) rather than including them (`a`)
+    pub inner: bool,
+}
+
+impl TextObject {
+    /// The object `key` names after `i` or `a`, if it is a delimited or indent one
+    pub fn from_key(key: char, inner: bool) -> Option<Self> {
+        let (open, close) = match key {
+            '(' | ')' | 'b' => ('(', ')'),
+            '[' | ']' => ('[', ']'),
+            '{' | '}' | 'B' => ('{', '}'),
+            '<' | '>' => ('<', '>'),
+            '"' | '\'' | '`' => (key, key),
+            'i' => {
+                return Some(Self {
+                    kind: ObjectKind::Indent,
+                    inner,
+                });
+            }
+            _ => return None,
+        };
+        Some(Self {
+            kind: ObjectKind::Pair { open, close },
+            inner,
+        })
+    }
+
+    /// Whether the object covers whole lines
+    pub fn is_linewise(&self) -> bool {
+        self.kind == ObjectKind::Indent
+    }
+}
+
+/// Start and exclusive end of `object` around `pos`, or `None` if the cursor
+/// isn't inside one. Brackets nest and may span lines; quotes pair up within
+/// the cursor line, and the next string on the line is used if the cursor is
+/// before it. Indentation is measured with `tab_width`.
+pub fn text_object_range(
+    buffer: &Buffer,
+    pos: Position,
+    object: TextObject,
+    tab_width: usize,
+) -> Option<(Position, Position)> {
+    let (open_char, close_char) = match object.kind {
+        ObjectKind::Pair { open, close } => (open, close),
+        ObjectKind::Indent => return indent_object_range(buffer, pos.line, object.inner, tab_width),
+    };
+    if open_char == close_char {
+        return quote_object_range(buffer, pos, open_char, object.inner);
+    }
+
+    let rope = &buffer.rope;
+    let cursor = buffer
+        .position_to_char(pos)
+        .min(rope.len_chars().checked_sub(1)?);
+
+    // Unmatched opening bracket at or before the cursor
+    let mut open = None;
+    let mut depth = 0;
+    for idx in (0..=cursor).rev() {
+        let c = rope.char(idx);
+        if c == open_char && (idx == cursor || depth == 0) {
+            open = Some(idx);
+            break;
+        } else if c == open_char {
+            depth -= 1;
+        } else if c == close_char && idx != cursor {
+            depth += 1;
+        }
+    }
+    let open = open?;
+
+    // Its matching closing bracket
+    let mut depth = 0;
+    let mut close = None;
+    for idx in open + 1..rope.len_chars() {
+        let c = rope.char(idx);
+        if c == open_char {
+            depth += 1;
+        } else if c == close_char {
+            if depth == 0 {
+                close = Some(idx);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let close = close?;
+
+    if !object.inner {
+        return Some((
+            buffer.char_to_position(open),
+            buffer.char_to_position(close + 1),
+        ));
+    }
+
+    // Like Vim, a block whose brackets end and start their lines keeps them
+    // there: only the lines in between are covered
+    let mut start = open + 1;
+    if start < close && rope.char(start) == '\n' {
+        start += 1;
+    }
+    let close_line_start = rope.line_to_char(rope.char_to_line(close));
+    let end = if close_line_start >= start
+        && rope
+            .slice(close_line_start..close)
+            .chars()
+            .all(|c| c == ' ' || c == '\t')
+    {
+        close_line_start
+    } else {
+        close
+    };
+    Some((
+        buffer.char_to_position(start),
+        buffer.char_to_position(end.max(start)),
+    ))
+}
+
+/// A quoted string on the cursor line. Escaped quotes don't count.
+fn quote_object_range(
+    buffer: &Buffer,
+    pos: Position,
+    quote: char,
+    inner: bool,
+) -> Option<(Position, Position)> {
+    let chars: Vec<char> = buffer.line(pos.line)?.chars().collect();
+    let quotes: Vec<usize> = (0..chars.len())
+        .filter(|&i| chars[i] == quote && (i == 0 || chars[i - 1] != '\\'))
+        .collect();
+    let (start, end) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, end)| end >= pos.col)?;
+
+    let (start, end) = if inner {
+        (start + 1, end)
+    } else {
+        // `a"` takes the whitespace after the string along with it
+        let mut end = end + 1;
+        while end < chars.len() && chars[end].is_whitespace() {
+            end += 1;
+        }
+        (start, end)
+    };
+    Some((Position::new(pos.line, start), Position::new(pos.line, end)))
+}
+
+/// Whole lines around `line` indented at least as far as it; blank lines
+/// count as part of the block when it continues after them. `ai` also takes
+/// the less indented line above, such as a Python `def` or `if`. A blank
+/// cursor line uses the indentation of the next line with text.
+fn indent_object_range(
+    buffer: &Buffer,
+    line: usize,
+    inner: bool,
+    tab_width: usize,
+) -> Option<(Position, Position)> {
+    let last = buffer.last_content_line();
+    let indent = |line: usize| line_indent(buffer, line, tab_width);
+    let anchor = (line.min(last)..=last).find(|&l| indent(l).is_some())?;
+    let level = indent(anchor)?;
+    let in_block = |line: usize| indent(line).is_none_or(|i| i >= level);
+
+    let mut start = anchor;
+    while start > 0 && in_block(start - 1) {
+        start -= 1;
+    }
+    let mut end = anchor;
+    while end < last && in_block(end + 1) {
+        end += 1;
+    }
+    // Blank lines only belong to the block between lines of it
+    while indent(start).is_none() {
+        start += 1;
+    }
+    while indent(end).is_none() {
+        end -= 1;
+    }
+    if !inner && start > 0 {
+        start -= 1;
+    }
+
+    let end = if end + 1 < buffer.line_count() {
+        Position::new(end + 1, 0)
+    } else {
+        Position::new(end, buffer.get_line_content(end).chars().count())
+    };
+    Some((Position::new(start, 0), end))
+}
+
+/// Display width of `line`'s indentation, or None for a blank line
+fn line_indent(buffer: &Buffer, line: usize, tab_width: usize) -> Option<usize> {
+    let text = buffer.get_line_content(line);
+    let first = text.chars().position(|c| !c.is_whitespace())?;
+    Some(crate::display::display_col(&text, first, tab_width))
+}
+
+/// The `count`th line above or below `line` indented less than the lines
+/// before it, such as the header (`[i`) or the end (`]i`) of the enclosing
+/// block. Blank lines are skipped; a blank `line` uses the indentation of the
+/// next line with text. Stops at the outermost boundary if there are fewer.
+pub fn indent_boundary(
+    buffer: &Buffer,
+    line: usize,
+    forward: bool,
+    count: usize,
+    tab_width: usize,
+) -> Option<usize> {
+    let indent = |line: usize| line_indent(buffer, line, tab_width);
+    let last = buffer.last_content_line();
+    let line = line.min(last);
+    let mut level = (line..=last)
+        .find_map(indent)
+        .or_else(|| (0..line).rev().find_map(indent))?;
+
+    let mut found = None;
+    for _ in 0..count.max(1) {
+        let from = found.unwrap_or(line);
+        let outer = |l: &usize| indent(*l).is_some_and(|i| i < level);
+        let next = if forward {
+            (from + 1..=last).find(outer)
+        } else {
+            (0..from).rev().find(outer)
+        };
+        let Some(next) = next else {
+            break;
+        };
+        level = indent(next)?;
+        found = Some(next);
+    }
+    found
+}
+
 /// Find matching pair character (parens, braces, brackets)
 pub fn find_matching_pair(buffer: &Buffer, pos: Position) -> Option<Position> {
     let line = buffer.line(pos.line)?;
@@ -345,6 +821,167 @@ mod tests {
         assert_eq!(norm2.end, Position::new(5, 10));
     }
 
+    #[test]
+    fn test_find_char() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("a,b,c,d", 0, 0).unwrap();
+        let comma = |till, forward| CharSearch {
+            ch: ',',
+            till,
+            forward,
+        };
+
+        let find = |col, search, count, repeat| {
+            find_char(&buffer, Position::new(0, col), search, count, repeat).map(|pos| pos.col)
+        };
+
+        assert_eq!(find(0, comma(false, true), 1, false), Some(1));
+        assert_eq!(find(0, comma(false, true), 3, false), Some(5));
+        assert_eq!(find(0, comma(false, true), 4, false), None);
+        assert_eq!(find(0, comma(true, true), 2, false), Some(2));
+        assert_eq!(find(6, comma(false, false), 1, false), Some(5));
+        assert_eq!(find(6, comma(true, false), 1, false), Some(6));
+
+        // Repeating `t` from just before a match moves on to the next one
+        assert_eq!(find(2, comma(true, true), 1, false), Some(2));
+        assert_eq!(find(2, comma(true, true), 1, true), Some(4));
+        assert_eq!(find(4, comma(true, false), 1, true), Some(2));
+    }
+
+    #[test]
+    fn test_paragraph_motions() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("a\nb\n\nc\n\n\nd", 0, 0).unwrap();
+        assert_eq!(paragraph_forward(&buffer, 0, 1), 2);
+        assert_eq!(paragraph_forward(&buffer, 2, 1), 4);
+        assert_eq!(paragraph_forward(&buffer, 0, 3), 6);
+        assert_eq!(paragraph_backward(&buffer, 6, 1), 5);
+        assert_eq!(paragraph_backward(&buffer, 3, 1), 2);
+        assert_eq!(paragraph_backward(&buffer, 3, 2), 0);
+    }
+
+    fn object_text(text: &str, pos: Position, key: char, inner: bool) -> Option<String> {
+        let mut buffer = Buffer::new();
+        buffer.insert_text(text, 0, 0).unwrap();
+        let object = TextObject::from_key(key, inner).unwrap();
+        text_object_range(&buffer, pos, object, 4).map(|(start, end)| buffer.get_range(start, end))
+    }
+
+    #[test]
+    fn test_indent_boundary() {
+        let mut buffer = Buffer::new();
+        let text = "mod m {\n    fn f() {\n        if x {\n            a();\n\n            b();\n        }\n    }\n}\n";
+        buffer.insert_text(text, 0, 0).unwrap();
+        let up = |line, count| indent_boundary(&buffer, line, false, count, 4);
+        let down = |line, count| indent_boundary(&buffer, line, true, count, 4);
+
+        assert_eq!(up(3, 1), Some(2));
+        assert_eq!(down(3, 1), Some(6));
+        // Counts step out a level at a time, as far as there are levels
+        assert_eq!(up(3, 2), Some(1));
+        assert_eq!(up(3, 9), Some(0));
+        assert_eq!(down(3, 3), Some(8));
+        // The blank line takes its indent from `b();`
+        assert_eq!(up(4, 1), Some(2));
+        // Nothing is less indented than the outermost lines
+        assert_eq!(up(0, 1), None);
+        assert_eq!(down(8, 1), None);
+
+        // Tabs count up to the tab width
+        let mut buffer = Buffer::new();
+        buffer.insert_text("if a:\n\tx\n    y\nz\n", 0, 0).unwrap();
+        assert_eq!(indent_boundary(&buffer, 2, false, 1, 4), Some(0));
+        assert_eq!(indent_boundary(&buffer, 1, true, 1, 4), Some(3));
+    }
+
+    #[test]
+    fn test_indent_text_object() {
+        let text = "def f():\n    if x:\n        a()\n\n        b()\n    return 1\n\nprint()";
+        let at = |line| Position::new(line, 0);
+        assert_eq!(
+            object_text(text, at(2), 'i', true).as_deref(),
+            Some("        a()\n\n        b()\n")
+        );
+        assert_eq!(
+            object_text(text, at(2), 'i', false).as_deref(),
+            Some("    if x:\n        a()\n\n        b()\n")
+        );
+        // The blank line inside the block uses the next line's indent
+        assert_eq!(
+            object_text(text, at(3), 'i', true).as_deref(),
+            Some("        a()\n\n        b()\n")
+        );
+        // Trailing blank lines aren't part of the block
+        assert_eq!(
+            object_text(text, at(5), 'i', true).as_deref(),
+            Some("    if x:\n        a()\n\n        b()\n    return 1\n")
+        );
+        // Tabs count up to the tab width
+        assert_eq!(
+            object_text("if a:\n\tx\n    y\nz", at(1), 'i', true).as_deref(),
+            Some("\tx\n    y\n")
+        );
+        // The last line has no line break to take
+        assert_eq!(object_text("a\n  b", at(1), 'i', true).as_deref(), Some("  b"));
+    }
+
+    #[test]
+    fn test_bracket_text_objects() {
+        let text = "f(a, (b), c)";
+        let at = |col| Position::new(0, col);
+        assert_eq!(object_text(text, at(3), '(', true).as_deref(), Some("a, (b), c"));
+        assert_eq!(object_text(text, at(6), 'b', true).as_deref(), Some("b"));
+        assert_eq!(object_text(text, at(5), ')', false).as_deref(), Some("(b)"));
+        // On the closing bracket of the outer pair
+        assert_eq!(object_text(text, at(11), '(', true).as_deref(), Some("a, (b), c"));
+        assert_eq!(object_text(text, at(0), '(', true), None);
+        assert_eq!(object_text("x()", at(1), '(', true).as_deref(), Some(""));
+        assert_eq!(object_text("x()", at(1), '(', false).as_deref(), Some("()"));
+    }
+
+    #[test]
+    fn test_multiline_bracket_text_objects() {
+        let text = "fn f() {\n    if x {\n        y();\n    }\n    z();\n}\n";
+        let inner = object_text(text, Position::new(4, 4), '{', true);
+        assert_eq!(
+            inner.as_deref(),
+            Some("    if x {\n        y();\n    }\n    z();\n")
+        );
+        let inner = object_text(text, Position::new(2, 8), 'B', true);
+        assert_eq!(inner.as_deref(), Some("        y();\n"));
+        let around = object_text(text, Position::new(2, 8), '}', false);
+        assert_eq!(around.as_deref(), Some("{\n        y();\n    }"));
+
+        // Brackets sharing lines with other text keep it
+        let inner = object_text("call(a,\n  b)", Position::new(1, 2), '(', true);
+        assert_eq!(inner.as_deref(), Some("a,\n  b"));
+        let inner = object_text("{\n}", Position::new(0, 0), '{', true);
+        assert_eq!(inner.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_quote_text_objects() {
+        let text = r#"say("hi \"you\"", 'x') "last""#;
+        let at = |col| Position::new(0, col);
+        let hi = Some(r#"hi \"you\""#);
+        assert_eq!(object_text(text, at(6), '"', true).as_deref(), hi);
+        // Before the first string on the line
+        assert_eq!(object_text(text, at(0), '"', true).as_deref(), hi);
+        assert_eq!(object_text(text, at(19), '\'', false).as_deref(), Some("'x'"));
+        assert_eq!(object_text(text, at(24), '"', true).as_deref(), Some("last"));
+        let around = object_text(r#"a "b"  c"#, at(2), '"', false);
+        assert_eq!(around.as_deref(), Some("\"b\"  "));
+        assert_eq!(object_text("a \"\"", at(2), '"', true).as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_words(""), 0);
+        assert_eq!(count_words("  \n "), 0);
+        assert_eq!(count_words("one two\nthree"), 3);
+        assert_eq!(count_words("foo.bar(x_1);"), 6);
+    }
+
     #[test]
     fn test_line_start() {
         let pos = Position::new(5, 10);
@@ -361,15 +998,70 @@ mod tests {
         assert_eq!(result.col, 0);
     }
 
+    #[test]
+    fn test_inner_word() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("let foo_bar = 1;", 0, 0).unwrap();
+        assert_eq!(
+            inner_word(&buffer, Position::new(0, 6)),
+            (Position::new(0, 4), Position::new(0, 11))
+        );
+        assert_eq!(
+            inner_word(&buffer, Position::new(0, 11)),
+            (Position::new(0, 11), Position::new(0, 12))
+        );
+    }
+
+    #[test]
+    fn test_word_end_backward() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("foo.bar baz\n\n  qux", 0, 0).unwrap();
+        assert_eq!(word_end_backward(&buffer, Position::new(0, 9)), Position::new(0, 6));
+        assert_eq!(word_end_backward(&buffer, Position::new(0, 6)), Position::new(0, 3));
+        assert_eq!(word_end_backward(&buffer, Position::new(0, 3)), Position::new(0, 2));
+        // The empty line stops the motion, then the previous line's last word
+        assert_eq!(word_end_backward(&buffer, Position::new(2, 3)), Position::new(1, 0));
+        assert_eq!(word_end_backward(&buffer, Position::new(1, 0)), Position::new(0, 10));
+        assert_eq!(word_end_backward(&buffer, Position::new(0, 1)), Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_big_word_end_backward() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("foo.bar baz", 0, 0).unwrap();
+        assert_eq!(big_word_end_backward(&buffer, Position::new(0, 9)), Position::new(0, 6));
+        assert_eq!(big_word_end_backward(&buffer, Position::new(0, 5)), Position::new(0, 0));
+    }
+
     #[test]
     fn test_char_kind() {
-        assert_eq!(char_kind('a'), WordKind::Alphanumeric);
-        assert_eq!(char_kind('Z'), WordKind::Alphanumeric);
-        assert_eq!(char_kind('0'), WordKind::Alphanumeric);
-        assert_eq!(char_kind('_'), WordKind::Alphanumeric);
-        assert_eq!(char_kind(' '), WordKind::Whitespace);
-        assert_eq!(char_kind('\t'), WordKind::Whitespace);
-        assert_eq!(char_kind('.'), WordKind::Other);
-        assert_eq!(char_kind('('), WordKind::Other);
+        let words = WordChars::default();
+        assert_eq!(words.kind('a'), WordKind::Alphanumeric);
+        assert_eq!(words.kind('Z'), WordKind::Alphanumeric);
+        assert_eq!(words.kind('0'), WordKind::Alphanumeric);
+        assert_eq!(words.kind('_'), WordKind::Alphanumeric);
+        assert_eq!(words.kind(' '), WordKind::Whitespace);
+        assert_eq!(words.kind('\t'), WordKind::Whitespace);
+        assert_eq!(words.kind('.'), WordKind::Other);
+        assert_eq!(words.kind('('), WordKind::Other);
+    }
+
+    #[test]
+    fn test_word_chars_change_word_boundaries() {
+        let lisp = WordChars::new("_-?").unwrap();
+        assert_eq!(lisp.kind('-'), WordKind::Alphanumeric);
+        assert!(WordChars::new("a b").is_none());
+
+        let mut buffer = Buffer::new();
+        buffer.insert_text("(empty-list? x)", 0, 0).unwrap();
+        let at = |col| Position::new(0, col);
+        assert_eq!(word_forward(&buffer, at(1)), at(6));
+        assert_eq!(word_forward_with(&buffer, at(1), &lisp), at(13));
+        assert_eq!(word_end_with(&buffer, at(1), &lisp), at(11));
+        assert_eq!(word_backward_with(&buffer, at(13), &lisp), at(1));
+        assert_eq!(word_end_backward_with(&buffer, at(13), &lisp), at(11));
+        assert_eq!(inner_word_with(&buffer, at(7), &lisp), (at(1), at(12)));
+        assert_eq!(count_words_with("(empty-list? x)", &lisp), 4);
+        assert_eq!(count_words("(empty-list? x)"), 7);
     }
 }
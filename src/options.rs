@@ -0,0 +1,463 @@
+// src/options.rs - Editor options that affect editing behaviour
+
+use crate::config::{AutoPairsConfig, TextyConfig};
+use crate::editorconfig::{EditorConfig, LineEnding};
+use crate::mode::Mode;
+use crate::motion::WordChars;
+use crate::syntax::LanguageId;
+use lsp_types::CodeActionKind;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Mode, file name, modified flag and file type, then the cursor position
+pub const DEFAULT_STATUS_FORMAT: &str = "%M| %f %m%y | %l:%c %p";
+
+/// Parse a `colorcolumn` value such as `80,100` into sorted display
+/// columns; an empty value clears them. `None` if a column is not a
+/// positive number.
+pub fn parse_color_columns(value: &str) -> Option<Vec<usize>> {
+    let mut columns = Vec::new();
+    for part in value.split(',').filter(|part| !part.is_empty()) {
+        match part.trim().parse::<usize>() {
+            Ok(column) if column > 0 => columns.push(column),
+            _ => return None,
+        }
+    }
+    columns.sort_unstable();
+    columns.dedup();
+    Some(columns)
+}
+
+/// Brackets and quotes closed as they are typed
+pub const DEFAULT_AUTO_PAIRS: [(char, char); 6] = [
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+/// Parse an `auto_pairs` value such as `()[]""` into opening and closing
+/// characters. `None` if a character is left without its partner.
+pub fn parse_auto_pairs(value: &str) -> Option<Vec<(char, char)>> {
+    let chars: Vec<char> = value.chars().collect();
+    if !chars.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(chars.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+/// Terminal cursor shapes that can be requested with DECSCUSR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
+/// The cursor shape shown in each mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorShapes {
+    pub normal: CursorShape,
+    pub insert: CursorShape,
+    pub visual: CursorShape,
+    pub command: CursorShape,
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        Self {
+            normal: CursorShape::Block,
+            insert: CursorShape::Bar,
+            visual: CursorShape::Block,
+            command: CursorShape::Block,
+        }
+    }
+}
+
+impl CursorShapes {
+    /// Shape for `mode`; the fuzzy finder's prompt counts as the command line
+    pub fn for_mode(&self, mode: Mode) -> CursorShape {
+        match mode {
+            Mode::Normal => self.normal,
+            Mode::Insert => self.insert,
+            Mode::Visual => self.visual,
+            Mode::Command | Mode::FuzzySearch => self.command,
+        }
+    }
+
+    /// Set the shape for the mode called `name`; false if there is no such mode
+    pub fn set(&mut self, name: &str, shape: CursorShape) -> bool {
+        let slot = match name.to_lowercase().as_str() {
+            "normal" => &mut self.normal,
+            "insert" => &mut self.insert,
+            "visual" => &mut self.visual,
+            "command" => &mut self.command,
+            _ => return false,
+        };
+        *slot = shape;
+        true
+    }
+}
+
+/// Indentation and editing settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditorOptions {
+    /// Width of a tab stop, also used as the shift width for `>`/`<`
+    pub tab_width: usize,
+    /// Indent with spaces instead of tab characters
+    pub expand_tab: bool,
+    /// Keep the previous contents of a file in `<file>~` when saving
+    pub write_backup: bool,
+    /// Add a final newline when saving files that lack one
+    pub ensure_final_newline: bool,
+    /// Strip whitespace from the ends of lines when saving
+    pub trim_trailing_whitespace: bool,
+    /// Line break written when saving; `None` keeps the buffer's as they are
+    pub line_ending: Option<LineEnding>,
+    /// Layout of the status line; see `ui::widgets::status_bar::parse_format`
+    pub status_format: String,
+    /// Run the formatter before writing
+    pub format_on_save: bool,
+    /// Organize imports through the language server before writing
+    pub organize_imports_on_save: bool,
+    /// Apply the language server's fix-all action before writing
+    pub fix_all_on_save: bool,
+    /// How long the cursor rests before hover information pops up; `None` is off
+    pub hover_delay: Option<Duration>,
+    /// Extensions `:A` tries for a file's companion, keyed by its extension
+    pub alternate_extensions: HashMap<String, Vec<String>>,
+    /// Yanks without an explicit register also go to `+` and the system clipboard
+    pub yank_to_clipboard: bool,
+    /// Cursor shape for each mode; `None` leaves the cursor alone
+    pub cursor_shapes: Option<CursorShapes>,
+    /// Display columns (1-based) marked with a ruler, in ascending order
+    pub color_columns: Vec<usize>,
+    /// Show the language server's inlay hints
+    pub inlay_hints: bool,
+    /// Opening and closing characters typed as a pair in insert mode; empty
+    /// turns auto-pairing off
+    pub auto_pairs: Vec<(char, char)>,
+    /// Searches ignore case unless the pattern has an uppercase letter
+    pub smart_case: bool,
+    /// Enter in a comment starts the new line with the comment marker
+    pub continue_comments: bool,
+    /// `:grep` stops scanning after this many matches
+    pub grep_max_results: usize,
+    /// Shell command `:make` runs (Vim's `makeprg`)
+    pub make_command: String,
+    /// Regex picking locations out of `:make`'s output; see
+    /// `quickfix::parse_errors`
+    pub error_format: String,
+    /// Characters that make up words for the current file (`iskeyword`)
+    pub word_chars: WordChars,
+    /// Word characters from the config, replacing a language's own
+    pub language_word_chars: HashMap<LanguageId, WordChars>,
+}
+
+impl Default for EditorOptions {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            expand_tab: true,
+            write_backup: false,
+            ensure_final_newline: false,
+            trim_trailing_whitespace: false,
+            line_ending: None,
+            status_format: DEFAULT_STATUS_FORMAT.to_string(),
+            format_on_save: false,
+            organize_imports_on_save: false,
+            fix_all_on_save: false,
+            hover_delay: None,
+            alternate_extensions: crate::alternate::default_pairs(),
+            yank_to_clipboard: false,
+            cursor_shapes: Some(CursorShapes::default()),
+            color_columns: Vec::new(),
+            inlay_hints: true,
+            auto_pairs: DEFAULT_AUTO_PAIRS.to_vec(),
+            smart_case: true,
+            continue_comments: true,
+            grep_max_results: 5000,
+            make_command: "make".to_string(),
+            error_format: crate::quickfix::DEFAULT_ERROR_FORMAT.to_string(),
+            word_chars: WordChars::default(),
+            language_word_chars: HashMap::new(),
+        }
+    }
+}
+
+impl EditorOptions {
+    /// Apply settings from the user's config file
+    pub fn apply_config(&mut self, config: &TextyConfig) {
+        if let Some(backup) = config.backup {
+            self.write_backup = backup;
+        }
+        if let Some(ensure) = config.ensure_final_newline {
+            self.ensure_final_newline = ensure;
+        }
+        if let Some(format) = &config.status_line {
+            self.status_format = format.clone();
+        }
+        if let Some(format) = config.format_on_save {
+            self.format_on_save = format;
+        }
+        if let Some(organize) = config.organize_imports_on_save {
+            self.organize_imports_on_save = organize;
+        }
+        if let Some(fix_all) = config.fix_all_on_save {
+            self.fix_all_on_save = fix_all;
+        }
+        if let Some(delay) = config.hover_delay {
+            self.hover_delay = (delay > 0).then(|| Duration::from_millis(delay));
+        }
+        if let Some(inlay_hints) = config.inlay_hints {
+            self.inlay_hints = inlay_hints;
+        }
+        match &config.auto_pairs {
+            Some(AutoPairsConfig::Enabled(true)) => self.auto_pairs = DEFAULT_AUTO_PAIRS.to_vec(),
+            Some(AutoPairsConfig::Enabled(false)) => self.auto_pairs.clear(),
+            Some(AutoPairsConfig::Pairs(pairs)) => match parse_auto_pairs(pairs) {
+                Some(pairs) => self.auto_pairs = pairs,
+                None => log::warn!("auto_pairs must list characters in pairs, e.g. \"()[]\""),
+            },
+            None => {}
+        }
+        if let Some(max_results) = config.grep_max_results {
+            self.grep_max_results = max_results.max(1);
+        }
+        if let Some(command) = &config.make_command {
+            self.make_command = command.clone();
+        }
+        if let Some(format) = &config.error_format {
+            self.error_format = format.clone();
+        }
+        if let Some(smart_case) = config.smart_case {
+            self.smart_case = smart_case;
+        }
+        if let Some(continue_comments) = config.continue_comments {
+            self.continue_comments = continue_comments;
+        }
+        if let Some(clipboard) = config.yank_to_clipboard {
+            self.yank_to_clipboard = clipboard;
+        }
+        if let Some(shapes) = &config.cursor_shapes {
+            let current = self.cursor_shapes.get_or_insert_with(CursorShapes::default);
+            for (mode, shape) in shapes {
+                if !current.set(mode, *shape) {
+                    log::warn!("Unknown mode '{}' in cursor_shapes", mode);
+                }
+            }
+        }
+        if let Some(columns) = &config.colorcolumn {
+            let mut columns: Vec<usize> = columns.iter().copied().filter(|&c| c > 0).collect();
+            columns.sort_unstable();
+            columns.dedup();
+            self.color_columns = columns;
+        }
+        if config.cursor_shape == Some(false) {
+            self.cursor_shapes = None;
+        }
+        if let Some(word_chars) = &config.word_chars {
+            for (name, extra) in word_chars {
+                match (LanguageId::from_name(name), WordChars::new(extra)) {
+                    (Some(language), Some(chars)) => {
+                        self.language_word_chars.insert(language, chars);
+                    }
+                    (None, _) => log::warn!("Unknown language '{}' in word_chars", name),
+                    (_, None) => log::warn!("word_chars for {} can't contain spaces", name),
+                }
+            }
+        }
+        if let Some(alternates) = &config.alternates {
+            for (ext, companions) in alternates {
+                self.alternate_extensions
+                    .insert(ext.to_lowercase(), companions.clone());
+            }
+        }
+    }
+
+    /// Apply a file's `.editorconfig` settings over the user's
+    pub fn apply_editorconfig(&mut self, config: &EditorConfig) {
+        if let Some(expand_tab) = config.expand_tab {
+            self.expand_tab = expand_tab;
+        }
+        if let Some(size) = config.indent_size {
+            self.tab_width = size;
+        }
+        if let Some(ending) = config.end_of_line {
+            self.line_ending = Some(ending);
+        }
+        if let Some(trim) = config.trim_trailing_whitespace {
+            self.trim_trailing_whitespace = trim;
+        }
+        if let Some(final_newline) = config.insert_final_newline {
+            self.ensure_final_newline = final_newline;
+        }
+    }
+
+    /// Use the word characters for `language`: the config's if it sets them,
+    /// otherwise the language's own
+    pub fn use_language_word_chars(&mut self, language: Option<LanguageId>) {
+        self.word_chars = match language {
+            Some(language) => match self.language_word_chars.get(&language) {
+                Some(chars) => chars.clone(),
+                None => WordChars::new(crate::syntax::get_language_config(language).word_chars)
+                    .unwrap_or_default(),
+            },
+            None => WordChars::default(),
+        };
+    }
+
+    /// Code actions to apply before saving, in the order they run. Imports
+    /// are organized first so fixes see the final import list.
+    pub fn save_code_action_kinds(&self) -> Vec<CodeActionKind> {
+        let mut kinds = Vec::new();
+        if self.organize_imports_on_save {
+            kinds.push(CodeActionKind::SOURCE_ORGANIZE_IMPORTS);
+        }
+        if self.fix_all_on_save {
+            kinds.push(CodeActionKind::SOURCE_FIX_ALL);
+        }
+        kinds
+    }
+
+    /// Text inserted for one level of indentation
+    pub fn indent_unit(&self) -> String {
+        if self.expand_tab {
+            " ".repeat(self.tab_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indent_unit() {
+        let mut options = EditorOptions::default();
+        assert_eq!(options.indent_unit(), "    ");
+        options.tab_width = 2;
+        assert_eq!(options.indent_unit(), "  ");
+        options.expand_tab = false;
+        assert_eq!(options.indent_unit(), "\t");
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let mut options = EditorOptions::default();
+        options.apply_config(&TextyConfig::default());
+        assert!(!options.write_backup);
+
+        let config: TextyConfig = toml::from_str("backup = true").unwrap();
+        options.apply_config(&config);
+        assert!(options.write_backup);
+        assert!(!options.ensure_final_newline);
+
+        let config: TextyConfig = toml::from_str("ensure_final_newline = true").unwrap();
+        options.apply_config(&config);
+        assert!(options.ensure_final_newline);
+        assert_eq!(options.status_format, DEFAULT_STATUS_FORMAT);
+
+        let config: TextyConfig = toml::from_str("status_line = \"%f %l\"").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.status_format, "%f %l");
+
+        let config: TextyConfig = toml::from_str("yank_to_clipboard = true").unwrap();
+        options.apply_config(&config);
+        assert!(options.yank_to_clipboard);
+
+        let config: TextyConfig =
+            toml::from_str("cursor_shapes = { insert = \"underline\" }").unwrap();
+        options.apply_config(&config);
+        let shapes = options.cursor_shapes.clone().unwrap();
+        assert_eq!(shapes.for_mode(Mode::Insert), CursorShape::Underline);
+        assert_eq!(shapes.for_mode(Mode::Normal), CursorShape::Block);
+        let config: TextyConfig = toml::from_str("cursor_shape = false").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.cursor_shapes, None);
+
+        let config: TextyConfig = toml::from_str("colorcolumn = [100, 80]").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.color_columns, [80, 100]);
+
+        let config: TextyConfig = toml::from_str("hover_delay = 500").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.hover_delay, Some(Duration::from_millis(500)));
+        let config: TextyConfig = toml::from_str("hover_delay = 0").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.hover_delay, None);
+
+        let config: TextyConfig = toml::from_str("auto_pairs = \"()<>\"").unwrap();
+        options.apply_config(&config);
+        assert_eq!(options.auto_pairs, [('(', ')'), ('<', '>')]);
+        let config: TextyConfig = toml::from_str("auto_pairs = false").unwrap();
+        options.apply_config(&config);
+        assert!(options.auto_pairs.is_empty());
+    }
+
+    #[test]
+    fn test_word_chars_per_language() {
+        let mut options = EditorOptions::default();
+        options.use_language_word_chars(Some(LanguageId::JavaScript));
+        assert_eq!(options.word_chars.extra(), "_$");
+        options.use_language_word_chars(None);
+        assert_eq!(options.word_chars.extra(), "_");
+
+        let config = "word_chars = { js = \"_$-\", lisp = \"-\", rust = \"a b\" }";
+        let config: TextyConfig = toml::from_str(config).unwrap();
+        options.apply_config(&config);
+        options.use_language_word_chars(Some(LanguageId::JavaScript));
+        assert_eq!(options.word_chars.extra(), "_$-");
+        // Unknown languages and invalid sets are left out
+        assert_eq!(options.language_word_chars.len(), 1);
+        options.use_language_word_chars(Some(LanguageId::Rust));
+        assert_eq!(options.word_chars.extra(), "_");
+    }
+
+    #[test]
+    fn test_parse_color_columns() {
+        assert_eq!(parse_color_columns("80"), Some(vec![80]));
+        assert_eq!(parse_color_columns("120,80,80"), Some(vec![80, 120]));
+        assert_eq!(parse_color_columns(""), Some(vec![]));
+        assert_eq!(parse_color_columns("0"), None);
+        assert_eq!(parse_color_columns("80,wide"), None);
+    }
+
+    #[test]
+    fn test_apply_editorconfig() {
+        let mut options = EditorOptions::default();
+        options.apply_editorconfig(&EditorConfig {
+            expand_tab: Some(false),
+            indent_size: Some(8),
+            end_of_line: Some(LineEnding::CrLf),
+            ..Default::default()
+        });
+        assert!(!options.expand_tab);
+        assert_eq!(options.tab_width, 8);
+        assert_eq!(options.line_ending, Some(LineEnding::CrLf));
+        // Unset properties keep the user's settings
+        assert!(!options.ensure_final_newline);
+        assert!(!options.trim_trailing_whitespace);
+    }
+
+    #[test]
+    fn test_save_code_action_kinds() {
+        let mut options = EditorOptions::default();
+        assert!(options.save_code_action_kinds().is_empty());
+
+        let config: TextyConfig =
+            toml::from_str("fix_all_on_save = true\norganize_imports_on_save = true").unwrap();
+        options.apply_config(&config);
+        assert_eq!(
+            options.save_code_action_kinds(),
+            [CodeActionKind::SOURCE_ORGANIZE_IMPORTS, CodeActionKind::SOURCE_FIX_ALL]
+        );
+        assert!(!options.format_on_save);
+    }
+}
@@ -0,0 +1,293 @@
+// src/quickfix.rs - List of locations to step through: references, diagnostics, grep matches, build errors
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// One location in the quickfix list
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickfixEntry {
+    pub path: PathBuf,
+    /// 0-based line
+    pub line: usize,
+    /// 0-based char column
+    pub col: usize,
+    /// What is at the location: the matching line, a diagnostic message
+    pub text: String,
+}
+
+impl QuickfixEntry {
+    pub fn new(path: PathBuf, line: usize, col: usize, text: impl Into<String>) -> Self {
+        Self {
+            path,
+            line,
+            col,
+            text: text.into(),
+        }
+    }
+
+    /// `path:line:col: text`, with 1-based numbers and the path relative to
+    /// the working directory when it is inside it
+    pub fn display(&self) -> String {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = self.path.strip_prefix(&cwd).unwrap_or(&self.path);
+        format!(
+            "{}:{}:{}: {}",
+            path.display(),
+            self.line + 1,
+            self.col + 1,
+            self.text.trim()
+        )
+    }
+}
+
+/// Locations produced by find-references, `:grep`, `:make` or `:diagnostics`, stepped
+/// through with `]q`/`[q` or picked from the quickfix window
+#[derive(Debug, Clone, Default)]
+pub struct QuickfixList {
+    /// What produced the entries, shown as the window title
+    pub title: String,
+    pub entries: Vec<QuickfixEntry>,
+    /// Entry last jumped to
+    pub current: usize,
+    /// Entry highlighted in the quickfix window
+    pub selected: usize,
+    /// More entries were found than were kept
+    pub truncated: bool,
+}
+
+impl QuickfixList {
+    pub fn new(title: impl Into<String>, entries: Vec<QuickfixEntry>) -> Self {
+        Self {
+            title: title.into(),
+            entries,
+            current: 0,
+            selected: 0,
+            truncated: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn current_entry(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.current)
+    }
+
+    /// Move `count` entries forward, or back when `forward` is false, stopping
+    /// at either end. Returns the new current entry.
+    pub fn step(&mut self, count: usize, forward: bool) -> Option<&QuickfixEntry> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.current = if forward {
+            (self.current + count).min(self.entries.len() - 1)
+        } else {
+            self.current.saturating_sub(count)
+        };
+        self.selected = self.current;
+        self.current_entry()
+    }
+
+    /// Highlight the next entry in the window, wrapping around
+    pub fn select_next(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected = (self.selected + 1) % self.entries.len();
+        }
+    }
+
+    /// Highlight the previous entry in the window, wrapping around
+    pub fn select_prev(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected = self.selected.checked_sub(1).unwrap_or(self.entries.len() - 1);
+        }
+    }
+
+    /// Make the highlighted entry the current one and return it
+    pub fn accept_selected(&mut self) -> Option<&QuickfixEntry> {
+        self.current = self.selected.min(self.entries.len().saturating_sub(1));
+        self.current_entry()
+    }
+
+    /// `(n of total)` for the status line
+    pub fn position_text(&self) -> String {
+        format!("({} of {})", self.current + 1, self.entries.len())
+    }
+}
+
+/// Char column of a position `units` UTF-16 code units into `text`, as LSP
+/// positions count them. Clamped to the end of the line.
+pub fn utf16_to_char_col(text: &str, units: usize) -> usize {
+    let mut counted = 0;
+    text.chars()
+        .take_while(|c| {
+            counted += c.len_utf16();
+            counted <= units
+        })
+        .count()
+}
+
+/// Error format for `:make`: `file:line:col: message` or `file:line: message`,
+/// as GCC, Clang and `cargo build --message-format=short` print them
+pub const DEFAULT_ERROR_FORMAT: &str =
+    r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?P<message>.*)$";
+
+/// Locations in a build command's output. `format` has the groups `file` and
+/// `line` (1-based), and optionally `col` (1-based) and `message`; lines it
+/// doesn't match are skipped. Relative paths are taken from `root`.
+pub fn parse_errors(output: &str, format: &Regex, root: &Path) -> Vec<QuickfixEntry> {
+    output
+        .lines()
+        .filter_map(|text| {
+            let found = format.captures(text)?;
+            let file = found.name("file")?.as_str().trim();
+            let line: usize = found.name("line")?.as_str().parse().ok()?;
+            let col = found
+                .name("col")
+                .and_then(|col| col.as_str().parse::<usize>().ok())
+                .unwrap_or(1);
+            let message = found.name("message").map_or(text, |m| m.as_str());
+            Some(QuickfixEntry::new(
+                root.join(file),
+                line.saturating_sub(1),
+                col.saturating_sub(1),
+                message,
+            ))
+        })
+        .collect()
+}
+
+/// Run a build command through the shell and return everything it printed,
+/// stdout then stderr, and whether it exited successfully
+pub fn run_capturing(command: &str) -> Result<(String, bool), std::io::Error> {
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((text, output.status.success()))
+}
+
+/// Lines matching `pattern` in the files under `root`. Like the fuzzy finder,
+/// hidden files and whatever `.gitignore` ignores are skipped, and so are files
+/// that aren't UTF-8 text. The scan stops at `max_results` matches; the flag
+/// returned says whether it did.
+pub fn grep(root: &Path, pattern: &Regex, max_results: usize) -> (Vec<QuickfixEntry>, bool) {
+    let mut entries = Vec::new();
+    let walker = ignore::WalkBuilder::new(root)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        for (line, text) in content.lines().enumerate() {
+            if let Some(found) = pattern.find(text) {
+                if entries.len() == max_results {
+                    return (entries, true);
+                }
+                let col = text[..found.start()].chars().count();
+                entries.push(QuickfixEntry::new(entry.path().to_path_buf(), line, col, text));
+            }
+        }
+    }
+    (entries, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: usize) -> QuickfixEntry {
+        QuickfixEntry::new(PathBuf::from("a.rs"), line, 0, format!("line {}", line))
+    }
+
+    #[test]
+    fn test_step_stops_at_the_ends() {
+        let mut list = QuickfixList::new("test", (0..3).map(entry).collect());
+        assert_eq!(list.step(1, true).unwrap().line, 1);
+        assert_eq!(list.step(5, true).unwrap().line, 2);
+        assert_eq!(list.position_text(), "(3 of 3)");
+        assert_eq!(list.step(2, false).unwrap().line, 0);
+        assert_eq!(list.step(1, false).unwrap().line, 0);
+
+        let mut empty = QuickfixList::default();
+        assert!(empty.step(1, true).is_none());
+        assert!(empty.accept_selected().is_none());
+    }
+
+    #[test]
+    fn test_selection_wraps_and_is_accepted() {
+        let mut list = QuickfixList::new("test", (0..3).map(entry).collect());
+        list.select_prev();
+        assert_eq!(list.selected, 2);
+        list.select_next();
+        list.select_next();
+        assert_eq!(list.accept_selected().unwrap().line, 1);
+        assert_eq!(list.current, 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let output = "\
+   Compiling texty v0.1.0
+src/main.rs:4:5: error[E0308]: mismatched types
+/abs/lib.c:10: warning: unused variable
+warning: `texty` generated 1 warning
+error: could not compile `texty`";
+        let format = Regex::new(DEFAULT_ERROR_FORMAT).unwrap();
+        let entries = parse_errors(output, &format, Path::new("/work"));
+        assert_eq!(
+            entries,
+            [
+                QuickfixEntry::new(
+                    PathBuf::from("/work/src/main.rs"),
+                    3,
+                    4,
+                    "error[E0308]: mismatched types"
+                ),
+                QuickfixEntry::new(PathBuf::from("/abs/lib.c"), 9, 0, "warning: unused variable"),
+            ]
+        );
+
+        // Without a message group the whole line is kept
+        let format = Regex::new(r"--> (?P<file>\S+):(?P<line>\d+):(?P<col>\d+)").unwrap();
+        let entries = parse_errors("  --> src/a.rs:2:7", &format, Path::new("/work"));
+        assert_eq!(entries[0].text, "  --> src/a.rs:2:7");
+        assert_eq!((entries[0].line, entries[0].col), (1, 6));
+    }
+
+    #[test]
+    fn test_grep_finds_matching_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo ünïcode match\nthree\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "match at start\n").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "match\n").unwrap();
+        std::fs::write(dir.path().join("binary.bin"), [0xff, 0xfe, b'm']).unwrap();
+
+        let (entries, truncated) = grep(dir.path(), &Regex::new("match").unwrap(), 100);
+        assert!(!truncated);
+        let found: Vec<_> = entries
+            .iter()
+            .map(|e| (e.path.file_name().unwrap().to_str().unwrap(), e.line, e.col))
+            .collect();
+        assert_eq!(found, [("a.txt", 1, 12), ("b.txt", 0, 0)]);
+        assert_eq!(entries[0].text, "two ünïcode match");
+
+        // The scan stops once enough is found
+        let (entries, truncated) = grep(dir.path(), &Regex::new("match").unwrap(), 1);
+        assert_eq!(entries.len(), 1);
+        assert!(truncated);
+        let (_, truncated) = grep(dir.path(), &Regex::new("match").unwrap(), 2);
+        assert!(!truncated);
+    }
+}
@@ -0,0 +1,133 @@
+// src/recent_files.rs - Persisted list of recently opened files
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Older entries are forgotten beyond this many files
+const MAX_RECENT_FILES: usize = 100;
+
+/// Default location of the list, `~/.local/share/texty/recent_files` on Linux
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("texty").join("recent_files"))
+}
+
+/// Most recently opened files first, one absolute path per line on disk
+#[derive(Debug, Clone, Default)]
+pub struct RecentFiles {
+    /// Where the list is saved; `None` keeps it in memory only
+    store: Option<PathBuf>,
+    entries: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    /// An in-memory list that is never written to disk
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the list saved at `store`. A missing or unreadable file gives an
+    /// empty list that is created on the next change.
+    pub fn load(store: &Path) -> Self {
+        let entries = fs::read_to_string(store)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .take(MAX_RECENT_FILES)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            store: Some(store.to_path_buf()),
+            entries,
+        }
+    }
+
+    /// Move `file` to the front of the list and save it
+    pub fn add(&mut self, file: &Path) {
+        let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        self.entries.retain(|entry| *entry != file);
+        self.entries.insert(0, file);
+        self.entries.truncate(MAX_RECENT_FILES);
+        self.save();
+    }
+
+    /// Entries that still exist, most recent first. Deleted files are pruned
+    /// from the saved list.
+    pub fn existing(&mut self) -> Vec<PathBuf> {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.is_file());
+        if self.entries.len() != before {
+            self.save();
+        }
+        self.entries.clone()
+    }
+
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// Failing to save only loses history, so errors are logged and ignored
+    fn save(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if let Some(parent) = store.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            log::warn!("Failed to create {}: {}", parent.display(), e);
+            return;
+        }
+        let content: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{}\n", entry.display()))
+            .collect();
+        if let Err(e) = fs::write(store, content) {
+            log::warn!("Failed to save recent files to {}: {}", store.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_moves_file_to_front() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let mut recent = RecentFiles::new();
+        recent.add(&a);
+        recent.add(&b);
+        recent.add(&a);
+        let a = fs::canonicalize(a).unwrap();
+        let b = fs::canonicalize(b).unwrap();
+        assert_eq!(recent.entries(), &[a, b]);
+    }
+
+    #[test]
+    fn test_persisted_and_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("state").join("recent_files");
+        let kept = dir.path().join("kept.txt");
+        let deleted = dir.path().join("deleted.txt");
+        fs::write(&kept, "").unwrap();
+        fs::write(&deleted, "").unwrap();
+
+        let mut recent = RecentFiles::load(&store);
+        recent.add(&kept);
+        recent.add(&deleted);
+        fs::remove_file(&deleted).unwrap();
+
+        let mut reloaded = RecentFiles::load(&store);
+        assert_eq!(reloaded.entries().len(), 2);
+        assert_eq!(reloaded.existing(), vec![fs::canonicalize(&kept).unwrap()]);
+        assert_eq!(RecentFiles::load(&store).entries().len(), 1);
+    }
+}
@@ -1,6 +1,6 @@
 // src/registers.rs - Vim-style yank/paste register system
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 const NUMBERED_REGISTERS: usize = 9;
 const _MAX_UNDO_STACK: usize = 100;
@@ -21,6 +21,8 @@ pub struct Registers {
     pub clipboard: String,
     /// Last inserted text
     pub last_inserted: String,
+    /// Registers whose content is linewise (stored with a trailing newline)
+    linewise: HashSet<char>,
     /// Index for next numbered delete (1-9, wraps around)
     _next_delete_slot: usize,
 }
@@ -34,6 +36,7 @@ impl Registers {
             small_delete: String::new(),
             clipboard: String::new(),
             last_inserted: String::new(),
+            linewise: HashSet::new(),
             _next_delete_slot: 1,
         }
     }
@@ -41,7 +44,34 @@ impl Registers {
     /// Yank text to a register
     /// reg = '"' for unnamed, '0'-'9' for numbered, 'a'-'z' for named
     /// '*' and '+' for clipboard, '-' for small delete
+    /// 'A'-'Z' append to the matching named register
     pub fn yank(&mut self, text: String, reg: char) {
+        self.store(text, reg, false);
+    }
+
+    /// Yank whole lines to a register (linewise, like `yy`/`dd`)
+    pub fn yank_lines(&mut self, mut text: String, reg: char) {
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        self.store(text, reg, true);
+    }
+
+    /// Check if a register holds linewise content
+    pub fn is_linewise(&self, reg: char) -> bool {
+        let key = match reg {
+            'a'..='z' | 'A'..='Z' if self.named.contains_key(&reg.to_ascii_lowercase()) => {
+                reg.to_ascii_lowercase()
+            }
+            '0'..='9' if !self.numbered[reg as usize - '0' as usize].is_empty() => reg,
+            '-' if !self.small_delete.is_empty() => '-',
+            '*' | '+' if !self.clipboard.is_empty() => '+',
+            _ => '"',
+        };
+        self.linewise.contains(&key)
+    }
+
+    fn store(&mut self, text: String, reg: char, linewise: bool) {
         match reg {
             '"' => {
                 // Unnamed register
@@ -71,23 +101,49 @@ impl Registers {
             'a'..='z' | 'A'..='Z' => {
                 // Named registers
                 let key = reg.to_ascii_lowercase();
-                self.unnamed = text.clone();
-                if reg.is_uppercase() {
-                    // Uppercase means append
-                    if let Some(existing) = self.named.get_mut(&key) {
-                        existing.push_str(&text);
-                    } else {
-                        self.named.insert(key, text);
+                if reg.is_uppercase()
+                    && let Some(existing) = self.named.get_mut(&key)
+                    && !existing.is_empty()
+                {
+                    // Uppercase means append; mixing in a linewise yank makes the result linewise
+                    let was_linewise = self.linewise.contains(&key);
+                    if linewise && !was_linewise {
+                        existing.push('\n');
                     }
-                } else {
-                    self.named.insert(key, text);
+                    existing.push_str(&text);
+                    if was_linewise && !linewise {
+                        existing.push('\n');
+                    }
+                    let combined = existing.clone();
+                    self.set_linewise(key, was_linewise || linewise);
+                    self.set_linewise('"', was_linewise || linewise);
+                    self.unnamed = combined;
+                    return;
                 }
+                self.unnamed = text.clone();
+                self.named.insert(key, text);
+                self.set_linewise(key, linewise);
             }
             _ => {
                 // Unknown register, just use unnamed
                 self.unnamed = text;
             }
         }
+
+        let key = match reg {
+            '*' => '+',
+            _ => reg,
+        };
+        self.set_linewise(key, linewise);
+        self.set_linewise('"', linewise);
+    }
+
+    fn set_linewise(&mut self, key: char, linewise: bool) {
+        if linewise {
+            self.linewise.insert(key);
+        } else {
+            self.linewise.remove(&key);
+        }
     }
 
     /// Get text from a register
@@ -124,6 +180,24 @@ impl Registers {
         }
     }
 
+    /// Registers holding text, in `:reg` order: `"`, `0`-`9`, `-`, `a`-`z`, `*`, `+`
+    pub fn non_empty(&self) -> Vec<(char, &str)> {
+        let mut entries = vec![('"', self.unnamed.as_str())];
+        for (idx, text) in self.numbered.iter().enumerate() {
+            entries.push((char::from(b'0' + idx as u8), text.as_str()));
+        }
+        entries.push(('-', self.small_delete.as_str()));
+        for reg in 'a'..='z' {
+            if let Some(text) = self.named.get(&reg) {
+                entries.push((reg, text.as_str()));
+            }
+        }
+        entries.push(('*', self.clipboard.as_str()));
+        entries.push(('+', self.clipboard.as_str()));
+        entries.retain(|(_, text)| !text.is_empty());
+        entries
+    }
+
     /// Add delete to numbered registers
     /// This shifts 1->2, 2->3, etc. and puts new content in 1
     pub fn add_delete(&mut self, text: String) {
@@ -157,6 +231,7 @@ impl Registers {
         self.small_delete.clear();
         self.clipboard.clear();
         self.last_inserted.clear();
+        self.linewise.clear();
     }
 
     /// Check if register exists and has content
@@ -184,6 +259,17 @@ mod tests {
         assert_eq!(regs.get('"'), Some("yanked")); // Unnamed also updated
     }
 
+    #[test]
+    fn test_non_empty_registers() {
+        let mut regs = Registers::new();
+        assert!(regs.non_empty().is_empty());
+        regs.yank_lines("line".to_string(), 'b');
+        regs.yank("word".to_string(), '0');
+        let names: Vec<char> = regs.non_empty().iter().map(|(reg, _)| *reg).collect();
+        assert_eq!(names, vec!['"', '0', 'b']);
+        assert_eq!(regs.non_empty()[2].1, "line\n");
+    }
+
     #[test]
     fn test_named_registers() {
         let mut regs = Registers::new();
@@ -203,6 +289,56 @@ mod tests {
         assert_eq!(regs.get('a'), Some("hello world"));
     }
 
+    #[test]
+    fn test_append_linewise_yanks() {
+        let mut regs = Registers::new();
+        regs.yank_lines("first line".to_string(), 'a');
+        regs.yank_lines("second line\n".to_string(), 'A');
+
+        assert_eq!(regs.get('a'), Some("first line\nsecond line\n"));
+        assert!(regs.is_linewise('a'));
+        assert_eq!(regs.get('"'), Some("first line\nsecond line\n"));
+    }
+
+    #[test]
+    fn test_append_charwise_to_linewise() {
+        let mut regs = Registers::new();
+        regs.yank_lines("line".to_string(), 'a');
+        regs.yank("word".to_string(), 'A');
+
+        assert_eq!(regs.get('a'), Some("line\nword\n"));
+        assert!(regs.is_linewise('a'));
+    }
+
+    #[test]
+    fn test_append_linewise_to_charwise() {
+        let mut regs = Registers::new();
+        regs.yank("word".to_string(), 'a');
+        regs.yank_lines("line".to_string(), 'A');
+
+        assert_eq!(regs.get('a'), Some("word\nline\n"));
+        assert!(regs.is_linewise('a'));
+    }
+
+    #[test]
+    fn test_append_to_empty_register() {
+        let mut regs = Registers::new();
+        regs.yank("word".to_string(), 'B');
+
+        assert_eq!(regs.get('b'), Some("word"));
+        assert!(!regs.is_linewise('b'));
+    }
+
+    #[test]
+    fn test_lowercase_yank_replaces_append() {
+        let mut regs = Registers::new();
+        regs.yank_lines("line".to_string(), 'a');
+        regs.yank("word".to_string(), 'a');
+
+        assert_eq!(regs.get('a'), Some("word"));
+        assert!(!regs.is_linewise('a'));
+    }
+
     #[test]
     fn test_add_delete_shifts_registers() {
         let mut regs = Registers::new();
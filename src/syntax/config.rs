@@ -18,6 +18,8 @@ pub struct LanguageEntry {
     pub highlight_query: Option<String>,
     #[serde(rename = "injection-query")]
     pub injection_query: Option<String>,
+    #[serde(rename = "indent-query")]
+    pub indent_query: Option<String>,
 }
 
 /// Load language configuration from runtime/languages.toml
@@ -86,6 +88,7 @@ mod tests {
                 grammar: Some("rust".to_string()),
                 highlight_query: Some("runtime/queries/rust/highlights.scm".to_string()),
                 injection_query: None,
+                indent_query: None,
             }],
         };
 
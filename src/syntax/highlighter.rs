@@ -2,17 +2,25 @@ use crate::syntax::{LanguageConfig, QueryLoader};
 use log::{debug, trace};
 use std::collections::HashMap;
 use std::ops::Range;
-use tree_sitter::{Parser, Query, Tree};
+use tree_sitter::{InputEdit, Parser, Point, Query, Tree};
+
+/// Extra lines highlighted on each side of a requested range, so small
+/// scrolls don't need another query pass
+const HIGHLIGHT_MARGIN: usize = 50;
 
 pub struct SyntaxHighlighter {
     parser: Parser,
     tree: Option<Tree>,
     language_config: LanguageConfig,
-    highlights: HashMap<usize, Vec<HighlightToken>>, // line -> tokens
+    // Per-line token cache: line -> tokens starting on that line. A line that
+    // has been highlighted but has no tokens holds an empty Vec.
+    highlights: HashMap<usize, Vec<HighlightToken>>,
     query_loader: QueryLoader,
-    // Performance optimization: Track viewport to avoid re-highlighting unchanged regions
-    current_viewport: Option<Range<usize>>,
+    // Lines that should have tokens; only lines in this window are extracted
+    highlighted_lines: Range<usize>,
     full_text: Option<String>, // Cache full text for viewport updates
+    // The tree has been edited to match the text of the next `reparse`
+    tree_edited: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -34,73 +42,146 @@ impl SyntaxHighlighter {
             language_config,
             highlights: HashMap::new(),
             query_loader: QueryLoader::new(),
-            current_viewport: None,
+            highlighted_lines: 0..2 * HIGHLIGHT_MARGIN,
             full_text: None,
+            tree_edited: false,
         })
     }
 
     pub fn parse(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.tree = self.parser.parse(text, None);
+        self.highlights.clear();
+        self.tree_edited = false;
+        self.reparse(text)
+    }
+
+    /// Parse `text` again after edits that were reported through `edit` or
+    /// `invalidate_lines_from`, reusing cached tokens for untouched lines.
+    ///
+    /// After `edit`s the old tree is reused, and only lines whose syntax
+    /// changed lose their tokens on top of the edited ones.
+    pub fn reparse(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let old_tree = self.tree.take().filter(|_| self.tree_edited);
+        self.tree = self.parser.parse(text, old_tree.as_ref());
+        self.tree_edited = false;
+
+        if let (Some(old_tree), Some(tree)) = (&old_tree, &self.tree) {
+            for range in old_tree.changed_ranges(tree) {
+                let rows = range.start_point.row..=range.end_point.row;
+                self.highlights.retain(|line, _| !rows.contains(line));
+            }
+        }
+
         self.full_text = Some(text.to_string());
-        self.update_highlights(text, None);
+        self.refresh_highlights();
         Ok(())
     }
 
+    /// Record an edit made to the text since the last parse.
+    ///
+    /// Tokens on the edited lines are dropped, and tokens below them are moved
+    /// by the lines and bytes the edit added or removed rather than extracted
+    /// again. Edits must be reported in the order they were made.
+    pub fn edit(&mut self, edit: &InputEdit) {
+        let first = edit.start_position.row;
+        let old_last = edit.old_end_position.row;
+        let new_last = edit.new_end_position.row;
+
+        let old = std::mem::take(&mut self.highlights);
+        for (line, mut tokens) in old {
+            if line < first {
+                // Tokens spanning lines may reach into or past the edit
+                for token in &mut tokens {
+                    token.end = Self::shift_offset(token.end, edit);
+                }
+                self.highlights.insert(line, tokens);
+            } else if line > old_last {
+                for token in &mut tokens {
+                    token.start = Self::shift_offset(token.start, edit);
+                    token.end = Self::shift_offset(token.end, edit);
+                }
+                self.highlights.insert(line + new_last - old_last, tokens);
+            }
+        }
+
+        if let Some(tree) = &mut self.tree {
+            tree.edit(edit);
+            self.tree_edited = true;
+        }
+    }
+
+    /// Where byte `offset` of the old text ends up after `edit`
+    fn shift_offset(offset: usize, edit: &InputEdit) -> usize {
+        if offset >= edit.old_end_byte {
+            offset + edit.new_end_byte - edit.old_end_byte
+        } else if offset > edit.start_byte {
+            // Inside the replaced text; clamp to the inserted one
+            offset.min(edit.new_end_byte)
+        } else {
+            offset
+        }
+    }
+
+    /// Drop cached tokens for `line` and everything after it.
+    ///
+    /// For changes that weren't reported through `edit`: the next `reparse`
+    /// starts from scratch, and only the lines above `line` are reused.
+    pub fn invalidate_lines_from(&mut self, line: usize) {
+        self.highlights.retain(|&cached, _| cached < line);
+        self.tree_edited = false;
+    }
+
     pub fn update_parse(
         &mut self,
         text: &str,
         edit: tree_sitter::InputEdit,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(tree) = &mut self.tree {
-            tree.edit(&edit);
-            self.tree = self.parser.parse(text, Some(tree));
-        } else {
-            self.parse(text)?;
-            return Ok(());
+        if self.tree.is_none() {
+            return self.parse(text);
         }
-        self.full_text = Some(text.to_string());
-        self.update_highlights(text, None);
-        Ok(())
+        self.edit(&edit);
+        self.reparse(text)
     }
 
-    /// Update highlights for a specific viewport range (performance optimization)
-    pub fn update_highlights_viewport(&mut self, viewport: Range<usize>) {
-        if let Some(text) = &self.full_text {
-            // Check if viewport has changed significantly
-            let needs_update = match &self.current_viewport {
-                None => true,
-                Some(current) => {
-                    // Update if viewport moved by more than 10 lines
-                    let start_diff = viewport.start.abs_diff(current.start);
-                    let end_diff = viewport.end.abs_diff(current.end);
-                    start_diff > 10 || end_diff > 10
-                }
-            };
-
-            if needs_update {
-                // Clear old highlights that are no longer in viewport (keep some margin)
-                if let Some(current) = &self.current_viewport {
-                    let margin = 20;
-                    for line in current.start.saturating_sub(margin)..current.end + margin {
-                        if line < viewport.start.saturating_sub(margin) || line > viewport.end + margin {
-                            self.highlights.remove(&line);
-                        }
-                    }
-                }
-
-                // Clone text to avoid borrow checker issue
-                let text_clone = text.clone();
-                self.update_highlights(&text_clone, Some(viewport.clone()));
-                self.current_viewport = Some(viewport);
-            }
+    /// Make tokens available for `lines` (end exclusive), e.g. the visible viewport.
+    ///
+    /// The tree stays whole, but tokens are only extracted for the requested lines
+    /// plus a margin, so the work scales with the screen rather than the file.
+    /// Lines whose tokens are still cached are not extracted again.
+    pub fn highlight_range(&mut self, lines: Range<usize>) {
+        if lines.start >= self.highlighted_lines.start && lines.end <= self.highlighted_lines.end
+        {
+            return;
         }
+
+        self.highlighted_lines =
+            lines.start.saturating_sub(HIGHLIGHT_MARGIN)..lines.end + HIGHLIGHT_MARGIN;
+        self.refresh_highlights();
     }
 
-    fn update_highlights(&mut self, text: &str, viewport: Option<Range<usize>>) {
-        // Only clear highlights if we're not doing viewport-specific updates
-        if viewport.is_none() {
-            self.highlights.clear();
+    /// Extract tokens for the lines of the window that aren't cached, and
+    /// forget lines that have scrolled well out of it
+    fn refresh_highlights(&mut self) {
+        let keep = self.highlighted_lines.start.saturating_sub(HIGHLIGHT_MARGIN)
+            ..self.highlighted_lines.end + HIGHLIGHT_MARGIN;
+        self.highlights.retain(|line, _| keep.contains(line));
+
+        let mut missing = self
+            .highlighted_lines
+            .clone()
+            .filter(|line| !self.highlights.contains_key(line));
+        let Some(first) = missing.next() else {
+            return;
+        };
+        let last = missing.next_back().unwrap_or(first);
+
+        if let Some(text) = self.full_text.take() {
+            self.update_highlights(&text, first..last + 1);
+            self.full_text = Some(text);
         }
+    }
+
+    fn update_highlights(&mut self, text: &str, lines: Range<usize>) {
+        let mut fresh = HashMap::new();
 
         if let Some(tree) = &self.tree {
             let language = (self.language_config.tree_sitter_language)();
@@ -124,7 +205,7 @@ impl SyntaxHighlighter {
                 Some(self.language_config.highlight_query_fallback),
             ) {
                 debug!("Query loaded successfully");
-                Self::apply_query(&mut self.highlights, text, tree, &query, viewport.as_ref());
+                Self::apply_query(&mut fresh, text, tree, &query, &lines);
             } else {
                 debug!("Failed to load query");
             }
@@ -137,7 +218,7 @@ impl SyntaxHighlighter {
                     self.language_config.injection_query_fallback,
                 )
             {
-                Self::apply_query(&mut self.highlights, text, tree, &query, viewport.as_ref());
+                Self::apply_query(&mut fresh, text, tree, &query, &lines);
             }
 
             // Load and apply locals query
@@ -148,12 +229,15 @@ impl SyntaxHighlighter {
                     self.language_config.locals_query_fallback,
                 )
             {
-                Self::apply_query(&mut self.highlights, text, tree, &query, viewport.as_ref());
+                Self::apply_query(&mut fresh, text, tree, &query, &lines);
             }
 
-            // Sort tokens by start position
-            for tokens in self.highlights.values_mut() {
+            // Sort tokens by start position and cache every line of the range,
+            // including the ones without tokens
+            for line in lines {
+                let mut tokens = fresh.remove(&line).unwrap_or_default();
                 tokens.sort_by_key(|t| t.start);
+                self.highlights.insert(line, tokens);
             }
         }
     }
@@ -163,9 +247,10 @@ impl SyntaxHighlighter {
         text: &str,
         tree: &Tree,
         query: &Query,
-        viewport: Option<&Range<usize>>,
+        lines: &Range<usize>,
     ) {
         let mut cursor = tree_sitter::QueryCursor::new();
+        cursor.set_point_range(Point::new(lines.start, 0)..Point::new(lines.end, 0));
         let captures = cursor.captures(query, tree.root_node(), text.as_bytes());
 
         let mut capture_count = 0;
@@ -175,17 +260,11 @@ impl SyntaxHighlighter {
                 let capture_name = &query.capture_names()[capture.index as usize];
                 let start = capture.node.start_byte();
                 let end = capture.node.end_byte();
-                let line = text[..start].chars().filter(|&c| c == '\n').count();
-
-                // Performance optimization: Skip lines outside viewport
-                if let Some(viewport) = viewport {
-                    // Add margin for multi-line tokens and lookahead
-                    let margin = 5;
-                    if line < viewport.start.saturating_sub(margin)
-                        || line > viewport.end + margin
-                    {
-                        continue;
-                    }
+                let line = capture.node.start_position().row;
+
+                // Nodes that merely overlap the range start on an earlier line
+                if !lines.contains(&line) {
+                    continue;
                 }
 
                 highlights.entry(line).or_default().push(HighlightToken {
@@ -203,8 +282,26 @@ impl SyntaxHighlighter {
         }
     }
 
+    /// Capture names of the tokens covering `byte` (a byte offset into the
+    /// text) on `line`, outermost first. The first is the one drawn.
+    pub fn captures_at(&mut self, line: usize, byte: usize) -> Vec<String> {
+        self.highlight_range(line..line + 1);
+        self.highlights
+            .get(&line)
+            .into_iter()
+            .flatten()
+            .filter(|token| (token.start..token.end).contains(&byte))
+            .map(|token| token.capture_name.clone())
+            .collect()
+    }
+
     pub fn get_line_highlights(&self, line: usize) -> Option<&Vec<HighlightToken>> {
-        self.highlights.get(&line)
+        self.highlights.get(&line).filter(|tokens| !tokens.is_empty())
+    }
+
+    /// Whether tokens for `line` are cached and can be used without a query
+    pub fn is_line_cached(&self, line: usize) -> bool {
+        self.highlights.contains_key(&line)
     }
 
     pub fn get_tree(&self) -> &Option<Tree> {
@@ -212,7 +309,15 @@ impl SyntaxHighlighter {
     }
 
     pub fn get_highlights_len(&self) -> usize {
-        self.highlights.len()
+        self.highlights
+            .values()
+            .filter(|tokens| !tokens.is_empty())
+            .count()
+    }
+
+    /// Lines whose tokens are currently available from `get_line_highlights`
+    pub fn highlighted_lines(&self) -> Range<usize> {
+        self.highlighted_lines.clone()
     }
 }
 
@@ -271,4 +376,125 @@ mod tests {
         highlighter.update_parse(new_code, edit).unwrap();
         assert!(highlighter.tree.is_some());
     }
+
+    #[test]
+    fn test_captures_at() {
+        let config = get_language_config(LanguageId::Rust);
+        let mut highlighter = SyntaxHighlighter::new(config).unwrap();
+        let code = "fn main() {\n    let x = 1;\n}";
+        highlighter.parse(code).unwrap();
+        // Both captures of `main`, the one drawn first
+        assert_eq!(highlighter.captures_at(0, 3), ["variable", "function"]);
+        assert!(highlighter.captures_at(0, 2).is_empty());
+        // `1` on the second line
+        assert_eq!(highlighter.captures_at(1, 24), ["constant.numeric.integer"]);
+    }
+
+    #[test]
+    fn test_highlight_range_is_bounded() {
+        let config = get_language_config(LanguageId::Rust);
+        let mut highlighter = SyntaxHighlighter::new(config).unwrap();
+        let code = "fn f() {}\n".repeat(1000);
+        highlighter.parse(&code).unwrap();
+
+        // Only the top of the file is highlighted after parsing
+        assert!(highlighter.get_line_highlights(0).is_some());
+        assert!(highlighter.get_line_highlights(500).is_none());
+
+        highlighter.highlight_range(500..540);
+        let covered = highlighter.highlighted_lines();
+        assert!(covered.start <= 500 && covered.end >= 540);
+        assert!(covered.len() < 200);
+        assert!(highlighter.get_line_highlights(520).is_some());
+        assert!(highlighter.get_line_highlights(0).is_none());
+        assert!(highlighter.get_highlights_len() <= covered.len());
+
+        // Edits re-highlight the same window
+        highlighter.parse(&code).unwrap();
+        assert!(highlighter.get_line_highlights(520).is_some());
+    }
+
+    #[test]
+    fn test_edits_keep_tokens_above_the_edit() {
+        let config = get_language_config(LanguageId::Rust);
+        let mut highlighter = SyntaxHighlighter::new(config).unwrap();
+        let code = "fn f() {}\n".repeat(100);
+        highlighter.parse(&code).unwrap();
+        highlighter.highlight_range(0..40);
+        let before = highlighter.get_line_highlights(5).unwrap()[0].clone();
+
+        highlighter.invalidate_lines_from(20);
+        assert!(highlighter.is_line_cached(19));
+        assert!(!highlighter.is_line_cached(20));
+        assert!(!highlighter.is_line_cached(60));
+
+        let edited = format!("{}fn g() {{}}\n{}", &code[..200], &code[200..]);
+        highlighter.reparse(&edited).unwrap();
+        assert_eq!(highlighter.get_line_highlights(5).unwrap()[0].start, before.start);
+        assert!(highlighter.is_line_cached(20));
+        // Line 21 is an unchanged line moved down by the inserted one
+        let offset_in_line = before.start - 50;
+        assert_eq!(
+            highlighter.get_line_highlights(21).unwrap()[0].start,
+            210 + offset_in_line
+        );
+    }
+
+    #[test]
+    fn test_edit_shifts_tokens_below_it() {
+        let config = get_language_config(LanguageId::Rust);
+        let mut highlighter = SyntaxHighlighter::new(config).unwrap();
+        let code = "fn f() {}\n".repeat(100);
+        highlighter.parse(&code).unwrap();
+        highlighter.highlight_range(0..40);
+        let before = highlighter.get_line_highlights(24).unwrap()[0].start;
+
+        // Insert a line at the start of line 20
+        highlighter.edit(&InputEdit {
+            start_byte: 200,
+            old_end_byte: 200,
+            new_end_byte: 210,
+            start_position: Point::new(20, 0),
+            old_end_position: Point::new(20, 0),
+            new_end_position: Point::new(21, 0),
+        });
+        assert!(highlighter.is_line_cached(19));
+        assert!(!highlighter.is_line_cached(20));
+        assert_eq!(highlighter.get_line_highlights(25).unwrap()[0].start, before + 10);
+
+        let edited = format!("{}fn g() {{}}\n{}", &code[..200], &code[200..]);
+        highlighter.reparse(&edited).unwrap();
+
+        let mut fresh = SyntaxHighlighter::new(get_language_config(LanguageId::Rust)).unwrap();
+        fresh.parse(&edited).unwrap();
+        fresh.highlight_range(0..40);
+        let tokens = |h: &SyntaxHighlighter, line| {
+            h.get_line_highlights(line)
+                .map(|tokens| {
+                    tokens
+                        .iter()
+                        .map(|t| (t.start, t.end, t.capture_name.clone()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        for line in 0..40 {
+            assert_eq!(tokens(&highlighter, line), tokens(&fresh, line), "line {}", line);
+        }
+    }
+
+    #[test]
+    fn test_scrolling_reuses_cached_lines() {
+        let config = get_language_config(LanguageId::Rust);
+        let mut highlighter = SyntaxHighlighter::new(config).unwrap();
+        let code = "fn f() {}\n".repeat(2000);
+        highlighter.parse(&code).unwrap();
+
+        highlighter.highlight_range(1000..1040);
+        highlighter.highlight_range(1100..1140);
+        // The overlap stays cached, lines far behind are dropped
+        assert!(highlighter.is_line_cached(1050));
+        assert!(highlighter.get_line_highlights(1120).is_some());
+        assert!(!highlighter.is_line_cached(0));
+    }
 }
@@ -4,6 +4,9 @@ pub fn get_language_config(id: LanguageId) -> LanguageConfig {
     match id {
         LanguageId::Rust => LanguageConfig {
             id,
+            comment_token: line_comment_token(id),
+            block_comment_tokens: block_comment_tokens(id),
+            word_chars: identifier_chars(id),
             tree_sitter_language: || tree_sitter_rust::language(),
             highlight_query_path: Some("runtime/queries/rust/highlights.scm".to_string()),
             highlight_query_fallback: include_str!("../../queries/rust/highlights.scm"),
@@ -11,9 +14,14 @@ pub fn get_language_config(id: LanguageId) -> LanguageConfig {
             injection_query_fallback: None,
             locals_query_path: None,
             locals_query_fallback: None,
+            indent_query_path: Some("runtime/queries/rust/indents.scm".to_string()),
+            indent_query_fallback: Some(include_str!("../../queries/rust/indents.scm")),
         },
         LanguageId::Python => LanguageConfig {
             id,
+            comment_token: line_comment_token(id),
+            block_comment_tokens: block_comment_tokens(id),
+            word_chars: identifier_chars(id),
             tree_sitter_language: || tree_sitter_python::language(),
             highlight_query_path: Some("runtime/queries/python/highlights.scm".to_string()),
             highlight_query_fallback: include_str!("../../queries/python/highlights.scm"),
@@ -21,9 +29,14 @@ pub fn get_language_config(id: LanguageId) -> LanguageConfig {
             injection_query_fallback: None,
             locals_query_path: None,
             locals_query_fallback: None,
+            indent_query_path: Some("runtime/queries/python/indents.scm".to_string()),
+            indent_query_fallback: Some(include_str!("../../queries/python/indents.scm")),
         },
         LanguageId::JavaScript => LanguageConfig {
             id,
+            comment_token: line_comment_token(id),
+            block_comment_tokens: block_comment_tokens(id),
+            word_chars: identifier_chars(id),
             tree_sitter_language: || tree_sitter_javascript::language(),
             highlight_query_path: Some("runtime/queries/javascript/highlights.scm".to_string()),
             highlight_query_fallback: include_str!("../../queries/javascript/highlights.scm"),
@@ -31,9 +44,14 @@ pub fn get_language_config(id: LanguageId) -> LanguageConfig {
             injection_query_fallback: None,
             locals_query_path: None,
             locals_query_fallback: None,
+            indent_query_path: Some("runtime/queries/javascript/indents.scm".to_string()),
+            indent_query_fallback: Some(include_str!("../../queries/javascript/indents.scm")),
         },
         LanguageId::TypeScript => LanguageConfig {
             id,
+            comment_token: line_comment_token(id),
+            block_comment_tokens: block_comment_tokens(id),
+            word_chars: identifier_chars(id),
             tree_sitter_language: || tree_sitter_typescript::language_typescript(),
             highlight_query_path: Some("runtime/queries/typescript/highlights.scm".to_string()),
             highlight_query_fallback: include_str!("../../queries/typescript/highlights.scm"),
@@ -41,10 +59,36 @@ pub fn get_language_config(id: LanguageId) -> LanguageConfig {
             injection_query_fallback: None,
             locals_query_path: None,
             locals_query_fallback: None,
+            indent_query_path: Some("runtime/queries/typescript/indents.scm".to_string()),
+            indent_query_fallback: Some(include_str!("../../queries/typescript/indents.scm")),
         },
     }
 }
 
+/// Token that starts a line comment
+fn line_comment_token(id: LanguageId) -> &'static str {
+    match id {
+        LanguageId::Python => "#",
+        LanguageId::Rust | LanguageId::JavaScript | LanguageId::TypeScript => "//",
+    }
+}
+
+/// Tokens that open and close a block comment, if the language has them
+fn block_comment_tokens(id: LanguageId) -> Option<(&'static str, &'static str)> {
+    match id {
+        LanguageId::Python => None,
+        LanguageId::Rust | LanguageId::JavaScript | LanguageId::TypeScript => Some(("/*", "*/")),
+    }
+}
+
+/// Characters besides letters and digits that identifiers are made of
+fn identifier_chars(id: LanguageId) -> &'static str {
+    match id {
+        LanguageId::Rust | LanguageId::Python => "_",
+        LanguageId::JavaScript | LanguageId::TypeScript => "_$",
+    }
+}
+
 pub fn get_language_config_by_extension(ext: &str) -> Option<LanguageConfig> {
     match ext {
         "rs" => Some(get_language_config(LanguageId::Rust)),
@@ -71,6 +115,9 @@ pub fn get_language_config_from_registry(
 
     Some(LanguageConfig {
         id,
+        comment_token: line_comment_token(id),
+        block_comment_tokens: block_comment_tokens(id),
+        word_chars: identifier_chars(id),
         tree_sitter_language: match id {
             LanguageId::Rust => || tree_sitter_rust::language(),
             LanguageId::Python => || tree_sitter_python::language(),
@@ -88,6 +135,13 @@ pub fn get_language_config_from_registry(
         injection_query_fallback: None, // TODO: add fallbacks
         locals_query_path: None,        // TODO: add locals queries
         locals_query_fallback: None,
+        indent_query_path: entry.indent_query.clone(),
+        indent_query_fallback: Some(match id {
+            LanguageId::Rust => include_str!("../../queries/rust/indents.scm"),
+            LanguageId::Python => include_str!("../../queries/python/indents.scm"),
+            LanguageId::JavaScript => include_str!("../../queries/javascript/indents.scm"),
+            LanguageId::TypeScript => include_str!("../../queries/typescript/indents.scm"),
+        }),
     })
 }
 
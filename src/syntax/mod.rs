@@ -19,9 +19,44 @@ pub enum LanguageId {
     TypeScript,
 }
 
+impl LanguageId {
+    pub const ALL: [LanguageId; 4] = [
+        LanguageId::Rust,
+        LanguageId::Python,
+        LanguageId::JavaScript,
+        LanguageId::TypeScript,
+    ];
+
+    /// Look up a file type by name (`rust`) or by its usual extension (`rs`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL.into_iter().find(|id| {
+            id.name() == name
+                || language::get_language_config_by_extension(&name).is_some_and(|c| c.id == *id)
+        })
+    }
+
+    /// Lowercase name, as used in `languages.toml`
+    pub fn name(self) -> &'static str {
+        match self {
+            LanguageId::Rust => "rust",
+            LanguageId::Python => "python",
+            LanguageId::JavaScript => "javascript",
+            LanguageId::TypeScript => "typescript",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct LanguageConfig {
     pub id: LanguageId,
+    /// Token that starts a line comment, e.g. `//`
+    pub comment_token: &'static str,
+    /// Tokens that open and close a block comment, e.g. `/*` and `*/`
+    pub block_comment_tokens: Option<(&'static str, &'static str)>,
+    /// Characters besides letters and digits that are part of a word, the
+    /// default `iskeyword`
+    pub word_chars: &'static str,
     pub tree_sitter_language: fn() -> tree_sitter::Language,
     pub highlight_query_path: Option<String>,
     pub highlight_query_fallback: &'static str,
@@ -29,6 +64,52 @@ pub struct LanguageConfig {
     pub injection_query_fallback: Option<&'static str>,
     pub locals_query_path: Option<String>,
     pub locals_query_fallback: Option<&'static str>,
+    /// Query marking the nodes whose lines are indented, used by `=`
+    pub indent_query_path: Option<String>,
+    pub indent_query_fallback: Option<&'static str>,
+}
+
+impl LanguageConfig {
+    /// What starts the line after `before`, the text of a line up to the
+    /// cursor, to carry on the comment it is in: its indentation and comment
+    /// marker. `None` outside a comment. `in_block_comment` says whether an
+    /// earlier line left a block comment open, since a line starting with `* `
+    /// may just as well be code, like a wrapped multiplication.
+    pub fn comment_continuation(&self, before: &str, in_block_comment: bool) -> Option<String> {
+        let body = before.trim_start();
+        let indent = &before[..before.len() - body.len()];
+
+        if let Some((open, close)) = self.block_comment_tokens {
+            let opened = body.starts_with(open);
+            let inside = in_block_comment && (body == "*" || body.starts_with("* "));
+            if (opened || inside) && !body.contains(close) {
+                // Line the `*` up under the opener's
+                let pad = if opened { " " } else { "" };
+                return Some(format!("{}{}* ", indent, pad));
+            }
+        }
+
+        if body.starts_with(self.comment_token) {
+            // Doc comments such as `///` and `//!` keep their marker
+            let token = self.comment_token;
+            let rest =
+                body.trim_start_matches(|c| token.contains(c) || (c == '!' && token == "//"));
+            let marker = &body[..body.len() - rest.len()];
+            return Some(format!("{}{} ", indent, marker));
+        }
+        None
+    }
+
+    /// Whether `line` leaves a block comment open (`Some(true)`) or closed
+    /// (`Some(false)`), going by the last comment token on it. `None` if it
+    /// has neither.
+    pub fn block_comment_state(&self, line: &str) -> Option<bool> {
+        let (open, close) = self.block_comment_tokens?;
+        match (line.rfind(open), line.rfind(close)) {
+            (Some(opened), Some(closed)) => Some(opened > closed),
+            (opened, closed) => opened.or(closed).map(|_| opened.is_some()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -40,4 +121,49 @@ mod tests {
         assert_eq!(LanguageId::Rust, LanguageId::Rust);
         assert_ne!(LanguageId::Python, LanguageId::JavaScript);
     }
+
+    #[test]
+    fn test_comment_continuation() {
+        let rust = get_language_config(LanguageId::Rust);
+        let next = |before: &str| rust.comment_continuation(before, false);
+        assert_eq!(next("    // note").as_deref(), Some("    // "));
+        assert_eq!(next("/// Docs").as_deref(), Some("/// "));
+        assert_eq!(next("//! Crate").as_deref(), Some("//! "));
+        assert_eq!(next("  /** Docs").as_deref(), Some("   * "));
+        assert_eq!(next("/* closed */"), None);
+        assert_eq!(next("*ptr = 1;"), None);
+        assert_eq!(next("let x = 1; // trailing"), None);
+        // A wrapped expression isn't a comment
+        assert_eq!(next("    * rate;"), None);
+
+        let inside = |before: &str| rust.comment_continuation(before, true);
+        assert_eq!(inside("   * more").as_deref(), Some("   * "));
+        assert_eq!(inside("   */"), None);
+
+        let python = get_language_config(LanguageId::Python);
+        assert_eq!(python.comment_continuation("  # note", false).as_deref(), Some("  # "));
+        assert_eq!(python.comment_continuation("* x", true), None);
+    }
+
+    #[test]
+    fn test_block_comment_state() {
+        let rust = get_language_config(LanguageId::Rust);
+        assert_eq!(rust.block_comment_state("  /** Docs"), Some(true));
+        assert_eq!(rust.block_comment_state("/* closed */"), Some(false));
+        assert_eq!(rust.block_comment_state("   */ let x = 1; /* again"), Some(true));
+        assert_eq!(rust.block_comment_state("    * rate;"), None);
+        let python = get_language_config(LanguageId::Python);
+        assert_eq!(python.block_comment_state("/* x"), None);
+    }
+
+    #[test]
+    fn test_language_from_name() {
+        for id in LanguageId::ALL {
+            assert_eq!(LanguageId::from_name(id.name()), Some(id));
+        }
+        assert_eq!(LanguageId::from_name("Python"), Some(LanguageId::Python));
+        assert_eq!(LanguageId::from_name("ts"), Some(LanguageId::TypeScript));
+        assert_eq!(LanguageId::from_name("cobol"), None);
+        assert_eq!(LanguageId::from_name(""), None);
+    }
 }
@@ -94,6 +94,7 @@ pub struct EditorTheme {
     pub primary_selection: Option<ThemeStyle>,
     pub indent_guide: Option<ThemeStyle>,
     pub current_line: Option<ThemeStyle>,
+    pub ruler: Option<ThemeStyle>,
 }
 
 /// Status theme configuration
@@ -282,6 +283,7 @@ impl Theme {
                 "primary_selection" => editor.primary_selection.as_ref(),
                 "indent_guide" => editor.indent_guide.as_ref(),
                 "current_line" => editor.current_line.as_ref(),
+                "ruler" => editor.ruler.as_ref(),
                 _ => return ResolvedStyle::default(),
             };
 
@@ -447,6 +449,7 @@ impl EditorTheme {
             primary_selection: self.primary_selection.or(base.primary_selection.clone()),
             indent_guide: self.indent_guide.or(base.indent_guide.clone()),
             current_line: self.current_line.or(base.current_line.clone()),
+            ruler: self.ruler.or(base.ruler.clone()),
         }
     }
 }
@@ -3,24 +3,33 @@
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
     style::Style,
 };
+use crossterm::cursor::SetCursorStyle;
 use std::io::Stdout;
 
 use crate::editor::Editor;
+use crate::options::CursorShape;
+use crate::ui::system_theme::{TerminalCapability, detect_terminal_capability, downsample_color};
 use crate::ui::theme::Theme;
 use crate::ui::widgets::editor_pane::EditorPane;
 use crate::ui::widgets::fuzzy_search::FuzzySearchWidget;
 use crate::ui::widgets::gutter::Gutter;
 use crate::ui::widgets::hover::HoverWindow;
 use crate::ui::widgets::menu::CodeActionMenu;
+use crate::ui::widgets::quickfix::QuickfixWindow;
 use crate::ui::widgets::status_bar::StatusBar;
 
 /// Ratatui-based renderer for the text editor
 pub struct TuiRenderer {
     terminal: Terminal<CrosstermBackend<Stdout>>,
     theme: Theme,
+    /// Colors the terminal can display; RGB theme colors are mapped down to fit
+    capability: TerminalCapability,
+    /// Cursor shape last sent to the terminal, so it is only sent on a change
+    cursor_shape: Option<CursorShape>,
 }
 
 impl TuiRenderer {
@@ -75,15 +84,21 @@ impl TuiRenderer {
             }
         };
 
-        Ok(Self { terminal, theme })
+        Ok(Self {
+            terminal,
+            theme,
+            capability: detect_terminal_capability(),
+            cursor_shape: None,
+        })
     }
 
     /// Render the entire editor user interface into the terminal.
     ///
     /// This draws the main editor content (gutter and text), the status bar, the fuzzy-search
     /// UI when active (either full-screen preview or split view), and any overlays such as
-    /// hover windows and code-action menus. The function also clears the terminal buffer to
-    /// avoid visual artifacts and positions the cursor when the editor view is visible.
+    /// hover windows, code-action menus and the completion popup. The function also clears the
+    /// terminal buffer to avoid visual artifacts and positions the cursor when the editor view is
+    /// visible.
     ///
     /// # Errors
     ///
@@ -169,11 +184,18 @@ impl TuiRenderer {
                 let editor_chunks = Layout::default()
                     .direction(Direction::Horizontal)
                     .constraints([
-                        Constraint::Length(4), // Gutter
-                        Constraint::Min(1),    // Text area
+                        Constraint::Length(Gutter::WIDTH),
+                        Constraint::Min(1), // Text area
                     ])
                     .split(vertical_chunks[0]);
 
+                // Extract highlight tokens for just the visible lines
+                let first_line = editor.viewport.offset_line;
+                if let Some(highlighter) = &mut editor.buffer.highlighter {
+                    highlighter
+                        .highlight_range(first_line..first_line + editor_chunks[1].height as usize);
+                }
+
                 // Render gutter
                 f.render_widget(Gutter::new(editor, &self.theme), editor_chunks[0]);
 
@@ -187,8 +209,10 @@ impl TuiRenderer {
                         .line
                         .saturating_sub(editor.viewport.offset_line)
                         as u16;
-                    let cursor_col =
-                        editor.cursor.col.saturating_sub(editor.viewport.offset_col) as u16;
+                    let cursor_col = editor
+                        .cursor_display_col()
+                        .saturating_sub(editor.viewport.offset_col)
+                        as u16;
                     if cursor_row < editor_chunks[1].height && cursor_col < editor_chunks[1].width {
                         f.set_cursor(
                             editor_chunks[1].x + cursor_col,
@@ -241,8 +265,11 @@ impl TuiRenderer {
             } else {
                 (
                     content_area.x
-                        + 4
-                        + editor.cursor.col.saturating_sub(editor.viewport.offset_col) as u16, // +4 for gutter
+                        + Gutter::WIDTH
+                        + editor
+                            .cursor_display_col()
+                            .saturating_sub(editor.viewport.offset_col)
+                            as u16,
                     content_area.y
                         + editor
                             .cursor
@@ -252,6 +279,19 @@ impl TuiRenderer {
                 )
             };
 
+            // Overlays are drawn bottom to top: the quickfix window docked
+            // over the text, then hover information, listings, the code action
+            // menu and the completion popup. Inlay hints are part of the text
+            // below all of them.
+            if editor.quickfix_open {
+                let window = QuickfixWindow::new(&editor.quickfix, &self.theme);
+                let area = window.calculate_position(Rect {
+                    height: size.height.saturating_sub(1),
+                    ..size
+                });
+                f.render_widget(window, area);
+            }
+
             // Render hover window if active
             if let Some(content) = &editor.hover_content {
                 let hover_window = HoverWindow::new(content.clone(), &self.theme);
@@ -259,6 +299,18 @@ impl TuiRenderer {
                 f.render_widget(hover_window, hover_area);
             }
 
+            // Render the `:reg`, `:messages` or `:lsp log` listing if open
+            if let Some(listing) = &editor.listing {
+                let mut window = HoverWindow::new(listing.lines.clone(), &self.theme)
+                    .with_title(&listing.title)
+                    .with_errors(&listing.errors);
+                if listing.follow_end {
+                    window = window.scrolled_to_end();
+                }
+                let area = window.calculate_position(cursor_x, cursor_y, size);
+                f.render_widget(window, area);
+            }
+
             // Render code action menu if active
             if let Some(actions) = &editor.code_actions {
                 let mut menu = CodeActionMenu::new(actions.clone(), &self.theme);
@@ -266,7 +318,69 @@ impl TuiRenderer {
                 let menu_area = menu.calculate_position(cursor_x, cursor_y, size);
                 f.render_widget(menu, menu_area);
             }
+
+            // Render the completion popup on top of everything else
+            if editor.completion_popup.is_visible() {
+                let popup = &editor.completion_popup;
+                let area = popup.calculate_position(cursor_x, cursor_y, size.width, size.height);
+                f.render_widget(popup, area.intersection(size));
+            }
+
+            // Themes are specified in RGB; fit them to the terminal's palette
+            if self.capability != TerminalCapability::TrueColor {
+                downsample_buffer(f.buffer_mut(), self.capability);
+            }
         })?;
+        self.update_cursor_shape(editor)?;
         Ok(())
     }
+
+    /// Ask the terminal for the current mode's cursor shape if it changed
+    fn update_cursor_shape(&mut self, editor: &Editor) -> std::io::Result<()> {
+        let shape = editor
+            .options
+            .cursor_shapes
+            .as_ref()
+            .map(|shapes| shapes.for_mode(editor.mode));
+        if shape == self.cursor_shape {
+            return Ok(());
+        }
+        let style = match shape {
+            Some(CursorShape::Block) => SetCursorStyle::SteadyBlock,
+            Some(CursorShape::Bar) => SetCursorStyle::SteadyBar,
+            Some(CursorShape::Underline) => SetCursorStyle::SteadyUnderScore,
+            None => SetCursorStyle::DefaultUserShape,
+        };
+        crossterm::execute!(self.terminal.backend_mut(), style)?;
+        self.cursor_shape = shape;
+        Ok(())
+    }
+}
+
+/// Replace every cell's RGB colors with the nearest color `capability` supports
+fn downsample_buffer(buf: &mut Buffer, capability: TerminalCapability) {
+    for cell in buf.content.iter_mut() {
+        cell.fg = downsample_color(cell.fg, capability);
+        cell.bg = downsample_color(cell.bg, capability);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Color;
+
+    #[test]
+    fn test_downsample_buffer() {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 2, 1));
+        buf.get_mut(0, 0)
+            .set_fg(Color::Rgb(255, 0, 0))
+            .set_bg(Color::Rgb(0, 0, 0));
+        buf.get_mut(1, 0).set_fg(Color::Yellow);
+
+        downsample_buffer(&mut buf, TerminalCapability::Color256);
+        assert_eq!(buf.get(0, 0).fg, Color::Indexed(196));
+        assert_eq!(buf.get(0, 0).bg, Color::Indexed(16));
+        assert_eq!(buf.get(1, 0).fg, Color::Yellow);
+    }
 }
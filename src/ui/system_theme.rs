@@ -1,7 +1,12 @@
 // ui/system_theme.rs - System theme detection
 
 use ratatui::style::Color;
-use std::io::{self, Read, Write};
+use std::io::{self, IsTerminal, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the terminal to answer a color query
+const OSC_QUERY_TIMEOUT: Duration = Duration::from_millis(50);
 
 /// Detected system theme
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -166,13 +171,15 @@ impl TerminalPalette {
 /// The function checks environment hints (COLORFGBG, DARK_MODE, and TERM) to determine
 /// whether the system or terminal prefers a light or dark appearance. If an explicit
 /// preference is present but not recognized, `SystemTheme::Unknown` is returned. When
-/// no hint is available, the function returns `SystemTheme::Dark`.
+/// no hint is available, the terminal is asked for its background color and the theme
+/// is chosen from that color's luminance.
 ///
 /// # Returns
 ///
 /// `SystemTheme::Light` if a light preference is detected, `SystemTheme::Dark` if a dark
 /// preference is detected, `SystemTheme::Unknown` if a preference string is present but
-/// not recognized; defaults to `SystemTheme::Dark` when no environment hints are found.
+/// not recognized; defaults to `SystemTheme::Dark` when neither the environment nor the
+/// terminal gives an answer.
 ///
 /// # Examples
 ///
@@ -209,11 +216,72 @@ pub fn detect_system_theme() -> SystemTheme {
         return SystemTheme::Dark;
     }
 
-    // Use common light/dark color schemes as fallback
-    // Most modern terminals default to dark, but we'll check some common patterns
+    // Ask the terminal for its actual background color
+    if let Some(background) = query_terminal_background() {
+        return theme_for_background(background);
+    }
+
+    // Most modern terminals default to dark
     SystemTheme::Dark
 }
 
+/// Classify a background color as light or dark by its perceived luminance.
+///
+/// # Examples
+///
+/// ```
+/// use texty::ui::system_theme::{theme_for_background, SystemTheme};
+/// assert_eq!(theme_for_background((253, 246, 227)), SystemTheme::Light);
+/// assert_eq!(theme_for_background((40, 42, 54)), SystemTheme::Dark);
+/// ```
+pub fn theme_for_background((r, g, b): (u8, u8, u8)) -> SystemTheme {
+    let luminance = 0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b);
+    if luminance > 127.5 {
+        SystemTheme::Light
+    } else {
+        SystemTheme::Dark
+    }
+}
+
+/// Query the terminal's background color with OSC 11.
+///
+/// The query is only sent when stdin and stdout are both a terminal, and gives up
+/// after a short timeout so terminals that never reply don't hang startup. The
+/// answer is cached for the rest of the session.
+pub fn query_terminal_background() -> Option<(u8, u8, u8)> {
+    static BACKGROUND: OnceLock<Option<(u8, u8, u8)>> = OnceLock::new();
+
+    *BACKGROUND.get_or_init(|| {
+        if !osc_queries_supported() {
+            return None;
+        }
+
+        // The reply only arrives unbuffered in raw mode
+        let was_raw = crossterm::terminal::is_raw_mode_enabled().unwrap_or(false);
+        if !was_raw && crossterm::terminal::enable_raw_mode().is_err() {
+            return None;
+        }
+
+        let background = query_terminal_special_color(11);
+
+        if !was_raw {
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+        background
+    })
+}
+
+/// Whether OSC queries can be sent: both ends must be an interactive terminal
+fn osc_queries_supported() -> bool {
+    if cfg!(test) || !cfg!(unix) || std::env::var("TEXTY_TEST").is_ok() {
+        return false;
+    }
+    if std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        return false;
+    }
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
 /// Determine the terminal's color capability from environment variables.
 ///
 /// The detection checks `COLORTERM`, `TERM`, and `NO_COLOR` for hints and
@@ -266,9 +334,106 @@ pub fn detect_terminal_capability() -> TerminalCapability {
     TerminalCapability::Color256
 }
 
+/// Approximate RGB values of the 16 ANSI colors (xterm defaults)
+const ANSI16_RGB: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Channel levels of the 6x6x6 color cube in the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Map a color to the closest one the terminal can display.
+///
+/// RGB colors are passed through on truecolor terminals, mapped to the nearest
+/// entry of the 256-color palette or the 16 ANSI colors otherwise, and dropped
+/// entirely when colors are disabled. Named and indexed colors are left alone.
+///
+/// # Examples
+///
+/// ```
+/// use ratatui::style::Color;
+/// use texty::ui::system_theme::{downsample_color, TerminalCapability};
+/// let red = Color::Rgb(255, 0, 0);
+/// assert_eq!(downsample_color(red, TerminalCapability::TrueColor), red);
+/// assert_eq!(downsample_color(red, TerminalCapability::Color256), Color::Indexed(196));
+/// assert_eq!(downsample_color(red, TerminalCapability::Basic16), Color::LightRed);
+/// ```
+pub fn downsample_color(color: Color, capability: TerminalCapability) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    match capability {
+        TerminalCapability::TrueColor => color,
+        TerminalCapability::Color256 => Color::Indexed(rgb_to_ansi256((r, g, b))),
+        TerminalCapability::Basic16 => rgb_to_ansi16((r, g, b)),
+        TerminalCapability::NoColor => Color::Reset,
+    }
+}
+
+/// Nearest index in the 256-color palette, choosing between the color cube
+/// and the grayscale ramp
+fn rgb_to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (
+        nearest_level(rgb.0),
+        nearest_level(rgb.1),
+        nearest_level(rgb.2),
+    );
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // Grayscale ramp 232..=255 runs from 8 to 238 in steps of 10
+    let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_step;
+    let gray = (gray_level, gray_level, gray_level);
+
+    if color_distance(rgb, gray) < color_distance(rgb, cube) {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Nearest of the 16 ANSI colors
+fn rgb_to_ansi16(rgb: (u8, u8, u8)) -> Color {
+    ANSI16_RGB
+        .iter()
+        .min_by_key(|(_, ansi)| color_distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Squared euclidean distance between two colors
+fn color_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let dr = u32::from(a.0.abs_diff(b.0));
+    let dg = u32::from(a.1.abs_diff(b.1));
+    let db = u32::from(a.2.abs_diff(b.2));
+    dr * dr + dg * dg + db * db
+}
+
 /// Queries the terminal for its ANSI and special (foreground/background) colors using OSC sequences and constructs a TerminalPalette from any successfully retrieved values.
 ///
-/// The returned palette will contain any colors the terminal responded with; fields for colors that were not returned remain at their initialized defaults. The function wraps the resulting palette in `Some` (the returned `Option` may contain a palette with no gathered responses).
+/// The returned palette will contain any colors the terminal responded with; fields for colors that were not returned remain at their initialized defaults. Each query gives up after a short timeout, and once one palette query goes unanswered the remaining ones are skipped. Returns `None` when the terminal answered none of the queries.
 ///
 /// # Examples
 ///
@@ -304,48 +469,57 @@ pub fn query_terminal_palette() -> Option<TerminalPalette> {
         "bright-white",
     ];
 
+    let mut answered = false;
     for (idx, &color_name) in palette_ansi_colors.iter().enumerate() {
-        if let Some(rgb) = query_terminal_color(idx) {
-            let color = Color::Rgb(rgb.0, rgb.1, rgb.2);
-
-            match color_name {
-                "black" => palette.black = color,
-                "red" => palette.red = color,
-                "green" => palette.green = color,
-                "yellow" => palette.yellow = color,
-                "blue" => palette.blue = color,
-                "magenta" => palette.magenta = color,
-                "cyan" => palette.cyan = color,
-                "white" => palette.white = color,
-                "bright-black" => palette.bright_black = color,
-                "bright-red" => palette.bright_red = color,
-                "bright-green" => palette.bright_green = color,
-                "bright-yellow" => palette.bright_yellow = color,
-                "bright-blue" => palette.bright_blue = color,
-                "bright-magenta" => palette.bright_magenta = color,
-                "bright-cyan" => palette.bright_cyan = color,
-                "bright-white" => palette.bright_white = color,
-                _ => {}
-            }
+        // A terminal that ignores one OSC 4 query ignores them all
+        let Some(rgb) = query_terminal_color(idx) else {
+            break;
+        };
+        answered = true;
+
+        let color = Color::Rgb(rgb.0, rgb.1, rgb.2);
+
+        match color_name {
+            "black" => palette.black = color,
+            "red" => palette.red = color,
+            "green" => palette.green = color,
+            "yellow" => palette.yellow = color,
+            "blue" => palette.blue = color,
+            "magenta" => palette.magenta = color,
+            "cyan" => palette.cyan = color,
+            "white" => palette.white = color,
+            "bright-black" => palette.bright_black = color,
+            "bright-red" => palette.bright_red = color,
+            "bright-green" => palette.bright_green = color,
+            "bright-yellow" => palette.bright_yellow = color,
+            "bright-blue" => palette.bright_blue = color,
+            "bright-magenta" => palette.bright_magenta = color,
+            "bright-cyan" => palette.bright_cyan = color,
+            "bright-white" => palette.bright_white = color,
+            _ => {}
         }
     }
 
-    if let Some(bg_rgb) = query_terminal_special_color(10) {
-        palette.background = Some(Color::Rgb(bg_rgb.0, bg_rgb.1, bg_rgb.2));
+    // OSC 10 is the default foreground, OSC 11 the default background
+    if let Some(fg_rgb) = query_terminal_special_color(10) {
+        palette.foreground = Some(Color::Rgb(fg_rgb.0, fg_rgb.1, fg_rgb.2));
+        answered = true;
     }
 
-    if let Some(fg_rgb) = query_terminal_special_color(11) {
-        palette.foreground = Some(Color::Rgb(fg_rgb.0, fg_rgb.1, fg_rgb.2));
+    if let Some(bg_rgb) = query_terminal_special_color(11) {
+        palette.background = Some(Color::Rgb(bg_rgb.0, bg_rgb.1, bg_rgb.2));
+        answered = true;
     }
 
-    Some(palette)
+    answered.then_some(palette)
 }
 
 /// Query the terminal's ANSI color for the given color index using OSC 4.
 ///
 /// Attempts to write an OSC 4 query for `color_index` to stdout, flush the output,
 /// and read a color reply. If the terminal responds with an RGB value the function
-/// returns `Some((r, g, b))`; otherwise it returns `None`.
+/// returns `Some((r, g, b))`; otherwise it returns `None`. Nothing is sent unless
+/// stdin and stdout are both a terminal.
 ///
 /// # Examples
 ///
@@ -361,6 +535,10 @@ pub fn query_terminal_color(color_index: usize) -> Option<(u8, u8, u8)> {
     query.extend_from_slice(format!("4;{};?", color_index).as_bytes());
     query.extend_from_slice(&[0x1b, b'\\']);
 
+    if !osc_queries_supported() {
+        return None;
+    }
+
     if io::stdout().write_all(&query).is_err() {
         return None;
     }
@@ -378,6 +556,10 @@ fn query_terminal_special_color(color_id: usize) -> Option<(u8, u8, u8)> {
     query.extend_from_slice(format!("{};?", color_id).as_bytes());
     query.extend_from_slice(&[0x1b, b'\\']);
 
+    if !osc_queries_supported() {
+        return None;
+    }
+
     if io::stdout().write_all(&query).is_err() {
         return None;
     }
@@ -391,8 +573,9 @@ fn query_terminal_special_color(color_id: usize) -> Option<(u8, u8, u8)> {
 
 /// Attempts to read an OSC color response from stdin and parse it into an RGB triple.
 ///
-/// Reads up to 1024 bytes from the standard input, interprets the bytes as an OSC color response,
-/// and returns the parsed `(r, g, b)` values if parsing succeeds.
+/// Waits at most 50ms for the terminal to reply, so terminals that don't support
+/// the query can't hang startup, and returns the parsed `(r, g, b)` values if
+/// parsing succeeds.
 ///
 /// # Returns
 ///
@@ -402,29 +585,71 @@ fn query_terminal_special_color(color_id: usize) -> Option<(u8, u8, u8)> {
 ///
 /// ```no_run
 /// use texty::ui::system_theme::read_osc_response;
-/// // Reads from the current process's stdin; may block if no data is available.
+/// // Reads from the current process's stdin; gives up if no reply arrives in time.
 /// if let Some((r, g, b)) = read_osc_response() {
 ///     println!("Detected color: #{:02X}{:02X}{:02X}", r, g, b);
 /// }
 /// ```
 pub fn read_osc_response() -> Option<(u8, u8, u8)> {
-    let stdin = io::stdin();
-    let mut stdin_handle = stdin.lock();
+    read_osc_response_timeout(OSC_QUERY_TIMEOUT)
+}
 
-    let mut buffer = [0u8; 1024];
+/// Read an OSC color response, giving up if none has arrived within `timeout`.
+///
+/// Returns `None` on timeout, when stdin is closed, or if the reply can't be parsed.
+pub fn read_osc_response_timeout(timeout: Duration) -> Option<(u8, u8, u8)> {
+    let deadline = Instant::now() + timeout;
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 256];
+
+    while !response.ends_with(b"\x07") && !response.ends_with(b"\x1b\\") {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let bytes_read = read_stdin_with_timeout(&mut chunk, remaining)?;
+        response.extend_from_slice(&chunk[..bytes_read]);
+    }
 
-    let bytes_read = stdin_handle.read(&mut buffer).ok()?;
-    let response = String::from_utf8_lossy(&buffer[..bytes_read]);
+    parse_osc_color_response(&String::from_utf8_lossy(&response))
+}
 
-    parse_osc_color_response(&response)
+/// Read whatever is available on stdin once it becomes readable.
+///
+/// Reads the file descriptor directly so no bytes are left behind in the
+/// buffered `io::stdin()` handle that the event loop reads later.
+#[cfg(unix)]
+fn read_stdin_with_timeout(buf: &mut [u8], timeout: Duration) -> Option<usize> {
+    let mut poll_fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+
+    // SAFETY: `poll_fd` is a valid pollfd for the duration of the call and
+    // `buf` is a writable buffer of the length passed to `read`.
+    unsafe {
+        if libc::poll(&mut poll_fd, 1, millis) <= 0 {
+            return None;
+        }
+        match libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) {
+            n if n > 0 => Some(n as usize),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn read_stdin_with_timeout(_buf: &mut [u8], _timeout: Duration) -> Option<usize> {
+    None
 }
 
 /// Extracts an RGB triplet from a terminal OSC color response string.
 ///
-/// Recognizes OSC color responses that begin with an OSC introducer (ESC `]` or ST `]`)
-/// and contain semicolon-separated fields including RGB components. Accepts numeric RGB
-/// values in decimal form and values prefixed with `rgb:` and/or terminated with `/`.
-/// Returns `Some((r, g, b))` when three valid 0–255 components are found, `None` otherwise.
+/// Recognizes OSC color responses that begin with an OSC introducer (ESC `]` or ST `]`),
+/// terminated by ST or BEL. Both palette replies (`4;<index>;<color>`) and special color
+/// replies (`10;<color>`, `11;<color>`) are accepted, where the color is either
+/// `rgb:<r>/<g>/<b>` with 1–4 hex digits per channel (as sent by real terminals, e.g.
+/// `rgb:ffff/8080/0000`) or three semicolon-separated decimal values.
+/// Returns `Some((r, g, b))` scaled to 8 bits per channel, `None` otherwise.
 ///
 /// # Examples
 ///
@@ -432,6 +657,8 @@ pub fn read_osc_response() -> Option<(u8, u8, u8)> {
 /// use texty::ui::system_theme::parse_osc_color_response;
 /// let resp = "\x1b]4;0;12;34;56\x1b\\";
 /// assert_eq!(parse_osc_color_response(resp), Some((12, 34, 56)));
+/// let resp = "\x1b]11;rgb:ffff/8080/0000\x07";
+/// assert_eq!(parse_osc_color_response(resp), Some((255, 128, 0)));
 /// ```
 pub fn parse_osc_color_response(response: &str) -> Option<(u8, u8, u8)> {
     let response = response.trim();
@@ -442,33 +669,49 @@ pub fn parse_osc_color_response(response: &str) -> Option<(u8, u8, u8)> {
     let prefix1 = format!("{}]", esc);
     let prefix2 = format!("{}]", st);
 
-    if response.starts_with(&prefix1) || response.starts_with(&prefix2) {
-        let mut response = response
-            .strip_prefix(&prefix1)
-            .or_else(|| response.strip_prefix(&prefix2))?;
+    let mut response = response
+        .strip_prefix(&prefix1)
+        .or_else(|| response.strip_prefix(&prefix2))?;
 
-        if let Some(stripped) = response.strip_suffix("\x1b\\") {
-            response = stripped;
-        }
+    if let Some(stripped) = response
+        .strip_suffix("\x1b\\")
+        .or_else(|| response.strip_suffix('\x07'))
+    {
+        response = stripped;
+    }
 
-        let parts: Vec<&str> = response.split(';').collect();
+    // Palette replies carry the color index before the color itself
+    let (id, mut color) = response.split_once(';')?;
+    if id == "4" {
+        color = color.split_once(';')?.1;
+    }
 
-        if parts.len() >= 3
-            && let (Some(r_str), Some(g_str), Some(b_str)) =
-                (parts.get(2), parts.get(3), parts.get(4))
-        {
-            let r = r_str
-                .trim_start_matches("rgb:")
-                .trim_end_matches('/')
-                .parse::<u8>()
-                .ok()?;
-            let g = g_str.trim_end_matches('/').parse::<u8>().ok()?;
-            let b = b_str.trim_end_matches('/').parse::<u8>().ok()?;
-            return Some((r, g, b));
+    let (components, hex) = match color.strip_prefix("rgb:") {
+        Some(rgb) => (rgb.split('/').collect::<Vec<_>>(), true),
+        None => (color.split(';').collect(), false),
+    };
+    let [r, g, b] = components.as_slice() else {
+        return None;
+    };
+
+    let parse_channel = |channel: &str| {
+        if hex {
+            parse_hex_channel(channel)
+        } else {
+            channel.parse::<u8>().ok()
         }
-    }
+    };
+    Some((parse_channel(r)?, parse_channel(g)?, parse_channel(b)?))
+}
 
-    None
+/// Parse an X11 color channel of 1–4 hex digits, scaled to 8 bits
+fn parse_hex_channel(channel: &str) -> Option<u8> {
+    if channel.is_empty() || channel.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(channel, 16).ok()?;
+    let max = (1u32 << (4 * channel.len())) - 1;
+    Some(((value * 255 + max / 2) / max) as u8)
 }
 
 /// Get appropriate colors based on system theme
@@ -592,3 +835,95 @@ impl ThemeColors {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_background_response() {
+        assert_eq!(
+            parse_osc_color_response("\x1b]11;rgb:1212/3434/5656\x1b\\"),
+            Some((18, 52, 86))
+        );
+        assert_eq!(
+            parse_osc_color_response("\x1b]10;200;201;202\x07"),
+            Some((200, 201, 202))
+        );
+        assert_eq!(parse_osc_color_response("\x1b]11;\x1b\\"), None);
+        assert_eq!(parse_osc_color_response("11;rgb:1/2/3"), None);
+    }
+
+    #[test]
+    fn test_parse_hex_components() {
+        assert_eq!(
+            parse_osc_color_response("\x1b]4;1;rgb:ffff/0000/0000\x1b\\"),
+            Some((255, 0, 0))
+        );
+        assert_eq!(
+            parse_osc_color_response("\x1b]4;0;12;34;56\x1b\\"),
+            Some((12, 34, 56))
+        );
+        // One to four hex digits per channel
+        assert_eq!(
+            parse_osc_color_response("\x1b]11;rgb:f/80/abc\x07"),
+            Some((255, 128, 171))
+        );
+        assert_eq!(parse_osc_color_response("\x1b]11;rgb:fffff/0/0\x07"), None);
+        assert_eq!(parse_osc_color_response("\x1b]11;rgb:zz/0/0\x07"), None);
+    }
+
+    #[test]
+    fn test_theme_for_background() {
+        assert_eq!(theme_for_background((255, 255, 255)), SystemTheme::Light);
+        assert_eq!(theme_for_background((0, 0, 0)), SystemTheme::Dark);
+        // Saturated blue is dark even though one channel is at full intensity
+        assert_eq!(theme_for_background((0, 0, 255)), SystemTheme::Dark);
+        assert_eq!(theme_for_background((238, 232, 213)), SystemTheme::Light);
+    }
+
+    #[test]
+    fn test_downsample_to_256() {
+        let downsample =
+            |r, g, b| downsample_color(Color::Rgb(r, g, b), TerminalCapability::Color256);
+        assert_eq!(downsample(0, 0, 0), Color::Indexed(16));
+        assert_eq!(downsample(255, 255, 255), Color::Indexed(231));
+        assert_eq!(downsample(255, 121, 198), Color::Indexed(212));
+        // Near-grays prefer the finer grayscale ramp
+        assert_eq!(downsample(40, 44, 52), Color::Indexed(236));
+        assert_eq!(downsample(128, 128, 128), Color::Indexed(244));
+    }
+
+    #[test]
+    fn test_downsample_to_16_and_no_color() {
+        let rgb = Color::Rgb(255, 85, 85);
+        assert_eq!(
+            downsample_color(rgb, TerminalCapability::Basic16),
+            Color::LightRed
+        );
+        assert_eq!(
+            downsample_color(rgb, TerminalCapability::NoColor),
+            Color::Reset
+        );
+        assert_eq!(
+            downsample_color(Color::Blue, TerminalCapability::Basic16),
+            Color::Blue
+        );
+        assert_eq!(
+            downsample_color(Color::Indexed(42), TerminalCapability::Color256),
+            Color::Indexed(42)
+        );
+    }
+
+    #[test]
+    fn test_background_query_skipped_in_tests() {
+        assert_eq!(query_terminal_background(), None);
+    }
+
+    #[test]
+    fn test_palette_query_falls_back_without_terminal() {
+        let start = Instant::now();
+        assert!(query_terminal_palette().is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -37,6 +37,12 @@ pub struct SyntaxTheme {
 pub struct UiTheme {
     pub status_bar_bg: Color,
     pub status_bar_fg: Color,
+    /// Mode indicator colors in insert mode; normal mode uses the status bar's
+    pub status_insert_bg: Color,
+    pub status_insert_fg: Color,
+    /// Mode indicator colors in visual mode
+    pub status_select_bg: Color,
+    pub status_select_fg: Color,
     pub gutter_fg: Color,
     pub cursor_bg: Color,
     pub cursor_fg: Color,
@@ -44,6 +50,9 @@ pub struct UiTheme {
     pub diagnostic_warning: Color,
     pub diagnostic_info: Color,
     pub diagnostic_hint: Color,
+    pub git_added: Color,
+    pub git_modified: Color,
+    pub git_removed: Color,
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +70,10 @@ pub struct EditorTheme {
     pub indent_guide: Color,
     pub whitespace: Color,
     pub invisible: Color,
+    /// Background of the `colorcolumn` rulers
+    pub ruler_bg: Color,
+    /// Inlay hints drawn between the characters of a line
+    pub inlay_hint: Color,
 }
 
 #[derive(Debug, Clone)]
@@ -135,6 +148,10 @@ impl Default for UiTheme {
         Self {
             status_bar_bg: Color::Blue,
             status_bar_fg: Color::White,
+            status_insert_bg: Color::Green,
+            status_insert_fg: Color::Black,
+            status_select_bg: Color::Magenta,
+            status_select_fg: Color::Black,
             gutter_fg: Color::DarkGray,
             cursor_bg: Color::Gray,
             cursor_fg: Color::Black,
@@ -142,6 +159,9 @@ impl Default for UiTheme {
             diagnostic_warning: Color::Yellow,
             diagnostic_info: Color::Blue,
             diagnostic_hint: Color::Cyan,
+            git_added: Color::Green,
+            git_modified: Color::Yellow,
+            git_removed: Color::Red,
         }
     }
 }
@@ -162,6 +182,8 @@ impl Default for EditorTheme {
             indent_guide: Color::Rgb(68, 71, 90),
             whitespace: Color::Rgb(68, 71, 90),
             invisible: Color::Rgb(68, 71, 90),
+            ruler_bg: Color::Rgb(40, 44, 52),
+            inlay_hint: Color::Rgb(110, 115, 135),
         }
     }
 }
@@ -254,13 +276,31 @@ impl Theme {
             indent_guide: Self::style_to_fg(&syntax_theme.get_editor_style("indent_guide")),
             whitespace: Self::style_to_fg(&syntax_theme.get_editor_style("whitespace")),
             invisible: Self::style_to_fg(&syntax_theme.get_editor_style("whitespace")),
+            ruler_bg: Self::ruler_color(syntax_theme),
+            inlay_hint: Self::inlay_hint_color(syntax_theme),
         }
     }
 
     fn extract_ui_theme(syntax_theme: &crate::syntax::Theme) -> UiTheme {
+        let normal = syntax_theme.get_status_style("normal");
+        // Modes the theme leaves unstyled look like normal mode
+        let mode_style = |mode: &str| {
+            let style = syntax_theme.get_status_style(mode);
+            if style.fg.is_none() && style.bg.is_none() {
+                normal
+            } else {
+                style
+            }
+        };
+        let insert = mode_style("insert");
+        let select = mode_style("select");
         UiTheme {
-            status_bar_bg: Self::style_to_bg(&syntax_theme.get_status_style("normal")),
-            status_bar_fg: Self::style_to_fg(&syntax_theme.get_status_style("normal")),
+            status_bar_bg: Self::style_to_bg(&normal),
+            status_bar_fg: Self::style_to_fg(&normal),
+            status_insert_bg: Self::style_to_bg(&insert),
+            status_insert_fg: Self::style_to_fg(&insert),
+            status_select_bg: Self::style_to_bg(&select),
+            status_select_fg: Self::style_to_fg(&select),
             gutter_fg: Self::style_to_fg(&syntax_theme.get_editor_style("line_number")),
             cursor_bg: Self::style_to_bg(&syntax_theme.get_editor_style("cursor")),
             cursor_fg: Self::style_to_fg(&syntax_theme.get_editor_style("cursor")),
@@ -268,9 +308,45 @@ impl Theme {
             diagnostic_warning: Color::Yellow,
             diagnostic_info: Color::Blue,
             diagnostic_hint: Color::Cyan,
+            git_added: Self::diff_gutter_color(syntax_theme, "diff.plus.gutter", Color::Green),
+            git_modified: Self::diff_gutter_color(syntax_theme, "diff.delta.gutter", Color::Yellow),
+            git_removed: Self::diff_gutter_color(syntax_theme, "diff.minus.gutter", Color::Red),
         }
     }
 
+    /// Background of the `ruler` editor style, falling back to the current
+    /// line's as both mark out a stripe of the text
+    fn ruler_color(syntax_theme: &crate::syntax::Theme) -> Color {
+        ["ruler", "current_line"]
+            .iter()
+            .find_map(|key| syntax_theme.get_editor_style(key).bg)
+            .map(|c| Color::Rgb(c.r, c.g, c.b))
+            .unwrap_or(EditorTheme::default().ruler_bg)
+    }
+
+    /// Foreground of the `ui.virtual.inlay-hint` scope, falling back to
+    /// comments' as both are text to read past
+    fn inlay_hint_color(syntax_theme: &crate::syntax::Theme) -> Color {
+        ["ui.virtual.inlay-hint", "comment"]
+            .iter()
+            .find_map(|scope| syntax_theme.get_style(scope).fg)
+            .map(|c| Color::Rgb(c.r, c.g, c.b))
+            .unwrap_or(EditorTheme::default().inlay_hint)
+    }
+
+    /// Foreground of a `diff.*.gutter` scope, or `fallback` if the theme lacks it
+    fn diff_gutter_color(
+        syntax_theme: &crate::syntax::Theme,
+        scope: &str,
+        fallback: Color,
+    ) -> Color {
+        syntax_theme
+            .get_style(scope)
+            .fg
+            .map(|c| Color::Rgb(c.r, c.g, c.b))
+            .unwrap_or(fallback)
+    }
+
     fn extract_popup_theme(syntax_theme: &crate::syntax::Theme) -> PopupTheme {
         PopupTheme {
             background: Self::style_to_bg(&syntax_theme.get_popup_style("background")),
@@ -1,6 +1,7 @@
 // ui/widgets/editor_pane.rs - Editor pane widget
 
 use lsp_types::DiagnosticSeverity;
+use std::borrow::Cow;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
@@ -58,6 +59,7 @@ impl Widget for EditorPane<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         self.clear_editor_area(area, buf);
         self.render_content(area, buf);
+        self.render_color_columns(area, buf);
         self.render_cursor(area, buf);
     }
 }
@@ -70,169 +72,137 @@ impl EditorPane<'_> {
     }
 
     fn render_content(&self, area: Rect, buf: &mut Buffer) {
-        for i in 0..area.height as usize {
-            let line_idx = self.editor.viewport.offset_line + i;
-            if let Some(line) = self.editor.buffer.line(line_idx) {
-                let visible_line = line
-                    .chars()
-                    .skip(self.editor.viewport.offset_col)
-                    .collect::<String>();
-
-                // Get diagnostics for this line
-                let line_diagnostics = self.get_line_diagnostics(line_idx);
-
-                if let Some(highlights) = self
-                    .editor
-                    .buffer
-                    .highlighter
-                    .as_ref()
-                    .and_then(|h| h.get_line_highlights(line_idx))
-                {
-                    self.render_highlighted_line(
-                        line_idx,
-                        &visible_line,
-                        &line_diagnostics,
-                        highlights,
-                        area,
-                        i,
-                        buf,
-                    );
-                } else {
-                    self.render_plain_line(&visible_line, area, i, buf);
-                }
-            } else {
-                self.render_empty_line(area, i, buf);
-            }
+        let first_line = self.editor.viewport.offset_line;
+        let mut rows = 0;
+        for (line_idx, line) in self
+            .editor
+            .buffer
+            .visible_lines(first_line, area.height as usize)
+        {
+            self.render_line(line_idx, &line, area, line_idx - first_line, buf);
+            rows += 1;
+        }
+        for i in rows..area.height as usize {
+            self.render_empty_line(area, i, buf);
         }
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn render_highlighted_line(
-        &self,
-        line_idx: usize,
-        visible_line: &str,
-        line_diagnostics: &[lsp_types::Diagnostic],
-        highlights: &[crate::syntax::HighlightToken],
-        area: Rect,
-        line_offset: usize,
-        buf: &mut Buffer,
-    ) {
-        let mut spans = Vec::new();
-        let mut pos = 0;
-
-        // Combine syntax highlights with diagnostic highlights
-        let mut highlight_ranges = Vec::new();
-
-        // Add syntax highlights
-        let line_text = self.editor.buffer.line(line_idx).unwrap();
-        let line_start_byte = self.editor.buffer.rope.line_to_byte(line_idx);
+    /// Style for each character of the line: syntax highlights first, then
+    /// diagnostics on top
+    fn line_styles(&self, line_idx: usize, line: &str) -> Vec<Style> {
+        let default_style = Style::default().fg(self.theme.general.foreground);
+        let mut styles = vec![default_style; line.chars().count()];
 
-        for token in highlights {
-            let rel_byte_start = token.start - line_start_byte;
-            let rel_byte_end = token.end - line_start_byte;
-
-            // Calculate character positions with bounds checking
-            let char_start = line_text
-                .char_indices()
-                .find(|(byte_idx, _)| *byte_idx == rel_byte_start)
-                .map(|(idx, _)| idx)
-                .unwrap_or(line_text.len());
-            let char_end = line_text
-                .char_indices()
-                .find(|(byte_idx, _)| *byte_idx == rel_byte_end)
-                .map(|(idx, _)| idx)
-                .unwrap_or(line_text.len());
-
-            if char_start < self.editor.viewport.offset_col + visible_line.len()
-                && char_end > self.editor.viewport.offset_col
-            {
-                let start = char_start.saturating_sub(self.editor.viewport.offset_col);
-                let end = char_end
-                    .min(self.editor.viewport.offset_col + visible_line.len())
-                    .saturating_sub(self.editor.viewport.offset_col);
+        if let Some(highlights) = self
+            .editor
+            .buffer
+            .highlighter
+            .as_ref()
+            .and_then(|h| h.get_line_highlights(line_idx))
+        {
+            let line_start_byte = self.editor.buffer.rope.line_to_byte(line_idx);
+            let mut styled = vec![false; styles.len()];
 
-                // Use Helix-compatible theme if loaded, otherwise use fallback
+            // Tokens are sorted by start; the outermost (earliest) one wins
+            for token in highlights {
                 let style = if let Some(ref loaded_theme) = self.theme.loaded_syntax_theme {
                     loaded_theme
                         .get_style(&token.capture_name)
                         .to_ratatui_style()
                 } else {
-                    ratatui::style::Style::default()
-                        .fg(self.theme.syntax_color(&token.capture_name))
+                    Style::default().fg(self.theme.syntax_color(&token.capture_name))
                 };
-
-                highlight_ranges.push((start, end, style));
+                let bytes = token.start.saturating_sub(line_start_byte)
+                    ..token.end.saturating_sub(line_start_byte);
+                for (idx, (byte, _)) in line.char_indices().enumerate() {
+                    if bytes.contains(&byte) && !styled[idx] {
+                        styles[idx] = style;
+                        styled[idx] = true;
+                    }
+                }
             }
         }
 
-        // Add diagnostic highlights (these take precedence)
-        for diag in line_diagnostics {
-            let start_char = diag.range.start.character as usize;
-            let end_char = diag.range.end.character as usize;
-
-            if start_char >= self.editor.viewport.offset_col
-                && start_char < self.editor.viewport.offset_col + visible_line.len()
-            {
-                let start = start_char.saturating_sub(self.editor.viewport.offset_col);
-                let end = end_char
-                    .min(self.editor.viewport.offset_col + visible_line.len())
-                    .saturating_sub(self.editor.viewport.offset_col);
-
-                let diag_style = self.diagnostic_style(&diag.severity);
-                highlight_ranges.push((start, end, diag_style));
+        // Diagnostics take precedence over syntax highlighting
+        for diag in self.get_line_diagnostics(line_idx) {
+            let start = diag.range.start.character as usize;
+            let end = if diag.range.end.line == diag.range.start.line {
+                diag.range.end.character as usize
+            } else {
+                styles.len()
+            };
+            let diag_style = self.diagnostic_style(&diag.severity);
+            for style in styles.iter_mut().take(end).skip(start) {
+                *style = diag_style;
             }
         }
 
-        // Sort ranges by start position and merge overlapping
-        highlight_ranges.sort_by_key(|(start, _, _)| *start);
-        let mut merged_ranges: Vec<(usize, usize, ratatui::style::Style)> = Vec::new();
-        for (start, end, style) in highlight_ranges {
-            if let Some((_, last_end, _)) = merged_ranges.last_mut()
-                && *last_end >= start
-            {
-                *last_end = (*last_end).max(end);
-                continue;
-            }
-            merged_ranges.push((start, end, style));
-        }
+        styles
+    }
 
-        // Build spans from merged ranges
-        for (start, end, style) in merged_ranges {
-            if start > pos {
-                let start_idx = start.min(visible_line.len());
-                spans.push(Span::styled(
-                    visible_line[pos..start_idx].to_string(),
-                    Style::default().fg(self.theme.general.foreground),
-                ));
-            }
-            let end_idx = end.min(visible_line.len());
-            let clamped_start = start.min(end_idx);
-            spans.push(Span::styled(
-                visible_line[clamped_start..end_idx].to_string(),
-                style,
-            ));
-            pos = end;
-        }
+    /// Draw the visible part of a line, expanding tabs and laying out wide
+    /// characters by their display width
+    fn render_line(
+        &self,
+        line_idx: usize,
+        line: &str,
+        area: Rect,
+        line_offset: usize,
+        buf: &mut Buffer,
+    ) {
+        let styles = self.line_styles(line_idx, line);
+        let hints = self.editor.inlay_hints.line(line_idx);
+        let (line, styles) = if hints.is_empty() {
+            (Cow::Borrowed(line), styles)
+        } else {
+            // Hints are drawn in their own style between the buffer's characters
+            let (drawn, is_buffer) = crate::display::with_virtual_text(line, hints);
+            let hint_style = Style::default().fg(self.theme.editor.inlay_hint);
+            let mut buffer_styles = styles.into_iter();
+            let styles = is_buffer
+                .iter()
+                .map(|&is_buffer| {
+                    if is_buffer {
+                        buffer_styles.next().unwrap_or(hint_style)
+                    } else {
+                        hint_style
+                    }
+                })
+                .collect();
+            (Cow::Owned(drawn), styles)
+        };
 
-        if pos < visible_line.len() {
-            spans.push(Span::styled(
-                visible_line[pos..].to_string(),
-                Style::default().fg(self.theme.general.foreground),
-            ));
+        // Group consecutive cells of the same style into spans
+        let mut spans: Vec<Span> = Vec::new();
+        let mut current: Option<(Style, String)> = None;
+        crate::display::layout_visible(
+            &line,
+            self.editor.viewport.offset_col,
+            area.width as usize,
+            self.editor.options.tab_width,
+            |char_idx, _, glyph| {
+                let style = styles[char_idx];
+                match &mut current {
+                    Some((current_style, text)) if *current_style == style => glyph.push_to(text),
+                    _ => {
+                        if let Some((style, text)) = current.take() {
+                            spans.push(Span::styled(text, style));
+                        }
+                        let mut text = String::new();
+                        glyph.push_to(&mut text);
+                        current = Some((style, text));
+                    }
+                }
+            },
+        );
+        if let Some((style, text)) = current {
+            spans.push(Span::styled(text, style));
         }
 
         let line_widget = Line::from(spans);
         buf.set_line(area.x, area.y + line_offset as u16, &line_widget, area.width);
     }
 
-    fn render_plain_line(&self, visible_line: &str, area: Rect, line_offset: usize, buf: &mut Buffer) {
-        let line_widget = Line::from(vec![Span::styled(
-            visible_line,
-            Style::default().fg(self.theme.general.foreground),
-        )]);
-        buf.set_line(area.x, area.y + line_offset as u16, &line_widget, area.width);
-    }
-
     fn render_empty_line(&self, area: Rect, line_offset: usize, buf: &mut Buffer) {
         let line_widget = Line::from(vec![Span::styled(
             "~",
@@ -241,6 +211,30 @@ impl EditorPane<'_> {
         buf.set_line(area.x, area.y + line_offset as u16, &line_widget, area.width);
     }
 
+    /// Shade the `colorcolumn` display columns on the buffer's lines. Only
+    /// the background changes, so the text and cursor drawn over them keep
+    /// their colors.
+    fn render_color_columns(&self, area: Rect, buf: &mut Buffer) {
+        let rows = self
+            .editor
+            .buffer
+            .line_count()
+            .saturating_sub(self.editor.viewport.offset_line)
+            .min(area.height as usize);
+        for &column in &self.editor.options.color_columns {
+            let Some(col) = (column - 1).checked_sub(self.editor.viewport.offset_col) else {
+                continue;
+            };
+            if col >= area.width as usize {
+                continue;
+            }
+            for row in 0..rows {
+                buf.get_mut(area.x + col as u16, area.y + row as u16)
+                    .set_bg(self.theme.editor.ruler_bg);
+            }
+        }
+    }
+
     fn render_cursor(&self, area: Rect, buf: &mut Buffer) {
         let cursor_row = self
             .editor
@@ -249,13 +243,26 @@ impl EditorPane<'_> {
             .saturating_sub(self.editor.viewport.offset_line) as u16;
         let cursor_col = self
             .editor
-            .cursor
-            .col
+            .cursor_display_col()
             .saturating_sub(self.editor.viewport.offset_col) as u16;
 
         if cursor_row < area.height && cursor_col < area.width {
             buf.get_mut(area.x + cursor_col, area.y + cursor_row)
                 .set_style(Style::default().bg(self.theme.ui.cursor_bg).fg(self.theme.ui.cursor_fg));
         }
+
+        // Extra cursors from multi-cursor editing
+        for cursor in &self.editor.cursors {
+            let col = self.editor.display_col_at(cursor.line, cursor.col);
+            let row = cursor.line.checked_sub(self.editor.viewport.offset_line);
+            let col = col.checked_sub(self.editor.viewport.offset_col);
+            if let (Some(row), Some(col)) = (row, col)
+                && row < area.height as usize
+                && col < area.width as usize
+            {
+                buf.get_mut(area.x + col as u16, area.y + row as u16)
+                    .set_style(Style::default().bg(self.theme.ui.cursor_bg).fg(self.theme.ui.cursor_fg));
+            }
+        }
     }
 }
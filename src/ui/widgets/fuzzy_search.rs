@@ -1,4 +1,4 @@
-use crate::fuzzy_search::FuzzySearchState;
+use crate::fuzzy_search::{FuzzySearchState, SymbolSearch};
 use crate::ui::theme::Theme;
 use crate::ui::widgets::preview::render_preview_content;
 
@@ -62,7 +62,7 @@ impl<'a> Widget for FuzzySearchWidget<'a> {
     /// // let mut buf = Buffer::empty(Rect::new(0,0,100,30));
     /// // widget.render(Rect::new(0,0,100,30), &mut buf);
     /// ```
-    fn render(self, area: Rect, buf: &mut Buffer) {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
         let block = Block::default().style(Style::default().bg(self.theme.ui.status_bar_bg));
         let inner_area = block.inner(area);
 
@@ -103,14 +103,23 @@ impl<'a> FuzzySearchWidget<'a> {
         };
         let result_display = binding.as_str();
 
-        let mode_title = if self.state.recursive_search && self.state.follow_gitignore {
-            String::from("Search[R][G]:")
-        } else if self.state.recursive_search {
-            String::from("Search[R]:")
-        } else if self.state.follow_gitignore {
-            String::from("Search[G]:")
+        let mode_title = if self.state.symbols.is_some() {
+            String::from("Symbols:")
+        } else if self.state.recent_files.is_some() {
+            String::from("Recent:")
         } else {
-            String::from("Search:")
+            let mut title = String::from("Search");
+            for (flag, on) in [
+                ("[R]", self.state.recursive_search),
+                ("[G]", self.state.follow_gitignore),
+                ("[H]", self.state.show_hidden),
+            ] {
+                if on {
+                    title.push_str(flag);
+                }
+            }
+            title.push(':');
+            title
         };
 
         let result_title = if !self.state.query.is_empty() {
@@ -135,6 +144,10 @@ impl<'a> FuzzySearchWidget<'a> {
     }
 
     fn render_file_list(&self, area: Rect, buf: &mut Buffer) {
+        if let Some(symbols) = &self.state.symbols {
+            self.render_symbol_list(symbols, area, buf);
+            return;
+        }
         let file_list_area = area;
 
         let file_list_block = Block::default().borders(Borders::NONE).title("Files");
@@ -303,7 +316,60 @@ impl<'a> FuzzySearchWidget<'a> {
         file_list_paragraph.render(file_list_area, buf);
     }
 
-    fn render_preview(&self, area: Rect, buf: &mut Buffer) {
+    /// Symbols as `name kind in container  path:line`
+    fn render_symbol_list(&self, symbols: &SymbolSearch, area: Rect, buf: &mut Buffer) {
+        let dim = Style::default()
+            .fg(self.theme.general.foreground)
+            .add_modifier(Modifier::DIM);
+        let visible = area.height as usize;
+        let first = (self.state.selected_index + 1).saturating_sub(visible);
+
+        let lines: Vec<Line> = symbols
+            .filtered
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible)
+            .map(|(i, symbol)| {
+                let path = symbol
+                    .path
+                    .strip_prefix(&self.state.current_path)
+                    .unwrap_or(&symbol.path);
+                let mut spans = vec![
+                    Span::styled(
+                        symbol.name.clone(),
+                        Style::default().fg(self.theme.popup.foreground),
+                    ),
+                    Span::styled(format!(" {}", symbol.kind), Style::default().fg(self.theme.syntax.r#type)),
+                ];
+                if let Some(container) = &symbol.container {
+                    spans.push(Span::styled(format!(" in {}", container), dim));
+                }
+                spans.push(Span::styled(
+                    format!("  {}:{}", path.display(), symbol.line + 1),
+                    dim,
+                ));
+
+                let line = Line::from(spans);
+                if i == self.state.selected_index {
+                    line.style(
+                        Style::default()
+                            .bg(self.theme.popup.highlight_bg)
+                            .fg(self.theme.popup.highlight_fg)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    line
+                }
+            })
+            .collect();
+
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::NONE).title("Symbols"))
+            .render(area, buf);
+    }
+
+    fn render_preview(&mut self, area: Rect, buf: &mut Buffer) {
         if let Some(_preview_buffer) = &self.state.current_preview {
             let preview_block = Block::default()
                 .borders(Borders::ALL)
@@ -313,7 +379,10 @@ impl<'a> FuzzySearchWidget<'a> {
             let inner_area = preview_block.inner(area);
             preview_block.render(area, buf);
 
-            if let Some(preview_buffer) = &self.state.current_preview {
+            if let Some(preview_buffer) = &mut self.state.current_preview {
+                // Only the visible part of the file is highlighted
+                preview_buffer
+                    .ensure_highlighted(preview_buffer.top_line, inner_area.height as usize);
                 let preview_paragraph =
                     render_preview_content(preview_buffer, self.theme, inner_area);
                 preview_paragraph.render(inner_area, buf);
@@ -1,4 +1,4 @@
-// ui/widgets/gutter.rs - Gutter widget for line numbers and diagnostics
+// ui/widgets/gutter.rs - Gutter widget for git signs, line numbers and diagnostics
 
 use lsp_types::DiagnosticSeverity;
 use ratatui::{
@@ -10,15 +10,19 @@ use ratatui::{
 };
 
 use crate::editor::Editor;
+use crate::git::LineChange;
 use crate::ui::theme::Theme;
 
-/// Gutter widget that shows line numbers and diagnostic indicators
+/// Gutter widget that shows git change signs, line numbers and diagnostic indicators
 pub struct Gutter<'a> {
     pub editor: &'a Editor,
     pub theme: &'a Theme,
 }
 
 impl<'a> Gutter<'a> {
+    /// Columns taken: a git sign, three for the line number and a diagnostic
+    pub const WIDTH: u16 = 5;
+
     pub fn new(editor: &'a Editor, theme: &'a Theme) -> Self {
         Self { editor, theme }
     }
@@ -33,16 +37,27 @@ impl Widget for Gutter<'_> {
             // Get diagnostics for this line
             let diagnostic_symbol = self.get_diagnostic_symbol(line_idx as u32);
 
-            let text = if self.editor.buffer.line(line_idx).is_some() {
-                format!("{:>3}{}{}", line_number, diagnostic_symbol, " ")
+            let text = if line_idx < self.editor.buffer.line_count() {
+                format!("{:>3}{}", line_number, diagnostic_symbol)
             } else {
-                format!("    {}", diagnostic_symbol)
+                format!("   {}", diagnostic_symbol)
             };
 
-            let line_widget = Line::from(Span::styled(
-                text,
-                Style::default().fg(self.theme.ui.gutter_fg),
-            ));
+            let sign = match self.editor.git_signs.sign(line_idx) {
+                Some(change) => {
+                    let color = match change {
+                        LineChange::Added => self.theme.ui.git_added,
+                        LineChange::Modified => self.theme.ui.git_modified,
+                        LineChange::Removed => self.theme.ui.git_removed,
+                    };
+                    Span::styled(change.sign().to_string(), Style::default().fg(color))
+                }
+                None => Span::raw(" "),
+            };
+            let line_widget = Line::from(vec![
+                sign,
+                Span::styled(text, Style::default().fg(self.theme.ui.gutter_fg)),
+            ]);
 
             buf.set_line(area.x, area.y + i as u16, &line_widget, area.width);
         }
@@ -85,9 +100,9 @@ impl Gutter<'_> {
                 }
             }
 
-            most_severe.unwrap_or("")
+            most_severe.unwrap_or(" ")
         } else {
-            ""
+            " "
         }
     }
 }
@@ -9,16 +9,70 @@ use ratatui::{
 };
 
 use crate::ui::theme::Theme;
+use lsp_types::{HoverContents, MarkedString};
+
+/// Lines of hover text for the window. Markdown code fences are dropped;
+/// the code inside them is kept.
+pub fn hover_lines(contents: &HoverContents) -> Vec<String> {
+    let marked = |marked: &MarkedString| match marked {
+        MarkedString::String(text) => text.clone(),
+        MarkedString::LanguageString(code) => code.value.clone(),
+    };
+    let text = match contents {
+        HoverContents::Scalar(text) => marked(text),
+        HoverContents::Array(texts) => texts.iter().map(marked).collect::<Vec<_>>().join("\n\n"),
+        HoverContents::Markup(markup) => markup.value.clone(),
+    };
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(str::to_string)
+        .collect();
+    // Nothing but blank lines means there is nothing to show
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return Vec::new();
+    }
+    lines
+}
 
 /// Hover information window that displays LSP hover content
 pub struct HoverWindow<'a> {
     pub content: Vec<String>,
     pub theme: &'a Theme,
+    pub title: &'a str,
+    /// Show the last lines rather than the first when they don't all fit
+    pub follow_end: bool,
+    /// Which lines to draw in the error color, by index
+    pub errors: &'a [bool],
 }
 
 impl<'a> HoverWindow<'a> {
     pub fn new(content: Vec<String>, theme: &'a Theme) -> Self {
-        Self { content, theme }
+        Self {
+            content,
+            theme,
+            title: " Hover ",
+            follow_end: false,
+            errors: &[],
+        }
+    }
+
+    /// Use the window for other read-only listings
+    pub fn with_title(mut self, title: &'a str) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Mark the lines that report errors, as `:messages` does
+    pub fn with_errors(mut self, errors: &'a [bool]) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Keep the end of long content in view, for logs
+    pub fn scrolled_to_end(mut self) -> Self {
+        self.follow_end = true;
+        self
     }
 
     /// Calculate the position for the hover window relative to cursor
@@ -55,11 +109,14 @@ impl Widget for HoverWindow<'_> {
         let lines: Vec<Line> = self
             .content
             .iter()
-            .map(|line| {
-                Line::from(vec![Span::styled(
-                    line.clone(),
-                    Style::default().fg(self.theme.general.foreground),
-                )])
+            .enumerate()
+            .map(|(i, line)| {
+                let color = if self.errors.get(i) == Some(&true) {
+                    self.theme.ui.diagnostic_error
+                } else {
+                    self.theme.general.foreground
+                };
+                Line::from(vec![Span::styled(line.clone(), Style::default().fg(color))])
             })
             .collect();
 
@@ -67,7 +124,7 @@ impl Widget for HoverWindow<'_> {
         let block = Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(self.theme.ui.gutter_fg))
-            .title(" Hover ")
+            .title(self.title)
             .title_style(
                 Style::default()
                     .fg(self.theme.syntax.comment)
@@ -76,11 +133,64 @@ impl Widget for HoverWindow<'_> {
             .padding(Padding::horizontal(1));
 
         // Create the paragraph widget
-        let paragraph = Paragraph::new(lines)
+        let mut paragraph = Paragraph::new(lines)
             .block(block)
             .alignment(Alignment::Left);
+        if self.follow_end {
+            let visible = area.height.saturating_sub(2) as usize;
+            let hidden = self.content.len().saturating_sub(visible);
+            paragraph = paragraph.scroll((hidden.min(u16::MAX as usize) as u16, 0));
+        }
 
         // Render the paragraph
         paragraph.render(area, buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{LanguageString, MarkupContent, MarkupKind};
+
+    #[test]
+    fn test_hover_lines() {
+        let markup = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```rust\nfn main()\n```\nEntry point".to_string(),
+        });
+        assert_eq!(hover_lines(&markup), ["fn main()", "Entry point"]);
+
+        let array = HoverContents::Array(vec![
+            MarkedString::LanguageString(LanguageString {
+                language: "python".to_string(),
+                value: "def f()".to_string(),
+            }),
+            MarkedString::String("Docs".to_string()),
+        ]);
+        assert_eq!(hover_lines(&array), ["def f()", "", "Docs"]);
+
+        let empty = HoverContents::Scalar(MarkedString::String("\n".to_string()));
+        assert!(hover_lines(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_scrolled_to_end_shows_last_lines() {
+        let theme = Theme::default();
+        let content: Vec<String> = (1..=5).map(|i| format!("line {}", i)).collect();
+        let area = Rect::new(0, 0, 20, 4);
+        let row = |buf: &Buffer, y: u16| -> String {
+            (1..area.width - 1).map(|x| buf.get(x, y).symbol()).collect::<String>()
+        };
+
+        let mut buf = Buffer::empty(area);
+        HoverWindow::new(content.clone(), &theme).render(area, &mut buf);
+        assert_eq!(row(&buf, 1).trim(), "line 1");
+
+        let mut buf = Buffer::empty(area);
+        HoverWindow::new(content, &theme)
+            .scrolled_to_end()
+            .render(area, &mut buf);
+        assert_eq!(row(&buf, 1).trim(), "line 4");
+        assert_eq!(row(&buf, 2).trim(), "line 5");
+    }
+}
@@ -7,4 +7,5 @@ pub mod gutter;
 pub mod hover;
 pub mod menu;
 pub mod preview;
+pub mod quickfix;
 pub mod status_bar;
@@ -54,6 +54,8 @@ pub struct PreviewBuffer {
     pub language: Option<LanguageId>,
     pub syntax_highlights: Option<Vec<crate::syntax::HighlightToken>>,
     pub highlight_progress: HighlightProgress,
+    /// First line shown in the preview pane
+    pub top_line: usize,
 }
 
 impl PreviewBuffer {
@@ -76,6 +78,7 @@ impl PreviewBuffer {
             language,
             syntax_highlights: None,
             highlight_progress: HighlightProgress::new(),
+            top_line: 0,
         })
     }
 
@@ -86,6 +89,9 @@ impl PreviewBuffer {
 
         let total_lines = self.content.lines().count();
         let end_line = (start_line + line_count).min(total_lines);
+        if (start_line..end_line).all(|line| self.highlight_progress.is_line_highlighted(line)) {
+            return;
+        }
 
         if let Some(lang) = self.language {
             let config = get_language_config(lang);
@@ -96,6 +102,7 @@ impl PreviewBuffer {
                     self.syntax_highlights = Some(Vec::new());
                 }
 
+                highlighter.highlight_range(start_line..end_line);
                 if let Some(highlights) = &mut self.syntax_highlights {
                     for line_idx in start_line..end_line {
                         if !self.highlight_progress.is_line_highlighted(line_idx)
@@ -104,6 +111,7 @@ impl PreviewBuffer {
                             highlights.extend(line_highlights.iter().cloned());
                         }
                     }
+                    highlights.sort_by_key(|token| token.start);
                 }
             }
         } else {
@@ -156,95 +164,125 @@ impl Default for PreviewCache {
     }
 }
 
-/// Render preview buffer content using editor-style highlighting
+/// Render the lines of the preview buffer visible from `top_line`, styled the
+/// way the editor styles them
 pub fn render_preview_content(
     preview_buffer: &PreviewBuffer,
     theme: &Theme,
     area: Rect,
 ) -> Paragraph<'static> {
-    let lines: Vec<Line> = preview_buffer
-        .content
-        .lines()
-        .enumerate()
-        .take(area.height as usize) // Limit to visible area
-        .map(|(line_idx, line_content)| {
-            let line_highlights: Vec<&crate::syntax::HighlightToken> = preview_buffer
-                .syntax_highlights
-                .as_ref()
-                .map(|highlights| {
-                    highlights
-                        .iter()
-                        .filter(|h| {
-                            let before_highlight = &preview_buffer.content[..h.start];
-                            let highlight_line =
-                                before_highlight.chars().filter(|&c| c == '\n').count();
-                            highlight_line == line_idx
-                        })
-                        .collect()
-                })
-                .unwrap_or_default();
-
-            if !line_highlights.is_empty() {
-                // Apply syntax highlighting like the editor does
-                let mut spans = Vec::new();
-                let mut pos = 0;
-
-                for highlight in line_highlights {
-                    // Calculate positions within the line
-                    let line_start_byte = preview_buffer
-                        .content
-                        .lines()
-                        .take(line_idx)
-                        .map(|l| l.len() + 1)
-                        .sum();
-
-                    let relative_start = highlight.start.saturating_sub(line_start_byte);
-                    let relative_end = highlight.end.saturating_sub(line_start_byte);
-
-                    if relative_start >= line_content.len() {
-                        continue; // Highlight is beyond this line
-                    }
+    let default_style = Style::default().fg(theme.general.foreground);
+    let tokens = preview_buffer.syntax_highlights.as_deref().unwrap_or(&[]);
+
+    let mut line_start = 0;
+    let mut lines = Vec::new();
+    for (line_idx, line_content) in preview_buffer.content.split('\n').enumerate() {
+        let start = line_start;
+        line_start += line_content.len() + 1;
+        if line_idx < preview_buffer.top_line {
+            continue;
+        }
+        if lines.len() == area.height as usize {
+            break;
+        }
 
-                    let actual_end = relative_end.min(line_content.len());
+        // Tokens are sorted by start; the outermost (earliest) one wins, and
+        // multi-line tokens such as block comments cover every line they span
+        let mut styles: Vec<Option<Style>> = vec![None; line_content.chars().count()];
+        for token in tokens
+            .iter()
+            .take_while(|token| token.start < start + line_content.len())
+            .filter(|token| token.end > start)
+        {
+            let style = match &theme.loaded_syntax_theme {
+                Some(loaded_theme) => loaded_theme
+                    .get_style(&token.capture_name)
+                    .to_ratatui_style(),
+                None => Style::default().fg(theme.syntax_color(&token.capture_name)),
+            };
+            for (idx, (byte, _)) in line_content.char_indices().enumerate() {
+                if (token.start..token.end).contains(&(start + byte)) && styles[idx].is_none() {
+                    styles[idx] = Some(style);
+                }
+            }
+        }
 
-                    // Add unhighlighted text before highlight
-                    if relative_start > pos {
-                        let before_text = line_content[pos..relative_start].to_string();
-                        spans.push(Span::styled(
-                            before_text,
-                            Style::default().fg(theme.general.foreground),
-                        ));
-                    }
+        // Group runs of equally styled cells into spans
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        let mut run = String::new();
+        let mut run_style = default_style;
+        for cell in crate::display::visible_cells(line_content, 0, area.width as usize, 4) {
+            let style = styles[cell.char_idx].unwrap_or(default_style);
+            if style != run_style && !run.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut run), run_style));
+            }
+            run_style = style;
+            run.push_str(&cell.text);
+        }
+        if !run.is_empty() {
+            spans.push(Span::styled(run, run_style));
+        }
+        lines.push(Line::from(spans));
+    }
 
-                    // Add highlighted text
-                    if actual_end > relative_start {
-                        let highlight_text = line_content[relative_start..actual_end].to_string();
-                        let color = theme.syntax_color(&highlight.capture_name);
-                        spans.push(Span::styled(highlight_text, Style::default().fg(color)));
-                    }
+    Paragraph::new(lines)
+}
 
-                    pos = actual_end;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::buffer::Buffer;
+    use ratatui::widgets::Widget;
+
+    fn rendered_rows(preview: &PreviewBuffer, width: u16, height: u16) -> Vec<String> {
+        let area = Rect::new(0, 0, width, height);
+        let mut buf = Buffer::empty(area);
+        render_preview_content(preview, &Theme::default(), area).render(area, &mut buf);
+        (0..height)
+            .map(|y| {
+                let row: String = (0..width)
+                    .map(|x| buf.get(x, y).symbol().to_string())
+                    .collect();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
 
-                // Add remaining unhighlighted text
-                if pos < line_content.len() {
-                    let remaining = line_content[pos..].to_string();
-                    spans.push(Span::styled(
-                        remaining,
-                        Style::default().fg(theme.general.foreground),
-                    ));
-                }
+    #[test]
+    fn test_overlapping_tokens_render_text_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "fn main() {\n\tlet x = 1; /* a\nb */\n}\n").unwrap();
+        let mut preview = PreviewBuffer::load_from_file(&path).unwrap();
+        preview.ensure_highlighted(0, 10);
+
+        let theme = Theme::default();
+        let area = Rect::new(0, 0, 30, 5);
+        let mut buf = Buffer::empty(area);
+        render_preview_content(&preview, &theme, area).render(area, &mut buf);
+        // Tabs are expanded, so `1` is drawn at column 12
+        assert_eq!(
+            buf.get(12, 1).fg,
+            theme.syntax_color("constant.numeric.integer")
+        );
+        assert_eq!(buf.get(15, 1).fg, theme.syntax_color("comment"));
+        assert_eq!(buf.get(0, 2).fg, theme.syntax_color("comment"));
+
+        let rows = rendered_rows(&preview, 30, 5);
+        assert_eq!(
+            rows,
+            ["fn main() {", "    let x = 1; /* a", "b */", "}", ""]
+        );
+    }
 
-                Line::from(spans)
-            } else {
-                // No highlights for this line
-                Line::from(Span::styled(
-                    line_content.to_owned(),
-                    Style::default().fg(theme.general.foreground),
-                ))
-            }
-        })
-        .collect();
+    #[test]
+    fn test_preview_starts_at_top_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let mut preview = PreviewBuffer::load_from_file(&path).unwrap();
+        preview.top_line = 1;
 
-    Paragraph::new(lines)
+        assert_eq!(rendered_rows(&preview, 10, 3), ["two", "three", ""]);
+    }
 }
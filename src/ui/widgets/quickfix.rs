@@ -0,0 +1,93 @@
+// src/ui/widgets/quickfix.rs - Quickfix window listing the locations to step through
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Widget},
+};
+
+use crate::quickfix::QuickfixList;
+use crate::ui::theme::Theme;
+
+/// Most entries shown at once; longer lists scroll with the selection
+const MAX_VISIBLE: u16 = 10;
+
+/// Window along the bottom of the editor listing the quickfix entries
+pub struct QuickfixWindow<'a> {
+    pub list: &'a QuickfixList,
+    pub theme: &'a Theme,
+}
+
+impl<'a> QuickfixWindow<'a> {
+    pub fn new(list: &'a QuickfixList, theme: &'a Theme) -> Self {
+        Self { list, theme }
+    }
+
+    /// The bottom rows of `area`, enough for the entries up to `MAX_VISIBLE`
+    pub fn calculate_position(&self, area: Rect) -> Rect {
+        let height = (self.list.len().max(1) as u16).min(MAX_VISIBLE) + 2;
+        let height = height.min(area.height);
+        Rect {
+            x: area.x,
+            y: area.y + area.height - height,
+            width: area.width,
+            height,
+        }
+    }
+}
+
+impl Widget for QuickfixWindow<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        // Scroll just far enough to keep the selection in view
+        let visible = area.height.saturating_sub(2) as usize;
+        let first = (self.list.selected + 1).saturating_sub(visible);
+
+        let lines: Vec<Line> = self
+            .list
+            .entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible)
+            .map(|(i, entry)| {
+                let (prefix, style) = if i == self.list.selected {
+                    (
+                        "▶ ",
+                        Style::default()
+                            .fg(self.theme.general.background)
+                            .bg(self.theme.general.foreground)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    ("  ", Style::default().fg(self.theme.general.foreground))
+                };
+                Line::from(vec![Span::styled(
+                    format!("{}{}", prefix, entry.display()),
+                    style,
+                )])
+            })
+            .collect();
+
+        let truncated = if self.list.truncated { ", truncated" } else { "" };
+        let title = format!(" {} ({}{}) ", self.list.title, self.list.len(), truncated);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.theme.ui.gutter_fg))
+            .title(title)
+            .title_style(
+                Style::default()
+                    .fg(self.theme.syntax.function)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .padding(Padding::horizontal(1));
+
+        Paragraph::new(lines)
+            .block(block)
+            .alignment(Alignment::Left)
+            .render(area, buf);
+    }
+}
@@ -1,8 +1,15 @@
 // ui/widgets/status_bar.rs - Status bar widget
 
-use ratatui::{buffer::Buffer, layout::Rect, style::Style, text::Line, widgets::Widget};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Widget,
+};
 
 use crate::editor::Editor;
+use crate::mode::Mode;
 use crate::ui::theme::Theme;
 
 /// Status bar widget showing mode, file info, cursor position, LSP status
@@ -23,41 +30,35 @@ impl Widget for StatusBar<'_> {
         let progress_info = self.get_progress_info();
 
         // Format the status line
-        let base_status = format!(
-            " {} | {}:{} | Modified: {}",
-            mode_to_str(&self.editor.mode),
-            self.editor.cursor.line,
-            self.editor.cursor.col,
-            self.editor.buffer.modified
-        );
+        let mut spans = self.status_spans(&parse_format(&self.editor.options.status_format));
+        let mut status = String::new();
+        if let Some(size) = self.editor.selection_size() {
+            status = format!(" | {}", size);
+        }
 
-        let status = if let Some(msg) = &self.editor.status_message {
-            format!("{} | {}", base_status, msg)
+        if let Some(msg) = &self.editor.status_message {
+            status = format!("{} | {}", status, msg);
         } else {
-            format!(
+            status = format!(
                 "{} | LSP: {}{}",
-                base_status,
+                status,
                 lsp_status,
                 if progress_info.is_empty() {
                     String::new()
                 } else {
                     format!(" | {}", progress_info)
                 }
-            )
-        };
+            );
+        }
 
         // Pad the status text to fill the entire width
-        let padded_status = if status.len() < area.width as usize {
-            format!(
-                "{}{}",
-                status,
-                " ".repeat(area.width as usize - status.len())
-            )
-        } else {
-            status
-        };
+        let used: usize = spans.iter().map(|span| span.width()).sum::<usize>() + status.len();
+        if used < area.width as usize {
+            status.push_str(&" ".repeat(area.width as usize - used));
+        }
+        spans.push(Span::raw(status));
 
-        let line_widget = Line::from(padded_status).style(
+        let line_widget = Line::from(spans).style(
             Style::default()
                 .bg(self.theme.ui.status_bar_bg)
                 .fg(self.theme.ui.status_bar_fg),
@@ -68,6 +69,70 @@ impl Widget for StatusBar<'_> {
 }
 
 impl StatusBar<'_> {
+    /// The status line format as spans, with the mode as a colored block
+    fn status_spans(&self, segments: &[Segment]) -> Vec<Span<'static>> {
+        let mut spans = Vec::new();
+        let mut text = String::new();
+        for segment in segments {
+            if *segment == Segment::Mode {
+                if !text.is_empty() {
+                    spans.push(Span::raw(std::mem::take(&mut text)));
+                }
+                let mode = format!(" {} ", mode_name(self.editor));
+                spans.push(Span::styled(mode, self.mode_style()));
+            } else {
+                text.push_str(&self.format_status(std::slice::from_ref(segment)));
+            }
+        }
+        if !text.is_empty() {
+            spans.push(Span::raw(text));
+        }
+        spans
+    }
+
+    /// Theme colors for the mode indicator; modes without their own use normal's
+    fn mode_style(&self) -> Style {
+        let ui = &self.theme.ui;
+        let (bg, fg) = match self.editor.mode {
+            Mode::Insert => (ui.status_insert_bg, ui.status_insert_fg),
+            Mode::Visual => (ui.status_select_bg, ui.status_select_fg),
+            _ => (ui.status_bar_bg, ui.status_bar_fg),
+        };
+        Style::default().bg(bg).fg(fg).add_modifier(Modifier::BOLD)
+    }
+
+    fn format_status(&self, segments: &[Segment]) -> String {
+        let editor = self.editor;
+        let mut status = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => status.push_str(text),
+                Segment::FileName => {
+                    status.push_str(editor.buffer.file_path.as_deref().unwrap_or("[No Name]"))
+                }
+                Segment::Modified => {
+                    if editor.buffer.modified {
+                        status.push_str("[+]");
+                    }
+                }
+                Segment::FileType => {
+                    if let Some(language) = editor.current_language {
+                        status.push_str(&format!("[{}]", language.name()));
+                    }
+                }
+                Segment::Line => status.push_str(&(editor.cursor.line + 1).to_string()),
+                Segment::Column => status.push_str(&(editor.cursor.col + 1).to_string()),
+                Segment::Percent => {
+                    let lines = editor.buffer.line_count().max(1);
+                    let percent = (editor.cursor.line + 1).min(lines) * 100 / lines;
+                    status.push_str(&format!("{}%", percent));
+                }
+                Segment::Mode => status.push_str(&format!(" {} ", mode_name(editor))),
+            }
+        }
+        status
+    }
+
     fn get_lsp_status(&self) -> &'static str {
         if let Some(_language) = self.editor.current_language {
             // Check if we have an LSP client for this language
@@ -98,12 +163,187 @@ impl StatusBar<'_> {
     }
 }
 
-fn mode_to_str(mode: &crate::mode::Mode) -> &'static str {
-    match mode {
-        crate::mode::Mode::Normal => "NORMAL",
-        crate::mode::Mode::Insert => "INSERT",
-        crate::mode::Mode::Visual => "VISUAL",
-        crate::mode::Mode::Command => "COMMAND",
-        crate::mode::Mode::FuzzySearch => "FUZZY",
+/// A piece of the status line format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(String),
+    /// `%f`: the file name, `[No Name]` for a new buffer
+    FileName,
+    /// `%m`: `[+]` when the buffer has unsaved changes
+    Modified,
+    /// `%y`: the file type, e.g. `[rust]`
+    FileType,
+    /// `%l`: the cursor line, counting from 1
+    Line,
+    /// `%c`: the cursor column, counting from 1
+    Column,
+    /// `%p`: how far through the file the cursor is
+    Percent,
+    /// `%M`: the editing mode, drawn as a block in the theme's mode color
+    Mode,
+}
+
+/// Split a status line format into segments. `%%` is a literal `%`; unknown
+/// specifiers are kept as written.
+pub fn parse_format(format: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        let segment = match chars.next() {
+            Some('f') => Segment::FileName,
+            Some('m') => Segment::Modified,
+            Some('y') => Segment::FileType,
+            Some('l') => Segment::Line,
+            Some('c') => Segment::Column,
+            Some('p') => Segment::Percent,
+            Some('M') => Segment::Mode,
+            Some('%') => {
+                text.push('%');
+                continue;
+            }
+            other => {
+                text.push('%');
+                text.extend(other);
+                continue;
+            }
+        };
+        if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+        }
+        segments.push(segment);
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+/// Name of the editor's mode; a normal-mode command run from insert mode
+/// with `Ctrl-O` shows as `(INSERT)`, as in Vim
+fn mode_name(editor: &Editor) -> &'static str {
+    if editor.mode == Mode::Normal && editor.in_insert_normal_command() {
+        return "(INSERT)";
+    }
+    match editor.mode {
+        Mode::Normal => "NORMAL",
+        Mode::Insert => "INSERT",
+        Mode::Visual => "VISUAL",
+        Mode::Command => "COMMAND",
+        Mode::FuzzySearch => "FUZZY",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::LanguageId;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("%f%m %l:%c"),
+            [
+                Segment::FileName,
+                Segment::Modified,
+                Segment::Text(" ".to_string()),
+                Segment::Line,
+                Segment::Text(":".to_string()),
+                Segment::Column,
+            ]
+        );
+        // Unknown specifiers and a trailing `%` are literal text
+        assert_eq!(
+            parse_format("100%% %q %"),
+            [Segment::Text("100% %q %".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_format_status() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("a\nb\nc\nd", 0, 0).unwrap();
+        editor.buffer.file_path = Some("src/main.rs".to_string());
+        editor.current_language = Some(LanguageId::Rust);
+        editor.cursor.line = 1;
+        editor.cursor.col = 0;
+        let theme = Theme::default();
+        let bar = StatusBar::new(&editor, &theme);
+
+        assert_eq!(
+            bar.format_status(&parse_format(crate::options::DEFAULT_STATUS_FORMAT)),
+            " NORMAL | src/main.rs [+][rust] | 2:1 50%"
+        );
+
+        editor.buffer.file_path = None;
+        editor.buffer.modified = false;
+        editor.current_language = None;
+        let bar = StatusBar::new(&editor, &theme);
+        assert_eq!(bar.format_status(&parse_format("%f%m %y%M")), "[No Name]  NORMAL ");
+    }
+
+    #[test]
+    fn test_selection_size_is_shown() {
+        let mut editor = Editor::new();
+        editor.buffer.insert_text("one\ntwo\nthree\n", 0, 0).unwrap();
+        editor.options.status_format = "%M".to_string();
+        editor.current_language = None;
+        let theme = Theme::default();
+        let render = |editor: &Editor| {
+            let area = Rect::new(0, 0, 40, 1);
+            let mut buf = Buffer::empty(area);
+            StatusBar::new(editor, &theme).render(area, &mut buf);
+            (0..area.width)
+                .map(|x| buf.get(x, 0).symbol().to_string())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+        assert_eq!(render(&editor), " NORMAL  | LSP: none");
+
+        editor.execute_command(crate::command::Command::VisualChar);
+        editor.execute_command(crate::command::Command::MoveRight);
+        assert_eq!(render(&editor), " VISUAL  | 2 chars | LSP: none");
+        // Extending onto the next line counts the line break
+        editor.execute_command(crate::command::Command::MoveDown);
+        assert_eq!(editor.selection_size().as_deref(), Some("6 chars"));
+
+        editor.execute_command(crate::command::Command::NormalMode);
+        editor.execute_command(crate::command::Command::VisualLine);
+        editor.execute_command(crate::command::Command::MoveDown);
+        assert_eq!(editor.selection_size().as_deref(), Some("2 lines"));
+        editor.execute_command(crate::command::Command::MoveUp);
+        assert_eq!(editor.selection_size().as_deref(), Some("1 line"));
+    }
+
+    #[test]
+    fn test_mode_block_uses_theme_colors() {
+        use ratatui::style::Color;
+
+        let mut editor = Editor::new();
+        editor.options.status_format = "%M|".to_string();
+        let theme = Theme::default();
+        let block_style = |editor: &Editor| {
+            let area = Rect::new(0, 0, 30, 1);
+            let mut buf = Buffer::empty(area);
+            StatusBar::new(editor, &theme).render(area, &mut buf);
+            let (block, rest) = (buf.get(1, 0), buf.get(9, 0));
+            assert_eq!(rest.bg, theme.ui.status_bar_bg);
+            assert!(block.modifier.contains(Modifier::BOLD));
+            assert!(!rest.modifier.contains(Modifier::BOLD));
+            (block.bg, block.fg)
+        };
+
+        assert_eq!(block_style(&editor), (Color::Blue, Color::White));
+        editor.mode = Mode::Insert;
+        assert_eq!(block_style(&editor), (Color::Green, Color::Black));
+        editor.mode = Mode::Visual;
+        assert_eq!(block_style(&editor), (Color::Magenta, Color::Black));
+        editor.mode = Mode::Command;
+        assert_eq!(block_style(&editor), (Color::Blue, Color::White));
     }
 }
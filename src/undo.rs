@@ -0,0 +1,127 @@
+// src/undo.rs - Snapshot-based undo/redo history
+
+use ropey::Rope;
+
+use crate::motion::Position;
+
+/// Oldest changes are dropped beyond this many undo steps
+const MAX_UNDO_LEVELS: usize = 1000;
+
+/// Buffer contents and cursor position at one point in the history.
+///
+/// Ropes share unchanged chunks, so keeping a copy per change is cheap.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub rope: Rope,
+    pub cursor: Position,
+}
+
+/// Undo and redo stacks for one buffer
+#[derive(Debug, Clone, Default)]
+pub struct UndoHistory {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Bumped by undo, redo and `clear`, so that moving through or dropping the
+    /// history isn't itself recorded as a change
+    generation: usize,
+}
+
+impl UndoHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state from before a change. A new change makes the undone
+    /// changes unreachable, so the redo stack is dropped.
+    pub fn record(&mut self, before: Snapshot) {
+        if self.undo.len() == MAX_UNDO_LEVELS {
+            self.undo.remove(0);
+        }
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    /// Step back one change, saving `current` for redo
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        self.generation += 1;
+        Some(previous)
+    }
+
+    /// Step forward one undone change, saving `current` for undo
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        self.generation += 1;
+        Some(next)
+    }
+
+    /// Number of changes that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Number of changes that can be redone
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Forget all changes, e.g. when another file is opened
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.generation += 1;
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str) -> Snapshot {
+        Snapshot {
+            rope: Rope::from_str(text),
+            cursor: Position::new(0, 0),
+        }
+    }
+
+    #[test]
+    fn test_undo_redo_round_trip() {
+        let mut history = UndoHistory::new();
+        history.record(snapshot("a"));
+        history.record(snapshot("ab"));
+
+        let undone = history.undo(snapshot("abc")).unwrap();
+        assert_eq!(undone.rope, "ab");
+        let undone = history.undo(undone).unwrap();
+        assert_eq!(undone.rope, "a");
+        assert!(history.undo(undone.clone()).is_none());
+
+        let redone = history.redo(undone).unwrap();
+        assert_eq!(redone.rope, "ab");
+        assert_eq!((history.undo_len(), history.redo_len()), (1, 1));
+    }
+
+    #[test]
+    fn test_new_change_drops_redo() {
+        let mut history = UndoHistory::new();
+        history.record(snapshot("a"));
+        history.undo(snapshot("ab")).unwrap();
+        history.record(snapshot("a"));
+        assert_eq!(history.redo_len(), 0);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = UndoHistory::new();
+        for i in 0..MAX_UNDO_LEVELS + 5 {
+            history.record(snapshot(&i.to_string()));
+        }
+        assert_eq!(history.undo_len(), MAX_UNDO_LEVELS);
+    }
+}
@@ -1,5 +1,6 @@
 pub struct Viewport {
     pub offset_line: usize,
+    /// First visible screen column (tabs and wide characters expanded)
     pub offset_col: usize,
     pub rows: usize,
     pub cols: usize,
@@ -15,6 +16,7 @@ impl Viewport {
         }
     }
 
+    /// `cursor_col` is the cursor's display column, not its char index
     pub fn scroll_to_cursor(&mut self, cursor_line: usize, cursor_col: usize) {
         // Only scroll if cursor is outside visible area (don't center unnecessarily)
         if cursor_line < self.offset_line {
@@ -22,7 +24,7 @@ impl Viewport {
             self.offset_line = cursor_line;
         } else if cursor_line >= self.offset_line + self.rows {
             // Cursor below viewport: scroll to show cursor at bottom
-            self.offset_line = cursor_line.saturating_sub(self.rows - 1);
+            self.offset_line = cursor_line.saturating_sub(self.rows.saturating_sub(1));
         }
 
         if cursor_col < self.offset_col {
@@ -30,7 +32,7 @@ impl Viewport {
             self.offset_col = cursor_col;
         } else if cursor_col >= self.offset_col + self.cols {
             // Cursor right of viewport: scroll to show cursor at right edge
-            self.offset_col = cursor_col.saturating_sub(self.cols - 1);
+            self.offset_col = cursor_col.saturating_sub(self.cols.saturating_sub(1));
         }
     }
 }
@@ -1,7 +1,9 @@
 // src/vim_parser.rs - Multi-key command parser for Vim-style key sequences
 
-use crate::command::Command;
-use crossterm::event::{KeyEvent, KeyCode};
+use crate::buffer::CaseChange;
+use crate::command::{Command, LineMotion};
+use crate::motion::{CharSearch, TextObject};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 /// Result of parsing a key event
 #[derive(Debug, Clone, PartialEq)]
@@ -23,6 +25,8 @@ enum Operator {
     Indent,
     Unindent,
     Format,
+    Case(CaseChange),
+    Comment,
 }
 
 /// Parser state machine
@@ -36,6 +40,16 @@ enum ParserState {
     ReadingMotion,
     ReadingTextObject,
     ReadingReplaceChar,
+    /// After `g`, waiting for the second key of a `g`-prefixed command
+    ReadingG,
+    /// After `gr`, waiting for the key of a language server command
+    ReadingGr,
+    /// After `[` or `]`, waiting for what to jump to
+    ReadingBracket(char),
+    /// After `f`, `t`, `F` or `T`, waiting for the character to search for
+    ReadingFindChar { till: bool, forward: bool },
+    /// After `@`, waiting for what to run
+    ReadingAt,
 }
 
 /// Parser for Vim-style multi-key commands
@@ -48,6 +62,8 @@ pub struct VimParser {
     operator_count: Option<usize>,
     motion_buffer: Vec<char>,
     _replace_char: Option<char>,
+    /// Register selected for the most recently completed command
+    active_register: Option<char>,
 }
 
 impl Default for VimParser {
@@ -66,6 +82,7 @@ impl VimParser {
             operator_count: None,
             motion_buffer: Vec::new(),
             _replace_char: None,
+            active_register: None,
         }
     }
 
@@ -76,8 +93,6 @@ impl VimParser {
 
     /// Process a key event and return the parse result
     pub fn process_key(&mut self, key: KeyEvent) -> ParseResult {
-        use crossterm::event::{KeyCode, KeyModifiers};
-
         // Handle arrow keys directly
         match key.code {
             KeyCode::Left => {
@@ -107,7 +122,7 @@ impl VimParser {
             KeyCode::Backspace => return ParseResult::Command(Command::DeleteChar),
             KeyCode::Esc => {
                 self.reset();
-                return ParseResult::Command(Command::NormalMode);
+                return ParseResult::Command(Command::Escape);
             }
             _ => None,
         };
@@ -117,7 +132,8 @@ impl VimParser {
             return self.process_ctrl_key(key.code);
         }
 
-        match self.state {
+        let register = self.register;
+        let result = match self.state {
             ParserState::Idle => self.process_idle(ch),
             ParserState::ReadingCount => self.process_reading_count(ch),
             ParserState::ReadingRegister => self.process_reading_register(ch),
@@ -126,13 +142,116 @@ impl VimParser {
             ParserState::ReadingMotion => self.process_reading_motion(ch),
             ParserState::ReadingTextObject => self.process_reading_text_object(ch),
             ParserState::ReadingReplaceChar => self.process_reading_replace_char(ch),
+            ParserState::ReadingG => self.process_reading_g(ch),
+            ParserState::ReadingGr => self.process_reading_gr(ch),
+            ParserState::ReadingBracket(bracket) => self.process_reading_bracket(bracket, ch),
+            ParserState::ReadingFindChar { till, forward } => {
+                self.process_reading_find_char(till, forward, ch)
+            }
+            ParserState::ReadingAt => self.process_reading_at(ch),
+        };
+
+        if matches!(result, ParseResult::Command(_)) {
+            self.active_register = register;
         }
+        result
+    }
+
+    /// Process a key event in visual mode. Operators apply to the selection
+    /// right away; anything else (motions, counts) is parsed as in normal mode.
+    pub fn process_visual_key(&mut self, key: KeyEvent) -> ParseResult {
+        if self.state == ParserState::ReadingG && key.code == KeyCode::Char('c') {
+            self.reset();
+            return ParseResult::Command(Command::ToggleCommentSelection);
+        }
+        if self.state == ParserState::ReadingG && key.code == KeyCode::Char('J') {
+            self.reset();
+            return ParseResult::Command(Command::JoinSelectionNoSpace);
+        }
+        if self.state == ParserState::ReadingReplaceChar
+            && let KeyCode::Char(ch) = key.code
+            && !ch.is_control()
+        {
+            self.reset();
+            return ParseResult::Command(Command::ReplaceSelection(ch));
+        }
+        let pending_count = matches!(self.state, ParserState::Idle | ParserState::ReadingCount);
+        // Terminals send uppercase letters and shifted symbols with SHIFT
+        let plain = !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if pending_count && plain {
+            let count = self.count.unwrap_or(1);
+            let cmd = match key.code {
+                KeyCode::Char('>') => Some(Command::IndentSelection(count)),
+                KeyCode::Char('<') => Some(Command::UnindentSelection(count)),
+                KeyCode::Char('u') => Some(Command::CaseSelection(CaseChange::Lower)),
+                KeyCode::Char('U') => Some(Command::CaseSelection(CaseChange::Upper)),
+                KeyCode::Char('~') => Some(Command::CaseSelection(CaseChange::Toggle)),
+                KeyCode::Char('o' | 'O') => Some(Command::VisualSwapEnds),
+                KeyCode::Char('y') => Some(Command::YankSelection),
+                KeyCode::Char('d' | 'x') => Some(Command::DeleteSelection),
+                KeyCode::Char('=') => Some(Command::FormatSelection),
+                KeyCode::Char('J') => Some(Command::JoinSelection),
+                KeyCode::Char(ch @ ('i' | 'a')) => {
+                    // A text object to select
+                    self.motion_buffer.push(ch);
+                    self.state = ParserState::ReadingTextObject;
+                    return ParseResult::Pending;
+                }
+                _ => None,
+            };
+            if let Some(cmd) = cmd {
+                // Keep a register given with `"x` for the command
+                let register = self.register;
+                self.reset();
+                self.active_register = register;
+                return ParseResult::Command(cmd);
+            }
+        }
+        self.process_key(key)
+    }
+
+    /// Whether keys of an unfinished command (a count, register or operator) were typed
+    pub fn is_pending(&self) -> bool {
+        self.state != ParserState::Idle || self.count.is_some() || self.register.is_some()
+    }
+
+    /// Take the register (`"x`) selected for the last completed command.
+    /// Case is preserved so `"A` (append) can be told apart from `"a` (replace).
+    pub fn take_register(&mut self) -> Option<char> {
+        self.active_register.take()
     }
 
     fn process_ctrl_key(&mut self, code: KeyCode) -> ParseResult {
         match code {
-            KeyCode::Char('r') => ParseResult::Command(Command::Redo),
+            // Cancel a pending count, register or operator like Esc does
+            KeyCode::Char('c') => {
+                self.reset();
+                ParseResult::Command(Command::NormalMode)
+            }
+            KeyCode::Char('g') if self.state == ParserState::ReadingG => {
+                self.reset();
+                ParseResult::Command(Command::ShowCounts)
+            }
+            KeyCode::Char('r') => {
+                let count = self.count.unwrap_or(1);
+                self.reset();
+                ParseResult::Command(Command::Redo(count))
+            }
+            KeyCode::Char('t') => {
+                self.reset();
+                ParseResult::Command(Command::TransposeChars)
+            }
             KeyCode::Char('f') => ParseResult::Command(Command::OpenFuzzySearch),
+            KeyCode::Char('p') => ParseResult::Command(Command::OpenRecentFiles),
+            KeyCode::Char('n') => ParseResult::Command(Command::AddCursor),
+            KeyCode::Char('a') => {
+                self.reset();
+                ParseResult::Command(Command::SelectAll)
+            }
+            KeyCode::Char('l') => {
+                self.reset();
+                ParseResult::Command(Command::CloseAllOverlays)
+            }
             _ => ParseResult::Invalid,
         }
     }
@@ -167,6 +286,10 @@ impl VimParser {
                 self.reset();
                 ParseResult::Command(Command::SubstituteLine)
             }
+            '&' => {
+                self.reset();
+                ParseResult::Command(Command::RepeatSubstitute)
+            }
             'p' => {
                 self.reset();
                 ParseResult::Command(Command::PasteAfter)
@@ -185,8 +308,30 @@ impl VimParser {
                 ParseResult::Command(Command::JoinLines(count))
             }
             'u' => {
+                let count = self.count.unwrap_or(1);
+                self.reset();
+                ParseResult::Command(Command::Undo(count))
+            }
+            '.' => {
+                self.reset();
+                ParseResult::Command(Command::RepeatLastChange)
+            }
+            '~' => {
+                let count = self.count.unwrap_or(1);
                 self.reset();
-                ParseResult::Command(Command::Undo)
+                ParseResult::Command(Command::ToggleCaseChar(count))
+            }
+            'g' => {
+                self.state = ParserState::ReadingG;
+                ParseResult::Pending
+            }
+            '[' | ']' => {
+                self.state = ParserState::ReadingBracket(ch);
+                ParseResult::Pending
+            }
+            '@' => {
+                self.state = ParserState::ReadingAt;
+                ParseResult::Pending
             }
 
             // Motion commands
@@ -239,12 +384,37 @@ impl VimParser {
                 self.reset();
                 ParseResult::Command(Command::MoveFileEnd)
             }
+            // With a count, `%` goes that far through the file; without one
+            // it jumps between brackets
+            '%' => {
+                let count = self.count;
+                self.reset();
+                ParseResult::Command(match count {
+                    Some(percent) => Command::GotoPercent(percent),
+                    None => Command::MoveMatchingPair,
+                })
+            }
             'H' => ParseResult::Command(Command::MoveScreenTop),
             'M' => ParseResult::Command(Command::MoveScreenMiddle),
             'L' => ParseResult::Command(Command::MoveScreenBottom),
 
             // Operator-pending commands
-            'd' | 'y' | 'c' | '>' | '<' | '=' | 'g' | 'f' | 't' | 'T' | 'F' => {
+            'f' | 't' | 'F' | 'T' => {
+                self.state = ParserState::ReadingFindChar {
+                    till: matches!(ch, 't' | 'T'),
+                    forward: ch.is_lowercase(),
+                };
+                ParseResult::Pending
+            }
+            ';' | ',' => {
+                let count = self.count.unwrap_or(1);
+                self.reset();
+                ParseResult::Command(Command::RepeatFindChar {
+                    reverse: ch == ',',
+                    count,
+                })
+            }
+            'd' | 'y' | 'c' | '>' | '<' | '=' => {
                 let op = match ch {
                     'd' => Operator::Delete,
                     'y' => Operator::Yank,
@@ -276,9 +446,17 @@ impl VimParser {
             }
 
             // Mode switching
-            'i' => {
+            'i' | 'a' | 'A' | 'I' | 'o' | 'O' => {
+                let count = self.count.unwrap_or(1);
                 self.reset();
-                ParseResult::Command(Command::InsertMode)
+                ParseResult::Command(match ch {
+                    'i' => Command::InsertMode(count),
+                    'a' => Command::AppendAfter(count),
+                    'A' => Command::AppendLineEnd(count),
+                    'I' => Command::InsertLineStart(count),
+                    'o' => Command::OpenLineBelow(count),
+                    _ => Command::OpenLineAbove(count),
+                })
             }
             ':' => {
                 self.reset();
@@ -390,29 +568,40 @@ impl VimParser {
         let count = self.operator_count.or(self.count).unwrap_or(1);
 
         let cmd = match (self.operator, motion_str.as_str()) {
+            // Text objects: `iw`, `a(`, `i"` and so on
+            (
+                Some(Operator::Delete | Operator::Change | Operator::Yank | Operator::Case(_)),
+                "i" | "a",
+            ) => {
+                self.state = ParserState::ReadingTextObject;
+                return ParseResult::Pending;
+            }
+
             // Delete motions
-            (Some(Operator::Delete), "d") => Command::DeleteLine,
+            (Some(Operator::Delete), "d") => Command::DeleteLine(count),
             (Some(Operator::Delete), "w") => Command::DeleteWord(count),
             (Some(Operator::Delete), "e") => Command::DeleteToEndWord(count),
             (Some(Operator::Delete), "b") => Command::DeleteToStartWord(count),
+            (Some(Operator::Delete), "ge") => Command::DeleteWordEndBackward(count),
+            (Some(Operator::Delete), "gE") => Command::DeleteBigWordEndBackward(count),
             (Some(Operator::Delete), "$") => Command::DeleteToEnd,
             (Some(Operator::Delete), "0") => Command::DeleteToStart,
             (Some(Operator::Delete), "G") => Command::DeleteToEndOfFile,
             (Some(Operator::Delete), "gg") => Command::DeleteToStartOfFile,
-            (Some(Operator::Delete), "i") => {
-                self.state = ParserState::ReadingTextObject;
-                return ParseResult::Pending;
-            }
 
             // Yank motions
-            (Some(Operator::Yank), "y") => Command::YankLine,
+            (Some(Operator::Yank), "y") => Command::YankLine(count),
             (Some(Operator::Yank), "w") => Command::YankWord(count),
+            (Some(Operator::Yank), "ge") => Command::YankWordEndBackward(count),
+            (Some(Operator::Yank), "gE") => Command::YankBigWordEndBackward(count),
             (Some(Operator::Yank), "$") => Command::YankToEnd,
             (Some(Operator::Yank), "0") => Command::YankToStart,
 
             // Change motions
             (Some(Operator::Change), "c") => Command::ChangeLine,
             (Some(Operator::Change), "w") => Command::ChangeWord(count),
+            (Some(Operator::Change), "ge") => Command::ChangeWordEndBackward(count),
+            (Some(Operator::Change), "gE") => Command::ChangeBigWordEndBackward(count),
             (Some(Operator::Change), "$") => Command::ChangeToEnd,
             (Some(Operator::Change), "0") => Command::ChangeToStart,
 
@@ -421,6 +610,38 @@ impl VimParser {
             (Some(Operator::Unindent), "<") => Command::UnindentLine(count),
             (Some(Operator::Format), "=") => Command::FormatBuffer,
 
+            // Reindent motions
+            (Some(Operator::Format), "j") => Command::Reindent(LineMotion::Down(count)),
+            (Some(Operator::Format), "k") => Command::Reindent(LineMotion::Up(count)),
+            (Some(Operator::Format), "G") => Command::Reindent(LineMotion::FileEnd),
+            (Some(Operator::Format), "gg") => Command::Reindent(LineMotion::FileStart),
+            (Some(Operator::Format), "}") => Command::Reindent(LineMotion::ParagraphForward(count)),
+            (Some(Operator::Format), "{") => Command::Reindent(LineMotion::ParagraphBackward(count)),
+
+            // Case operators: `gu`, `gU`, `g~`, doubled for whole lines
+            (Some(Operator::Case(case)), "w") => Command::CaseWord(case, count),
+            (Some(Operator::Case(case)), "e") => Command::CaseToWordEnd(case, count),
+            (Some(Operator::Case(case)), "$") => Command::CaseToEnd(case),
+            (Some(Operator::Case(case)), "0") => Command::CaseToStart(case),
+            (Some(Operator::Case(case)), motion)
+                if motion.strip_prefix('g').unwrap_or(motion) == case_operator_key(case) =>
+            {
+                Command::CaseLine(case, count)
+            }
+
+            // Comment toggling: `gcc` for lines, or a linewise motion
+            (Some(Operator::Comment), "c") => Command::ToggleComment(LineMotion::Down(count.saturating_sub(1))),
+            (Some(Operator::Comment), "j") => Command::ToggleComment(LineMotion::Down(count)),
+            (Some(Operator::Comment), "k") => Command::ToggleComment(LineMotion::Up(count)),
+            (Some(Operator::Comment), "G") => Command::ToggleComment(LineMotion::FileEnd),
+            (Some(Operator::Comment), "gg") => Command::ToggleComment(LineMotion::FileStart),
+            (Some(Operator::Comment), "}") => {
+                Command::ToggleComment(LineMotion::ParagraphForward(count))
+            }
+            (Some(Operator::Comment), "{") => {
+                Command::ToggleComment(LineMotion::ParagraphBackward(count))
+            }
+
             _ => return ParseResult::Pending,
         };
 
@@ -441,6 +662,19 @@ impl VimParser {
         let inner = self.motion_buffer.contains(&'i');
         let text_obj = ch;
 
+        // Bracket and quote objects
+        if let Some(object) = TextObject::from_key(text_obj, inner) {
+            let cmd = match self.operator {
+                Some(Operator::Delete) => Some(Command::DeleteTextObject(object)),
+                Some(Operator::Change) => Some(Command::ChangeTextObject(object)),
+                Some(Operator::Yank) => Some(Command::YankTextObject(object)),
+                None => Some(Command::SelectTextObject(object)),
+                _ => None,
+            };
+            self.reset();
+            return cmd.map_or(ParseResult::Invalid, ParseResult::Command);
+        }
+
         let count = self.operator_count.or(self.count).unwrap_or(1);
         let cmd = match (self.operator, inner, text_obj) {
             (Some(Operator::Change), true, 'w') => Command::ChangeInnerWord(count),
@@ -449,6 +683,7 @@ impl VimParser {
             (Some(Operator::Delete), false, 'w') => Command::DeleteAWord(count),
             (Some(Operator::Yank), true, 'w') => Command::YankInnerWord(count),
             (Some(Operator::Yank), false, 'w') => Command::YankAWord(count),
+            (Some(Operator::Case(case)), true, 'w') => Command::CaseInnerWord(case, count),
             _ => {
                 self.reset();
                 return ParseResult::Invalid;
@@ -459,6 +694,108 @@ impl VimParser {
         ParseResult::Command(cmd)
     }
 
+    fn process_reading_g(&mut self, ch: Option<char>) -> ParseResult {
+        let case = match ch {
+            Some('u') => CaseChange::Lower,
+            Some('U') => CaseChange::Upper,
+            Some('~') => CaseChange::Toggle,
+            Some('a') => {
+                self.reset();
+                return ParseResult::Command(Command::ShowCharInfo);
+            }
+            Some('c') => {
+                self.operator = Some(Operator::Comment);
+                self.state = ParserState::ReadingOperator;
+                return ParseResult::Pending;
+            }
+            Some('g') => {
+                self.reset();
+                return ParseResult::Command(Command::MoveFileStart);
+            }
+            Some('J') => {
+                let count = self.count.unwrap_or(1);
+                self.reset();
+                return ParseResult::Command(Command::JoinLinesNoSpace(count));
+            }
+            Some('r') => {
+                self.state = ParserState::ReadingGr;
+                return ParseResult::Pending;
+            }
+            Some(motion @ ('e' | 'E')) => {
+                let count = self.count.unwrap_or(1);
+                self.reset();
+                return ParseResult::Command(if motion == 'e' {
+                    Command::MoveWordEndBackward(count)
+                } else {
+                    Command::MoveBigWordEndBackward(count)
+                });
+            }
+            _ => {
+                self.reset();
+                return ParseResult::Invalid;
+            }
+        };
+
+        self.operator = Some(Operator::Case(case));
+        self.state = ParserState::ReadingOperator;
+        ParseResult::Pending
+    }
+
+    /// `gr` prefixes language server commands: `gra` code actions, `grr`
+    /// references, `grw` workspace symbols
+    fn process_reading_gr(&mut self, ch: Option<char>) -> ParseResult {
+        self.reset();
+        match ch {
+            Some('a') => ParseResult::Command(Command::CodeAction),
+            Some('r') => ParseResult::Command(Command::FindReferences),
+            Some('w') => ParseResult::Command(Command::WorkspaceSymbols),
+            _ => ParseResult::Invalid,
+        }
+    }
+
+    /// `]x` jumps forward to the next x, `[x` back to the previous one
+    fn process_reading_bracket(&mut self, bracket: char, ch: Option<char>) -> ParseResult {
+        let count = self.count.unwrap_or(1);
+        let forward = bracket == ']';
+        self.reset();
+        match ch {
+            Some('c') if forward => ParseResult::Command(Command::NextHunk(count)),
+            Some('c') => ParseResult::Command(Command::PrevHunk(count)),
+            Some('i') if forward => ParseResult::Command(Command::MoveMatchingIndentDown(count)),
+            Some('i') => ParseResult::Command(Command::MoveMatchingIndentUp(count)),
+            Some('q') if forward => ParseResult::Command(Command::QuickfixNext(count)),
+            Some('q') => ParseResult::Command(Command::QuickfixPrev(count)),
+            _ => ParseResult::Invalid,
+        }
+    }
+
+    fn process_reading_at(&mut self, ch: Option<char>) -> ParseResult {
+        let count = self.count.unwrap_or(1);
+        self.reset();
+        match ch {
+            Some(':') => ParseResult::Command(Command::RepeatExCommand(count)),
+            Some('@') => ParseResult::Command(Command::RepeatLastExecuted(count)),
+            _ => ParseResult::Invalid,
+        }
+    }
+
+    fn process_reading_find_char(
+        &mut self,
+        till: bool,
+        forward: bool,
+        ch: Option<char>,
+    ) -> ParseResult {
+        let count = self.count.unwrap_or(1);
+        self.reset();
+        match ch {
+            Some(ch) if !ch.is_control() && ch != '\n' => {
+                let search = CharSearch { ch, till, forward };
+                ParseResult::Command(Command::FindChar(search, count))
+            }
+            _ => ParseResult::Invalid,
+        }
+    }
+
     fn process_reading_replace_char(&mut self, ch: Option<char>) -> ParseResult {
         let ch = match ch {
             Some(c) => c,
@@ -479,6 +816,15 @@ impl VimParser {
     }
 }
 
+/// Key that follows `g` for a case operator; repeating it applies to whole lines
+fn case_operator_key(case: CaseChange) -> &'static str {
+    match case {
+        CaseChange::Lower => "u",
+        CaseChange::Upper => "U",
+        CaseChange::Toggle => "~",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -513,8 +859,201 @@ mod tests {
         assert_eq!(parser.process_key(key_char('d')), ParseResult::Pending);
         assert_eq!(
             parser.process_key(key_char('d')),
-            ParseResult::Command(Command::DeleteLine)
+            ParseResult::Command(Command::DeleteLine(1))
+        );
+    }
+
+    #[test]
+    fn test_find_char_and_repeat() {
+        let mut parser = VimParser::new();
+        for c in "2t".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        let search = CharSearch {
+            ch: ')',
+            till: true,
+            forward: true,
+        };
+        assert_eq!(
+            parser.process_key(key_char(')')),
+            ParseResult::Command(Command::FindChar(search, 2))
+        );
+
+        assert_eq!(parser.process_key(key_char('F')), ParseResult::Pending);
+        assert!(matches!(
+            parser.process_key(key_char('x')),
+            ParseResult::Command(Command::FindChar(
+                CharSearch {
+                    till: false,
+                    forward: false,
+                    ..
+                },
+                1
+            ))
+        ));
+
+        for c in "3".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char(';')),
+            ParseResult::Command(Command::RepeatFindChar {
+                reverse: false,
+                count: 3
+            })
+        );
+        assert_eq!(
+            parser.process_key(key_char(',')),
+            ParseResult::Command(Command::RepeatFindChar {
+                reverse: true,
+                count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let mut parser = VimParser::new();
+        let mut parse = |keys: &str| {
+            let mut result = ParseResult::Invalid;
+            for c in keys.chars() {
+                result = parser.process_key(key_char(c));
+            }
+            result
+        };
+        assert_eq!(
+            parse("gcc"),
+            ParseResult::Command(Command::ToggleComment(LineMotion::Down(0)))
+        );
+        assert_eq!(
+            parse("3gcc"),
+            ParseResult::Command(Command::ToggleComment(LineMotion::Down(2)))
+        );
+        assert_eq!(
+            parse("gc2k"),
+            ParseResult::Command(Command::ToggleComment(LineMotion::Up(2)))
+        );
+        assert_eq!(
+            parse("gc}"),
+            ParseResult::Command(Command::ToggleComment(LineMotion::ParagraphForward(1)))
+        );
+        assert_eq!(
+            parse("gcgg"),
+            ParseResult::Command(Command::ToggleComment(LineMotion::FileStart))
+        );
+
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_visual_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(key_char('c')),
+            ParseResult::Command(Command::ToggleCommentSelection)
+        );
+    }
+
+    #[test]
+    fn test_percent_with_and_without_count() {
+        let mut parser = VimParser::new();
+        assert_eq!(
+            parser.process_key(key_char('%')),
+            ParseResult::Command(Command::MoveMatchingPair)
+        );
+        parser.process_key(key_char('2'));
+        parser.process_key(key_char('5'));
+        assert_eq!(
+            parser.process_key(key_char('%')),
+            ParseResult::Command(Command::GotoPercent(25))
+        );
+    }
+
+    #[test]
+    fn test_reindent_motions() {
+        let mut parser = VimParser::new();
+        let mut parse = |keys: &str| {
+            let mut result = ParseResult::Invalid;
+            for c in keys.chars() {
+                result = parser.process_key(key_char(c));
+            }
+            result
+        };
+        assert_eq!(
+            parse("=G"),
+            ParseResult::Command(Command::Reindent(LineMotion::FileEnd))
+        );
+        assert_eq!(
+            parse("=3j"),
+            ParseResult::Command(Command::Reindent(LineMotion::Down(3)))
+        );
+        assert_eq!(
+            parse("=gg"),
+            ParseResult::Command(Command::Reindent(LineMotion::FileStart))
         );
+        // Doubled it still formats the file
+        assert_eq!(parse("=="), ParseResult::Command(Command::FormatBuffer));
+    }
+
+    #[test]
+    fn test_repeat_ex_command() {
+        let mut parser = VimParser::new();
+        let mut parse = |keys: &str| {
+            let mut result = ParseResult::Invalid;
+            for c in keys.chars() {
+                result = parser.process_key(key_char(c));
+            }
+            result
+        };
+        assert_eq!(parse("@:"), ParseResult::Command(Command::RepeatExCommand(1)));
+        assert_eq!(parse("3@@"), ParseResult::Command(Command::RepeatLastExecuted(3)));
+        assert_eq!(parse("@"), ParseResult::Pending);
+        assert_eq!(parse("x"), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_hunk_jumps() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char(']')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('c')),
+            ParseResult::Command(Command::NextHunk(1))
+        );
+        for c in "2[".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char('c')),
+            ParseResult::Command(Command::PrevHunk(2))
+        );
+        assert_eq!(parser.process_key(key_char(']')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('x')), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_indent_boundary_motions() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char(']')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('i')),
+            ParseResult::Command(Command::MoveMatchingIndentDown(1))
+        );
+        for c in "3[".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char('i')),
+            ParseResult::Command(Command::MoveMatchingIndentUp(3))
+        );
+    }
+
+    #[test]
+    fn test_counted_linewise_operators() {
+        let mut parser = VimParser::new();
+        for c in "3d".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(parser.process_key(key_char('d')), ParseResult::Command(Command::DeleteLine(3)));
+        for c in "y2".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(parser.process_key(key_char('y')), ParseResult::Command(Command::YankLine(2)));
     }
 
     #[test]
@@ -545,10 +1084,306 @@ mod tests {
         let mut parser = VimParser::new();
         assert_eq!(parser.process_key(key_char('d')), ParseResult::Pending);
         let esc_key = KeyEvent::new(KeyCode::Esc, crossterm::event::KeyModifiers::NONE);
-        assert_eq!(parser.process_key(esc_key), ParseResult::Command(Command::NormalMode));
+        assert_eq!(parser.process_key(esc_key), ParseResult::Command(Command::Escape));
         assert_eq!(parser.state, ParserState::Idle);
     }
 
+    #[test]
+    fn test_ctrl_c_cancels_pending_keys() {
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), crossterm::event::KeyModifiers::CONTROL);
+        for keys in ["3", "\"ad2", "gr", "f", "ci", "r"] {
+            let mut parser = VimParser::new();
+            for c in keys.chars() {
+                assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+            }
+            assert_eq!(parser.process_key(ctrl_c), ParseResult::Command(Command::NormalMode));
+            assert_eq!(parser.state, ParserState::Idle);
+            assert_eq!(parser.count, None);
+            assert_eq!(parser.operator, None);
+            assert_eq!(parser.take_register(), None);
+            // The next command starts from scratch
+            assert_eq!(parser.process_key(key_char('w')), ParseResult::Command(Command::MoveWordForward(1)));
+        }
+    }
+
+    #[test]
+    fn test_register_preserves_case() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('"')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('A')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('y')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('y')), ParseResult::Command(Command::YankLine(1)));
+        assert_eq!(parser.take_register(), Some('A'));
+        assert_eq!(parser.take_register(), None);
+    }
+
+    #[test]
+    fn test_no_register_for_plain_command() {
+        let mut parser = VimParser::new();
+        parser.process_key(key_char('y'));
+        parser.process_key(key_char('y'));
+        assert_eq!(parser.take_register(), None);
+    }
+
+    #[test]
+    fn test_visual_indent_operators() {
+        let mut parser = VimParser::new();
+        assert_eq!(
+            parser.process_visual_key(key_char('>')),
+            ParseResult::Command(Command::IndentSelection(1))
+        );
+        assert_eq!(parser.process_visual_key(key_char('2')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(key_char('<')),
+            ParseResult::Command(Command::UnindentSelection(2))
+        );
+        assert_eq!(
+            parser.process_visual_key(key_char('j')),
+            ParseResult::Command(Command::MoveDown)
+        );
+        assert_eq!(
+            parser.process_visual_key(key_char('d')),
+            ParseResult::Command(Command::DeleteSelection)
+        );
+        assert_eq!(
+            parser.process_visual_key(key_char('=')),
+            ParseResult::Command(Command::FormatSelection)
+        );
+    }
+
+    #[test]
+    fn test_case_operators() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('U')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('w')),
+            ParseResult::Command(Command::CaseWord(CaseChange::Upper, 1))
+        );
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('u')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('e')),
+            ParseResult::Command(Command::CaseToWordEnd(CaseChange::Lower, 1))
+        );
+
+        for keys in ["guu", "gugu"] {
+            for c in keys[..keys.len() - 1].chars() {
+                assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+            }
+            assert_eq!(
+                parser.process_key(key_char(keys.chars().last().unwrap())),
+                ParseResult::Command(Command::CaseLine(CaseChange::Lower, 1))
+            );
+        }
+
+        assert_eq!(parser.process_key(key_char('3')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('~')),
+            ParseResult::Command(Command::ToggleCaseChar(3))
+        );
+    }
+
+    #[test]
+    fn test_case_text_object_and_visual() {
+        let mut parser = VimParser::new();
+        for c in "g~i".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char('w')),
+            ParseResult::Command(Command::CaseInnerWord(CaseChange::Toggle, 1))
+        );
+        assert_eq!(
+            parser.process_visual_key(key_char('U')),
+            ParseResult::Command(Command::CaseSelection(CaseChange::Upper))
+        );
+    }
+
+    #[test]
+    fn test_visual_operators_with_shift() {
+        let shifted = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::SHIFT);
+        let mut parser = VimParser::new();
+        for (c, cmd) in [
+            ('J', Command::JoinSelection),
+            ('U', Command::CaseSelection(CaseChange::Upper)),
+            ('~', Command::CaseSelection(CaseChange::Toggle)),
+            ('>', Command::IndentSelection(1)),
+            ('<', Command::UnindentSelection(1)),
+            ('O', Command::VisualSwapEnds),
+        ] {
+            assert_eq!(parser.process_visual_key(shifted(c)), ParseResult::Command(cmd));
+        }
+        assert_eq!(parser.process_visual_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(shifted('J')),
+            ParseResult::Command(Command::JoinSelectionNoSpace)
+        );
+    }
+
+    #[test]
+    fn test_gra_code_actions() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('r')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('a')),
+            ParseResult::Command(Command::CodeAction)
+        );
+        parser.process_key(key_char('g'));
+        parser.process_key(key_char('r'));
+        assert_eq!(
+            parser.process_key(key_char('r')),
+            ParseResult::Command(Command::FindReferences)
+        );
+    }
+
+    #[test]
+    fn test_visual_replace() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_visual_key(key_char('r')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(key_char('x')),
+            ParseResult::Command(Command::ReplaceSelection('x'))
+        );
+    }
+
+    #[test]
+    fn test_delimited_text_objects() {
+        let mut parser = VimParser::new();
+        let mut parse = |keys: &str| {
+            let mut result = ParseResult::Invalid;
+            for c in keys.chars() {
+                result = parser.process_key(key_char(c));
+            }
+            result
+        };
+        let object = |key, inner| TextObject::from_key(key, inner).unwrap();
+        assert_eq!(
+            parse("di)"),
+            ParseResult::Command(Command::DeleteTextObject(object('(', true)))
+        );
+        assert_eq!(
+            parse("ca\""),
+            ParseResult::Command(Command::ChangeTextObject(object('"', false)))
+        );
+        assert_eq!(
+            parse("yiB"),
+            ParseResult::Command(Command::YankTextObject(object('{', true)))
+        );
+        assert_eq!(parse("ciw"), ParseResult::Command(Command::ChangeInnerWord(1)));
+        assert_eq!(parse("gUi("), ParseResult::Invalid);
+        assert_eq!(
+            parse("dai"),
+            ParseResult::Command(Command::DeleteTextObject(object('i', false)))
+        );
+    }
+
+    #[test]
+    fn test_visual_text_objects() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_visual_key(key_char('i')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(key_char('i')),
+            ParseResult::Command(Command::SelectTextObject(
+                TextObject::from_key('i', true).unwrap()
+            ))
+        );
+        assert_eq!(parser.process_visual_key(key_char('a')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_visual_key(key_char('(')),
+            ParseResult::Command(Command::SelectTextObject(
+                TextObject::from_key('(', false).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ga_shows_char_info() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('a')),
+            ParseResult::Command(Command::ShowCharInfo)
+        );
+    }
+
+    #[test]
+    fn test_g_ctrl_g_shows_counts() {
+        use crossterm::event::KeyModifiers;
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        assert_eq!(
+            parser.process_key(ctrl_g),
+            ParseResult::Command(Command::ShowCounts)
+        );
+    }
+
+    #[test]
+    fn test_gg_moves_to_file_start() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('g')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('g')),
+            ParseResult::Command(Command::MoveFileStart)
+        );
+    }
+
+    #[test]
+    fn test_backward_word_end() {
+        let mut parser = VimParser::new();
+        for c in "2g".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char('e')),
+            ParseResult::Command(Command::MoveWordEndBackward(2))
+        );
+
+        for c in "dg".chars() {
+            assert_eq!(parser.process_key(key_char(c)), ParseResult::Pending);
+        }
+        assert_eq!(
+            parser.process_key(key_char('E')),
+            ParseResult::Command(Command::DeleteBigWordEndBackward(1))
+        );
+    }
+
+    #[test]
+    fn test_counted_insert_commands() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('3')), ParseResult::Pending);
+        assert_eq!(
+            parser.process_key(key_char('o')),
+            ParseResult::Command(Command::OpenLineBelow(3))
+        );
+        assert_eq!(
+            parser.process_key(key_char('a')),
+            ParseResult::Command(Command::AppendAfter(1))
+        );
+    }
+
+    #[test]
+    fn test_counted_undo_redo() {
+        let mut parser = VimParser::new();
+        assert_eq!(parser.process_key(key_char('3')), ParseResult::Pending);
+        assert_eq!(parser.process_key(key_char('u')), ParseResult::Command(Command::Undo(3)));
+        assert_eq!(parser.process_key(key_char('2')), ParseResult::Pending);
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), crossterm::event::KeyModifiers::CONTROL);
+        assert_eq!(parser.process_key(ctrl_r), ParseResult::Command(Command::Redo(2)));
+        assert_eq!(parser.process_key(ctrl_r), ParseResult::Command(Command::Redo(1)));
+    }
+
+    #[test]
+    fn test_dot_repeat() {
+        let mut parser = VimParser::new();
+        assert_eq!(
+            parser.process_key(key_char('.')),
+            ParseResult::Command(Command::RepeatLastChange)
+        );
+    }
+
     #[test]
     fn test_simple_delete_char() {
         let mut parser = VimParser::new();
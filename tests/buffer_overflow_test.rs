@@ -124,8 +124,8 @@ fn test_join_lines_normal() {
 
     let result = buffer.join_lines(0);
     assert!(result.is_ok());
-    // join_lines merges lines and adds trailing space
-    assert_eq!(buffer.line(0).unwrap(), "helloworld ");
+    // join_lines merges lines with a space between them
+    assert_eq!(buffer.line(0).unwrap(), "hello world");
     assert_eq!(buffer.line_count(), 1);
 }
 
@@ -249,7 +249,7 @@ fn test_cursor_after_line_operations() {
     editor.cursor.col = 0;
 
     // Delete current line
-    editor.execute_command(Command::DeleteLine);
+    editor.execute_command(Command::DeleteLine(1));
     validation::assert_cursor_valid(&editor);
 
     // Should still have valid cursor
@@ -132,13 +132,13 @@ fn test_complex_vim_operations() {
     ).unwrap();
 
     // Test: 3dd (delete 3 lines)
-    editor.execute_command(Command::DeleteLine);
+    editor.execute_command(Command::DeleteLine(1));
     validation::assert_cursor_valid(&editor);
 
-    editor.execute_command(Command::DeleteLine);
+    editor.execute_command(Command::DeleteLine(1));
     validation::assert_cursor_valid(&editor);
 
-    editor.execute_command(Command::DeleteLine);
+    editor.execute_command(Command::DeleteLine(1));
     validation::assert_cursor_valid(&editor);
 
     // Reset
@@ -368,7 +368,7 @@ fn test_rapid_mode_switching() {
     editor.buffer.insert_text("test", 0, 0).unwrap();
 
     for _ in 0..100 {
-        editor.execute_command(Command::InsertMode);
+        editor.execute_command(Command::InsertMode(1));
         validation::assert_cursor_valid(&editor);
 
         editor.execute_command(Command::NormalMode);
@@ -25,7 +25,7 @@ fn test_load_edit_save_file() {
     assert_eq!(editor.buffer.line(2).unwrap(), "");
 
     // Edit: insert text at end of first line
-    editor.execute_command(Command::InsertMode);
+    editor.execute_command(Command::InsertMode(1));
     for _ in 0..5 {
         // Move to end of "Hello"
         editor.execute_command(Command::MoveRight);
@@ -115,7 +115,7 @@ fn test_mode_switching() {
 
     assert!(matches!(editor.mode, texty::mode::Mode::Normal));
 
-    editor.execute_command(Command::InsertMode);
+    editor.execute_command(Command::InsertMode(1));
     assert!(matches!(editor.mode, texty::mode::Mode::Insert));
 
     editor.execute_command(Command::NormalMode);
@@ -276,6 +276,9 @@ fn test_command_line_functionality() {
     use texty::mode::Mode;
 
     let mut editor = Editor::new();
+    // `:wq` writes the buffer; keep it out of the working directory
+    let dir = TempDir::new().unwrap();
+    editor.buffer.file_path = Some(dir.path().join("buffer.txt").to_string_lossy().into_owned());
 
     // Test entering command mode
     editor.enter_command_mode();
@@ -528,7 +531,7 @@ fn test_arrow_key_movement() {
     assert_eq!(editor.cursor.col, 0);
 
     // Test in insert mode
-    editor.execute_command(Command::InsertMode);
+    editor.execute_command(Command::InsertMode(1));
     editor.buffer.insert_text("test", 0, 0).unwrap();
     editor.cursor.col = 4; // Move to end of "test"
 